@@ -43,9 +43,31 @@ fn easy_fs_pack() -> std::io::Result<()> {
                 .takes_value(true)
                 .help("Executable target dir(with backslash)"),
         )
+        .arg(
+            Arg::with_name("block-size")
+                .short('b')
+                .long("block-size")
+                .takes_value(true)
+                .default_value("512")
+                .help("Filesystem block size in bytes(power of two, at least 512)"),
+        )
+        .arg(
+            Arg::with_name("inode-bitmap-blocks")
+                .short('i')
+                .long("inode-bitmap-blocks")
+                .takes_value(true)
+                .default_value("1")
+                .help("Number of inode bitmap blocks"),
+        )
         .get_matches();
     let src_path = matches.value_of("source").unwrap();
     let target_path = matches.value_of("target").unwrap();
+    let block_size: usize = matches.value_of("block-size").unwrap().parse().unwrap();
+    let inode_bitmap_blocks: u32 = matches
+        .value_of("inode-bitmap-blocks")
+        .unwrap()
+        .parse()
+        .unwrap();
     println!("src_path = {}\ntarget_path = {}", src_path, target_path);
     let block_file = Arc::new(BlockFile(Mutex::new({
         let f = OpenOptions::new()
@@ -56,8 +78,13 @@ fn easy_fs_pack() -> std::io::Result<()> {
         f.set_len(16 * 2048 * 512).unwrap();
         f
     })));
-    // 16MiB, at most 4095 files
-    let efs = EasyFileSystem::create(block_file, 16 * 2048, 1);
+    // 16MiB, at most 4095 files per inode bitmap block of 512 bytes
+    let efs = EasyFileSystem::create_with_block_size(
+        block_file,
+        (16 * 2048 * 512 / block_size) as u32,
+        inode_bitmap_blocks,
+        block_size,
+    );
     let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
     let apps: Vec<_> = read_dir(src_path)
         .unwrap()
@@ -82,6 +109,7 @@ fn easy_fs_pack() -> std::io::Result<()> {
     for app in root_inode.ls() {
         println!("{}", app);
     }
+    println!("{:?}", efs.lock().statfs());
     Ok(())
 }
 
@@ -148,6 +176,262 @@ fn efs_test() -> std::io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn efs_v2_test() -> std::io::Result<()> {
+    let block_file = Arc::new(BlockFile(Mutex::new({
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open("target/fs_v2.img")?;
+        f.set_len(32 * 2048 * 512).unwrap();
+        f
+    })));
+    // bigger blocks and long names
+    EasyFileSystem::create_with_block_size(block_file.clone(), 4096, 1, 2048);
+    let efs = EasyFileSystem::open(block_file.clone());
+    assert_eq!(efs.lock().version(), easy_fs::EFS_VERSION);
+    assert_eq!(efs.lock().block_size(), 2048);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let long_name = "a".repeat(easy_fs::NAME_LENGTH_LIMIT_V2);
+    assert!(root_inode.create(long_name.as_str()).is_some());
+    assert!(root_inode
+        .create((long_name.clone() + "a").as_str())
+        .is_none());
+    assert!(root_inode.create_fifo("fifo").is_some());
+    assert!(root_inode.find("fifo").unwrap().is_fifo());
+    let file = root_inode.find(long_name.as_str()).unwrap();
+    assert!(!file.is_fifo());
+    assert_eq!(root_inode.inode_id(), 0);
+    assert_eq!(file.inode_id(), 1);
+    let data: Vec<u8> = (0..300 * 2048).map(|i| (i % 251) as u8).collect();
+    assert_eq!(file.write_at(0, &data), data.len());
+    assert_eq!(file.size(), data.len());
+    let mut read_back = vec![0u8; data.len()];
+    assert_eq!(file.read_at(0, &mut read_back), data.len());
+    assert_eq!(data, read_back);
+    file.clear();
+
+    // several inode bitmap blocks, and files larger than indirect2 can
+    // address on 512-byte blocks
+    EasyFileSystem::create(block_file.clone(), 32 * 2048, 2);
+    let efs = EasyFileSystem::open(block_file);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    for i in 0..5000 {
+        assert!(root_inode
+            .create(format!("file_with_a_rather_long_name_{}", i).as_str())
+            .is_some());
+    }
+    assert_eq!(root_inode.ls().len(), 5000);
+    let file = root_inode
+        .find("file_with_a_rather_long_name_4999")
+        .unwrap();
+    let len = (28 + 128 + 128 * 128 + 1000) * BLOCK_SZ;
+    let data: Vec<u8> = (0..len).map(|i| (i % 253) as u8).collect();
+    assert_eq!(file.write_at(0, &data), len);
+    let mut read_back = vec![0u8; len];
+    assert_eq!(file.read_at(0, &mut read_back), len);
+    assert_eq!(data, read_back);
+    file.clear();
+    Ok(())
+}
+
+#[test]
+fn efs_statfs_test() -> std::io::Result<()> {
+    let block_file = Arc::new(BlockFile(Mutex::new({
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open("target/fs_full.img")?;
+        f.set_len(4096 * 512).unwrap();
+        f
+    })));
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let fresh = efs.lock().statfs();
+    // the root directory takes the first inode
+    assert_eq!(fresh.free_inodes, fresh.total_inodes - 1);
+    assert_eq!(fresh.free_blocks, fresh.total_blocks);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("filler").unwrap();
+    // filling up the disk gives a short write instead of a panic
+    let data = vec![0x5au8; 4096 * BLOCK_SZ];
+    let written = file.write_at(0, &data);
+    assert!(written > 0 && written < data.len());
+    assert_eq!(efs.lock().statfs().free_blocks, 0);
+    assert_eq!(file.write_at(written, &data), 0);
+    // new dirents only fit into what is left of the root directory block
+    let mut created = 0;
+    while root_inode
+        .create(format!("f{}", created).as_str())
+        .is_some()
+    {
+        created += 1;
+    }
+    assert!(created < (BLOCK_SZ / 8) as u32);
+    // counters survive a remount
+    drop(root_inode);
+    drop(file);
+    let efs = EasyFileSystem::open(block_file);
+    assert_eq!(efs.lock().statfs().free_blocks, 0);
+    assert_eq!(
+        efs.lock().statfs().free_inodes,
+        fresh.free_inodes - 1 - created
+    );
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    root_inode.find("filler").unwrap().clear();
+    let stat = efs.lock().statfs();
+    // only the root directory keeps its block
+    assert_eq!(stat.free_blocks, fresh.free_blocks - 1);
+    Ok(())
+}
+
+/// Write a version 1 image by hand, since `EasyFileSystem::create` only
+/// writes the current format: one inode bitmap block and a root directory
+/// holding `files`, each small enough for the direct pointers
+#[cfg(test)]
+fn write_v1_image(
+    file: &mut File,
+    total_blocks: usize,
+    files: &[(&str, &[u8])],
+) -> std::io::Result<()> {
+    const INODE_SZ: usize = 128;
+    const DIRENT_SZ: usize = 32;
+    let inode_area_blocks = BLOCK_SZ * 8 * INODE_SZ / BLOCK_SZ;
+    let data_total_blocks = total_blocks - 2 - inode_area_blocks;
+    let block_bits = BLOCK_SZ * 8;
+    let data_bitmap_blocks = (data_total_blocks + block_bits) / (block_bits + 1);
+    let data_bitmap_start = 2 + inode_area_blocks;
+    let data_area_start = data_bitmap_start + data_bitmap_blocks;
+    let mut image = vec![0u8; total_blocks * BLOCK_SZ];
+    // the version and the free counters of the super block stay 0
+    let super_block = [
+        easy_fs::EFS_MAGIC,
+        total_blocks as u32,
+        1,
+        inode_area_blocks as u32,
+        data_bitmap_blocks as u32,
+        (data_total_blocks - data_bitmap_blocks) as u32,
+    ];
+    for (i, field) in super_block.iter().enumerate() {
+        image[i * 4..i * 4 + 4].copy_from_slice(&field.to_le_bytes());
+    }
+    let mut used_blocks = 0;
+    // inode `id` of type `type_` (0 for a file, 1 for a directory)
+    let mut add_inode = |image: &mut [u8], id: usize, type_: u8, data: &[u8]| {
+        let inode = (2 + id / 4) * BLOCK_SZ + id % 4 * INODE_SZ;
+        image[inode..inode + 4].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        for (i, chunk) in data.chunks(BLOCK_SZ).enumerate() {
+            let block_id = data_area_start + used_blocks;
+            image[block_id * BLOCK_SZ..][..chunk.len()].copy_from_slice(chunk);
+            image[inode + 4 + i * 4..][..4].copy_from_slice(&(block_id as u32).to_le_bytes());
+            image[data_bitmap_start * BLOCK_SZ + used_blocks / 8] |= 1 << (used_blocks % 8);
+            used_blocks += 1;
+        }
+        image[inode + 124] = type_;
+        image[BLOCK_SZ + id / 8] |= 1 << (id % 8);
+    };
+    let mut root = vec![0u8; files.len() * DIRENT_SZ];
+    for (i, (name, _)) in files.iter().enumerate() {
+        let dirent = &mut root[i * DIRENT_SZ..(i + 1) * DIRENT_SZ];
+        dirent[..name.len()].copy_from_slice(name.as_bytes());
+        dirent[28..].copy_from_slice(&(i as u32 + 1).to_le_bytes());
+    }
+    add_inode(&mut image, 0, 1, &root);
+    for (i, (_, data)) in files.iter().enumerate() {
+        add_inode(&mut image, i + 1, 0, data);
+    }
+    file.write_all(&image)
+}
+
+#[test]
+fn efs_v1_test() -> std::io::Result<()> {
+    let hello = b"hello, version 1";
+    let pattern: Vec<u8> = (0..20 * BLOCK_SZ).map(|i| (i % 241) as u8).collect();
+    let mut f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open("target/fs_v1.img")?;
+    write_v1_image(&mut f, 20000, &[("hello", hello), ("pattern", &pattern)])?;
+    let block_file = Arc::new(BlockFile(Mutex::new(f)));
+    let efs = EasyFileSystem::open(block_file.clone());
+    assert_eq!(efs.lock().version(), easy_fs::EFS_VERSION_1);
+    assert_eq!(efs.lock().block_size(), BLOCK_SZ);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    assert_eq!(root_inode.ls(), vec!["hello", "pattern"]);
+    // existing files read back as they were written
+    let file = root_inode.find("pattern").unwrap();
+    let mut read_back = vec![0u8; pattern.len()];
+    assert_eq!(file.read_at(0, &mut read_back), pattern.len());
+    assert_eq!(read_back, pattern);
+    // growing a file into indirect1 and indirect2
+    let file = root_inode.find("hello").unwrap();
+    let data: Vec<u8> = (0..300 * BLOCK_SZ).map(|i| (i % 239) as u8).collect();
+    assert_eq!(file.write_at(hello.len(), &data), data.len());
+    // new files get version 1 directory entries
+    assert!(root_inode.create("n".repeat(28).as_str()).is_none());
+    assert_eq!(root_inode.create("new").unwrap().write_at(0, b"fresh"), 5);
+
+    // everything survives a remount
+    drop(root_inode);
+    drop(file);
+    let efs = EasyFileSystem::open(block_file);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    assert_eq!(root_inode.ls(), vec!["hello", "new", "pattern"]);
+    let file = root_inode.find("hello").unwrap();
+    let mut read_back = vec![0u8; hello.len() + data.len()];
+    assert_eq!(file.read_at(0, &mut read_back), read_back.len());
+    assert_eq!(&read_back[..hello.len()], hello);
+    assert_eq!(&read_back[hello.len()..], &data[..]);
+    let mut fresh = [0u8; 5];
+    assert_eq!(root_inode.find("new").unwrap().read_at(0, &mut fresh), 5);
+    assert_eq!(&fresh, b"fresh");
+    // files stop at the version 1 maximum, which needs no indirect3
+    let max = (28 + 128 + 128 * 128) * BLOCK_SZ;
+    let file = root_inode.find("new").unwrap();
+    assert_eq!(file.write_at(0, &vec![0x5au8; max + BLOCK_SZ]), max);
+    assert_eq!(file.write_at(max, b"x"), 0);
+    assert_eq!(file.size(), max);
+    Ok(())
+}
+
+#[test]
+fn efs_bad_dirent_test() -> std::io::Result<()> {
+    let block_file = Arc::new(BlockFile(Mutex::new({
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open("target/fs_bad_dirent.img")?;
+        f.set_len(4096 * 512).unwrap();
+        f
+    })));
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    assert!(root_inode.create("good").is_some());
+    // an entry whose name is not UTF-8 is skipped
+    let end = root_inode.size();
+    let bad_name = [1, 0, 0, 0, 12, 0, 2, 0, 0xff, 0xfe, 0, 0];
+    assert_eq!(root_inode.write_at(end, &bad_name), bad_name.len());
+    assert_eq!(root_inode.ls(), vec!["good"]);
+    // rec_len too short, running past the end, or too short for the name
+    let end = root_inode.size();
+    for header in [
+        [1, 0, 0, 0, 0, 0, 0, 0],
+        [1, 0, 0, 0, 64, 0, 1, 0],
+        [1, 0, 0, 0, 8, 0, 5, 0],
+    ] {
+        assert_eq!(root_inode.write_at(end, &header), header.len());
+        assert_eq!(root_inode.ls(), vec!["good"]);
+        assert!(root_inode.find("good").is_some());
+    }
+    Ok(())
+}
+
 fn main() {
     easy_fs_pack().expect("Error when packing easy-fs!");
 }
@@ -1,31 +1,29 @@
 use alloc::sync::Arc;
 
-use super::{get_block_cache, BlockDevice, BLOCK_SZ};
-/// A bitmap block
-type BitmapBlock = [u64; 64];
-/// Number of bits in a block
-const BLOCK_BITS: usize = BLOCK_SZ * 8;
+use super::{get_block_cache, BlockDevice};
 /// A bitmap
 pub struct Bitmap {
     start_block_id: usize,
     blocks: usize,
-}
-
-/// Decompose bits into (block_pos, bits64_pos, inner_pos)
-fn decomposition(mut bit: usize) -> (usize, usize, usize) {
-    let block_pos = bit / BLOCK_BITS;
-    bit %= BLOCK_BITS;
-    (block_pos, bit / 64, bit % 64)
+    /// Number of bits in a block
+    block_bits: usize,
 }
 
 impl Bitmap {
-    /// A new bitmap from start block id and number of blocks
-    pub fn new(start_block_id: usize, blocks: usize) -> Self {
+    /// A new bitmap from start block id, number of blocks and block size
+    pub fn new(start_block_id: usize, blocks: usize, block_size: usize) -> Self {
         Self {
             start_block_id,
             blocks,
+            block_bits: block_size * 8,
         }
     }
+    /// Decompose bits into (block_pos, bits64_pos, inner_pos)
+    fn decomposition(&self, mut bit: usize) -> (usize, usize, usize) {
+        let block_pos = bit / self.block_bits;
+        bit %= self.block_bits;
+        (block_pos, bit / 64, bit % 64)
+    }
     /// Allocate a new block from a block device
     pub fn alloc(&self, block_device: &Arc<dyn BlockDevice>) -> Option<usize> {
         for block_id in 0..self.blocks {
@@ -34,7 +32,7 @@ impl Bitmap {
                 Arc::clone(block_device),
             )
             .lock()
-            .modify(0, |bitmap_block: &mut BitmapBlock| {
+            .modify_slice(|bitmap_block: &mut [u64]| {
                 if let Some((bits64_pos, inner_pos)) = bitmap_block
                     .iter()
                     .enumerate()
@@ -43,7 +41,7 @@ impl Bitmap {
                 {
                     // modify cache
                     bitmap_block[bits64_pos] |= 1u64 << inner_pos;
-                    Some(block_id * BLOCK_BITS + bits64_pos * 64 + inner_pos as usize)
+                    Some(block_id * self.block_bits + bits64_pos * 64 + inner_pos as usize)
                 } else {
                     None
                 }
@@ -56,16 +54,31 @@ impl Bitmap {
     }
     /// Deallocate a block
     pub fn dealloc(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) {
-        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        let (block_pos, bits64_pos, inner_pos) = self.decomposition(bit);
         get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
             .lock()
-            .modify(0, |bitmap_block: &mut BitmapBlock| {
+            .modify_slice(|bitmap_block: &mut [u64]| {
                 assert!(bitmap_block[bits64_pos] & (1u64 << inner_pos) > 0);
                 bitmap_block[bits64_pos] -= 1u64 << inner_pos;
             });
     }
+    /// Count the allocated bits by scanning the whole bitmap
+    pub fn count_allocated(&self, block_device: &Arc<dyn BlockDevice>) -> usize {
+        (0..self.blocks)
+            .map(|block_id| {
+                get_block_cache(block_id + self.start_block_id, Arc::clone(block_device))
+                    .lock()
+                    .read_slice(|bitmap_block: &[u64]| {
+                        bitmap_block
+                            .iter()
+                            .map(|bits64| bits64.count_ones() as usize)
+                            .sum::<usize>()
+                    })
+            })
+            .sum()
+    }
     /// Get the max number of allocatable blocks
     pub fn maximum(&self) -> usize {
-        self.blocks * BLOCK_BITS
+        self.blocks * self.block_bits
     }
 }
@@ -1,13 +1,16 @@
-use alloc::{collections::VecDeque, sync::Arc};
+use alloc::{collections::VecDeque, sync::Arc, vec, vec::Vec};
 
 use lazy_static::*;
 use spin::Mutex;
 
-use super::{BlockDevice, BLOCK_SZ};
+use super::BlockDevice;
 /// Cached block inside memory
 pub struct BlockCache {
-    /// cached block data
-    cache: [u8; BLOCK_SZ],
+    /// cached block data, kept in `u64` words so that it is aligned for
+    /// every on-disk structure
+    cache: Vec<u64>,
+    /// size of the cached block in bytes
+    block_size: usize,
     /// underlying block id
     block_id: usize,
     /// underlying block device
@@ -19,18 +22,26 @@ pub struct BlockCache {
 impl BlockCache {
     /// Load a new BlockCache from disk.
     pub fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Self {
-        let mut cache = [0u8; BLOCK_SZ];
-        block_device.read_block(block_id, &mut cache);
+        let block_size = block_device.block_size();
+        let mut cache = vec![0u64; block_size / 8];
+        block_device.read_block(block_id, Self::as_bytes_mut(&mut cache));
         Self {
             cache,
+            block_size,
             block_id,
             block_device,
             modified: false,
         }
     }
+    fn as_bytes(cache: &[u64]) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(cache.as_ptr() as *const u8, cache.len() * 8) }
+    }
+    fn as_bytes_mut(cache: &mut [u64]) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(cache.as_mut_ptr() as *mut u8, cache.len() * 8) }
+    }
     /// Get the address of an offset inside the cached block data
     fn addr_of_offset(&self, offset: usize) -> usize {
-        &self.cache[offset] as *const _ as usize
+        self.cache.as_ptr() as usize + offset
     }
 
     pub fn get_ref<T>(&self, offset: usize) -> &T
@@ -38,7 +49,7 @@ impl BlockCache {
         T: Sized,
     {
         let type_size = core::mem::size_of::<T>();
-        assert!(offset + type_size <= BLOCK_SZ);
+        assert!(offset + type_size <= self.block_size);
         let addr = self.addr_of_offset(offset);
         unsafe { &*(addr as *const T) }
     }
@@ -48,7 +59,7 @@ impl BlockCache {
         T: Sized,
     {
         let type_size = core::mem::size_of::<T>();
-        assert!(offset + type_size <= BLOCK_SZ);
+        assert!(offset + type_size <= self.block_size);
         self.modified = true;
         let addr = self.addr_of_offset(offset);
         unsafe { &mut *(addr as *mut T) }
@@ -62,10 +73,24 @@ impl BlockCache {
         f(self.get_mut(offset))
     }
 
+    /// View the whole block as a slice of `T`
+    pub fn read_slice<T, V>(&self, f: impl FnOnce(&[T]) -> V) -> V {
+        let len = self.block_size / core::mem::size_of::<T>();
+        f(unsafe { core::slice::from_raw_parts(self.cache.as_ptr() as *const T, len) })
+    }
+
+    /// View the whole block as a mutable slice of `T`
+    pub fn modify_slice<T, V>(&mut self, f: impl FnOnce(&mut [T]) -> V) -> V {
+        let len = self.block_size / core::mem::size_of::<T>();
+        self.modified = true;
+        f(unsafe { core::slice::from_raw_parts_mut(self.cache.as_mut_ptr() as *mut T, len) })
+    }
+
     pub fn sync(&mut self) {
         if self.modified {
             self.modified = false;
-            self.block_device.write_block(self.block_id, &self.cache);
+            self.block_device
+                .write_block(self.block_id, Self::as_bytes(&self.cache));
         }
     }
 }
@@ -75,11 +100,16 @@ impl Drop for BlockCache {
         self.sync()
     }
 }
+/// Identify a block device by the address of its shared handle; cached
+/// blocks keep the handle alive so the address cannot be reused meanwhile
+fn device_id(block_device: &Arc<dyn BlockDevice>) -> usize {
+    Arc::as_ptr(block_device) as *const () as usize
+}
 /// Use a block cache of 16 blocks
 const BLOCK_CACHE_SIZE: usize = 16;
 
 pub struct BlockCacheManager {
-    queue: VecDeque<(usize, Arc<Mutex<BlockCache>>)>,
+    queue: VecDeque<(usize, usize, Arc<Mutex<BlockCache>>)>,
 }
 
 impl BlockCacheManager {
@@ -94,8 +124,13 @@ impl BlockCacheManager {
         block_id: usize,
         block_device: Arc<dyn BlockDevice>,
     ) -> Arc<Mutex<BlockCache>> {
-        if let Some(pair) = self.queue.iter().find(|pair| pair.0 == block_id) {
-            Arc::clone(&pair.1)
+        let device = device_id(&block_device);
+        if let Some(entry) = self
+            .queue
+            .iter()
+            .find(|entry| entry.0 == device && entry.1 == block_id)
+        {
+            Arc::clone(&entry.2)
         } else {
             // substitute
             if self.queue.len() == BLOCK_CACHE_SIZE {
@@ -104,7 +139,7 @@ impl BlockCacheManager {
                     .queue
                     .iter()
                     .enumerate()
-                    .find(|(_, pair)| Arc::strong_count(&pair.1) == 1)
+                    .find(|(_, entry)| Arc::strong_count(&entry.2) == 1)
                 {
                     self.queue.drain(idx..=idx);
                 } else {
@@ -116,7 +151,8 @@ impl BlockCacheManager {
                 block_id,
                 Arc::clone(&block_device),
             )));
-            self.queue.push_back((block_id, Arc::clone(&block_cache)));
+            self.queue
+                .push_back((device, block_id, Arc::clone(&block_cache)));
             block_cache
         }
     }
@@ -139,7 +175,7 @@ pub fn get_block_cache(
 /// Sync all block cache to block device
 pub fn block_cache_sync_all() {
     let manager = BLOCK_CACHE_MANAGER.lock();
-    for (_, cache) in manager.queue.iter() {
+    for (_, _, cache) in manager.queue.iter() {
         cache.lock().sync();
     }
 }
@@ -1,4 +1,7 @@
+use alloc::sync::Arc;
 use core::any::Any;
+
+use super::BLOCK_SZ;
 /// Trait for block devices
 /// which reads and writes data in the unit of blocks
 pub trait BlockDevice: Send + Sync + Any {
@@ -6,4 +9,46 @@ pub trait BlockDevice: Send + Sync + Any {
     fn read_block(&self, block_id: usize, buf: &mut [u8]);
     ///Write data from buffer to block
     fn write_block(&self, block_id: usize, buf: &[u8]);
+    ///Size of a block in bytes
+    fn block_size(&self) -> usize {
+        BLOCK_SZ
+    }
+}
+
+/// A view of a sector device in units of larger filesystem blocks.
+///
+/// Block `i` of the view covers sectors `i * k .. (i + 1) * k` of the inner
+/// device, where `k = block_size / BLOCK_SZ`.
+pub struct ScaledBlockDevice {
+    inner: Arc<dyn BlockDevice>,
+    block_size: usize,
+}
+
+impl ScaledBlockDevice {
+    /// Wrap `inner` so that it is addressed in blocks of `block_size` bytes
+    pub fn new(inner: Arc<dyn BlockDevice>, block_size: usize) -> Self {
+        assert!(block_size >= BLOCK_SZ && block_size % BLOCK_SZ == 0);
+        Self { inner, block_size }
+    }
+    fn sectors_per_block(&self) -> usize {
+        self.block_size / BLOCK_SZ
+    }
+}
+
+impl BlockDevice for ScaledBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let k = self.sectors_per_block();
+        for (i, sector) in buf.chunks_mut(BLOCK_SZ).take(k).enumerate() {
+            self.inner.read_block(block_id * k + i, sector);
+        }
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let k = self.sectors_per_block();
+        for (i, sector) in buf.chunks(BLOCK_SZ).take(k).enumerate() {
+            self.inner.write_block(block_id * k + i, sector);
+        }
+    }
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
 }
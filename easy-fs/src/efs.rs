@@ -3,8 +3,8 @@ use alloc::sync::Arc;
 use spin::Mutex;
 
 use super::{
-    block_cache_sync_all, get_block_cache, Bitmap, BlockDevice, DiskInode, DiskInodeType, Inode,
-    SuperBlock,
+    block_cache_sync_all, get_block_cache, Bitmap, BlockDevice, DirEntry, DiskInode, DiskInodeType,
+    Inode, ScaledBlockDevice, SuperBlock, EFS_VERSION, EFS_VERSION_1, NAME_LENGTH_LIMIT_V2,
 };
 use crate::BLOCK_SZ;
 ///An easy file system on block
@@ -17,29 +17,75 @@ pub struct EasyFileSystem {
     pub data_bitmap: Bitmap,
     inode_area_start_block: u32,
     data_area_start_block: u32,
+    data_area_blocks: u32,
+    version: u32,
+    block_size: usize,
+    free_inodes: u32,
+    free_blocks: u32,
+}
+
+/// Usage statistics of a filesystem
+#[derive(Debug, Clone, Copy)]
+pub struct FsStat {
+    /// Size of a block in bytes
+    pub block_size: usize,
+    /// Number of data blocks
+    pub total_blocks: u32,
+    /// Number of unallocated data blocks
+    pub free_blocks: u32,
+    /// Number of inodes
+    pub total_inodes: u32,
+    /// Number of unallocated inodes
+    pub free_inodes: u32,
+    /// The max length of a file name
+    pub name_max: usize,
 }
 
-type DataBlock = [u8; BLOCK_SZ];
 /// An easy fs over a block device
 impl EasyFileSystem {
-    /// A data block of block size
+    /// Create a filesystem with the default block size
     pub fn create(
         block_device: Arc<dyn BlockDevice>,
         total_blocks: u32,
         inode_bitmap_blocks: u32,
     ) -> Arc<Mutex<Self>> {
+        Self::create_with_block_size(block_device, total_blocks, inode_bitmap_blocks, BLOCK_SZ)
+    }
+    /// Create a filesystem whose blocks are `block_size` bytes, a power of two
+    /// multiple of `BLOCK_SZ`. `total_blocks` and `inode_bitmap_blocks` are
+    /// counted in filesystem blocks.
+    pub fn create_with_block_size(
+        block_device: Arc<dyn BlockDevice>,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+        block_size: usize,
+    ) -> Arc<Mutex<Self>> {
+        assert!(
+            block_size.is_power_of_two() && block_size >= BLOCK_SZ,
+            "Unsupported block size {}",
+            block_size
+        );
+        assert!(inode_bitmap_blocks > 0);
+        let block_device = Self::scaled(block_device, block_size);
         // calculate block size of areas & create bitmaps
-        let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize);
+        let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize, block_size);
         let inode_num = inode_bitmap.maximum();
         let inode_area_blocks =
-            ((inode_num * core::mem::size_of::<DiskInode>() + BLOCK_SZ - 1) / BLOCK_SZ) as u32;
+            ((inode_num * DiskInode::disk_size(EFS_VERSION) + block_size - 1) / block_size) as u32;
         let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
+        assert!(
+            total_blocks > 1 + inode_total_blocks,
+            "Too many inode bitmap blocks for {} blocks",
+            total_blocks
+        );
         let data_total_blocks = total_blocks - 1 - inode_total_blocks;
-        let data_bitmap_blocks = (data_total_blocks + 4096) / 4097;
+        let block_bits = block_size as u32 * 8;
+        let data_bitmap_blocks = (data_total_blocks + block_bits) / (block_bits + 1);
         let data_area_blocks = data_total_blocks - data_bitmap_blocks;
         let data_bitmap = Bitmap::new(
             (1 + inode_bitmap_blocks + inode_area_blocks) as usize,
             data_bitmap_blocks as usize,
+            block_size,
         );
         let mut efs = Self {
             block_device: Arc::clone(&block_device),
@@ -47,12 +93,17 @@ impl EasyFileSystem {
             data_bitmap,
             inode_area_start_block: 1 + inode_bitmap_blocks,
             data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            data_area_blocks,
+            version: EFS_VERSION,
+            block_size,
+            free_inodes: inode_num as u32,
+            free_blocks: data_area_blocks,
         };
         // clear all blocks
         for i in 0..total_blocks {
             get_block_cache(i as usize, Arc::clone(&block_device))
                 .lock()
-                .modify(0, |data_block: &mut DataBlock| {
+                .modify_slice(|data_block: &mut [u8]| {
                     for byte in data_block.iter_mut() {
                         *byte = 0;
                     }
@@ -63,17 +114,19 @@ impl EasyFileSystem {
             0,
             |super_block: &mut SuperBlock| {
                 super_block.initialize(
+                    block_size as u32,
                     total_blocks,
                     inode_bitmap_blocks,
                     inode_area_blocks,
                     data_bitmap_blocks,
                     data_area_blocks,
                 );
+                super_block.free_inodes = inode_num as u32;
             },
         );
         // write back immediately
         // create a inode for root node "/"
-        assert_eq!(efs.alloc_inode(), 0);
+        assert_eq!(efs.alloc_inode(), Some(0));
         let (root_inode_block_id, root_inode_offset) = efs.get_disk_inode_pos(0);
         get_block_cache(root_inode_block_id as usize, Arc::clone(&block_device))
             .lock()
@@ -85,62 +138,169 @@ impl EasyFileSystem {
     }
     /// Open a block device as a filesystem
     pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<Self>> {
-        // read SuperBlock
-        get_block_cache(0, Arc::clone(&block_device))
-            .lock()
-            .read(0, |super_block: &SuperBlock| {
-                assert!(super_block.is_valid(), "Error loading EFS!");
+        // the super block lives in the first sector whatever the block size
+        // is, so peek at it before building the block cache view
+        let mut sector = [0u64; BLOCK_SZ / 8];
+        let bytes =
+            unsafe { core::slice::from_raw_parts_mut(sector.as_mut_ptr() as *mut u8, BLOCK_SZ) };
+        block_device.read_block(0, bytes);
+        let super_block = unsafe { &*(sector.as_ptr() as *const SuperBlock) };
+        assert!(super_block.is_valid(), "Error loading EFS!");
+        let block_size = super_block.block_size();
+        let block_device = Self::scaled(block_device, block_size);
+        let mut efs = get_block_cache(0, Arc::clone(&block_device)).lock().read(
+            0,
+            |super_block: &SuperBlock| {
                 let inode_total_blocks =
                     super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
-                let efs = Self {
+                Self {
                     block_device,
-                    inode_bitmap: Bitmap::new(1, super_block.inode_bitmap_blocks as usize),
+                    inode_bitmap: Bitmap::new(
+                        1,
+                        super_block.inode_bitmap_blocks as usize,
+                        block_size,
+                    ),
                     data_bitmap: Bitmap::new(
                         (1 + inode_total_blocks) as usize,
                         super_block.data_bitmap_blocks as usize,
+                        block_size,
                     ),
                     inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
                     data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
-                };
-                Arc::new(Mutex::new(efs))
-            })
+                    data_area_blocks: super_block.data_area_blocks,
+                    version: super_block.version(),
+                    block_size,
+                    free_inodes: super_block.free_inodes,
+                    free_blocks: super_block.free_blocks,
+                }
+            },
+        );
+        if efs.version == EFS_VERSION_1 {
+            // version 1 keeps no counters on disk, count the bitmaps instead
+            efs.free_inodes = (efs.inode_bitmap.maximum()
+                - efs.inode_bitmap.count_allocated(&efs.block_device))
+                as u32;
+            efs.free_blocks =
+                efs.data_area_blocks - efs.data_bitmap.count_allocated(&efs.block_device) as u32;
+        }
+        Arc::new(Mutex::new(efs))
+    }
+    /// View `block_device` in units of `block_size`
+    fn scaled(block_device: Arc<dyn BlockDevice>, block_size: usize) -> Arc<dyn BlockDevice> {
+        if block_size == block_device.block_size() {
+            block_device
+        } else {
+            Arc::new(ScaledBlockDevice::new(block_device, block_size))
+        }
     }
     /// Get the root inode of the filesystem
     pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
         let block_device = Arc::clone(&efs.lock().block_device);
         // acquire efs lock temporarily
         let (block_id, block_offset) = efs.lock().get_disk_inode_pos(0);
+        let version = efs.lock().version();
         // release efs lock
-        Inode::new(block_id, block_offset, Arc::clone(efs), block_device)
+        Inode::new(
+            block_id,
+            block_offset,
+            version,
+            Arc::clone(efs),
+            block_device,
+        )
+    }
+    /// On-disk format version
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+    /// Size of a filesystem block in bytes
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+    /// Largest file size this filesystem can hold
+    pub fn max_file_size(&self) -> usize {
+        DiskInode::max_size(self.version, self.block_size)
+    }
+    /// Number of unallocated data blocks
+    pub fn free_blocks(&self) -> u32 {
+        self.free_blocks
+    }
+    /// Usage statistics of the filesystem
+    pub fn statfs(&self) -> FsStat {
+        FsStat {
+            block_size: self.block_size,
+            total_blocks: self.data_area_blocks,
+            free_blocks: self.free_blocks,
+            total_inodes: self.inode_bitmap.maximum() as u32,
+            free_inodes: self.free_inodes,
+            name_max: if self.version == EFS_VERSION_1 {
+                DirEntry::name_limit()
+            } else {
+                NAME_LENGTH_LIMIT_V2
+            },
+        }
+    }
+    /// Write the free counters back into the super block
+    fn sync_counters(&self) {
+        if self.version == EFS_VERSION_1 {
+            return;
+        }
+        let (free_inodes, free_blocks) = (self.free_inodes, self.free_blocks);
+        get_block_cache(0, Arc::clone(&self.block_device))
+            .lock()
+            .modify(0, |super_block: &mut SuperBlock| {
+                super_block.free_inodes = free_inodes;
+                super_block.free_blocks = free_blocks;
+            });
     }
     /// Get inode by id
     pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
-        let inode_size = core::mem::size_of::<DiskInode>();
-        let inodes_per_block = (BLOCK_SZ / inode_size) as u32;
+        let inode_size = DiskInode::disk_size(self.version);
+        let inodes_per_block = (self.block_size / inode_size) as u32;
         let block_id = self.inode_area_start_block + inode_id / inodes_per_block;
         (
             block_id,
             (inode_id % inodes_per_block) as usize * inode_size,
         )
     }
+    /// Get inode id by the position of its disk inode
+    pub fn get_inode_id(&self, block_id: u32, block_offset: usize) -> u32 {
+        let inode_size = DiskInode::disk_size(self.version);
+        let inodes_per_block = (self.block_size / inode_size) as u32;
+        (block_id - self.inode_area_start_block) * inodes_per_block
+            + (block_offset / inode_size) as u32
+    }
     /// Get data block by id
     pub fn get_data_block_id(&self, data_block_id: u32) -> u32 {
         self.data_area_start_block + data_block_id
     }
-    /// Allocate a new inode
-    pub fn alloc_inode(&mut self) -> u32 {
-        self.inode_bitmap.alloc(&self.block_device).unwrap() as u32
+    /// Allocate a new inode, or `None` if all inodes are in use
+    pub fn alloc_inode(&mut self) -> Option<u32> {
+        if self.free_inodes == 0 {
+            return None;
+        }
+        let inode_id = self.inode_bitmap.alloc(&self.block_device)? as u32;
+        self.free_inodes -= 1;
+        self.sync_counters();
+        Some(inode_id)
     }
 
-    /// Allocate a data block
-    pub fn alloc_data(&mut self) -> u32 {
-        self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_area_start_block
+    /// Allocate a data block, or `None` if the disk is full
+    pub fn alloc_data(&mut self) -> Option<u32> {
+        // the last data bitmap block may describe more bits than there are
+        // data blocks, so the counter is what bounds the allocation
+        if self.free_blocks == 0 {
+            return None;
+        }
+        let block_id = self.data_bitmap.alloc(&self.block_device)? as u32;
+        self.free_blocks -= 1;
+        self.sync_counters();
+        Some(block_id + self.data_area_start_block)
     }
     /// Deallocate a data block
     pub fn dealloc_data(&mut self, block_id: u32) {
         get_block_cache(block_id as usize, Arc::clone(&self.block_device))
             .lock()
-            .modify(0, |data_block: &mut DataBlock| {
+            .modify_slice(|data_block: &mut [u8]| {
                 data_block.iter_mut().for_each(|p| {
                     *p = 0;
                 })
@@ -148,6 +308,8 @@ impl EasyFileSystem {
         self.data_bitmap.dealloc(
             &self.block_device,
             (block_id - self.data_area_start_block) as usize,
-        )
+        );
+        self.free_blocks += 1;
+        self.sync_counters();
     }
 }
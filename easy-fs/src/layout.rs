@@ -4,22 +4,21 @@ use core::fmt::{Debug, Formatter, Result};
 use super::{get_block_cache, BlockDevice, BLOCK_SZ};
 
 /// Magic number for sanity check
-const EFS_MAGIC: u32 = 0x3b800001;
+pub const EFS_MAGIC: u32 = 0x3b800001;
+/// The first on-disk format: 512-byte blocks, 128-byte inodes with
+/// direct/indirect1/indirect2 pointers and fixed 32-byte directory entries
+pub const EFS_VERSION_1: u32 = 1;
+/// The current on-disk format: configurable block size, 256-byte inodes with
+/// an additional indirect3 pointer and variable-length directory entries
+pub const EFS_VERSION_2: u32 = 2;
+/// Version written by `EasyFileSystem::create`
+pub const EFS_VERSION: u32 = EFS_VERSION_2;
 /// The max number of direct inodes
 const INODE_DIRECT_COUNT: usize = 28;
-/// The max length of inode name
+/// The max length of inode name in a version 1 directory entry
 const NAME_LENGTH_LIMIT: usize = 27;
-/// The max number of indirect1 inodes
-const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
-/// The max number of indirect2 inodes
-const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
-/// The upper bound of direct inode index
-const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
-/// The upper bound of indirect1 inode index
-const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
-/// The upper bound of indirect2 inode indexs
-#[allow(unused)]
-const INDIRECT2_BOUND: usize = INDIRECT1_BOUND + INODE_INDIRECT2_COUNT;
+/// The max length of inode name in a version 2 directory entry
+pub const NAME_LENGTH_LIMIT_V2: usize = 255;
 /// Super block of a filesystem
 #[repr(C)]
 pub struct SuperBlock {
@@ -29,16 +28,25 @@ pub struct SuperBlock {
     pub inode_area_blocks: u32,
     pub data_bitmap_blocks: u32,
     pub data_area_blocks: u32,
+    // fields below are zero on version 1 images
+    version: u32,
+    block_size: u32,
+    pub free_inodes: u32,
+    pub free_blocks: u32,
 }
 
 impl Debug for SuperBlock {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         f.debug_struct("SuperBlock")
+            .field("version", &self.version())
+            .field("block_size", &self.block_size())
             .field("total_blocks", &self.total_blocks)
             .field("inode_bitmap_blocks", &self.inode_bitmap_blocks)
             .field("inode_area_blocks", &self.inode_area_blocks)
             .field("data_bitmap_blocks", &self.data_bitmap_blocks)
             .field("data_area_blocks", &self.data_area_blocks)
+            .field("free_inodes", &self.free_inodes)
+            .field("free_blocks", &self.free_blocks)
             .finish()
     }
 }
@@ -47,6 +55,7 @@ impl SuperBlock {
     /// Initialize a super block
     pub fn initialize(
         &mut self,
+        block_size: u32,
         total_blocks: u32,
         inode_bitmap_blocks: u32,
         inode_area_blocks: u32,
@@ -60,24 +69,52 @@ impl SuperBlock {
             inode_area_blocks,
             data_bitmap_blocks,
             data_area_blocks,
+            version: EFS_VERSION,
+            block_size,
+            free_inodes: 0,
+            free_blocks: data_area_blocks,
         }
     }
     /// Check if a super block is valid using efs magic
     pub fn is_valid(&self) -> bool {
-        self.magic == EFS_MAGIC
+        self.magic == EFS_MAGIC && (EFS_VERSION_1..=EFS_VERSION).contains(&self.version())
+    }
+    /// On-disk format version
+    pub fn version(&self) -> u32 {
+        if self.version == 0 {
+            EFS_VERSION_1
+        } else {
+            self.version
+        }
+    }
+    /// Size of a filesystem block in bytes
+    pub fn block_size(&self) -> usize {
+        if self.version() == EFS_VERSION_1 {
+            BLOCK_SZ
+        } else {
+            self.block_size as usize
+        }
     }
 }
 /// Type of a disk inode
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum DiskInodeType {
     File,
     Directory,
+    /// A named pipe, which never holds any data on disk
+    Fifo,
+}
+
+/// A version 1 disk inode, converted to and from [`DiskInode`] on access
+#[repr(C)]
+pub struct DiskInodeV1 {
+    size: u32,
+    direct: [u32; INODE_DIRECT_COUNT],
+    indirect1: u32,
+    indirect2: u32,
+    type_: DiskInodeType,
 }
 
-/// A indirect block
-type IndirectBlock = [u32; BLOCK_SZ / 4];
-/// A data block
-type DataBlock = [u8; BLOCK_SZ];
 /// A disk inode
 #[repr(C)]
 pub struct DiskInode {
@@ -87,16 +124,68 @@ pub struct DiskInode {
     pub indirect1: u32,
     pub indirect2: u32,
     type_: DiskInodeType,
+    pub indirect3: u32,
+    _reserved: [u32; 31],
 }
 
 impl DiskInode {
+    /// Size of an on-disk inode in the given format version
+    pub fn disk_size(version: u32) -> usize {
+        if version == EFS_VERSION_1 {
+            core::mem::size_of::<DiskInodeV1>()
+        } else {
+            core::mem::size_of::<DiskInode>()
+        }
+    }
+    /// Number of index levels below the direct pointers in the given format
+    /// version
+    pub fn index_levels(version: u32) -> u32 {
+        if version == EFS_VERSION_1 {
+            2
+        } else {
+            3
+        }
+    }
+    /// Largest file size supported by the given format version and block size
+    pub fn max_size(version: u32, block_size: usize) -> usize {
+        let ptrs = block_size / 4;
+        let mut blocks = INODE_DIRECT_COUNT;
+        for level in 1..=Self::index_levels(version) {
+            blocks = blocks.saturating_add(ptrs.saturating_pow(level));
+        }
+        blocks.saturating_mul(block_size).min(u32::MAX as usize)
+    }
+    /// Build an in-memory inode from a version 1 one
+    pub fn from_v1(v1: &DiskInodeV1) -> Self {
+        Self {
+            size: v1.size,
+            direct: v1.direct,
+            indirect1: v1.indirect1,
+            indirect2: v1.indirect2,
+            type_: v1.type_,
+            indirect3: 0,
+            _reserved: [0; 31],
+        }
+    }
+    /// Write this inode back in the version 1 format, which has no room for
+    /// indirect3: growth is capped at the version 1 maximum size
+    pub fn store_v1(&self, v1: &mut DiskInodeV1) {
+        assert_eq!(self.indirect3, 0);
+        v1.size = self.size;
+        v1.direct = self.direct;
+        v1.indirect1 = self.indirect1;
+        v1.indirect2 = self.indirect2;
+        v1.type_ = self.type_;
+    }
     /// Initialize a disk inode, as well as all direct inodes under it
-    /// indirect1 and indirect2 block are allocated only when they are needed
+    /// indirect1, indirect2 and indirect3 block are allocated only when they
+    /// are needed
     pub fn initialize(&mut self, type_: DiskInodeType) {
         self.size = 0;
         self.direct.iter_mut().for_each(|v| *v = 0);
         self.indirect1 = 0;
         self.indirect2 = 0;
+        self.indirect3 = 0;
         self.type_ = type_;
     }
     /// Whether this inode is a directory
@@ -108,59 +197,142 @@ impl DiskInode {
     pub fn is_file(&self) -> bool {
         self.type_ == DiskInodeType::File
     }
+    /// Whether this inode is a named pipe
+    pub fn is_fifo(&self) -> bool {
+        self.type_ == DiskInodeType::Fifo
+    }
     /// Return block number correspond to size.
-    pub fn data_blocks(&self) -> u32 {
-        Self::_data_blocks(self.size)
-    }
-    fn _data_blocks(size: u32) -> u32 {
-        (size + BLOCK_SZ as u32 - 1) / BLOCK_SZ as u32
-    }
-    /// Return number of blocks needed include indirect1/2.
-    pub fn total_blocks(size: u32) -> u32 {
-        let data_blocks = Self::_data_blocks(size) as usize;
-        let mut total = data_blocks as usize;
-        // indirect1
-        if data_blocks > INODE_DIRECT_COUNT {
-            total += 1;
-        }
-        // indirect2
-        if data_blocks > INDIRECT1_BOUND {
-            total += 1;
-            // sub indirect1
-            total +=
-                (data_blocks - INDIRECT1_BOUND + INODE_INDIRECT1_COUNT - 1) / INODE_INDIRECT1_COUNT;
+    pub fn data_blocks(&self, block_size: usize) -> u32 {
+        Self::_data_blocks(self.size, block_size)
+    }
+    fn _data_blocks(size: u32, block_size: usize) -> u32 {
+        ((size as usize + block_size - 1) / block_size) as u32
+    }
+    /// Return number of blocks needed include indirect1/2/3.
+    pub fn total_blocks(size: u32, block_size: usize) -> u32 {
+        let ptrs = block_size / 4;
+        let mut remaining = Self::_data_blocks(size, block_size) as usize;
+        let mut total = remaining;
+        remaining = remaining.saturating_sub(INODE_DIRECT_COUNT);
+        let mut level = 1;
+        while remaining > 0 {
+            let capacity = ptrs.pow(level);
+            let leaves = remaining.min(capacity);
+            // index blocks at each depth of a tree with `leaves` leaves
+            for depth in 0..level {
+                let span = ptrs.pow(level - depth);
+                total += (leaves + span - 1) / span;
+            }
+            remaining -= leaves;
+            level += 1;
         }
         total as u32
     }
     /// Get the number of data blocks that have to be allocated given the new
     /// size of data
-    pub fn blocks_num_needed(&self, new_size: u32) -> u32 {
+    pub fn blocks_num_needed(&self, new_size: u32, block_size: usize) -> u32 {
         assert!(new_size >= self.size);
-        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+        Self::total_blocks(new_size, block_size) - Self::total_blocks(self.size, block_size)
+    }
+    /// The largest size not above `limit` this inode can grow to when at most
+    /// `free_blocks` more blocks can be allocated and files of the format end
+    /// at `max_size`
+    pub fn grow_limit(
+        &self,
+        limit: usize,
+        max_size: usize,
+        free_blocks: u32,
+        block_size: usize,
+    ) -> u32 {
+        let limit = limit.min(max_size) as u32;
+        if limit <= self.size || self.blocks_num_needed(limit, block_size) <= free_blocks {
+            return limit;
+        }
+        // binary search for the largest number of data blocks that fits,
+        // `lo` always fits and `hi` never does
+        let mut lo = self.data_blocks(block_size) as usize;
+        let mut hi = Self::_data_blocks(limit, block_size) as usize;
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            if self.blocks_num_needed((mid * block_size) as u32, block_size) <= free_blocks {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        ((lo * block_size) as u32).max(self.size)
+    }
+    /// Locate the index tree holding data block `inner_id`: returns the root
+    /// slot, the depth of the tree and the leaf index inside it
+    fn locate(&mut self, inner_id: usize, block_size: usize) -> (&mut u32, u32, usize) {
+        let ptrs = block_size / 4;
+        if inner_id < INODE_DIRECT_COUNT {
+            return (&mut self.direct[inner_id], 0, 0);
+        }
+        let mut idx = inner_id - INODE_DIRECT_COUNT;
+        if idx < ptrs {
+            return (&mut self.indirect1, 1, idx);
+        }
+        idx -= ptrs;
+        if idx < ptrs * ptrs {
+            return (&mut self.indirect2, 2, idx);
+        }
+        idx -= ptrs * ptrs;
+        assert!(idx < ptrs * ptrs * ptrs);
+        (&mut self.indirect3, 3, idx)
     }
     /// Get id of block given inner id
     pub fn get_block_id(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        let block_size = block_device.block_size();
+        let ptrs = block_size / 4;
         let inner_id = inner_id as usize;
-        if inner_id < INODE_DIRECT_COUNT {
-            self.direct[inner_id]
-        } else if inner_id < INDIRECT1_BOUND {
-            get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-                .lock()
-                .read(0, |indirect_block: &IndirectBlock| {
-                    indirect_block[inner_id - INODE_DIRECT_COUNT]
-                })
+        let (mut block, mut idx, level) = if inner_id < INODE_DIRECT_COUNT {
+            return self.direct[inner_id];
+        } else if inner_id < INODE_DIRECT_COUNT + ptrs {
+            (self.indirect1, inner_id - INODE_DIRECT_COUNT, 1)
+        } else if inner_id < INODE_DIRECT_COUNT + ptrs + ptrs * ptrs {
+            (self.indirect2, inner_id - INODE_DIRECT_COUNT - ptrs, 2)
         } else {
-            let last = inner_id - INDIRECT1_BOUND;
-            let indirect1 = get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+            (
+                self.indirect3,
+                inner_id - INODE_DIRECT_COUNT - ptrs - ptrs * ptrs,
+                3,
+            )
+        };
+        for depth in (0..level).rev() {
+            let span = ptrs.pow(depth);
+            block = get_block_cache(block as usize, Arc::clone(block_device))
                 .lock()
-                .read(0, |indirect2: &IndirectBlock| {
-                    indirect2[last / INODE_INDIRECT1_COUNT]
-                });
-            get_block_cache(indirect1 as usize, Arc::clone(block_device))
+                .read_slice(|indirect: &[u32]| indirect[idx / span]);
+            idx %= span;
+        }
+        block
+    }
+    /// Fill the leaf `idx` of a `level`-deep index tree rooted at `root`,
+    /// taking index blocks that are still missing from `new_blocks` on the way
+    fn fill(
+        root: &mut u32,
+        level: u32,
+        idx: usize,
+        new_blocks: &mut impl Iterator<Item = u32>,
+        block_device: &Arc<dyn BlockDevice>,
+    ) {
+        if *root == 0 {
+            *root = new_blocks.next().unwrap();
+        }
+        if level == 0 {
+            return;
+        }
+        let span = (block_device.block_size() / 4).pow(level - 1);
+        let cache = get_block_cache(*root as usize, Arc::clone(block_device));
+        let mut child = cache
+            .lock()
+            .read_slice(|indirect: &[u32]| indirect[idx / span]);
+        if child == 0 || level > 1 {
+            Self::fill(&mut child, level - 1, idx % span, new_blocks, block_device);
+            cache
                 .lock()
-                .read(0, |indirect1: &IndirectBlock| {
-                    indirect1[last % INODE_INDIRECT1_COUNT]
-                })
+                .modify_slice(|indirect: &mut [u32]| indirect[idx / span] = child);
         }
     }
     /// Inncrease the size of current disk inode
@@ -170,144 +342,70 @@ impl DiskInode {
         new_blocks: Vec<u32>,
         block_device: &Arc<dyn BlockDevice>,
     ) {
-        let mut current_blocks = self.data_blocks();
+        let block_size = block_device.block_size();
+        let current_blocks = self.data_blocks(block_size);
         self.size = new_size;
-        let mut total_blocks = self.data_blocks();
+        let total_blocks = self.data_blocks(block_size);
         let mut new_blocks = new_blocks.into_iter();
-        // fill direct
-        while current_blocks < total_blocks.min(INODE_DIRECT_COUNT as u32) {
-            self.direct[current_blocks as usize] = new_blocks.next().unwrap();
-            current_blocks += 1;
-        }
-        // alloc indirect1
-        if total_blocks > INODE_DIRECT_COUNT as u32 {
-            if current_blocks == INODE_DIRECT_COUNT as u32 {
-                self.indirect1 = new_blocks.next().unwrap();
-            }
-            current_blocks -= INODE_DIRECT_COUNT as u32;
-            total_blocks -= INODE_DIRECT_COUNT as u32;
-        } else {
-            return;
+        for inner_id in current_blocks..total_blocks {
+            let (root, level, idx) = self.locate(inner_id as usize, block_size);
+            Self::fill(root, level, idx, &mut new_blocks, block_device);
         }
-        // fill indirect1
-        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect1: &mut IndirectBlock| {
-                while current_blocks < total_blocks.min(INODE_INDIRECT1_COUNT as u32) {
-                    indirect1[current_blocks as usize] = new_blocks.next().unwrap();
-                    current_blocks += 1;
-                }
-            });
-        // alloc indirect2
-        if total_blocks > INODE_INDIRECT1_COUNT as u32 {
-            if current_blocks == INODE_INDIRECT1_COUNT as u32 {
-                self.indirect2 = new_blocks.next().unwrap();
+        assert!(new_blocks.next().is_none());
+    }
+    /// Collect every block of a `level`-deep index tree rooted at `root` that
+    /// holds `leaves` data blocks
+    fn collect(
+        root: u32,
+        level: u32,
+        leaves: usize,
+        v: &mut Vec<u32>,
+        block_device: &Arc<dyn BlockDevice>,
+    ) {
+        if level > 0 {
+            let span = (block_device.block_size() / 4).pow(level - 1);
+            let children = (leaves + span - 1) / span;
+            let entries: Vec<u32> = get_block_cache(root as usize, Arc::clone(block_device))
+                .lock()
+                .read_slice(|indirect: &[u32]| indirect[..children].to_vec());
+            for (i, child) in entries.into_iter().enumerate() {
+                let child_leaves = span.min(leaves - i * span);
+                Self::collect(child, level - 1, child_leaves, v, block_device);
             }
-            current_blocks -= INODE_INDIRECT1_COUNT as u32;
-            total_blocks -= INODE_INDIRECT1_COUNT as u32;
-        } else {
-            return;
         }
-        // fill indirect2 from (a0, b0) -> (a1, b1)
-        let mut a0 = current_blocks as usize / INODE_INDIRECT1_COUNT;
-        let mut b0 = current_blocks as usize % INODE_INDIRECT1_COUNT;
-        let a1 = total_blocks as usize / INODE_INDIRECT1_COUNT;
-        let b1 = total_blocks as usize % INODE_INDIRECT1_COUNT;
-        // alloc low-level indirect1
-        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect2: &mut IndirectBlock| {
-                while (a0 < a1) || (a0 == a1 && b0 < b1) {
-                    if b0 == 0 {
-                        indirect2[a0] = new_blocks.next().unwrap();
-                    }
-                    // fill current
-                    get_block_cache(indirect2[a0] as usize, Arc::clone(block_device))
-                        .lock()
-                        .modify(0, |indirect1: &mut IndirectBlock| {
-                            indirect1[b0] = new_blocks.next().unwrap();
-                        });
-                    // move to next
-                    b0 += 1;
-                    if b0 == INODE_INDIRECT1_COUNT {
-                        b0 = 0;
-                        a0 += 1;
-                    }
-                }
-            });
+        v.push(root);
     }
 
     /// Clear size to zero and return blocks that should be deallocated.
     /// We will clear the block contents to zero later.
     pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let block_size = block_device.block_size();
+        let ptrs = block_size / 4;
         let mut v: Vec<u32> = Vec::new();
-        let mut data_blocks = self.data_blocks() as usize;
+        let mut data_blocks = self.data_blocks(block_size) as usize;
         self.size = 0;
-        let mut current_blocks = 0usize;
         // direct
-        while current_blocks < data_blocks.min(INODE_DIRECT_COUNT) {
-            v.push(self.direct[current_blocks]);
-            self.direct[current_blocks] = 0;
-            current_blocks += 1;
+        for direct in self.direct.iter_mut().take(data_blocks) {
+            v.push(*direct);
+            *direct = 0;
         }
-        // indirect1 block
-        if data_blocks > INODE_DIRECT_COUNT {
-            v.push(self.indirect1);
-            data_blocks -= INODE_DIRECT_COUNT;
-            current_blocks = 0;
-        } else {
-            return v;
-        }
-        // indirect1
-        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect1: &mut IndirectBlock| {
-                while current_blocks < data_blocks.min(INODE_INDIRECT1_COUNT) {
-                    v.push(indirect1[current_blocks]);
-                    //indirect1[current_blocks] = 0;
-                    current_blocks += 1;
-                }
-            });
-        self.indirect1 = 0;
-        // indirect2 block
-        if data_blocks > INODE_INDIRECT1_COUNT {
-            v.push(self.indirect2);
-            data_blocks -= INODE_INDIRECT1_COUNT;
-        } else {
-            return v;
+        data_blocks = data_blocks.saturating_sub(INODE_DIRECT_COUNT);
+        // indirect1/2/3
+        let roots = [
+            &mut self.indirect1,
+            &mut self.indirect2,
+            &mut self.indirect3,
+        ];
+        for (i, root) in roots.into_iter().enumerate() {
+            if data_blocks == 0 {
+                break;
+            }
+            let level = i as u32 + 1;
+            let leaves = data_blocks.min(ptrs.pow(level));
+            Self::collect(*root, level, leaves, &mut v, block_device);
+            *root = 0;
+            data_blocks -= leaves;
         }
-        // indirect2
-        assert!(data_blocks <= INODE_INDIRECT2_COUNT);
-        let a1 = data_blocks / INODE_INDIRECT1_COUNT;
-        let b1 = data_blocks % INODE_INDIRECT1_COUNT;
-        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect2: &mut IndirectBlock| {
-                // full indirect1 blocks
-                for entry in indirect2.iter_mut().take(a1) {
-                    v.push(*entry);
-                    get_block_cache(*entry as usize, Arc::clone(block_device))
-                        .lock()
-                        .modify(0, |indirect1: &mut IndirectBlock| {
-                            for entry in indirect1.iter() {
-                                v.push(*entry);
-                            }
-                        });
-                }
-                // last indirect1 block
-                if b1 > 0 {
-                    v.push(indirect2[a1]);
-                    get_block_cache(indirect2[a1] as usize, Arc::clone(block_device))
-                        .lock()
-                        .modify(0, |indirect1: &mut IndirectBlock| {
-                            for entry in indirect1.iter().take(b1) {
-                                v.push(*entry);
-                            }
-                        });
-                    //indirect2[a1] = 0;
-                }
-            });
-        self.indirect2 = 0;
         v
     }
     /// Read data from current disk inode
@@ -317,16 +415,17 @@ impl DiskInode {
         buf: &mut [u8],
         block_device: &Arc<dyn BlockDevice>,
     ) -> usize {
+        let block_size = block_device.block_size();
         let mut start = offset;
         let end = (offset + buf.len()).min(self.size as usize);
         if start >= end {
             return 0;
         }
-        let mut start_block = start / BLOCK_SZ;
+        let mut start_block = start / block_size;
         let mut read_size = 0usize;
         loop {
             // calculate end of current block
-            let mut end_current_block = (start / BLOCK_SZ + 1) * BLOCK_SZ;
+            let mut end_current_block = (start / block_size + 1) * block_size;
             end_current_block = end_current_block.min(end);
             // read and update read size
             let block_read_size = end_current_block - start;
@@ -336,8 +435,8 @@ impl DiskInode {
                 Arc::clone(block_device),
             )
             .lock()
-            .read(0, |data_block: &DataBlock| {
-                let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
+            .read_slice(|data_block: &[u8]| {
+                let src = &data_block[start % block_size..start % block_size + block_read_size];
                 dst.copy_from_slice(src);
             });
             read_size += block_read_size;
@@ -358,14 +457,18 @@ impl DiskInode {
         buf: &[u8],
         block_device: &Arc<dyn BlockDevice>,
     ) -> usize {
+        let block_size = block_device.block_size();
         let mut start = offset;
         let end = (offset + buf.len()).min(self.size as usize);
         assert!(start <= end);
-        let mut start_block = start / BLOCK_SZ;
+        if start == end {
+            return 0;
+        }
+        let mut start_block = start / block_size;
         let mut write_size = 0usize;
         loop {
             // calculate end of current block
-            let mut end_current_block = (start / BLOCK_SZ + 1) * BLOCK_SZ;
+            let mut end_current_block = (start / block_size + 1) * block_size;
             end_current_block = end_current_block.min(end);
             // write and update write size
             let block_write_size = end_current_block - start;
@@ -374,9 +477,10 @@ impl DiskInode {
                 Arc::clone(block_device),
             )
             .lock()
-            .modify(0, |data_block: &mut DataBlock| {
+            .modify_slice(|data_block: &mut [u8]| {
                 let src = &buf[write_size..write_size + block_write_size];
-                let dst = &mut data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_write_size];
+                let dst =
+                    &mut data_block[start % block_size..start % block_size + block_write_size];
                 dst.copy_from_slice(src);
             });
             write_size += block_write_size;
@@ -433,4 +537,64 @@ impl DirEntry {
     pub fn inode_number(&self) -> u32 {
         self.inode_number
     }
+    /// Whether `name` fits into a version 1 directory entry
+    pub fn name_fits(name: &str) -> bool {
+        name.len() <= NAME_LENGTH_LIMIT
+    }
+    /// The max length of a name in a version 1 directory entry
+    pub fn name_limit() -> usize {
+        NAME_LENGTH_LIMIT
+    }
+}
+
+/// Fixed header of a version 2 directory entry, followed by `name_len`
+/// bytes of name and padding up to `rec_len`
+#[repr(C)]
+pub struct DirEntryHeader {
+    pub inode_number: u32,
+    pub rec_len: u16,
+    pub name_len: u8,
+    _reserved: u8,
+}
+/// Size of a version 2 directory entry header
+pub const DIRENT_HEADER_SZ: usize = 8;
+
+impl DirEntryHeader {
+    /// Create an empty directory entry header
+    pub fn empty() -> Self {
+        Self {
+            inode_number: 0,
+            rec_len: 0,
+            name_len: 0,
+            _reserved: 0,
+        }
+    }
+    /// Serialize a whole entry for `name` and `inode_number`
+    pub fn encode(name: &str, inode_number: u32) -> Vec<u8> {
+        assert!(name.len() <= NAME_LENGTH_LIMIT_V2);
+        let rec_len = (DIRENT_HEADER_SZ + name.len() + 3) & !3;
+        let header = Self {
+            inode_number,
+            rec_len: rec_len as u16,
+            name_len: name.len() as u8,
+            _reserved: 0,
+        };
+        let mut v = Vec::with_capacity(rec_len);
+        v.extend_from_slice(header.as_bytes());
+        v.extend_from_slice(name.as_bytes());
+        v.resize(rec_len, 0);
+        v
+    }
+    /// Serialize into bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self as *const _ as usize as *const u8, DIRENT_HEADER_SZ)
+        }
+    }
+    /// Serialize into mutable bytes
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(self as *mut _ as usize as *mut u8, DIRENT_HEADER_SZ)
+        }
+    }
 }
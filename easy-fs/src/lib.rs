@@ -8,11 +8,13 @@ mod block_dev;
 mod efs;
 mod layout;
 mod vfs;
-/// Use a block size of 512 bytes
+/// Use a block size of 512 bytes, the sector size of block devices and the
+/// default filesystem block size
 pub const BLOCK_SZ: usize = 512;
 use bitmap::Bitmap;
 use block_cache::{block_cache_sync_all, get_block_cache};
-pub use block_dev::BlockDevice;
-pub use efs::EasyFileSystem;
+pub use block_dev::{BlockDevice, ScaledBlockDevice};
+pub use efs::{EasyFileSystem, FsStat};
 use layout::*;
+pub use layout::{EFS_MAGIC, EFS_VERSION, EFS_VERSION_1, EFS_VERSION_2, NAME_LENGTH_LIMIT_V2};
 pub use vfs::Inode;
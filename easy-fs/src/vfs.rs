@@ -1,62 +1,154 @@
-use alloc::{string::String, sync::Arc, vec::Vec};
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
 
 use spin::{Mutex, MutexGuard};
 
 use super::{
-    block_cache_sync_all, get_block_cache, BlockDevice, DirEntry, DiskInode, DiskInodeType,
-    EasyFileSystem, DIRENT_SZ,
+    block_cache_sync_all, get_block_cache, BlockDevice, DirEntry, DirEntryHeader, DiskInode,
+    DiskInodeType, DiskInodeV1, EasyFileSystem, FsStat, DIRENT_HEADER_SZ, DIRENT_SZ, EFS_VERSION_1,
+    NAME_LENGTH_LIMIT_V2,
 };
 /// Virtual filesystem layer over easy-fs
 pub struct Inode {
     block_id: usize,
     block_offset: usize,
+    version: u32,
     fs: Arc<Mutex<EasyFileSystem>>,
     block_device: Arc<dyn BlockDevice>,
 }
 
+/// Call a function over the disk inode at the given position, converting it
+/// from the on-disk format of `version` if needed
+fn read_disk_inode_at<V>(
+    block_id: usize,
+    block_offset: usize,
+    version: u32,
+    block_device: &Arc<dyn BlockDevice>,
+    f: impl FnOnce(&DiskInode) -> V,
+) -> V {
+    let cache = get_block_cache(block_id, Arc::clone(block_device));
+    let cache = cache.lock();
+    if version == EFS_VERSION_1 {
+        cache.read(block_offset, |v1: &DiskInodeV1| f(&DiskInode::from_v1(v1)))
+    } else {
+        cache.read(block_offset, f)
+    }
+}
+
+/// Call a function over the disk inode at the given position to modify it,
+/// converting it from and back to the on-disk format of `version` if needed
+fn modify_disk_inode_at<V>(
+    block_id: usize,
+    block_offset: usize,
+    version: u32,
+    block_device: &Arc<dyn BlockDevice>,
+    f: impl FnOnce(&mut DiskInode) -> V,
+) -> V {
+    let cache = get_block_cache(block_id, Arc::clone(block_device));
+    let mut cache = cache.lock();
+    if version == EFS_VERSION_1 {
+        cache.modify(block_offset, |v1: &mut DiskInodeV1| {
+            let mut disk_inode = DiskInode::from_v1(v1);
+            let ret = f(&mut disk_inode);
+            disk_inode.store_v1(v1);
+            ret
+        })
+    } else {
+        cache.modify(block_offset, f)
+    }
+}
+
 impl Inode {
     /// Create a vfs inode
     pub fn new(
         block_id: u32,
         block_offset: usize,
+        version: u32,
         fs: Arc<Mutex<EasyFileSystem>>,
         block_device: Arc<dyn BlockDevice>,
     ) -> Self {
         Self {
             block_id: block_id as usize,
             block_offset,
+            version,
             fs,
             block_device,
         }
     }
     /// Call a function over a disk inode to read it
     fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
-        get_block_cache(self.block_id, Arc::clone(&self.block_device))
-            .lock()
-            .read(self.block_offset, f)
+        read_disk_inode_at(
+            self.block_id,
+            self.block_offset,
+            self.version,
+            &self.block_device,
+            f,
+        )
     }
     /// Call a function over a disk inode to modify it
     fn modify_disk_inode<V>(&self, f: impl FnOnce(&mut DiskInode) -> V) -> V {
-        get_block_cache(self.block_id, Arc::clone(&self.block_device))
-            .lock()
-            .modify(self.block_offset, f)
+        modify_disk_inode_at(
+            self.block_id,
+            self.block_offset,
+            self.version,
+            &self.block_device,
+            f,
+        )
     }
-    /// Find inode under a disk inode by name
-    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+    /// List the (name, inode number) pairs of a directory disk inode
+    fn dir_entries(&self, disk_inode: &DiskInode) -> Vec<(String, u32)> {
         // assert it is a directory
         assert!(disk_inode.is_dir());
-        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
-        let mut dirent = DirEntry::empty();
-        for i in 0..file_count {
-            assert_eq!(
-                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device,),
-                DIRENT_SZ,
-            );
-            if dirent.name() == name {
-                return Some(dirent.inode_number() as u32);
+        let mut data = vec![0u8; disk_inode.size as usize];
+        assert_eq!(
+            disk_inode.read_at(0, &mut data, &self.block_device),
+            data.len()
+        );
+        let mut v = Vec::new();
+        if self.version == EFS_VERSION_1 {
+            let mut dirent = DirEntry::empty();
+            for raw in data.chunks_exact(DIRENT_SZ) {
+                dirent.as_bytes_mut().copy_from_slice(raw);
+                v.push((dirent.name().to_string(), dirent.inode_number()));
+            }
+        } else {
+            let mut header = DirEntryHeader::empty();
+            let mut offset = 0usize;
+            while offset + DIRENT_HEADER_SZ <= data.len() {
+                header
+                    .as_bytes_mut()
+                    .copy_from_slice(&data[offset..offset + DIRENT_HEADER_SZ]);
+                let rec_len = header.rec_len as usize;
+                let name_len = header.name_len as usize;
+                // a broken entry hides where the next one starts, so the
+                // rest of the directory is unreadable
+                if rec_len < DIRENT_HEADER_SZ
+                    || rec_len > data.len() - offset
+                    || name_len > rec_len - DIRENT_HEADER_SZ
+                {
+                    break;
+                }
+                let name_start = offset + DIRENT_HEADER_SZ;
+                let name = &data[name_start..name_start + name_len];
+                // entries whose name is not UTF-8 are skipped
+                if let Ok(name) = core::str::from_utf8(name) {
+                    v.push((String::from(name), header.inode_number));
+                }
+                offset += rec_len;
             }
         }
-        None
+        v
+    }
+    /// Find inode under a disk inode by name
+    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        self.dir_entries(disk_inode)
+            .into_iter()
+            .find(|(entry_name, _)| entry_name == name)
+            .map(|(_, inode_id)| inode_id)
     }
     /// Find inode under current inode by name
     pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
@@ -67,13 +159,16 @@ impl Inode {
                 Arc::new(Self::new(
                     block_id,
                     block_offset,
+                    self.version,
                     self.fs.clone(),
                     self.block_device.clone(),
                 ))
             })
         })
     }
-    /// Increase the size of a disk inode
+    /// Increase the size of a disk inode, the caller must have checked with
+    /// `grow_limit` that there are enough free blocks and the format can hold
+    /// the new size
     fn increase_size(
         &self,
         new_size: u32,
@@ -83,48 +178,102 @@ impl Inode {
         if new_size < disk_inode.size {
             return;
         }
-        let blocks_needed = disk_inode.blocks_num_needed(new_size);
+        assert!(new_size as usize <= fs.max_file_size());
+        let blocks_needed = disk_inode.blocks_num_needed(new_size, fs.block_size());
+        assert!(blocks_needed <= fs.free_blocks());
         let mut v: Vec<u32> = Vec::new();
         for _ in 0..blocks_needed {
-            v.push(fs.alloc_data());
+            v.push(fs.alloc_data().unwrap());
         }
         disk_inode.increase_size(new_size, v, &self.block_device);
     }
-    /// Create inode under current inode by name
+    /// Create inode under current inode by name, `None` if the name is taken
+    /// or invalid, or the filesystem is out of inodes or blocks
     pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::File)
+    }
+    /// Create a named pipe under current inode by name
+    pub fn create_fifo(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Fifo)
+    }
+    /// Inode number of current inode
+    pub fn inode_id(&self) -> u32 {
+        self.fs
+            .lock()
+            .get_inode_id(self.block_id as u32, self.block_offset)
+    }
+    /// Size of current inode in bytes
+    pub fn size(&self) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+    /// Whether current inode is a directory
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+    /// Whether current inode is a named pipe
+    pub fn is_fifo(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_fifo())
+    }
+    fn create_inode(&self, name: &str, type_: DiskInodeType) -> Option<Arc<Inode>> {
+        let name_fits = if self.version == EFS_VERSION_1 {
+            DirEntry::name_fits(name)
+        } else {
+            name.len() <= NAME_LENGTH_LIMIT_V2
+        };
+        if !name_fits {
+            return None;
+        }
+        let dirent = if self.version == EFS_VERSION_1 {
+            DirEntry::new(name, 0).as_bytes().to_vec()
+        } else {
+            DirEntryHeader::encode(name, 0)
+        };
         let mut fs = self.fs.lock();
         let op = |root_inode: &DiskInode| {
             // assert it is a directory
             assert!(root_inode.is_dir());
+            // is there room for one more dirent?
+            let new_size = root_inode.size as usize + dirent.len();
+            let end = root_inode.grow_limit(
+                new_size,
+                fs.max_file_size(),
+                fs.free_blocks(),
+                fs.block_size(),
+            );
             // has the file been created?
-            self.find_inode_id(name, root_inode)
+            self.find_inode_id(name, root_inode).is_none() && end as usize == new_size
         };
-        if self.read_disk_inode(op).is_some() {
+        if !self.read_disk_inode(op) {
             return None;
         }
         // create a new file
         // alloc a inode with an indirect block
-        let new_inode_id = fs.alloc_inode();
+        let new_inode_id = fs.alloc_inode()?;
         // initialize inode
         let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);
-        get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
-            .lock()
-            .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
-                new_inode.initialize(DiskInodeType::File);
-            });
+        modify_disk_inode_at(
+            new_inode_block_id as usize,
+            new_inode_block_offset,
+            self.version,
+            &self.block_device,
+            |new_inode| new_inode.initialize(type_),
+        );
         self.modify_disk_inode(|root_inode| {
             // append file in the dirent
-            let file_count = (root_inode.size as usize) / DIRENT_SZ;
-            let new_size = (file_count + 1) * DIRENT_SZ;
+            let dirent_offset = root_inode.size as usize;
+            let dirent = if self.version == EFS_VERSION_1 {
+                DirEntry::new(name, new_inode_id).as_bytes().to_vec()
+            } else {
+                DirEntryHeader::encode(name, new_inode_id)
+            };
+            let new_size = dirent_offset + dirent.len();
             // increase size
             self.increase_size(new_size as u32, root_inode, &mut fs);
             // write dirent
-            let dirent = DirEntry::new(name, new_inode_id);
-            root_inode.write_at(
-                file_count * DIRENT_SZ,
-                dirent.as_bytes(),
-                &self.block_device,
-            );
+            root_inode.write_at(dirent_offset, &dirent, &self.block_device);
         });
 
         let (block_id, block_offset) = fs.get_disk_inode_pos(new_inode_id);
@@ -133,6 +282,7 @@ impl Inode {
         Some(Arc::new(Self::new(
             block_id,
             block_offset,
+            self.version,
             self.fs.clone(),
             self.block_device.clone(),
         )))
@@ -142,16 +292,11 @@ impl Inode {
     pub fn ls(&self) -> Vec<String> {
         let _fs = self.fs.lock();
         self.read_disk_inode(|disk_inode| {
-            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
-            let mut v: Vec<String> = Vec::new();
-            for i in 0..file_count {
-                let mut dirent = DirEntry::empty();
-                assert_eq!(
-                    disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device,),
-                    DIRENT_SZ,
-                );
-                v.push(String::from(dirent.name()));
-            }
+            let mut v: Vec<String> = self
+                .dir_entries(disk_inode)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect();
             v.sort();
             v
         })
@@ -161,23 +306,41 @@ impl Inode {
         let _fs = self.fs.lock();
         self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
     }
-    /// Write data to current inode
+    /// Write data to current inode, stopping at the largest file size the
+    /// filesystem supports or when the disk is full. Returns the number of
+    /// bytes written, which is 0 if nothing fits.
     pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
         let mut fs = self.fs.lock();
         let size = self.modify_disk_inode(|disk_inode| {
-            self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs);
-            disk_inode.write_at(offset, buf, &self.block_device)
+            let end = disk_inode.grow_limit(
+                offset.saturating_add(buf.len()),
+                fs.max_file_size(),
+                fs.free_blocks(),
+                fs.block_size(),
+            ) as usize;
+            if offset >= end {
+                return 0;
+            }
+            self.increase_size(end as u32, disk_inode, &mut fs);
+            disk_inode.write_at(offset, &buf[..end - offset], &self.block_device)
         });
         block_cache_sync_all();
         size
     }
+    /// Usage statistics of the filesystem holding current inode
+    pub fn fs_stat(&self) -> FsStat {
+        self.fs.lock().statfs()
+    }
     /// Clear the data in current inode
     pub fn clear(&self) {
         let mut fs = self.fs.lock();
+        let block_size = fs.block_size();
         self.modify_disk_inode(|disk_inode| {
             let size = disk_inode.size;
             let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
-            assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
+            assert!(
+                data_blocks_dealloc.len() == DiskInode::total_blocks(size, block_size) as usize
+            );
             for data_block in data_blocks_dealloc.into_iter() {
                 fs.dealloc_data(data_block);
             }
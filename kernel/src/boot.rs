@@ -1,7 +1,13 @@
-use super::main;
+use loongarch64::ipi::{csr_mail_send, send_ipi_single};
 
+use super::{main, main_secondary};
+use crate::config::MAX_CPUS;
+
+const BOOT_STACK_SIZE: usize = 4096 * 16;
+
+/// 每个 CPU 一段启动栈，之后作为它的 idle 控制流的栈
 #[link_section = ".bss.stack"]
-static mut BOOT_STACK: [u8; 4096 * 16] = [0; 4096 * 16];
+static mut BOOT_STACK: [u8; BOOT_STACK_SIZE * MAX_CPUS] = [0; BOOT_STACK_SIZE * MAX_CPUS];
 
 macro_rules! init_dwm {
     () => {
@@ -18,16 +24,10 @@ macro_rules! init_dwm {
         "
     };
 }
-const BOOT_STACK_SIZE: usize = 4096 * 16;
-/// The earliest entry point for the primary CPU.
-///
-/// We can't use bl to jump to higher address, so we use jirl to jump to higher
-/// address.
-#[no_mangle]
-#[link_section = ".text.entry"]
-unsafe extern "C" fn _start() -> ! {
-    core::arch::asm!(
-        init_dwm!(),
+
+/// 开启分页，按 cpuid 选择启动栈，`$a0` 为 cpuid
+macro_rules! init_cpu {
+    () => {
         "# Enable PG
         li.w        $t0, 0xb0       # PLV=0, IE=0, PG=1
         csrwr       $t0, 0x0        # LOONGARCH_CSR_CRMD
@@ -36,10 +36,27 @@ unsafe extern "C" fn _start() -> ! {
         li.w        $t0, 0x00       # FPE=0, SXE=0, ASXE=0, BTE=0
         csrwr       $t0, 0x2        # LOONGARCH_CSR_EUEN
 
+        csrrd       $a0, 0x20           # cpuid
+        addi.d      $t0, $a0, 1
+        li.d        $t1, {boot_stack_size}
+        mul.d       $t0, $t0, $t1
         la.global   $sp, {boot_stack}
-        li.d        $t0, {boot_stack_size}
         add.d       $sp, $sp, $t0       # setup boot stack
-        csrrd       $a0, 0x20           # cpuid
+        "
+    };
+}
+
+/// The earliest entry point for the primary CPU.
+///
+/// We can't use bl to jump to higher address, so we use jirl to jump to higher
+/// address.
+#[no_mangle]
+#[link_section = ".text.entry"]
+unsafe extern "C" fn _start() -> ! {
+    core::arch::asm!(
+        init_dwm!(),
+        init_cpu!(),
+        "
         la.global   $t0, {entry}
         jirl        $zero,$t0,0
         ",
@@ -49,3 +66,29 @@ unsafe extern "C" fn _start() -> ! {
         options(noreturn),
     )
 }
+
+/// 其他 CPU 被唤醒后的入口，固件从 0 号信箱读出这个地址并跳转过来
+#[no_mangle]
+unsafe extern "C" fn _start_secondary() -> ! {
+    core::arch::asm!(
+        init_dwm!(),
+        init_cpu!(),
+        "
+        la.global   $t0, {entry}
+        jirl        $zero,$t0,0
+        ",
+        entry = sym main_secondary,
+        boot_stack = sym BOOT_STACK,
+        boot_stack_size = const BOOT_STACK_SIZE,
+        options(noreturn),
+    )
+}
+
+/// 把入口地址写入其他 CPU 的 0 号信箱，再用核间中断唤醒它们。
+/// 不存在的核不会响应，启动的 CPU 也不等待它们
+pub fn start_secondary_cpus(boot_cpu: usize) {
+    for cpu in (0..MAX_CPUS).filter(|&cpu| cpu != boot_cpu) {
+        csr_mail_send(_start_secondary as usize as u64, cpu, 0);
+        send_ipi_single(cpu, 1);
+    }
+}
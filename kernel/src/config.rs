@@ -13,10 +13,28 @@ pub const UART: usize = 0x1FE001E0 + VIRT_BIAS;
 pub const USER_STACK_SIZE: usize = PAGE_SIZE; //用户栈大小
                                               // pub const KERNEL_STACK_SIZE: usize = PAGE_SIZE; //内核栈大小
 
+/// 用户地址空间的布局：程序从低地址开始，位置无关的程序在 ELF_DYN_BASE 之上，
+/// 之后是向上增长的堆；mmap 和动态链接器在 USER_MMAP_BASE 之上寻找空闲的位置；
+/// 线程的用户栈从 USER_STACK_BASE 开始依次向上排列
+pub const ELF_DYN_BASE: usize = 0x0400_0000_0000;
+pub const USER_MMAP_BASE: usize = 0x1000_0000_0000;
+pub const USER_STACK_BASE: usize = 0x7000_0000_0000;
+/// 三级页表、16KB 的页，用户地址共 47 位
+pub const USER_SPACE_END: usize = 0x8000_0000_0000;
+
 pub const KERNEL_HEAP_SIZE: usize = 0x1E0_0000; //内核的可分配堆大小3MB
 
+/// 时间片为 1/TICKS_PER_SEC 秒，只在 CPU 上有线程运行时才按时间片产生时钟中断
 pub const TICKS_PER_SEC: usize = 100;
+
+/// 最多启动的 CPU 数，多出来的核不会被唤醒
+pub const MAX_CPUS: usize = 4;
+/// 外部设备中断路由到的 CPU
+pub const IRQ_CPU: usize = 0;
 pub const MSEC_PER_SEC: usize = 1000;
+pub const NSEC_PER_SEC: usize = 1_000_000_000;
+pub const NSEC_PER_MSEC: usize = 1_000_000;
+pub const NSEC_PER_USEC: usize = 1_000;
 
 pub const MEMORY_END: usize = 0x000000000_1000_0000 + VIRT_BIAS;
 // pub const MEMORY_END: usize = 0x180000000;
@@ -27,6 +45,9 @@ pub const PAGE_SIZE_BITS: usize = 14; // 0xe
 
 pub const PALEN: usize = 48;
 
+pub const PIPE_BUFFER_SIZE: usize = PAGE_SIZE; //管道缓冲区的默认大小
+pub const PIPE_MAX_SIZE: usize = 16 * PAGE_SIZE; //F_SETPIPE_SZ 允许的最大缓冲区
+
 // pub const VALEN: usize = 48;
 
 // pub const MEMORY_HIGH_START: usize = 0x9000_0000;
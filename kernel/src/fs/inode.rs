@@ -2,25 +2,26 @@
 //! we need to wrap `Inode` into `Arc`,but `Mutex` in `Inode` prevents
 //! file systems from being accessed simultaneously
 //!
-//! `UPSafeCell<OSInodeInner>` -> `OSInode`: for static `ROOT_INODE`,we
-//! need to wrap `OSInodeInner` into `UPSafeCell`
-use alloc::{sync::Arc, vec::Vec};
+//! `SpinLock<OSInodeInner>` -> `OSInode`: for static `ROOT_INODE`,we
+//! need to wrap `OSInodeInner` into `SpinLock`
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
 
 use bitflags::*;
-use easy_fs::{EasyFileSystem, Inode};
+use easy_fs::{EasyFileSystem, Inode, EFS_MAGIC};
 use lazy_static::*;
 
-use super::File;
-use crate::{loongarch::BLOCK_DEVICE, mm::UserBuffer, println, sync::UPSafeCell};
+use super::{lock::flock_unlock, File};
+use crate::{error::SysResult, loongarch::BLOCK_DEVICE, mm::UserBuffer, println, sync::SpinLock};
 
 /// A wrapper around a filesystem inode
 /// to implement File trait atop
 pub struct OSInode {
     readable: bool,
     writable: bool,
-    inner: UPSafeCell<OSInodeInner>,
+    ino: u32,
+    inner: SpinLock<OSInodeInner>,
 }
-/// The OS inode inner in 'UPSafeCell'
+/// The OS inode inner in 'SpinLock'
 pub struct OSInodeInner {
     offset: usize,
     inode: Arc<Inode>,
@@ -32,9 +33,96 @@ impl OSInode {
         Self {
             readable,
             writable,
-            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+            ino: inode.inode_id(),
+            inner: SpinLock::new(OSInodeInner { offset: 0, inode }),
         }
     }
+    /// Inode number of the file
+    pub fn ino(&self) -> u32 {
+        self.ino
+    }
+    /// Current offset of reads and writes
+    pub fn offset(&self) -> usize {
+        self.inner.exclusive_access().offset
+    }
+    /// Size of the file in bytes
+    pub fn size(&self) -> usize {
+        self.inner.exclusive_access().inode.size()
+    }
+    /// Identity of this open file as an owner of `flock` locks
+    pub fn lock_owner(&self) -> usize {
+        self as *const Self as usize
+    }
+    /// Whether this is a named pipe, which has to be opened by `open_fifo`
+    pub fn is_fifo(&self) -> bool {
+        self.inner.exclusive_access().inode.is_fifo()
+    }
+    /// Whether this is the root directory
+    pub fn is_dir(&self) -> bool {
+        self.inner.exclusive_access().inode.is_dir()
+    }
+    /// Read at `offset` without moving the offset of the open file
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.inner.exclusive_access().inode.read_at(offset, buf)
+    }
+    /// Status of the file in the layout of `struct stat`
+    pub fn stat(&self) -> Stat {
+        let inner = self.inner.exclusive_access();
+        let block_size = ROOT_INODE.fs_stat().block_size;
+        let size = inner.inode.size();
+        let mode = if inner.inode.is_dir() {
+            S_IFDIR | 0o755
+        } else if inner.inode.is_fifo() {
+            S_IFIFO | 0o644
+        } else {
+            S_IFREG | 0o644
+        };
+        Stat {
+            st_ino: self.ino as u64,
+            st_mode: mode,
+            st_nlink: 1,
+            st_size: size as i64,
+            st_blksize: block_size as i32,
+            st_blocks: (size.div_ceil(block_size) * block_size / 512) as i64,
+            ..Default::default()
+        }
+    }
+    /// Fill `buf_len` bytes at most with `struct linux_dirent64` records of
+    /// the directory, starting from the entry at the offset of the open file.
+    /// `None` if not even the next entry fits.
+    pub fn read_dirents(&self, buf_len: usize) -> Option<Vec<u8>> {
+        let mut inner = self.inner.exclusive_access();
+        let root_ino = inner.inode.inode_id();
+        let mut entries = vec![
+            (String::from("."), root_ino, DT_DIR),
+            (String::from(".."), root_ino, DT_DIR),
+        ];
+        for name in inner.inode.ls() {
+            let inode = inner.inode.find(&name).unwrap();
+            let type_ = if inode.is_fifo() { DT_FIFO } else { DT_REG };
+            entries.push((name, inode.inode_id(), type_));
+        }
+        let mut buf = Vec::new();
+        for (name, ino, type_) in entries.iter().skip(inner.offset) {
+            // d_ino, d_off, d_reclen, d_type, d_name and its NUL, 8-byte aligned
+            let reclen = (8 + 8 + 2 + 1 + name.len() + 1).next_multiple_of(8);
+            if buf.len() + reclen > buf_len {
+                break;
+            }
+            inner.offset += 1;
+            let start = buf.len();
+            buf.extend_from_slice(&(*ino as u64).to_le_bytes());
+            buf.extend_from_slice(&(inner.offset as i64).to_le_bytes());
+            buf.extend_from_slice(&(reclen as u16).to_le_bytes());
+            buf.push(*type_);
+            buf.extend_from_slice(name.as_bytes());
+            buf.resize(start + reclen, 0);
+        }
+        if buf.is_empty() && inner.offset < entries.len() {
+            return None;
+        }
+        Some(buf)
+    }
     /// Read all data inside a inode into vector
     pub fn read_all(&self) -> Vec<u8> {
         let mut inner = self.inner.exclusive_access();
@@ -52,6 +140,13 @@ impl OSInode {
     }
 }
 
+impl Drop for OSInode {
+    fn drop(&mut self) {
+        // the last fd referring to this open file is gone
+        flock_unlock(self.ino, self.lock_owner());
+    }
+}
+
 lazy_static! {
     pub static ref ROOT_INODE: Arc<Inode> = {
         let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
@@ -77,9 +172,17 @@ bitflags! {
         ///Read & Write
         const RDWR = 1 << 1;
         ///Allow create
-        const CREATE = 1 << 9;
+        const CREATE = 0o100;
         ///Clear file and return an empty one
-        const TRUNC = 1 << 10;
+        const TRUNC = 0o1000;
+        ///Return EAGAIN instead of blocking
+        const NONBLOCK = 0o4000;
+        ///Always set on 64-bit, no effect
+        const LARGEFILE = 0o100000;
+        ///Fail unless the path is a directory
+        const DIRECTORY = 0o200000;
+        ///Close on exec, not supported and ignored
+        const CLOEXEC = 0o2000000;
     }
 }
 
@@ -87,47 +190,145 @@ impl OpenFlags {
     /// Do not check validity for simplicity
     /// Return (readable, writable)
     pub fn read_write(&self) -> (bool, bool) {
-        if self.is_empty() {
+        // only the lowest two bits are the access mode
+        let mode = *self & (Self::WRONLY | Self::RDWR);
+        if mode.is_empty() {
             (true, false)
-        } else if self.contains(Self::WRONLY) {
+        } else if mode.contains(Self::WRONLY) {
             (false, true)
         } else {
             (true, true)
         }
     }
 }
-///Open file with flags
+/// Filesystem statistics in the layout of the generic `struct statfs`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StatFs {
+    /// Type of filesystem
+    pub f_type: u64,
+    /// Size of a block in bytes
+    pub f_bsize: u64,
+    /// Number of data blocks
+    pub f_blocks: u64,
+    /// Free blocks
+    pub f_bfree: u64,
+    /// Free blocks available to unprivileged users
+    pub f_bavail: u64,
+    /// Number of inodes
+    pub f_files: u64,
+    /// Free inodes
+    pub f_ffree: u64,
+    /// Filesystem id
+    pub f_fsid: [i32; 2],
+    /// The max length of a file name
+    pub f_namelen: u64,
+    /// Fragment size
+    pub f_frsize: u64,
+    /// Mount flags
+    pub f_flags: u64,
+    pub f_spare: [u64; 4],
+}
+
+/// 文件类型，`st_mode` 的高位
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFREG: u32 = 0o100000;
+
+/// `d_type` of `struct linux_dirent64`
+const DT_FIFO: u8 = 1;
+const DT_DIR: u8 = 4;
+const DT_REG: u8 = 8;
+
+/// File status in the layout of the generic `struct stat`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stat {
+    pub st_dev: u64,
+    pub st_ino: u64,
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: u64,
+    pub __pad1: u64,
+    pub st_size: i64,
+    /// Preferred size of I/O
+    pub st_blksize: i32,
+    pub __pad2: i32,
+    /// Number of 512-byte blocks allocated
+    pub st_blocks: i64,
+    pub st_atime_sec: i64,
+    pub st_atime_nsec: i64,
+    pub st_mtime_sec: i64,
+    pub st_mtime_nsec: i64,
+    pub st_ctime_sec: i64,
+    pub st_ctime_nsec: i64,
+    pub __unused: [u32; 2],
+}
+
+/// Statistics of the filesystem holding `path`, `None` if it does not exist
+pub fn statfs(path: &str) -> Option<StatFs> {
+    if path != "/" && ROOT_INODE.find(path.trim_start_matches('/')).is_none() {
+        return None;
+    }
+    let stat = ROOT_INODE.fs_stat();
+    Some(StatFs {
+        f_type: EFS_MAGIC as u64,
+        f_bsize: stat.block_size as u64,
+        f_blocks: stat.total_blocks as u64,
+        f_bfree: stat.free_blocks as u64,
+        f_bavail: stat.free_blocks as u64,
+        f_files: stat.total_inodes as u64,
+        f_ffree: stat.free_inodes as u64,
+        f_namelen: stat.name_max as u64,
+        f_frsize: stat.block_size as u64,
+        ..Default::default()
+    })
+}
+
+/// Create a named pipe, return false if the name is taken
+pub fn make_fifo(name: &str) -> bool {
+    ROOT_INODE.create_fifo(name).is_some()
+}
+
+///Open file with flags, an empty name is the root directory
 pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
     let (readable, writable) = flags.read_write();
-    if flags.contains(OpenFlags::CREATE) {
-        if let Some(inode) = ROOT_INODE.find(name) {
-            // clear size
-            inode.clear();
-            Some(Arc::new(OSInode::new(readable, writable, inode)))
-        } else {
-            // create file
-            ROOT_INODE
-                .create(name)
-                .map(|inode| Arc::new(OSInode::new(readable, writable, inode)))
-        }
-    } else {
-        ROOT_INODE.find(name).map(|inode| {
+    if name.is_empty() {
+        return Some(Arc::new(OSInode::new(
+            readable,
+            writable,
+            ROOT_INODE.clone(),
+        )));
+    }
+    let inode = match ROOT_INODE.find(name) {
+        Some(inode) => {
             if flags.contains(OpenFlags::TRUNC) {
                 inode.clear();
             }
-            Arc::new(OSInode::new(readable, writable, inode))
-        })
-    }
+            inode
+        }
+        // create file
+        None if flags.contains(OpenFlags::CREATE) => ROOT_INODE.create(name)?,
+        None => return None,
+    };
+    Some(Arc::new(OSInode::new(readable, writable, inode)))
 }
 
 impl File for OSInode {
     fn readable(&self) -> bool {
         self.readable
     }
+    fn as_inode(&self) -> Option<&OSInode> {
+        Some(self)
+    }
     fn writable(&self) -> bool {
         self.writable
     }
-    fn read(&self, mut buf: UserBuffer) -> usize {
+    fn read(&self, mut buf: UserBuffer) -> SysResult {
         let mut inner = self.inner.exclusive_access();
         let mut total_read_size = 0usize;
         for slice in buf.buffers.iter_mut() {
@@ -138,16 +339,19 @@ impl File for OSInode {
             inner.offset += read_size;
             total_read_size += read_size;
         }
-        total_read_size
+        Ok(total_read_size)
     }
     fn write(&self, buf: UserBuffer) -> usize {
         let mut inner = self.inner.exclusive_access();
         let mut total_write_size = 0usize;
         for slice in buf.buffers.iter() {
             let write_size = inner.inode.write_at(inner.offset, *slice);
-            assert_eq!(write_size, slice.len());
             inner.offset += write_size;
             total_write_size += write_size;
+            // the disk is full
+            if write_size < slice.len() {
+                break;
+            }
         }
         total_write_size
     }
@@ -0,0 +1,191 @@
+//! Advisory file locks
+//!
+//! `flock` locks cover a whole file and belong to an open file, i.e. an
+//! [`OSInode`](super::OSInode), so they are shared across `fork` and go away
+//! with the last fd referring to it. `fcntl` record locks cover byte ranges
+//! and belong to a process. They go away when the process closes any fd of
+//! the file or exits.
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+
+use lazy_static::*;
+
+use crate::sync::{SpinLock, WaitQueue};
+
+/// A record lock on the bytes `[start, end)` of a file
+#[derive(Clone, Copy)]
+pub struct RecordLock {
+    pub pid: usize,
+    pub start: u64,
+    pub end: u64,
+    pub exclusive: bool,
+}
+
+impl RecordLock {
+    fn conflicts_with(&self, other: &RecordLock) -> bool {
+        self.pid != other.pid
+            && self.start < other.end
+            && other.start < self.end
+            && (self.exclusive || other.exclusive)
+    }
+}
+
+/// Locks of a single inode
+struct InodeLocks {
+    /// (open file, exclusive)
+    flocks: Vec<(usize, bool)>,
+    records: Vec<RecordLock>,
+    /// Tasks waiting for any lock of the inode to be released
+    wait_queue: Arc<WaitQueue>,
+}
+
+impl InodeLocks {
+    fn new() -> Self {
+        Self {
+            flocks: Vec::new(),
+            records: Vec::new(),
+            wait_queue: Arc::new(WaitQueue::new()),
+        }
+    }
+    /// Drop the part of the record locks of `pid` inside `[start, end)`
+    fn unlock_records(&mut self, pid: usize, start: u64, end: u64) {
+        let mut records = Vec::with_capacity(self.records.len());
+        for lock in self.records.drain(..) {
+            if lock.pid != pid || lock.end <= start || end <= lock.start {
+                records.push(lock);
+                continue;
+            }
+            // keep what sticks out on either side
+            if lock.start < start {
+                records.push(RecordLock { end: start, ..lock });
+            }
+            if end < lock.end {
+                records.push(RecordLock { start: end, ..lock });
+            }
+        }
+        self.records = records;
+    }
+}
+
+lazy_static! {
+    /// 按 inode 编号索引的文件锁
+    static ref FILE_LOCKS: SpinLock<BTreeMap<u32, InodeLocks>> =
+        SpinLock::new(BTreeMap::new());
+}
+
+/// Run `f` over the locks of inode `ino`, forgetting them once none is left,
+/// and wake the tasks waiting on the inode if `f` returns true
+fn update_locks(ino: u32, f: impl FnOnce(&mut InodeLocks) -> bool) {
+    let mut file_locks = FILE_LOCKS.exclusive_access();
+    let Some(locks) = file_locks.get_mut(&ino) else {
+        return;
+    };
+    let wake = f(locks);
+    let wait_queue = locks.wait_queue.clone();
+    if locks.flocks.is_empty() && locks.records.is_empty() {
+        file_locks.remove(&ino);
+    }
+    drop(file_locks);
+    if wake {
+        wait_queue.wake_all();
+    }
+}
+
+/// Take a `flock` lock on inode `ino` for open file `file`, converting the
+/// one it holds if any. Blocks while other open files hold conflicting
+/// locks, or returns false at once if `nonblocking` is set.
+pub fn flock_lock(ino: u32, file: usize, exclusive: bool, nonblocking: bool) -> bool {
+    // like Linux, a conversion drops the old lock first
+    flock_unlock(ino, file);
+    loop {
+        let mut file_locks = FILE_LOCKS.exclusive_access();
+        let locks = file_locks.entry(ino).or_insert_with(InodeLocks::new);
+        let conflict = locks
+            .flocks
+            .iter()
+            .any(|(_, held_exclusive)| exclusive || *held_exclusive);
+        if !conflict {
+            locks.flocks.push((file, exclusive));
+            return true;
+        }
+        if nonblocking {
+            if locks.flocks.is_empty() && locks.records.is_empty() {
+                file_locks.remove(&ino);
+            }
+            return false;
+        }
+        let wait_queue = locks.wait_queue.clone();
+        wait_queue.wait_and_release(file_locks);
+    }
+}
+
+/// Release the `flock` lock of open file `file` on inode `ino`
+pub fn flock_unlock(ino: u32, file: usize) {
+    update_locks(ino, |locks| {
+        let len = locks.flocks.len();
+        locks.flocks.retain(|(owner, _)| *owner != file);
+        locks.flocks.len() != len
+    });
+}
+
+/// The first record lock on inode `ino` that keeps `lock` from being taken
+pub fn record_conflict(ino: u32, lock: &RecordLock) -> Option<RecordLock> {
+    let file_locks = FILE_LOCKS.exclusive_access();
+    file_locks
+        .get(&ino)?
+        .records
+        .iter()
+        .find(|held| held.conflicts_with(lock))
+        .copied()
+}
+
+/// Take a record lock on inode `ino`, replacing the locks the process holds
+/// on the range. Blocks while other processes hold conflicting locks, or
+/// returns false at once if `nonblocking` is set.
+pub fn record_lock(ino: u32, lock: RecordLock, nonblocking: bool) -> bool {
+    loop {
+        let mut file_locks = FILE_LOCKS.exclusive_access();
+        let locks = file_locks.entry(ino).or_insert_with(InodeLocks::new);
+        if !locks.records.iter().any(|held| held.conflicts_with(&lock)) {
+            locks.unlock_records(lock.pid, lock.start, lock.end);
+            locks.records.push(lock);
+            let wait_queue = locks.wait_queue.clone();
+            drop(file_locks);
+            // turning a write lock into a read lock may let readers in
+            wait_queue.wake_all();
+            return true;
+        }
+        if nonblocking {
+            if locks.flocks.is_empty() && locks.records.is_empty() {
+                file_locks.remove(&ino);
+            }
+            return false;
+        }
+        let wait_queue = locks.wait_queue.clone();
+        wait_queue.wait_and_release(file_locks);
+    }
+}
+
+/// Release the record locks of `pid` on the bytes `[start, end)` of inode
+/// `ino`
+pub fn record_unlock(ino: u32, pid: usize, start: u64, end: u64) {
+    update_locks(ino, |locks| {
+        locks.unlock_records(pid, start, end);
+        true
+    });
+}
+
+/// Release the record locks of `pid` on inode `ino`, or on every inode if
+/// `ino` is `None`
+pub fn release_record_locks(pid: usize, ino: Option<u32>) {
+    let inodes: Vec<u32> = match ino {
+        Some(ino) => [ino].into(),
+        None => FILE_LOCKS.exclusive_access().keys().copied().collect(),
+    };
+    for ino in inodes {
+        update_locks(ino, |locks| {
+            let len = locks.records.len();
+            locks.records.retain(|lock| lock.pid != pid);
+            locks.records.len() != len
+        });
+    }
+}
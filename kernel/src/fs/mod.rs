@@ -1,21 +1,113 @@
 //! File system in os
 mod inode;
+mod lock;
 mod pipe;
 mod stdio;
+mod timerfd;
+
+use alloc::sync::Arc;
+
+use bitflags::*;
+
+use crate::{error::SysResult, mm::UserBuffer, sync::WaitQueue};
+
+bitflags! {
+    /// Events of `ppoll`
+    pub struct PollEvents: i16 {
+        /// There is data to read
+        const POLLIN = 0x001;
+        /// There is urgent data to read
+        const POLLPRI = 0x002;
+        /// Writing will not block
+        const POLLOUT = 0x004;
+        /// Error condition, e.g. all readers of a pipe have gone away
+        const POLLERR = 0x008;
+        /// All writers of a pipe have gone away
+        const POLLHUP = 0x010;
+        /// The fd is not open
+        const POLLNVAL = 0x020;
+    }
+}
+
+/// An entry of the `fds` array of `ppoll`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PollFd {
+    /// File descriptor, negative ones are skipped
+    pub fd: i32,
+    /// Requested events
+    pub events: i16,
+    /// Returned events
+    pub revents: i16,
+}
 
-use crate::mm::UserBuffer;
 /// File trait
 pub trait File: Send + Sync {
     /// If readable
     fn readable(&self) -> bool;
     /// If writable
     fn writable(&self) -> bool;
-    /// Read file to `UserBuffer`
-    fn read(&self, buf: UserBuffer) -> usize;
-    /// Write `UserBuffer` to file
+    /// Read file to `UserBuffer`, EINTR if a signal interrupted a blocking
+    /// read before any data arrived
+    fn read(&self, buf: UserBuffer) -> SysResult;
+    /// Write `UserBuffer` to file, returning 0 for a non-empty buffer when
+    /// nothing can be written
     fn write(&self, buf: UserBuffer) -> usize;
+    /// Events that are ready without blocking, regular files are always
+    /// ready for what they are opened for
+    fn poll(&self) -> PollEvents {
+        let mut events = PollEvents::empty();
+        if self.readable() {
+            events |= PollEvents::POLLIN;
+        }
+        if self.writable() {
+            events |= PollEvents::POLLOUT;
+        }
+        events
+    }
+    /// The queue woken whenever `poll` may give a different answer, `None`
+    /// if it never changes
+    fn wait_queue(&self) -> Option<Arc<WaitQueue>> {
+        None
+    }
+    /// Whether I/O fails with EAGAIN instead of blocking
+    fn nonblocking(&self) -> bool {
+        false
+    }
+    /// Set `O_NONBLOCK`, ignored by files that never block
+    fn set_nonblocking(&self, _nonblocking: bool) {}
+    /// The easy-fs file behind this fd, which can be locked, `None` for
+    /// other kinds of files
+    fn as_inode(&self) -> Option<&OSInode> {
+        None
+    }
+    /// Capacity of the buffer behind a pipe, `None` for other files
+    fn pipe_size(&self) -> Option<usize> {
+        None
+    }
+    /// Resize the buffer behind a pipe and return the new capacity, `None` if
+    /// this is not a pipe or the size is not acceptable
+    fn set_pipe_size(&self, _size: usize) -> Option<usize> {
+        None
+    }
+    /// The timer behind a timerfd, `None` for other files
+    fn as_timerfd(&self) -> Option<&TimerFd> {
+        None
+    }
+    /// Whether this is the console, which has a foreground process group
+    fn is_tty(&self) -> bool {
+        false
+    }
 }
 
-pub use inode::{list_apps, open_file, OpenFlags, ROOT_INODE};
-pub use pipe::make_pipe;
-pub use stdio::{Stdin, Stdout};
+pub use inode::{
+    list_apps, make_fifo, open_file, statfs, OSInode, OpenFlags, Stat, StatFs, ROOT_INODE, S_IFCHR,
+    S_IFDIR, S_IFIFO, S_IFMT, S_IFREG,
+};
+pub use lock::{
+    flock_lock, flock_unlock, record_conflict, record_lock, record_unlock, release_record_locks,
+    RecordLock,
+};
+pub use pipe::{make_pipe, open_fifo};
+pub use stdio::{poll_console, set_tty_foreground, tty_foreground, Stdin, Stdout};
+pub use timerfd::TimerFd;
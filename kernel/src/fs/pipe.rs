@@ -1,108 +1,197 @@
-use alloc::sync::{Arc, Weak};
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
 
-use super::File;
-use crate::{mm::UserBuffer, sync::UPSafeCell, task::suspend_current_and_run_next};
+use lazy_static::*;
+
+use super::{File, PollEvents};
+use crate::{
+    config::{PAGE_SIZE, PIPE_BUFFER_SIZE, PIPE_MAX_SIZE},
+    error::SysResult,
+    mm::UserBuffer,
+    sync::{SpinLock, WaitQueue},
+};
 
 pub struct Pipe {
     readable: bool,
     writable: bool,
-    buffer: Arc<UPSafeCell<PipeRingBuffer>>,
+    nonblocking: AtomicBool,
+    buffer: Arc<SpinLock<PipeRingBuffer>>,
 }
 
 impl Pipe {
-    pub fn read_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
-        Self {
-            readable: true,
-            writable: false,
-            buffer,
+    /// An end of the pipe behind `buffer`, registered as a reader and/or a
+    /// writer until it is dropped
+    pub fn new(readable: bool, writable: bool, buffer: Arc<SpinLock<PipeRingBuffer>>) -> Self {
+        let mut ring_buffer = buffer.exclusive_access();
+        if readable {
+            ring_buffer.readers += 1;
         }
-    }
-    pub fn write_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+        if writable {
+            ring_buffer.writers += 1;
+        }
+        drop(ring_buffer);
         Self {
-            readable: false,
-            writable: true,
+            readable,
+            writable,
+            nonblocking: AtomicBool::new(false),
             buffer,
         }
     }
+    pub fn read_end_with_buffer(buffer: Arc<SpinLock<PipeRingBuffer>>) -> Self {
+        Self::new(true, false, buffer)
+    }
+    pub fn write_end_with_buffer(buffer: Arc<SpinLock<PipeRingBuffer>>) -> Self {
+        Self::new(false, true, buffer)
+    }
 }
 
-const RING_BUFFER_SIZE: usize = 32;
-
-#[derive(Copy, Clone, PartialEq)]
-enum RingBufferStatus {
-    Full,
-    Empty,
-    Normal,
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut ring_buffer = self.buffer.exclusive_access();
+        if self.readable {
+            ring_buffer.readers -= 1;
+        }
+        if self.writable {
+            ring_buffer.writers -= 1;
+        }
+        // readers may see EOF and writers a broken pipe now
+        let wait_queue = ring_buffer.wait_queue.clone();
+        drop(ring_buffer);
+        wait_queue.wake_all();
+    }
 }
 
 pub struct PipeRingBuffer {
-    arr: [u8; RING_BUFFER_SIZE],
+    arr: Vec<u8>,
     head: usize,
-    tail: usize,
-    status: RingBufferStatus,
-    write_end: Option<Weak<Pipe>>,
+    len: usize,
+    readers: usize,
+    writers: usize,
+    /// Tasks waiting for data, room or the other end to show up
+    wait_queue: Arc<WaitQueue>,
 }
 
 impl PipeRingBuffer {
-    pub fn new() -> Self {
+    pub fn new(capacity: usize) -> Self {
         Self {
-            arr: [0; RING_BUFFER_SIZE],
+            arr: vec![0; capacity],
             head: 0,
-            tail: 0,
-            status: RingBufferStatus::Empty,
-            write_end: None,
+            len: 0,
+            readers: 0,
+            writers: 0,
+            wait_queue: Arc::new(WaitQueue::new()),
         }
     }
-    pub fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
-        self.write_end = Some(Arc::downgrade(write_end));
+    pub fn capacity(&self) -> usize {
+        self.arr.len()
     }
-    pub fn write_byte(&mut self, byte: u8) {
-        self.status = RingBufferStatus::Normal;
-        self.arr[self.tail] = byte;
-        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
-        if self.tail == self.head {
-            self.status = RingBufferStatus::Full;
-        }
+    /// Move as many bytes as possible into `dst`, return the number moved
+    pub fn read_into(&mut self, dst: &mut [u8]) -> usize {
+        let n = dst.len().min(self.len);
+        let first = n.min(self.capacity() - self.head);
+        dst[..first].copy_from_slice(&self.arr[self.head..self.head + first]);
+        dst[first..n].copy_from_slice(&self.arr[..n - first]);
+        self.head = (self.head + n) % self.capacity();
+        self.len -= n;
+        n
     }
-    pub fn read_byte(&mut self) -> u8 {
-        self.status = RingBufferStatus::Normal;
-        let c = self.arr[self.head];
-        self.head = (self.head + 1) % RING_BUFFER_SIZE;
-        if self.head == self.tail {
-            self.status = RingBufferStatus::Empty;
-        }
-        c
+    /// Move as many bytes as possible from `src`, return the number moved
+    pub fn write_from(&mut self, src: &[u8]) -> usize {
+        let n = src.len().min(self.available_write());
+        let tail = (self.head + self.len) % self.capacity();
+        let first = n.min(self.capacity() - tail);
+        self.arr[tail..tail + first].copy_from_slice(&src[..first]);
+        self.arr[..n - first].copy_from_slice(&src[first..n]);
+        self.len += n;
+        n
     }
     pub fn available_read(&self) -> usize {
-        if self.status == RingBufferStatus::Empty {
-            0
-        } else if self.tail > self.head {
-            self.tail - self.head
-        } else {
-            self.tail + RING_BUFFER_SIZE - self.head
-        }
+        self.len
     }
     pub fn available_write(&self) -> usize {
-        if self.status == RingBufferStatus::Full {
-            0
-        } else {
-            RING_BUFFER_SIZE - self.available_read()
+        self.capacity() - self.len
+    }
+    /// Change the capacity, keeping buffered data. Fails if the data would
+    /// not fit.
+    pub fn resize(&mut self, capacity: usize) -> bool {
+        if capacity < self.len {
+            return false;
         }
+        let mut arr = vec![0; capacity];
+        let len = self.len;
+        self.read_into(&mut arr[..len]);
+        self.arr = arr;
+        self.head = 0;
+        self.len = len;
+        true
     }
     pub fn all_write_ends_closed(&self) -> bool {
-        self.write_end.as_ref().unwrap().upgrade().is_none()
+        self.writers == 0
+    }
+    pub fn all_read_ends_closed(&self) -> bool {
+        self.readers == 0
     }
 }
 
 /// Return (read_end, write_end)
 pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
-    let buffer = Arc::new(unsafe { UPSafeCell::new(PipeRingBuffer::new()) });
+    let buffer = Arc::new(SpinLock::new(PipeRingBuffer::new(PIPE_BUFFER_SIZE)));
     let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
     let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
-    buffer.exclusive_access().set_write_end(&write_end);
     (read_end, write_end)
 }
 
+lazy_static! {
+    /// 命名管道的缓冲区，所有端都关闭后缓冲区随之释放
+    static ref FIFOS: SpinLock<BTreeMap<String, Weak<SpinLock<PipeRingBuffer>>>> =
+        SpinLock::new(BTreeMap::new());
+}
+
+/// Open an end of the named pipe `name`. Unless it is opened for both
+/// reading and writing, block until the other side is opened as well. A
+/// non-blocking read end is returned at once, a non-blocking write end
+/// without any reader is an error.
+pub fn open_fifo(
+    name: &str,
+    readable: bool,
+    writable: bool,
+    nonblocking: bool,
+) -> Option<Arc<Pipe>> {
+    let mut fifos = FIFOS.exclusive_access();
+    let buffer = match fifos.get(name).and_then(Weak::upgrade) {
+        Some(buffer) => buffer,
+        None => {
+            let buffer = Arc::new(SpinLock::new(PipeRingBuffer::new(PIPE_BUFFER_SIZE)));
+            fifos.insert(name.to_string(), Arc::downgrade(&buffer));
+            buffer
+        }
+    };
+    drop(fifos);
+    let pipe = Arc::new(Pipe::new(readable, writable, buffer.clone()));
+    pipe.set_nonblocking(nonblocking);
+    // the new end may be what the other side is waiting for
+    let wait_queue = buffer.exclusive_access().wait_queue.clone();
+    wait_queue.wake_all();
+    loop {
+        let ring_buffer = buffer.exclusive_access();
+        let readers_ready = !readable || !ring_buffer.all_write_ends_closed();
+        let writers_ready = !writable || !ring_buffer.all_read_ends_closed();
+        if (readers_ready && writers_ready) || (nonblocking && !writable) {
+            return Some(pipe);
+        }
+        if nonblocking {
+            return None;
+        }
+        wait_queue.wait_and_release(ring_buffer);
+    }
+}
+
 impl File for Pipe {
     fn readable(&self) -> bool {
         self.readable
@@ -110,55 +199,107 @@ impl File for Pipe {
     fn writable(&self) -> bool {
         self.writable
     }
-    fn read(&self, buf: UserBuffer) -> usize {
+    fn read(&self, buf: UserBuffer) -> SysResult {
         assert!(self.readable());
-        let mut buf_iter = buf.into_iter();
         let mut read_size = 0usize;
-        loop {
-            let mut ring_buffer = self.buffer.exclusive_access();
-            let loop_read = ring_buffer.available_read();
-            if loop_read == 0 {
-                if ring_buffer.all_write_ends_closed() {
-                    return read_size;
-                }
-                drop(ring_buffer);
-                suspend_current_and_run_next();
-                continue;
-            }
-            // read at most loop_read bytes
-            for _ in 0..loop_read {
-                if let Some(byte_ref) = buf_iter.next() {
-                    unsafe {
-                        *byte_ref = ring_buffer.read_byte();
+        for slice in buf.buffers {
+            let mut done = 0usize;
+            while done < slice.len() {
+                let mut ring_buffer = self.buffer.exclusive_access();
+                let loop_read = ring_buffer.read_into(&mut slice[done..]);
+                let wait_queue = ring_buffer.wait_queue.clone();
+                if loop_read == 0 {
+                    if ring_buffer.all_write_ends_closed() || self.nonblocking() {
+                        return Ok(read_size + done);
+                    }
+                    if let Err(error) = wait_queue.wait_interruptible_and_release(ring_buffer) {
+                        // 被信号打断，已经读到数据时返回它们
+                        return match read_size + done {
+                            0 => Err(error),
+                            read => Ok(read),
+                        };
                     }
-                    read_size += 1;
-                } else {
-                    return read_size;
+                    continue;
                 }
+                drop(ring_buffer);
+                // there is room for blocked writers now
+                wait_queue.wake_all();
+                done += loop_read;
             }
+            read_size += done;
         }
+        Ok(read_size)
     }
     fn write(&self, buf: UserBuffer) -> usize {
         assert!(self.writable());
-        let mut buf_iter = buf.into_iter();
         let mut write_size = 0usize;
-        loop {
-            let mut ring_buffer = self.buffer.exclusive_access();
-            let loop_write = ring_buffer.available_write();
-            if loop_write == 0 {
+        for slice in buf.buffers {
+            let mut done = 0usize;
+            while done < slice.len() {
+                let mut ring_buffer = self.buffer.exclusive_access();
+                // nobody will ever read the rest
+                if ring_buffer.all_read_ends_closed() {
+                    return write_size + done;
+                }
+                let loop_write = ring_buffer.write_from(&slice[done..]);
+                let wait_queue = ring_buffer.wait_queue.clone();
+                if loop_write == 0 {
+                    if self.nonblocking() {
+                        return write_size + done;
+                    }
+                    wait_queue.wait_and_release(ring_buffer);
+                    continue;
+                }
                 drop(ring_buffer);
-                suspend_current_and_run_next();
-                continue;
+                // wake blocked readers
+                wait_queue.wake_all();
+                done += loop_write;
             }
-            // write at most loop_write bytes
-            for _ in 0..loop_write {
-                if let Some(byte_ref) = buf_iter.next() {
-                    ring_buffer.write_byte(unsafe { *byte_ref });
-                    write_size += 1;
-                } else {
-                    return write_size;
-                }
+            write_size += done;
+        }
+        write_size
+    }
+    fn poll(&self) -> PollEvents {
+        let ring_buffer = self.buffer.exclusive_access();
+        let mut events = PollEvents::empty();
+        if self.readable {
+            if ring_buffer.available_read() > 0 {
+                events |= PollEvents::POLLIN;
+            }
+            if ring_buffer.all_write_ends_closed() {
+                events |= PollEvents::POLLHUP;
+            }
+        }
+        if self.writable {
+            if ring_buffer.available_write() > 0 {
+                events |= PollEvents::POLLOUT;
+            }
+            if ring_buffer.all_read_ends_closed() {
+                events |= PollEvents::POLLERR;
             }
         }
+        events
+    }
+    fn wait_queue(&self) -> Option<Arc<WaitQueue>> {
+        Some(self.buffer.exclusive_access().wait_queue.clone())
+    }
+    fn nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Relaxed)
+    }
+    fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+    }
+    fn pipe_size(&self) -> Option<usize> {
+        Some(self.buffer.exclusive_access().capacity())
+    }
+    fn set_pipe_size(&self, size: usize) -> Option<usize> {
+        // 按页对齐，最小一页
+        let size = size.max(1).div_ceil(PAGE_SIZE) * PAGE_SIZE;
+        if size > PIPE_MAX_SIZE || !self.buffer.exclusive_access().resize(size) {
+            return None;
+        }
+        // a bigger buffer may unblock writers
+        self.wait_queue().unwrap().wake_all();
+        Some(size)
     }
 }
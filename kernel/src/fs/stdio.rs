@@ -1,11 +1,115 @@
 //!Stdin & Stdout
-use super::File;
-use crate::{mm::UserBuffer, print, print::get_char, task::suspend_current_and_run_next};
+use alloc::{collections::VecDeque, sync::Arc};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use lazy_static::*;
+
+use super::{File, PollEvents};
+use crate::{
+    error::SysResult,
+    mm::UserBuffer,
+    print::CONSOLE,
+    sync::{SpinLock, WaitQueue},
+    task::{send_signal_to_group, SignalFlags},
+};
+
+/// 控制台输入缓冲区的最大长度，超出的字符被丢弃
+const STDIN_BUFFER_SIZE: usize = 4096;
+
+/// Ctrl-C
+const VINTR: u8 = 0x03;
+/// Ctrl-\
+const VQUIT: u8 = 0x1c;
+/// Ctrl-Z
+const VSUSP: u8 = 0x1a;
+
+/// The console as a controlling terminal
+struct Tty {
+    /// The session that took the terminal
+    sid: Option<usize>,
+    /// The process group that gets the signals typed on the terminal
+    foreground: Option<usize>,
+}
+
+lazy_static! {
+    /// 控制台输入缓冲区，在时钟中断和读标准输入时从串口中取出字符填充
+    static ref STDIN_BUFFER: SpinLock<VecDeque<u8>> =
+        SpinLock::new(VecDeque::new());
+    /// 等待控制台输入的任务
+    static ref STDIN_QUEUE: Arc<WaitQueue> = Arc::new(WaitQueue::new());
+    static ref TTY: SpinLock<Tty> = SpinLock::new(Tty {
+            sid: None,
+            foreground: None,
+        });
+}
+
+/// The foreground process group of the console, if a session has set one
+pub fn tty_foreground() -> Option<usize> {
+    TTY.exclusive_access().foreground
+}
+
+/// Make `pgid` of session `sid` the foreground group. The first session to
+/// do so takes the console, other sessions are refused afterwards.
+pub fn set_tty_foreground(sid: usize, pgid: usize) -> bool {
+    let mut tty = TTY.exclusive_access();
+    if tty.sid.is_some_and(|owner| owner != sid) {
+        return false;
+    }
+    tty.sid = Some(sid);
+    tty.foreground = Some(pgid);
+    true
+}
+
+/// Move pending characters from the UART into the stdin buffer, waking the
+/// tasks waiting for input if there is any
+pub fn poll_console() {
+    let mut buffer = STDIN_BUFFER.exclusive_access();
+    let mut console = CONSOLE.exclusive_access();
+    let mut signals = SignalFlags::empty();
+    while let Some(ch) = console.get_char() {
+        // job control characters become signals instead of input
+        match ch {
+            VINTR => signals |= SignalFlags::SIGINT,
+            VQUIT => signals |= SignalFlags::SIGQUIT,
+            VSUSP => signals |= SignalFlags::SIGTSTP,
+            _ if buffer.len() < STDIN_BUFFER_SIZE => buffer.push_back(ch),
+            _ => {}
+        }
+    }
+    drop(console);
+    let has_input = !buffer.is_empty();
+    drop(buffer);
+    if has_input {
+        STDIN_QUEUE.wake_all();
+    }
+    if let Some(pgid) = tty_foreground() {
+        for signal in [
+            SignalFlags::SIGINT,
+            SignalFlags::SIGQUIT,
+            SignalFlags::SIGTSTP,
+        ] {
+            if signals.contains(signal) {
+                send_signal_to_group(pgid, signal);
+            }
+        }
+    }
+}
+
 ///Standard input
-pub struct Stdin;
+pub struct Stdin {
+    nonblocking: AtomicBool,
+}
 ///Standard output
 pub struct Stdout;
 
+impl Stdin {
+    pub fn new() -> Self {
+        Self {
+            nonblocking: AtomicBool::new(false),
+        }
+    }
+}
+
 impl File for Stdin {
     fn readable(&self) -> bool {
         true
@@ -13,28 +117,53 @@ impl File for Stdin {
     fn writable(&self) -> bool {
         false
     }
-    fn read(&self, mut user_buf: UserBuffer) -> usize {
-        assert_eq!(user_buf.len(), 1);
-        // busy loop
-        let mut c: u8;
-        loop {
-            c = get_char();
-            if c == 0 {
-                suspend_current_and_run_next();
-                continue;
-            } else {
-                break;
-            }
+    fn read(&self, user_buf: UserBuffer) -> SysResult {
+        let len = user_buf.len();
+        if len == 0 {
+            return Ok(0);
         }
-        let ch = c as u8;
-        unsafe {
-            user_buf.buffers[0].as_mut_ptr().write_volatile(ch);
+        // sleep until the timer interrupt finds some input
+        let mut buffer = loop {
+            poll_console();
+            let buffer = STDIN_BUFFER.exclusive_access();
+            if !buffer.is_empty() {
+                break buffer;
+            }
+            // 被信号打断时返回 EINTR
+            STDIN_QUEUE.wait_interruptible_and_release(buffer)?;
+        };
+        // 返回已经到达的输入，不等待填满缓冲区
+        let count = len.min(buffer.len());
+        for (dst, ch) in user_buf.into_iter().zip(buffer.drain(..count)) {
+            unsafe {
+                dst.write_volatile(ch);
+            }
         }
-        1
+        Ok(count)
     }
     fn write(&self, _user_buf: UserBuffer) -> usize {
         panic!("Cannot write to stdin!");
     }
+    fn poll(&self) -> PollEvents {
+        poll_console();
+        if STDIN_BUFFER.exclusive_access().is_empty() {
+            PollEvents::empty()
+        } else {
+            PollEvents::POLLIN
+        }
+    }
+    fn wait_queue(&self) -> Option<Arc<WaitQueue>> {
+        Some(STDIN_QUEUE.clone())
+    }
+    fn nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Relaxed)
+    }
+    fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+    }
+    fn is_tty(&self) -> bool {
+        true
+    }
 }
 
 impl File for Stdout {
@@ -44,13 +173,18 @@ impl File for Stdout {
     fn writable(&self) -> bool {
         true
     }
-    fn read(&self, _user_buf: UserBuffer) -> usize {
+    fn read(&self, _user_buf: UserBuffer) -> SysResult {
         panic!("Cannot read from stdout!");
     }
     fn write(&self, user_buf: UserBuffer) -> usize {
+        // 直接输出原始字节，多字节字符可能跨页被拆开
+        let mut console = CONSOLE.exclusive_access();
         for buffer in user_buf.buffers.iter() {
-            print!("{}", core::str::from_utf8(*buffer).unwrap());
+            console.write_bytes(buffer);
         }
         user_buf.len()
     }
+    fn is_tty(&self) -> bool {
+        true
+    }
 }
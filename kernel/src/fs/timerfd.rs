@@ -0,0 +1,156 @@
+use alloc::sync::{Arc, Weak};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::{File, PollEvents};
+use crate::{
+    error::SysResult,
+    mm::UserBuffer,
+    sync::{SpinLock, WaitQueue},
+    timer::{add_timer_event, get_time_ns, remove_timer_events, TimerEvent},
+};
+
+/// A timer whose expirations are read from a file descriptor, see
+/// `timerfd_create(2)`. Expiry times are kept on the monotonic clock, the
+/// clock chosen at creation only matters when an absolute time is set.
+pub struct TimerFd {
+    clock_id: usize,
+    nonblocking: AtomicBool,
+    inner: SpinLock<TimerFdInner>,
+    /// Readers waiting for the timer to expire
+    wait_queue: Arc<WaitQueue>,
+    /// Handed to the timer heap, which must not keep the file open
+    this: Weak<TimerFd>,
+}
+
+struct TimerFdInner {
+    /// 开机以来的纳秒数，0 表示没有启动
+    expire_ns: usize,
+    interval_ns: usize,
+    /// 上次读取之后到期的次数
+    expirations: u64,
+}
+
+impl TimerFd {
+    pub fn new(clock_id: usize, nonblocking: bool) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            clock_id,
+            nonblocking: AtomicBool::new(nonblocking),
+            inner: SpinLock::new(TimerFdInner {
+                expire_ns: 0,
+                interval_ns: 0,
+                expirations: 0,
+            }),
+            wait_queue: Arc::new(WaitQueue::new()),
+            this: this.clone(),
+        })
+    }
+    pub fn clock_id(&self) -> usize {
+        self.clock_id
+    }
+    /// Time left until the next expiry and the interval
+    pub fn get(&self) -> (usize, usize) {
+        let inner = self.inner.exclusive_access();
+        let remaining = match inner.expire_ns {
+            0 => 0,
+            expire_ns => expire_ns.saturating_sub(get_time_ns()).max(1),
+        };
+        (remaining, inner.interval_ns)
+    }
+    /// Expire at `expire_ns` and then every `interval_ns`, or disarm if
+    /// `expire_ns` is 0. Pending expirations are discarded. Returns the old
+    /// setting like [`TimerFd::get`].
+    pub fn set(&self, expire_ns: usize, interval_ns: usize) -> (usize, usize) {
+        let old = self.get();
+        let mut inner = self.inner.exclusive_access();
+        inner.expire_ns = expire_ns;
+        inner.interval_ns = interval_ns;
+        inner.expirations = 0;
+        drop(inner);
+        let this = self.this.as_ptr();
+        remove_timer_events(
+            |event| matches!(event, TimerEvent::TimerFd(timerfd) if timerfd.as_ptr() == this),
+        );
+        if expire_ns != 0 {
+            add_timer_event(expire_ns, TimerEvent::TimerFd(self.this.clone()));
+        }
+        old
+    }
+    /// Called from the timer heap. Expirations missed while the timer
+    /// interrupt was late are counted as well.
+    pub fn expired(&self, expire_ns: usize) {
+        let mut inner = self.inner.exclusive_access();
+        // 已经被重新设置过
+        if inner.expire_ns != expire_ns {
+            return;
+        }
+        inner.expirations += 1;
+        let mut next = 0;
+        if inner.interval_ns != 0 {
+            let missed = (get_time_ns().saturating_sub(expire_ns) / inner.interval_ns) as u64;
+            inner.expirations += missed;
+            next = expire_ns.saturating_add((missed as usize + 1) * inner.interval_ns);
+        }
+        inner.expire_ns = next;
+        drop(inner);
+        if next != 0 {
+            add_timer_event(next, TimerEvent::TimerFd(self.this.clone()));
+        }
+        self.wait_queue.wake_all();
+    }
+}
+
+impl File for TimerFd {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    /// Block until the timer has expired and read the number of expirations
+    /// as a u64. A buffer shorter than that reads nothing.
+    fn read(&self, buf: UserBuffer) -> SysResult {
+        if buf.len() < size_of::<u64>() {
+            return Ok(0);
+        }
+        let bytes = loop {
+            let mut inner = self.inner.exclusive_access();
+            if inner.expirations > 0 {
+                let bytes = inner.expirations.to_ne_bytes();
+                inner.expirations = 0;
+                break bytes;
+            }
+            if self.nonblocking() {
+                return Ok(0);
+            }
+            self.wait_queue.wait_and_release(inner);
+        };
+        for (dst, byte) in buf.into_iter().zip(bytes) {
+            unsafe {
+                *dst = byte;
+            }
+        }
+        Ok(bytes.len())
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn poll(&self) -> PollEvents {
+        if self.inner.exclusive_access().expirations > 0 {
+            PollEvents::POLLIN
+        } else {
+            PollEvents::empty()
+        }
+    }
+    fn wait_queue(&self) -> Option<Arc<WaitQueue>> {
+        Some(self.wait_queue.clone())
+    }
+    fn nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Relaxed)
+    }
+    fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+    }
+    fn as_timerfd(&self) -> Option<&TimerFd> {
+        Some(self)
+    }
+}
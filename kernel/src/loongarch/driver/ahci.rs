@@ -10,15 +10,15 @@ use crate::{
     loongarch::VIRT_BIAS,
     mm::{frame_alloc, frame_dealloc, PhysAddr},
     phys_to_virt, println,
-    sync::UPSafeCell,
+    sync::SpinLock,
     virt_to_phys,
 };
 
-pub struct AHCIDriver(UPSafeCell<AHCI<Provider>>);
+pub struct AHCIDriver(SpinLock<AHCI<Provider>>);
 
 impl AHCIDriver {
     pub fn new(header: usize, size: usize) -> Option<Self> {
-        unsafe { AHCI::new(header, size).map(|x| Self(UPSafeCell::new(x))) }
+        unsafe { AHCI::new(header, size).map(|x| Self(SpinLock::new(x))) }
     }
 }
 
@@ -10,6 +10,26 @@ pub struct RtcTime {
     pub second: u32,
 }
 
+impl RtcTime {
+    /// 把 UTC 时间换算成从 1970-01-01 开始的秒数
+    pub fn unix_time(&self) -> usize {
+        // 把 3 月当作一年的开始，闰日落在一年的最后
+        let (year, month) = if self.month <= 2 {
+            (self.year as i64 - 1, self.month as i64 + 9)
+        } else {
+            (self.year as i64, self.month as i64 - 3)
+        };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * month + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146097 + day_of_era - 719468;
+        let secs =
+            days * 86400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+        secs.max(0) as usize
+    }
+}
+
 impl Debug for RtcTime {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(
@@ -2,14 +2,14 @@ use bit_field::BitField;
 use log::info;
 
 use crate::{loongarch::{
-    iocsr_read_b, iocsr_read_d, iocsr_write_b, iocsr_write_d, iocsr_write_h, iocsr_write_w,
+    iocsr_read_b, iocsr_read_d, iocsr_write_b, iocsr_write_d, iocsr_write_h,
     KEYBOARD_IRQ, LOONGARCH_IOCSR_EXRIOI_NODETYPE_BASE, LOONGARCH_IOCSR_EXTIOI_EN_BASE,
     LOONGARCH_IOCSR_EXTIOI_ISR_BASE, LOONGARCH_IOCSR_EXTIOI_MAP_BASE,
     LOONGARCH_IOCSR_EXTIOI_ROUTE_BASE, MOUSE_IRQ, UART0_IRQ,
 }, println};
 
-/// 初始化外部中断
-pub fn extioi_init() {
+/// 初始化外部中断，设备中断都路由到 `cpu` 上
+pub fn extioi_init(cpu: usize) {
     let mut enable = 0;
     enable
         .set_bit(KEYBOARD_IRQ, true)
@@ -21,9 +21,10 @@ pub fn extioi_init() {
     // extioi[31:0] map to cpu irq pin INT1, other to INT0
     //路由到INT1上
     iocsr_write_b(LOONGARCH_IOCSR_EXTIOI_MAP_BASE, 0x1);
-    // extioi IRQ 0-7 route to core 0, use node type 0
-    //路由到EXT_IOI_node_type0指向的0号处理器上
-    iocsr_write_w(LOONGARCH_IOCSR_EXTIOI_ROUTE_BASE, 0x0);
+    // extioi IRQ route to core `cpu`, use node type 0
+    for irq in [UART0_IRQ, KEYBOARD_IRQ, MOUSE_IRQ] {
+        extioi_route(irq, cpu);
+    }
     // nodetype0 set to 1, always trigger at node 0 */
     //固定分发模式时,只在0号处理器上触发
     iocsr_write_h(LOONGARCH_IOCSR_EXRIOI_NODETYPE_BASE, 0x1);
@@ -35,6 +36,11 @@ pub fn extioi_init() {
     assert_eq!(extioi_isr, 0);
 }
 
+/// 把中断 `irq` 路由到 `cpu` 上，每个中断一个字节，低 4 位是处理器核的位图
+pub fn extioi_route(irq: usize, cpu: usize) {
+    iocsr_write_b(LOONGARCH_IOCSR_EXTIOI_ROUTE_BASE + irq, 1 << cpu);
+}
+
 // ask the extioi what interrupt we should serve.
 pub fn extioi_claim() -> u64 {
     iocsr_read_d(LOONGARCH_IOCSR_EXTIOI_ISR_BASE)
@@ -9,24 +9,37 @@ pub const RTC_YEAR: usize = 0x30;
 pub const RTC_TOYREAD0: usize = 0x2c; //月日时分
 pub const RTC_CTRL: usize = 0x40;
 
-pub fn rtc_time_read() -> RtcTime {
+/// RTC 中保存的是 UTC 时间
+fn rtc_utc_read() -> RtcTime {
     let value = ls7a_read_w(LS7A_RTC_REG_BASE + RTC_TOYREAD0);
     let sec = (value >> 4) & 0x3f;
     let min = (value >> 10) & 0x3f;
-    let mut hour = (value >> 16) & 0x1f;
+    let hour = (value >> 16) & 0x1f;
     let day = (value >> 21) & 0x1f;
     let mon = (value >> 26) & 0x3f;
     let year = ls7a_read_w(LS7A_RTC_REG_BASE + RTC_YEAR) + 1900;
-    hour = (hour + 8) % 24;
-    return RtcTime {
+    RtcTime {
         year,
         month: mon,
         day,
         hour,
         minute: min,
         second: sec,
-    };
+    }
+}
+
+/// 北京时间
+pub fn rtc_time_read() -> RtcTime {
+    let mut time = rtc_utc_read();
+    time.hour = (time.hour + 8) % 24;
+    time
 }
+
+/// 从 1970-01-01 00:00:00 UTC 开始的秒数
+pub fn rtc_unix_time() -> usize {
+    rtc_utc_read().unix_time()
+}
+
 pub fn check_rtc() {
     let val = ls7a_read_w(LS7A_RTC_REG_BASE + RTC_CTRL);
     println!(
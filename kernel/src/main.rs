@@ -5,6 +5,7 @@
 #![feature(naked_functions)]
 mod boot;
 mod config;
+mod error;
 mod fs;
 mod info;
 mod lang_items;
@@ -22,11 +23,12 @@ extern crate alloc;
 
 use core::arch::global_asm;
 
-use config::{FLAG, UART};
+use config::{FLAG, IRQ_CPU, UART};
 pub use log::info;
 use uart::Uart;
 
 use crate::{
+    boot::start_secondary_cpus,
     fs::list_apps,
     info::{kernel_layout, print_machine_info},
     loongarch::{
@@ -63,12 +65,13 @@ pub fn main(cpu: usize) {
     log::error!("Logging init success");
     rtc_init();
     println!("CURRENT TIME {:?}", rtc_time_read());
+    timer::init_realtime();
     kernel_layout();
 
     mm::init();
     if cfg!(feature = "gui") {
         // 外部中断控制器初始化
-        extioi_init();
+        extioi_init(IRQ_CPU);
         println!("extioi init success");
         // 桥片中断初始化
         ls7a_intc_init();
@@ -97,6 +100,17 @@ pub fn main(cpu: usize) {
     add_initproc(); //添加初始化程序
     println!("add initproc success");
 
+    start_secondary_cpus(cpu);
     task::run_tasks(); //运行程序
     panic!("main end");
 }
+
+/// 其他 CPU 的入口，内存、设备和第一个进程都已经由启动的 CPU 准备好了
+#[no_mangle]
+pub fn main_secondary(cpu: usize) {
+    trap::init();
+    enable_timer_interrupt();
+    println!("cpu {} online", cpu);
+    task::run_tasks();
+    panic!("main end");
+}
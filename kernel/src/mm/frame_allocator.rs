@@ -5,7 +5,7 @@ use lazy_static::*;
 use log::info;
 
 use super::{PhysAddr, PhysPageNum};
-use crate::{config::MEMORY_END, loongarch::VIRT_BIAS, println, sync::UPSafeCell, virt_to_phys};
+use crate::{config::MEMORY_END, loongarch::VIRT_BIAS, println, sync::SpinLock, virt_to_phys};
 
 #[derive(Clone)]
 pub struct FrameTracker {
@@ -52,6 +52,10 @@ impl StackFrameAllocator {
         self.current = l.0;
         self.end = r.0;
     }
+    /// 还能分配的页帧数
+    pub fn free(&self) -> usize {
+        self.end - self.current + self.recycled.len()
+    }
 }
 impl FrameAllocator for StackFrameAllocator {
     fn new() -> Self {
@@ -90,8 +94,8 @@ impl FrameAllocator for StackFrameAllocator {
 type FrameAllocatorImpl = StackFrameAllocator;
 
 lazy_static! {
-    pub static ref FRAME_ALLOCATOR: UPSafeCell<FrameAllocatorImpl> =
-        unsafe { UPSafeCell::new(FrameAllocatorImpl::new()) };
+    pub static ref FRAME_ALLOCATOR: SpinLock<FrameAllocatorImpl> =
+        SpinLock::new(FrameAllocatorImpl::new());
 }
 
 pub fn init_frame_allocator() {
@@ -116,6 +120,10 @@ pub fn frame_alloc() -> Option<FrameTracker> {
         .map(|ppn| FrameTracker::new(ppn))
 }
 
+pub fn free_frames() -> usize {
+    FRAME_ALLOCATOR.exclusive_access().free()
+}
+
 pub fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
 }
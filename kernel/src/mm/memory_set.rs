@@ -1,13 +1,136 @@
-use alloc::{collections::BTreeMap, vec::Vec};
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
 
 use bitflags::bitflags;
 use log::debug;
+use loongarch64::time::Time;
+use xmas_elf::{
+    header::{self, Class, Data, Machine},
+    program::{self, ProgramHeader},
+    ElfFile,
+};
 
 use super::{
     frame_alloc, FrameTracker, PTEFlags, PageTable, PageTableEntry, PhysPageNum, StepByOne,
     VPNRange, VirtAddr, VirtPageNum,
 };
-use crate::config::PAGE_SIZE;
+use crate::{
+    config::{ELF_DYN_BASE, PAGE_SIZE, USER_MMAP_BASE, USER_STACK_BASE},
+    error::{SysError, SysResult},
+};
+
+const EM_LOONGARCH: u16 = 258;
+const PT_GNU_STACK: u32 = 0x6474_e551;
+/// 位置无关的程序在 ELF_DYN_BASE 之上随机偏移的页数
+const ELF_DYN_RANDOM_PAGES: usize = 0x1_0000;
+
+/// 程序的入口和程序头表的位置，exec 把它们放进辅助向量
+#[derive(Debug, Clone)]
+pub struct ElfInfo {
+    /// 程序自己的入口
+    pub entry: usize,
+    /// 开始执行的位置，有动态链接器时是动态链接器的入口
+    pub start: usize,
+    /// 动态链接器的加载地址，没有动态链接器时为 0
+    pub base: usize,
+    /// 最高的段结束之后的第一页，堆从这里开始
+    pub brk: usize,
+    /// 程序头表在用户地址空间中的地址，没有被加载时为 0
+    pub phdr: usize,
+    pub phent: usize,
+    pub phnum: usize,
+    /// PT_INTERP 中的动态链接器，由调用者读出文件交给 `load_interp`
+    pub interp: Option<String>,
+    /// 内核为主线程准备的 TLS 块，没有 PT_TLS 或者有动态链接器时为 0
+    pub tp: usize,
+    /// 用户栈的权限，PT_GNU_STACK 没有 X 标志时不可执行
+    pub stack_permission: MapPermission,
+}
+
+/// 检查 ELF 头和程序头表，不能加载的文件返回 ENOEXEC
+fn parse_elf(elf_data: &[u8]) -> SysResult<ElfFile<'_>> {
+    let elf = ElfFile::new(elf_data).map_err(|_| SysError::ENOEXEC)?;
+    let (pt1, pt2) = (&elf.header.pt1, &elf.header.pt2);
+    if pt1.class() != Class::SixtyFour
+        || pt1.data() != Data::LittleEndian
+        || pt2.machine().as_machine() != Machine::Other(EM_LOONGARCH)
+        || !matches!(
+            pt2.type_().as_type(),
+            header::Type::Executable | header::Type::SharedObject
+        )
+    {
+        return Err(SysError::ENOEXEC);
+    }
+    let ph_size = pt2.ph_entry_size() as usize;
+    let ph_end = (pt2.ph_count() as usize)
+        .checked_mul(ph_size)
+        .and_then(|size| size.checked_add(pt2.ph_offset() as usize));
+    if pt2.ph_count() == 0
+        || pt2.ph_offset() == 0
+        || ph_size != core::mem::size_of::<program::ProgramHeader64>()
+        || !ph_end.is_some_and(|end| end <= elf_data.len())
+    {
+        return Err(SysError::ENOEXEC);
+    }
+    if find_program_header(&elf, program::Type::Load).is_none() {
+        return Err(SysError::ENOEXEC);
+    }
+    for ph in elf.program_iter() {
+        let (offset, file_size) = (ph.offset() as usize, ph.file_size() as usize);
+        let in_file = offset
+            .checked_add(file_size)
+            .is_some_and(|end| end <= elf_data.len());
+        let valid = match ph.get_type() {
+            Ok(program::Type::Load) => {
+                in_file
+                    && file_size <= ph.mem_size() as usize
+                    && ph.virtual_addr() as usize % PAGE_SIZE == offset % PAGE_SIZE
+            }
+            Ok(program::Type::Interp) => in_file && file_size > 1,
+            Ok(program::Type::Tls) => {
+                let align = ph.align() as usize;
+                in_file
+                    && file_size <= ph.mem_size() as usize
+                    && align <= PAGE_SIZE
+                    && (align == 0 || align.is_power_of_two())
+            }
+            _ => true,
+        };
+        if !valid {
+            return Err(SysError::ENOEXEC);
+        }
+    }
+    Ok(elf)
+}
+
+/// 所有 PT_LOAD 段覆盖的页
+fn load_span(elf: &ElfFile) -> (VirtPageNum, VirtPageNum) {
+    let (mut start, mut end) = (usize::MAX, 0);
+    for ph in elf.program_iter() {
+        if ph.get_type() == Ok(program::Type::Load) {
+            start = start.min(ph.virtual_addr() as usize);
+            end = end.max(ph.virtual_addr().saturating_add(ph.mem_size()) as usize);
+        }
+    }
+    (VirtAddr::from(start).floor(), VirtAddr::from(end).ceil())
+}
+
+fn find_program_header<'a>(elf: &ElfFile<'a>, type_: program::Type) -> Option<ProgramHeader<'a>> {
+    elf.program_iter().find(|ph| ph.get_type() == Ok(type_))
+}
+
+fn segment_permission(flags: program::Flags) -> MapPermission {
+    let mut map_perm = MapPermission::default();
+    if !flags.is_read() {
+        map_perm |= MapPermission::NR;
+    }
+    if flags.is_write() {
+        map_perm |= MapPermission::W;
+    }
+    if !flags.is_execute() {
+        map_perm |= MapPermission::NX;
+    }
+    map_perm
+}
 
 #[derive(Clone)]
 pub struct MemorySet {
@@ -32,10 +155,20 @@ impl MemorySet {
     pub fn insert_area(&mut self, start_va: VirtAddr, end_va: VirtAddr, permission: MapPermission) {
         self.push(MapArea::new(start_va, end_va, permission), None);
     }
+    /// 同 `insert_area`，并从开头复制 `data`
+    pub fn insert_area_with_data(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+        data: &[u8],
+    ) {
+        self.push(MapArea::new(start_va, end_va, permission), Some(data));
+    }
     fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
         map_area.map(&mut self.page_table);
         if let Some(data) = data {
-            map_area.copy_data(&mut self.page_table, data);
+            map_area.copy_data(&mut self.page_table, data, 0);
         }
         self.areas.push(map_area);
     }
@@ -59,57 +192,152 @@ impl MemorySet {
     }
 
     /// Include sections in elf and trampoline and TrapContext and user stack,
-    /// also returns user_sp and entry point.
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    /// also returns the base of user stacks and what exec needs to know of the elf.
+    /// ET_DYN 的程序加载在 ELF_DYN_BASE 之上随机的位置；PT_INTERP 只记录在
+    /// `ElfInfo` 中，由调用者用 `load_interp` 加载
+    pub fn from_elf(elf_data: &[u8]) -> SysResult<(Self, usize, ElfInfo)> {
+        let elf = parse_elf(elf_data)?;
         let mut memory_set = Self::new_bare();
-        // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
-        let elf_header = elf.header;
-        let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
-        let ph_count = elf_header.pt2.ph_count();
-        let mut max_end_vpn = VirtPageNum(0);
-
-        for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
-                let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
-                let mut map_perm = MapPermission::default();
-                let ph_flags = ph.flags();
-                if !ph_flags.is_read() {
-                    map_perm |= MapPermission::NR;
-                }
-                if ph_flags.is_write() {
-                    map_perm |= MapPermission::W;
+        let bias = match elf.header.pt2.type_().as_type() {
+            header::Type::SharedObject => {
+                let (start, _) = load_span(&elf);
+                let random_pages = Time::read() % ELF_DYN_RANDOM_PAGES;
+                (ELF_DYN_BASE + random_pages * PAGE_SIZE).wrapping_sub(VirtAddr::from(start).into())
+            }
+            _ => 0,
+        };
+        let (end, phdr) = memory_set.map_segments(&elf, bias)?;
+        let interp = find_program_header(&elf, program::Type::Interp).map(|ph| {
+            let path = &elf_data[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize];
+            let len = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+            String::from_utf8_lossy(&path[..len]).into_owned()
+        });
+        let stack_permission =
+            match find_program_header(&elf, program::Type::OsSpecific(PT_GNU_STACK)) {
+                Some(ph) if !ph.flags().is_execute() => {
+                    MapPermission::default() | MapPermission::W | MapPermission::NX
                 }
-                if !ph_flags.is_execute() {
-                    map_perm |= MapPermission::NX;
+                _ => MapPermission::default() | MapPermission::W,
+            };
+        // 动态链接器自己准备 TLS
+        let tp = match find_program_header(&elf, program::Type::Tls) {
+            Some(ph) if interp.is_none() && ph.mem_size() > 0 => memory_set.alloc_tls(
+                &elf_data[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize],
+                ph.mem_size() as usize,
+            )?,
+            _ => 0,
+        };
+        let entry = bias.wrapping_add(elf.header.pt2.entry_point() as usize);
+        //返回地址空间,用户栈的起点,入口地址等
+        let elf_info = ElfInfo {
+            entry,
+            start: entry,
+            base: 0,
+            brk: end,
+            phdr,
+            phent: elf.header.pt2.ph_entry_size() as usize,
+            phnum: elf.header.pt2.ph_count() as usize,
+            interp,
+            tp,
+            stack_permission,
+        };
+        Ok((memory_set, USER_STACK_BASE, elf_info))
+    }
+
+    /// 把动态链接器加载到 mmap 的区域中，程序从它的入口开始执行
+    pub fn load_interp(&mut self, elf_data: &[u8], elf_info: &mut ElfInfo) -> SysResult<()> {
+        let elf = parse_elf(elf_data)?;
+        // the interpreter must not need another one
+        if find_program_header(&elf, program::Type::Interp).is_some() {
+            return Err(SysError::ENOEXEC);
+        }
+        let bias = match elf.header.pt2.type_().as_type() {
+            header::Type::SharedObject => {
+                let (start, end) = load_span(&elf);
+                let base = self
+                    .find_free(
+                        VirtAddr::from(USER_MMAP_BASE).floor(),
+                        VirtAddr::from(USER_STACK_BASE).floor(),
+                        end.0.saturating_sub(start.0),
+                    )
+                    .ok_or(SysError::ENOMEM)?;
+                VirtAddr::from(base)
+                    .0
+                    .wrapping_sub(VirtAddr::from(start).into())
+            }
+            _ => 0,
+        };
+        self.map_segments(&elf, bias)?;
+        elf_info.base = bias;
+        elf_info.start = bias.wrapping_add(elf.header.pt2.entry_point() as usize);
+        Ok(())
+    }
+
+    /// 把 PT_LOAD 段映射到加上 `bias` 的位置，返回最高的段结束之后的第一页
+    /// 和程序头表的地址。段的位置越界或者和已有的逻辑段重叠时返回 ENOEXEC
+    fn map_segments(&mut self, elf: &ElfFile, bias: usize) -> SysResult<(usize, usize)> {
+        let ph_offset = elf.header.pt2.ph_offset() as usize;
+        let mut max_end_vpn = VirtPageNum(0);
+        let mut phdr = 0;
+        for ph in elf.program_iter() {
+            let vaddr = bias.wrapping_add(ph.virtual_addr() as usize);
+            match ph.get_type() {
+                Ok(program::Type::Phdr) => phdr = vaddr,
+                Ok(program::Type::Load) => {
+                    // the program headers are loaded with the segment that covers them
+                    let (offset, file_size) = (ph.offset() as usize, ph.file_size() as usize);
+                    if phdr == 0 && offset <= ph_offset && ph_offset < offset + file_size {
+                        phdr = vaddr + ph_offset - offset;
+                    }
+                    let end = vaddr
+                        .checked_add(ph.mem_size() as usize)
+                        .filter(|&end| end <= USER_STACK_BASE)
+                        .ok_or(SysError::ENOEXEC)?;
+                    let start_va: VirtAddr = vaddr.into();
+                    let end_va: VirtAddr = end.into();
+                    if !self.is_free(start_va.floor(), end_va.ceil()) {
+                        return Err(SysError::ENOEXEC);
+                    }
+                    let map_perm = segment_permission(ph.flags());
+                    debug!(
+                        "start_va: {:?}, end_va: {:?}, map_perm: {:?}",
+                        start_va, end_va, map_perm
+                    );
+                    let mut map_area = MapArea::new(start_va, end_va, map_perm);
+                    max_end_vpn = max_end_vpn.max(map_area.vpn_range.get_end());
+                    map_area.map(&mut self.page_table);
+                    map_area.copy_data(
+                        &mut self.page_table,
+                        &elf.input[offset..offset + file_size],
+                        start_va.page_offset(),
+                    );
+                    self.areas.push(map_area);
                 }
-                debug!(
-                    "start_va: {:?}, end_va: {:?}, map_perm: {:?}",
-                    start_va, end_va, map_perm
-                );
-                let map_area = MapArea::new(start_va, end_va, map_perm);
-                debug!("map_area: {:?}", map_area);
-                max_end_vpn = map_area.vpn_range.get_end();
-                memory_set.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
+                _ => {}
             }
         }
-        // map user stack with U flags
         let max_end_va: VirtAddr = max_end_vpn.into();
-        let mut user_stack_bottom: usize = max_end_va.into();
-        // guard page
-        user_stack_bottom += PAGE_SIZE; //用户栈
-                                        //返回地址空间,用户栈顶,入口地址
-        (
-            memory_set,
-            user_stack_bottom,
-            elf.header.pt2.entry_point() as usize,
-        )
+        Ok((max_end_va.into(), phdr))
+    }
+
+    /// 为主线程分配 TLS 块：开头是 `tdata`，其余部分为 0，返回块的地址
+    fn alloc_tls(&mut self, tdata: &[u8], size: usize) -> SysResult<usize> {
+        let pages = size.div_ceil(PAGE_SIZE);
+        let start = self
+            .find_free(
+                VirtAddr::from(USER_MMAP_BASE).floor(),
+                VirtAddr::from(USER_STACK_BASE).floor(),
+                pages,
+            )
+            .ok_or(SysError::ENOMEM)?;
+        let start_va: VirtAddr = start.into();
+        self.insert_area_with_data(
+            start_va,
+            (start + pages).into(),
+            MapPermission::default() | MapPermission::W | MapPermission::NX,
+            tdata,
+        );
+        Ok(start_va.into())
     }
     ///Remove `MapArea` that starts with `start_vpn`
     pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
@@ -124,6 +352,65 @@ impl MemorySet {
         }
     }
 
+    /// Whether `vpn` belongs to some `MapArea`
+    pub fn is_mapped(&self, vpn: VirtPageNum) -> bool {
+        self.areas.iter().any(|area| area.contains(vpn))
+    }
+    /// Whether no `MapArea` overlaps `start..end`
+    pub fn is_free(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        self.areas
+            .iter()
+            .all(|area| area.vpn_range.get_end() <= start || end <= area.vpn_range.get_start())
+    }
+    /// 在 `start..end` 中找到能放下 `pages` 页的最低的空闲位置
+    pub fn find_free(
+        &self,
+        start: VirtPageNum,
+        end: VirtPageNum,
+        pages: usize,
+    ) -> Option<VirtPageNum> {
+        let mut ranges: Vec<_> = self
+            .areas
+            .iter()
+            .map(|area| (area.vpn_range.get_start(), area.vpn_range.get_end()))
+            .filter(|&(area_start, area_end)| start < area_end && area_start < end)
+            .collect();
+        ranges.sort_by_key(|&(area_start, _)| area_start.0);
+        let mut candidate = start;
+        for (area_start, area_end) in ranges {
+            if candidate.0 + pages <= area_start.0 {
+                break;
+            }
+            candidate = VirtPageNum(candidate.0.max(area_end.0));
+        }
+        (candidate.0 + pages <= end.0).then_some(candidate)
+    }
+    /// 取消 `start..end` 中所有页的映射，部分重叠的逻辑段被截断或者分成两段
+    pub fn remove_range(&mut self, start: VirtPageNum, end: VirtPageNum) {
+        let mut i = 0;
+        while i < self.areas.len() {
+            let area = &mut self.areas[i];
+            let (area_start, area_end) = (area.vpn_range.get_start(), area.vpn_range.get_end());
+            if area_end <= start || end <= area_start {
+                i += 1;
+                continue;
+            }
+            if area_start < start {
+                // keep the part before the range, look at the rest next
+                let rest = area.split_off(start);
+                self.areas.insert(i + 1, rest);
+                i += 1;
+                continue;
+            }
+            if end < area_end {
+                let rest = area.split_off(end);
+                self.areas.insert(i + 1, rest);
+            }
+            self.areas[i].unmap(&mut self.page_table);
+            self.areas.remove(i);
+        }
+    }
+
     ///Remove all `MapArea`
     pub fn recycle_data_pages(&mut self) {
         //*self = Self::new_bare();
@@ -132,6 +419,42 @@ impl MemorySet {
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
+    /// 把不超过一页的 `data` 复制到页 `vpn` 的开头，这一页没有映射时返回 false
+    pub fn copy_to_page(&self, vpn: VirtPageNum, data: &[u8]) -> bool {
+        let Some(pte) = self.translate(vpn).filter(|pte| pte.is_valid()) else {
+            return false;
+        };
+        pte.ppn().get_bytes_array()[..data.len()].copy_from_slice(data);
+        true
+    }
+    /// 检查 `start..start + len` 的每一页都在用户态可读的逻辑段中并且已经映射，
+    /// write 为真时还要求可写
+    pub fn check_user_range(&self, start: usize, len: usize, write: bool) -> SysResult<()> {
+        if len == 0 {
+            return Ok(());
+        }
+        let end = start.checked_add(len).ok_or(SysError::EFAULT)?;
+        let mut vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(end).ceil();
+        while vpn < end_vpn {
+            let area = self
+                .areas
+                .iter()
+                .find(|area| area.contains(vpn))
+                .ok_or(SysError::EFAULT)?;
+            if !area.user_accessible(write) {
+                return Err(SysError::EFAULT);
+            }
+            let area_end = area.vpn_range.get_end();
+            while vpn < end_vpn && vpn < area_end {
+                if !self.translate(vpn).is_some_and(|pte| pte.is_valid()) {
+                    return Err(SysError::EFAULT);
+                }
+                vpn.step();
+            }
+        }
+        Ok(())
+    }
 }
 //  PTEFlags 的一个子集
 // 主要含有几个读写标志位和存在位，对于其它控制位
@@ -178,6 +501,28 @@ impl MapArea {
         }
     }
 
+    /// 在 `at` 处把逻辑段分成两段，返回后一段
+    fn split_off(&mut self, at: VirtPageNum) -> Self {
+        let end = self.vpn_range.get_end();
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), at);
+        Self {
+            vpn_range: VPNRange::new(at, end),
+            data_frames: self.data_frames.split_off(&at),
+            map_perm: self.map_perm,
+        }
+    }
+
+    fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() <= vpn && vpn < self.vpn_range.get_end()
+    }
+    /// 用户态能否读取这个逻辑段，write 为真时还要能写入
+    fn user_accessible(&self, write: bool) -> bool {
+        self.map_perm
+            .contains(MapPermission::PLVL | MapPermission::PLVH)
+            && !self.map_perm.contains(MapPermission::NR)
+            && (!write || self.map_perm.contains(MapPermission::W))
+    }
+
     pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         let ppn: PhysPageNum;
         let frame = frame_alloc().unwrap();
@@ -200,24 +545,22 @@ impl MapArea {
             self.unmap_one(page_table, vpn);
         }
     }
-    /// data: start-aligned but maybe with shorter length
+    /// data: starts at `offset` of the first page, maybe with shorter length
     /// assume that all frames were cleared before
-    pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8]) {
-        let mut start: usize = 0;
+    pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8], offset: usize) {
         let mut current_vpn = self.vpn_range.get_start();
-        let len = data.len();
-        loop {
-            let src = &data[start..len.min(start + PAGE_SIZE)];
-            let dst = &mut page_table
+        let mut page_offset = offset;
+        let mut rest = data;
+        while !rest.is_empty() {
+            let len = rest.len().min(PAGE_SIZE - page_offset);
+            page_table
                 .translate(current_vpn)
                 .unwrap()
                 .ppn()
-                .get_bytes_array()[..src.len()];
-            dst.copy_from_slice(src);
-            start += PAGE_SIZE;
-            if start >= len {
-                break;
-            }
+                .get_bytes_array()[page_offset..page_offset + len]
+                .copy_from_slice(&rest[..len]);
+            rest = &rest[len..];
+            page_offset = 0;
             current_vpn.step();
         }
     }
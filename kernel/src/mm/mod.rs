@@ -3,14 +3,13 @@ mod frame_allocator;
 mod memory_set;
 mod page_table;
 pub mod system_allocator;
+mod user_ptr;
 
 pub use address::{PhysAddr, PhysPageNum, StepByOne, VPNRange, VirtAddr, VirtPageNum};
-pub use frame_allocator::{frame_alloc, frame_dealloc, FrameTracker};
-pub use memory_set::{MapPermission, MemorySet};
-pub use page_table::{
-    translated_byte_buffer, translated_ref, translated_refmut, translated_str, PTEFlags, PageTable,
-    PageTableEntry, UserBuffer,
-};
+pub use frame_allocator::{frame_alloc, frame_dealloc, free_frames, FrameTracker};
+pub use memory_set::{ElfInfo, MapPermission, MemorySet};
+pub use page_table::{PTEFlags, PageTable, PageTableEntry, UserBuffer};
+pub use user_ptr::{UserPtr, UserSlice};
 
 use crate::{loongarch::VIRT_BIAS, mm::system_allocator::init_heap};
 
@@ -1,25 +1,26 @@
 ///! 堆分配器的通用函数
 use alloc::alloc::Layout;
 
-use spin::{Mutex, MutexGuard};
+use crate::sync::{IrqSafeSpinLock, IrqSafeSpinLockGuard};
 
 #[alloc_error_handler]
 fn alloc_error_handler(layout: Layout) -> ! {
     panic!("allocation error: {:?}", layout)
 }
 
-/// 包装一下Mutex使得我们可以使用Mutex
+/// 包装一下锁使得我们可以为它实现GlobalAlloc。
+/// 中断处理程序也会分配内存，所以持有期间关中断
 pub struct Locked<T> {
-    inner: Mutex<T>,
+    inner: IrqSafeSpinLock<T>,
 }
 impl<T> Locked<T> {
     pub const fn new(inner: T) -> Self {
         Locked {
-            inner: Mutex::new(inner),
+            inner: IrqSafeSpinLock::new(inner),
         }
     }
-    pub fn lock(&self) -> MutexGuard<T> {
-        self.inner.lock()
+    pub fn lock(&self) -> IrqSafeSpinLockGuard<T> {
+        self.inner.exclusive_access()
     }
 }
 
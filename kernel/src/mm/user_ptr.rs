@@ -0,0 +1,194 @@
+//! 内核访问用户地址空间的接口
+//!
+//! 系统调用传入的指针不可信任：访问前按当前进程的逻辑段逐页检查，
+//! 要求用户态可以访问、可读，写入时还要可写，否则返回 EFAULT。
+//! 跨页的数据按物理页分段复制。
+
+use alloc::{string::String, vec::Vec};
+use core::{
+    marker::PhantomData,
+    mem::{size_of, MaybeUninit},
+};
+
+use super::{MemorySet, StepByOne, UserBuffer, VirtAddr};
+use crate::{
+    config::PAGE_SIZE,
+    error::{SysError, SysResult},
+    task::current_process,
+};
+
+/// 从用户态读取的字符串（包括结尾的 `\0`）的最大长度
+const PATH_MAX: usize = 4096;
+
+/// 检查当前进程的用户地址范围并把它切成各个物理页中的片段，write 表示内核要写入。
+/// 调用时不能持有当前进程的 inner。
+fn user_pages(start: usize, len: usize, write: bool) -> SysResult<Vec<&'static mut [u8]>> {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    pages_in(&inner.memory_set, start, len, write)
+}
+
+fn pages_in(
+    memory_set: &MemorySet,
+    start: usize,
+    len: usize,
+    write: bool,
+) -> SysResult<Vec<&'static mut [u8]>> {
+    memory_set.check_user_range(start, len, write)?;
+    let end = start + len;
+    let mut pieces = Vec::new();
+    let mut current = start;
+    while current < end {
+        let va = VirtAddr::from(current);
+        let mut vpn = va.floor();
+        let ppn = memory_set.translate(vpn).unwrap().ppn();
+        vpn.step();
+        let page_end: VirtAddr = vpn.into();
+        let piece_end = end.min(page_end.into());
+        let offset = va.page_offset();
+        pieces.push(&mut ppn.get_bytes_array()[offset..offset + piece_end - current]);
+        current = piece_end;
+    }
+    Ok(pieces)
+}
+
+/// 用户地址空间中的一段字节
+#[derive(Debug, Clone, Copy)]
+pub struct UserSlice {
+    addr: usize,
+    len: usize,
+}
+
+impl UserSlice {
+    pub fn new(ptr: *const u8, len: usize) -> Self {
+        Self {
+            addr: ptr as usize,
+            len,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    /// 检查权限后交给文件读写的缓冲区，write 表示内核要写入这段内存
+    pub fn buffer(&self, write: bool) -> SysResult<UserBuffer> {
+        Ok(UserBuffer::new(user_pages(self.addr, self.len, write)?))
+    }
+    /// 把这段用户内存复制到 `data`，长度必须相同
+    pub fn read(&self, data: &mut [u8]) -> SysResult<()> {
+        assert_eq!(data.len(), self.len);
+        let mut copied = 0;
+        for piece in user_pages(self.addr, self.len, false)? {
+            data[copied..copied + piece.len()].copy_from_slice(piece);
+            copied += piece.len();
+        }
+        Ok(())
+    }
+    /// 把 `data` 复制到这段用户内存，长度必须相同
+    pub fn write(&self, data: &[u8]) -> SysResult<()> {
+        let process = current_process();
+        let inner = process.inner_exclusive_access();
+        self.write_in(&inner.memory_set, data)
+    }
+    /// 同 `write`，但写入指定的地址空间，用于还没有运行的进程
+    pub fn write_in(&self, memory_set: &MemorySet, data: &[u8]) -> SysResult<()> {
+        assert_eq!(data.len(), self.len);
+        let mut copied = 0;
+        for piece in pages_in(memory_set, self.addr, self.len, true)? {
+            piece.copy_from_slice(&data[copied..copied + piece.len()]);
+            copied += piece.len();
+        }
+        Ok(())
+    }
+}
+
+/// 指向用户地址空间中一个 `T` 的指针，按字节复制读写，不要求对齐。
+/// `T` 必须对任意字节内容都是合法的值。
+#[derive(Debug)]
+pub struct UserPtr<T> {
+    addr: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Clone for UserPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for UserPtr<T> {}
+
+impl<T> From<*const T> for UserPtr<T> {
+    fn from(ptr: *const T) -> Self {
+        Self {
+            addr: ptr as usize,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> From<*mut T> for UserPtr<T> {
+    fn from(ptr: *mut T) -> Self {
+        Self::from(ptr as *const T)
+    }
+}
+
+impl<T: Copy> UserPtr<T> {
+    pub fn is_null(&self) -> bool {
+        self.addr == 0
+    }
+    /// 向后第 count 个元素
+    pub fn add(&self, count: usize) -> Self {
+        Self {
+            addr: self.addr.wrapping_add(count * size_of::<T>()),
+            _marker: PhantomData,
+        }
+    }
+    fn slice(&self) -> UserSlice {
+        UserSlice::new(self.addr as *const u8, size_of::<T>())
+    }
+    pub fn read(&self) -> SysResult<T> {
+        let mut value = MaybeUninit::<T>::uninit();
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>())
+        };
+        self.slice().read(bytes)?;
+        Ok(unsafe { value.assume_init() })
+    }
+    pub fn write(&self, value: T) -> SysResult<()> {
+        let bytes =
+            unsafe { core::slice::from_raw_parts(&value as *const T as *const u8, size_of::<T>()) };
+        self.slice().write(bytes)
+    }
+    /// 指向的数据在内核中的地址，不同地址空间映射同一物理页时相同。
+    /// 数据不能跨页
+    pub fn kernel_addr(&self) -> SysResult<usize> {
+        let pieces = user_pages(self.addr, size_of::<T>(), false)?;
+        if pieces.len() != 1 {
+            return Err(SysError::EINVAL);
+        }
+        Ok(pieces[0].as_ptr() as usize)
+    }
+}
+
+impl UserPtr<u8> {
+    /// 读取以 `\0` 结尾的字符串，每次检查到当前页的末尾。
+    /// 包括 `\0` 超过 [`PATH_MAX`] 时返回 ENAMETOOLONG，不是 UTF-8 时返回 EINVAL
+    pub fn read_str(&self) -> SysResult<String> {
+        let mut bytes = Vec::new();
+        let mut addr = self.addr;
+        loop {
+            let len = PAGE_SIZE - addr % PAGE_SIZE;
+            for piece in user_pages(addr, len, false)? {
+                let end = piece.iter().position(|&byte| byte == 0);
+                bytes.extend_from_slice(&piece[..end.unwrap_or(piece.len())]);
+                if bytes.len() >= PATH_MAX {
+                    return Err(SysError::ENAMETOOLONG);
+                }
+                if end.is_some() {
+                    return String::from_utf8(bytes).map_err(|_| SysError::EINVAL);
+                }
+            }
+            addr += len;
+        }
+    }
+}
@@ -1,8 +1,6 @@
 use core::fmt::{Arguments, Write};
 
-use spin::{Lazy, Mutex};
-
-use crate::{config::UART, uart::Uart};
+use crate::{config::UART, sync::IrqSafeSpinLock, uart::Uart};
 
 pub struct Console {
     inner: Uart,
@@ -18,6 +16,12 @@ impl Console {
             self.inner.put(ch)
         }
     }
+    /// Write raw bytes, no matter whether they are valid UTF-8
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &ch in bytes {
+            self.inner.put(ch)
+        }
+    }
     pub fn get_char(&mut self) -> Option<u8> {
         self.inner.get()
     }
@@ -29,12 +33,12 @@ impl Write for Console {
     }
 }
 
-pub static CONSOLE: Mutex<Console> = Mutex::new(Console::new(UART));
+pub static CONSOLE: IrqSafeSpinLock<Console> = IrqSafeSpinLock::new(Console::new(UART));
 
 pub fn get_char() -> u8 {
     // todo!根据rcore内部实现推测这里应该是一个阻塞调用
     loop {
-        let ch = CONSOLE.lock().get_char();
+        let ch = CONSOLE.exclusive_access().get_char();
         if let Some(ch) = ch {
             return ch;
         }
@@ -42,7 +46,7 @@ pub fn get_char() -> u8 {
 }
 
 pub fn _print(arg: Arguments) {
-    CONSOLE.lock().write_fmt(arg).unwrap()
+    CONSOLE.exclusive_access().write_fmt(arg).unwrap()
 }
 
 #[macro_export]
@@ -0,0 +1,49 @@
+use alloc::{collections::VecDeque, sync::Arc};
+
+use super::SpinLock;
+use crate::task::{
+    block_current_and_run_next, current_task, prepare_block, wakeup_task, TaskControlBlock,
+};
+
+/// 屏障：前 `count - 1` 个到达的线程阻塞，第 `count` 个到达时全部放行，
+/// 之后屏障可以再次使用
+pub struct Barrier {
+    count: usize,
+    inner: SpinLock<BarrierInner>,
+}
+
+pub struct BarrierInner {
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl Barrier {
+    pub fn new(count: usize) -> Self {
+        Self {
+            count,
+            inner: SpinLock::new(BarrierInner {
+                wait_queue: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// 最后到达的线程返回 true，和 PTHREAD_BARRIER_SERIAL_THREAD 一样
+    /// 可以用它来做只需要一个线程完成的工作
+    pub fn wait(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.wait_queue.len() + 1 >= self.count {
+            for task in core::mem::take(&mut inner.wait_queue) {
+                wakeup_task(task);
+            }
+            return true;
+        }
+        prepare_block();
+        inner.wait_queue.push_back(current_task().unwrap());
+        drop(inner);
+        block_current_and_run_next();
+        false
+    }
+
+    pub fn has_waiters(&self) -> bool {
+        !self.inner.exclusive_access().wait_queue.is_empty()
+    }
+}
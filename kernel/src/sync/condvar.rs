@@ -1,12 +1,16 @@
 use alloc::{collections::VecDeque, sync::Arc};
 
+use super::{wait_queue::remove_waiter, Mutex, SpinLock};
 use crate::{
-    sync::{Mutex, UPSafeCell},
-    task::{add_task, block_current_and_run_next, current_task, TaskControlBlock},
+    error::{SysError, SysResult},
+    task::{
+        block_current_until, cancel_block, current_task, prepare_block, wakeup_task,
+        TaskControlBlock,
+    },
 };
 
 pub struct Condvar {
-    pub inner: UPSafeCell<CondvarInner>,
+    pub inner: SpinLock<CondvarInner>,
 }
 
 pub struct CondvarInner {
@@ -16,27 +20,57 @@ pub struct CondvarInner {
 impl Condvar {
     pub fn new() -> Self {
         Self {
-            inner: unsafe {
-                UPSafeCell::new(CondvarInner {
-                    wait_queue: VecDeque::new(),
-                })
-            },
+            inner: SpinLock::new(CondvarInner {
+                wait_queue: VecDeque::new(),
+            }),
         }
     }
 
     pub fn signal(&self) {
         let mut inner = self.inner.exclusive_access();
         if let Some(task) = inner.wait_queue.pop_front() {
-            add_task(task);
+            wakeup_task(task);
         }
     }
 
-    pub fn wait(&self, mutex: Arc<dyn Mutex>) {
-        mutex.unlock();
-        let mut inner = self.inner.exclusive_access();
-        inner.wait_queue.push_back(current_task().unwrap());
-        drop(inner);
-        block_current_and_run_next();
+    /// 唤醒所有等待的线程
+    pub fn broadcast(&self) {
+        let waiters = core::mem::take(&mut self.inner.exclusive_access().wait_queue);
+        for task in waiters {
+            wakeup_task(task);
+        }
+    }
+
+    /// 释放 mutex 并等待，返回前重新获得 mutex。mutex 没有被持有时直接返回 false
+    pub fn wait(&self, mutex: Arc<dyn Mutex>) -> bool {
+        self.wait_timeout(mutex, None).is_ok()
+    }
+
+    /// 同 `wait`，到 `expire_ns` 还没有被唤醒时返回 ETIMEDOUT，这时也重新获得了 mutex。
+    /// 先加入等待队列再释放 mutex，其他线程在这之后的 signal 不会丢失
+    pub fn wait_timeout(&self, mutex: Arc<dyn Mutex>, expire_ns: Option<usize>) -> SysResult<()> {
+        let task = current_task().unwrap();
+        prepare_block();
+        self.inner
+            .exclusive_access()
+            .wait_queue
+            .push_back(Arc::clone(&task));
+        if !mutex.unlock() {
+            remove_waiter(&mut self.inner.exclusive_access().wait_queue, &task);
+            cancel_block();
+            return Err(SysError::EPERM);
+        }
+        block_current_until(expire_ns);
+        let timed_out = remove_waiter(&mut self.inner.exclusive_access().wait_queue, &task);
         mutex.lock();
+        if timed_out {
+            Err(SysError::ETIMEDOUT)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn has_waiters(&self) -> bool {
+        !self.inner.exclusive_access().wait_queue.is_empty()
     }
 }
@@ -0,0 +1,126 @@
+//! 用银行家算法检测死锁
+//!
+//! 把进程的互斥锁和信号量看作资源，记录每种资源的可用数量（Available）、
+//! 每个线程已经持有的数量（Allocation）和正在等待的数量（Need）。
+//! 线程申请资源时先假设申请已经发生，如果找不到一个让所有线程都能完成的
+//! 顺序，说明继续等待会死锁，申请失败。
+//!
+//! 算法假设线程最终会归还自己持有的资源，所以初值为 0、由其他线程 up 的
+//! 信号量（用来通知而不是互斥）会被误判，这类程序不应该打开检测。
+
+use alloc::collections::{BTreeMap, BTreeSet};
+
+use crate::error::{SysError, SysResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Resource {
+    Mutex(usize),
+    Semaphore(usize),
+}
+
+type Counts = BTreeMap<Resource, usize>;
+
+/// 进程的资源分配情况，不论是否打开检测都会记录
+#[derive(Default)]
+pub struct DeadlockDetector {
+    /// 是否在申请资源时检测
+    pub enabled: bool,
+    available: Counts,
+    /// 线程号 -> 持有的资源
+    allocation: BTreeMap<usize, Counts>,
+    /// 线程号 -> 等待的资源
+    need: BTreeMap<usize, Counts>,
+}
+
+fn add(counts: &mut Counts, res: Resource) {
+    *counts.entry(res).or_default() += 1;
+}
+
+fn sub(counts: &mut Counts, res: Resource) {
+    if let Some(n) = counts.get_mut(&res) {
+        *n -= 1;
+        if *n == 0 {
+            counts.remove(&res);
+        }
+    }
+}
+
+impl DeadlockDetector {
+    /// 新建或者重新使用编号为 `res` 的资源
+    pub fn add_resource(&mut self, res: Resource, available: usize) {
+        self.available.insert(res, available);
+        for counts in self.allocation.values_mut().chain(self.need.values_mut()) {
+            counts.remove(&res);
+        }
+    }
+    /// 资源被销毁，线程对它的持有和等待一起清除
+    pub fn remove_resource(&mut self, res: Resource) {
+        self.available.remove(&res);
+        for counts in self.allocation.values_mut().chain(self.need.values_mut()) {
+            counts.remove(&res);
+        }
+    }
+    /// 线程 `tid` 申请一个 `res`，打开检测时申请会导致不安全状态则返回 EDEADLK
+    pub fn request(&mut self, tid: usize, res: Resource) -> SysResult<()> {
+        add(self.need.entry(tid).or_default(), res);
+        if self.enabled && !self.is_safe() {
+            self.cancel_request(tid, res);
+            return Err(SysError::EDEADLK);
+        }
+        Ok(())
+    }
+    /// 线程 `tid` 得到了申请的 `res`
+    pub fn acquired(&mut self, tid: usize, res: Resource) {
+        self.cancel_request(tid, res);
+        add(self.allocation.entry(tid).or_default(), res);
+        sub(&mut self.available, res);
+    }
+    /// 线程 `tid` 释放了一个 `res`，信号量可以由没有持有它的线程释放
+    pub fn release(&mut self, tid: usize, res: Resource) {
+        if let Some(counts) = self.allocation.get_mut(&tid) {
+            sub(counts, res);
+            if counts.is_empty() {
+                self.allocation.remove(&tid);
+            }
+        }
+        add(&mut self.available, res);
+    }
+    /// 线程退出，它持有的资源不会再释放，仍然不可用
+    pub fn remove_thread(&mut self, tid: usize) {
+        self.allocation.remove(&tid);
+        self.need.remove(&tid);
+    }
+    /// 线程 `tid` 不再等待 `res`，例如等待超时了
+    pub fn cancel_request(&mut self, tid: usize, res: Resource) {
+        if let Some(counts) = self.need.get_mut(&tid) {
+            sub(counts, res);
+            if counts.is_empty() {
+                self.need.remove(&tid);
+            }
+        }
+    }
+    /// 安全性检查：反复找出等待的资源都能满足的线程，假设它完成后归还持有的资源
+    fn is_safe(&self) -> bool {
+        let mut work = self.available.clone();
+        let mut unfinished: BTreeSet<usize> = self
+            .need
+            .keys()
+            .chain(self.allocation.keys())
+            .copied()
+            .collect();
+        while let Some(tid) = unfinished.iter().copied().find(|tid| {
+            self.need.get(tid).map_or(true, |need| {
+                need.iter()
+                    .all(|(res, &n)| n <= work.get(res).copied().unwrap_or(0))
+            })
+        }) {
+            if let Some(allocation) = self.allocation.get(&tid) {
+                for (&res, &n) in allocation {
+                    *work.entry(res).or_default() += n;
+                }
+            }
+            unfinished.remove(&tid);
+        }
+        unfinished.is_empty()
+    }
+}
@@ -0,0 +1,100 @@
+//! futex：用户态同步原语的慢路径
+//!
+//! 等待队列按用户地址对应的物理地址组织，共享同一物理页的不同地址空间
+//! 也能互相唤醒。用户态只在竞争时才进入内核。
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+
+use crate::{
+    error::{SysError, SysResult},
+    mm::UserPtr,
+    sync::SpinLock,
+    task::{
+        block_current_until, current_task, prepare_interruptible_block, signal_interrupted,
+        wakeup_task, TaskControlBlock,
+    },
+};
+
+static FUTEX_QUEUES: SpinLock<BTreeMap<usize, VecDeque<Arc<TaskControlBlock>>>> =
+    SpinLock::new(BTreeMap::new());
+
+/// `uaddr` 处的值仍然是 `expected` 时在 `key` 上阻塞，直到被唤醒、超时或者被信号打断。
+/// 比较在持有队列锁时进行，唤醒者先改值再唤醒，所以唤醒不会丢失
+pub fn futex_wait(
+    key: usize,
+    uaddr: UserPtr<u32>,
+    expected: u32,
+    expire_ns: Option<usize>,
+) -> SysResult {
+    let task = current_task().unwrap();
+    let mut queues = FUTEX_QUEUES.exclusive_access();
+    if uaddr.read()? != expected {
+        return Err(SysError::EAGAIN);
+    }
+    prepare_interruptible_block()?;
+    queues.entry(key).or_default().push_back(Arc::clone(&task));
+    drop(queues);
+    block_current_until(expire_ns);
+    // 还在队列里说明是定时器或者信号唤醒的。FUTEX_REQUEUE 可能把它移到了别的键上
+    let mut queues = FUTEX_QUEUES.exclusive_access();
+    let mut woken = true;
+    queues.retain(|_, waiters| {
+        let len = waiters.len();
+        waiters.retain(|waiter| !Arc::ptr_eq(waiter, &task));
+        woken &= waiters.len() == len;
+        !waiters.is_empty()
+    });
+    if woken {
+        Ok(0)
+    } else if signal_interrupted() {
+        Err(SysError::EINTR)
+    } else {
+        Err(SysError::ETIMEDOUT)
+    }
+}
+
+/// 从 `key` 的队列中取出最多 `count` 个线程
+fn take_waiters(
+    queues: &mut BTreeMap<usize, VecDeque<Arc<TaskControlBlock>>>,
+    key: usize,
+    count: usize,
+) -> VecDeque<Arc<TaskControlBlock>> {
+    let Some(waiters) = queues.get_mut(&key) else {
+        return VecDeque::new();
+    };
+    let taken = waiters.drain(..count.min(waiters.len())).collect();
+    if waiters.is_empty() {
+        queues.remove(&key);
+    }
+    taken
+}
+
+/// 唤醒 `key` 上最多 `count` 个线程，返回唤醒的数量
+pub fn futex_wake(key: usize, count: usize) -> usize {
+    let waiters = take_waiters(&mut FUTEX_QUEUES.exclusive_access(), key, count);
+    let woken = waiters.len();
+    for task in waiters {
+        wakeup_task(task);
+    }
+    woken
+}
+
+/// 唤醒 `key` 上最多 `wake` 个线程，剩下的最多 `requeue` 个移到 `key2` 上等待，
+/// 返回唤醒的数量
+pub fn futex_requeue(key: usize, wake: usize, key2: usize, requeue: usize) -> usize {
+    let mut queues = FUTEX_QUEUES.exclusive_access();
+    let waiters = take_waiters(&mut queues, key, wake);
+    let moved = take_waiters(&mut queues, key, requeue);
+    if !moved.is_empty() {
+        queues.entry(key2).or_default().extend(moved);
+    }
+    drop(queues);
+    let woken = waiters.len();
+    for task in waiters {
+        wakeup_task(task);
+    }
+    woken
+}
@@ -0,0 +1,56 @@
+//! 关中断和抢占计数
+//!
+//! 内核在大部分路径上打开中断运行，时钟中断到来时如果当前 CPU 没有持有
+//! 任何锁（抢占计数为 0），就可以切换到其他线程。
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use loongarch64::register::crmd;
+
+use crate::{config::MAX_CPUS, task::cpu_id};
+
+/// 每个 CPU 关中断的嵌套层数
+static IRQ_DEPTH: [AtomicUsize; MAX_CPUS] = [const { AtomicUsize::new(0) }; MAX_CPUS];
+/// 最外层关中断之前中断是否打开
+static IRQ_ENABLED: [AtomicBool; MAX_CPUS] = [const { AtomicBool::new(false) }; MAX_CPUS];
+/// 每个 CPU 上持有的锁的数量
+static PREEMPT_COUNT: [AtomicUsize; MAX_CPUS] = [const { AtomicUsize::new(0) }; MAX_CPUS];
+
+/// 关闭中断，可以嵌套，和 [`pop_off`] 成对使用
+pub fn push_off() {
+    let enabled = crmd::read().ie();
+    crmd::set_ie(false);
+    // 关中断之后才能读 cpuid，否则可能被抢占到其他 CPU 上
+    let cpu = cpu_id();
+    if IRQ_DEPTH[cpu].fetch_add(1, Ordering::Relaxed) == 0 {
+        IRQ_ENABLED[cpu].store(enabled, Ordering::Relaxed);
+    }
+}
+
+/// 最外层的 `pop_off` 恢复 [`push_off`] 之前的中断状态
+pub fn pop_off() {
+    assert!(!crmd::read().ie(), "pop_off: interrupts enabled");
+    let cpu = cpu_id();
+    let depth = IRQ_DEPTH[cpu].fetch_sub(1, Ordering::Relaxed);
+    assert!(depth > 0, "pop_off: unbalanced");
+    if depth == 1 && IRQ_ENABLED[cpu].load(Ordering::Relaxed) {
+        crmd::set_ie(true);
+    }
+}
+
+pub fn preempt_disable() {
+    push_off();
+    PREEMPT_COUNT[cpu_id()].fetch_add(1, Ordering::Relaxed);
+    pop_off();
+}
+
+pub fn preempt_enable() {
+    push_off();
+    PREEMPT_COUNT[cpu_id()].fetch_sub(1, Ordering::Relaxed);
+    pop_off();
+}
+
+/// 当前 CPU 没有持有锁，可以切换到其他线程。在中断处理程序中调用
+pub fn preemptible() -> bool {
+    PREEMPT_COUNT[cpu_id()].load(Ordering::Relaxed) == 0
+}
@@ -1,9 +1,23 @@
+mod barrier;
 mod condvar;
+mod deadlock;
+mod futex;
+mod irq;
 mod mutex;
+mod rwlock;
 mod semaphore;
+mod spin;
 mod up;
+mod wait_queue;
 
+pub use barrier::Barrier;
 pub use condvar::Condvar;
-pub use mutex::{Mutex, MutexBlocking, MutexSpin};
+pub use deadlock::{DeadlockDetector, Resource};
+pub use futex::{futex_requeue, futex_wait, futex_wake};
+pub use irq::{pop_off, preemptible, push_off};
+pub use mutex::{update_priority, Mutex, MutexBlocking, MutexBlockingInner, MutexSpin};
+pub use rwlock::RwLock;
 pub use semaphore::Semaphore;
+pub use spin::{IrqSafeSpinLock, IrqSafeSpinLockGuard, SpinLock, SpinLockGuard};
 pub use up::UPSafeCell;
+pub use wait_queue::WaitQueue;
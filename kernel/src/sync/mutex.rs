@@ -1,93 +1,224 @@
-use alloc::{collections::VecDeque, sync::Arc};
+use alloc::{
+    collections::VecDeque,
+    sync::{Arc, Weak},
+};
 
-use super::UPSafeCell;
-use crate::task::{
-    add_task, block_current_and_run_next, current_task, suspend_current_and_run_next,
-    TaskControlBlock,
+use super::{wait_queue::remove_waiter, SpinLock};
+use crate::{
+    task::{
+        block_current_until, current_task, prepare_block, set_task_prio,
+        suspend_current_and_run_next, wakeup_task, TaskControlBlock,
+    },
+    timer::get_time_ns,
 };
 
 pub trait Mutex: Sync + Send {
-    fn lock(&self);
-    fn unlock(&self);
+    fn lock(&self) {
+        self.lock_timeout(None);
+    }
+    /// 锁被占用时立即返回 false
+    fn try_lock(&self) -> bool;
+    /// 到 `expire_ns` 还没有得到锁时返回 false，为空时一直等待
+    fn lock_timeout(&self, expire_ns: Option<usize>) -> bool;
+    /// 释放锁，锁本来就没有被持有时返回 false
+    fn unlock(&self) -> bool;
+    fn is_locked(&self) -> bool;
 }
 
 /// 自旋锁
 pub struct MutexSpin {
-    locked: UPSafeCell<bool>,
+    locked: SpinLock<bool>,
 }
 
 impl MutexSpin {
     pub fn new() -> Self {
         Self {
-            locked: unsafe { UPSafeCell::new(false) },
+            locked: SpinLock::new(false),
         }
     }
 }
 
 impl Mutex for MutexSpin {
-    fn lock(&self) {
-        loop {
-            let mut locked = self.locked.exclusive_access();
-            if *locked {
-                drop(locked);
-                suspend_current_and_run_next(); //没有获得锁,切换到下一个线程
-                continue;
-            } else {
-                *locked = true;
-                return;
+    fn try_lock(&self) -> bool {
+        let mut locked = self.locked.exclusive_access();
+        !core::mem::replace(&mut *locked, true)
+    }
+
+    fn lock_timeout(&self, expire_ns: Option<usize>) -> bool {
+        while !self.try_lock() {
+            if expire_ns.is_some_and(|expire_ns| get_time_ns() >= expire_ns) {
+                return false;
             }
+            suspend_current_and_run_next(); //没有获得锁,切换到下一个线程
         }
+        true
     }
 
-    fn unlock(&self) {
+    fn unlock(&self) -> bool {
         let mut locked = self.locked.exclusive_access();
-        *locked = false;
+        core::mem::replace(&mut *locked, false)
+    }
+
+    fn is_locked(&self) -> bool {
+        *self.locked.exclusive_access()
     }
 }
 
-/// 具有等待队列的锁
+/// 具有等待队列的锁。记录持有锁的线程，等待者的优先级更高时临时提高持有者的优先级，
+/// 避免优先级反转；释放锁时交给优先级最高的等待者
 pub struct MutexBlocking {
-    inner: UPSafeCell<MutexBlockingInner>,
+    inner: Arc<SpinLock<MutexBlockingInner>>,
 }
 
 pub struct MutexBlockingInner {
-    locked: bool,
+    /// 持有锁的线程，为空表示没有被持有
+    owner: Option<Weak<TaskControlBlock>>,
     wait_queue: VecDeque<Arc<TaskControlBlock>>,
 }
 
 impl MutexBlocking {
     pub fn new() -> Self {
         Self {
-            inner: unsafe {
-                UPSafeCell::new(MutexBlockingInner {
-                    locked: false,
-                    wait_queue: VecDeque::new(),
-                })
-            },
+            inner: Arc::new(SpinLock::new(MutexBlockingInner {
+                owner: None,
+                wait_queue: VecDeque::new(),
+            })),
         }
     }
+
+    /// `task` 成为持有者。在锁内记录，等待者随后计算优先级时一定能看到
+    fn acquire(&self, mutex_inner: &mut MutexBlockingInner, task: &Arc<TaskControlBlock>) {
+        mutex_inner.owner = Some(Arc::downgrade(task));
+        task.inner_exclusive_access()
+            .held_mutexes
+            .push(Arc::clone(&self.inner));
+    }
+}
+
+impl MutexBlockingInner {
+    fn owner(&self) -> Option<Arc<TaskControlBlock>> {
+        self.owner.as_ref().and_then(Weak::upgrade)
+    }
 }
 
 impl Mutex for MutexBlocking {
-    fn lock(&self) {
+    fn try_lock(&self) -> bool {
         let mut mutex_inner = self.inner.exclusive_access();
-        if mutex_inner.locked {
-            // 如果锁已经被获取了,则将任务加入队列中
-            mutex_inner.wait_queue.push_back(current_task().unwrap());
-            drop(mutex_inner);
-            block_current_and_run_next(); //暂停当前任务,并切换到下一个任务
-        } else {
-            mutex_inner.locked = true; //获取锁
+        if mutex_inner.owner.is_some() {
+            return false;
         }
+        self.acquire(&mut mutex_inner, &current_task().unwrap());
+        true
     }
 
-    fn unlock(&self) {
+    fn lock_timeout(&self, expire_ns: Option<usize>) -> bool {
+        let mut mutex_inner = self.inner.exclusive_access();
+        let task = current_task().unwrap();
+        if mutex_inner.owner.is_none() {
+            self.acquire(&mut mutex_inner, &task); //获取锁
+            return true;
+        }
+        // 如果锁已经被获取了,则将任务加入队列中
+        prepare_block();
+        mutex_inner.wait_queue.push_back(Arc::clone(&task));
+        task.inner_exclusive_access().blocked_on = Some(Arc::clone(&self.inner));
+        let owner = mutex_inner.owner();
+        drop(mutex_inner);
+        if let Some(owner) = owner {
+            update_priority(owner);
+        }
+        block_current_until(expire_ns); //暂停当前任务,并切换到下一个任务
         let mut mutex_inner = self.inner.exclusive_access();
-        assert!(mutex_inner.locked);
-        if let Some(waking_task) = mutex_inner.wait_queue.pop_front() {
-            add_task(waking_task); //唤醒任务
-        } else {
-            mutex_inner.locked = false;
+        // unlock 把锁直接交给等待者，还在队列中说明超时了
+        if !remove_waiter(&mut mutex_inner.wait_queue, &task) {
+            return true;
+        }
+        task.inner_exclusive_access().blocked_on = None;
+        let owner = mutex_inner.owner();
+        drop(mutex_inner);
+        if let Some(owner) = owner {
+            update_priority(owner);
+        }
+        false
+    }
+
+    fn unlock(&self) -> bool {
+        let mut mutex_inner = self.inner.exclusive_access();
+        let Some(owner) = mutex_inner.owner.take() else {
+            return false;
+        };
+        let owner = owner.upgrade();
+        if let Some(owner) = &owner {
+            owner
+                .inner_exclusive_access()
+                .held_mutexes
+                .retain(|mutex| !Arc::ptr_eq(mutex, &self.inner));
+        }
+        // 优先级最高的等待者中最早到达的一个
+        let next = mutex_inner
+            .wait_queue
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, task)| task.inner_exclusive_access().sched.prio)
+            .map(|(id, _)| id)
+            .and_then(|id| mutex_inner.wait_queue.remove(id));
+        if let Some(next) = &next {
+            next.inner_exclusive_access().blocked_on = None;
+            self.acquire(&mut mutex_inner, next);
+        }
+        drop(mutex_inner);
+        if let Some(owner) = owner {
+            update_priority(owner);
+        }
+        if let Some(next) = next {
+            update_priority(Arc::clone(&next));
+            wakeup_task(next); //唤醒任务
+        }
+        true
+    }
+
+    fn is_locked(&self) -> bool {
+        self.inner.exclusive_access().owner.is_some()
+    }
+}
+
+/// 串行化优先级的传递，保证每次都基于最新的等待关系计算
+static PI_LOCK: SpinLock<()> = SpinLock::new(());
+
+/// 沿着等待链最多传递这么多层，锁的等待关系成环时也能结束
+const MAX_PI_DEPTH: usize = 16;
+
+/// `task` 的 nice、持有的锁或者锁的等待者改变之后重新计算它的有效优先级，
+/// 如果它也在等待某个锁，继续更新那个锁的持有者，直到优先级不再变化
+pub fn update_priority(mut task: Arc<TaskControlBlock>) {
+    let _guard = PI_LOCK.exclusive_access();
+    for _ in 0..MAX_PI_DEPTH {
+        let task_inner = task.inner_exclusive_access();
+        let nice = task_inner.sched.nice;
+        let held = task_inner.held_mutexes.clone();
+        drop(task_inner);
+        // 先锁互斥锁再锁线程，和加锁解锁时的顺序一致
+        let prio = held
+            .iter()
+            .flat_map(|mutex| {
+                let mutex_inner = mutex.exclusive_access();
+                mutex_inner
+                    .wait_queue
+                    .iter()
+                    .map(|waiter| waiter.inner_exclusive_access().sched.prio)
+                    .min()
+            })
+            .fold(nice, i32::min);
+        let mut task_inner = task.inner_exclusive_access();
+        if task_inner.sched.prio == prio {
+            return;
+        }
+        set_task_prio(&mut task_inner.sched, prio);
+        let blocked_on = task_inner.blocked_on.clone();
+        drop(task_inner);
+        match blocked_on.and_then(|mutex| mutex.exclusive_access().owner()) {
+            Some(owner) => task = owner,
+            None => return,
         }
     }
 }
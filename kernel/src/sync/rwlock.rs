@@ -0,0 +1,88 @@
+use alloc::{collections::VecDeque, sync::Arc};
+
+use super::SpinLock;
+use crate::task::{
+    block_current_and_run_next, current_task, prepare_block, wakeup_task, TaskControlBlock,
+};
+
+/// 读写锁。等待的线程按到达的顺序得到锁，有写者在等待时新来的读者也要排队，
+/// 写者不会饿死
+pub struct RwLock {
+    inner: SpinLock<RwLockInner>,
+}
+
+pub struct RwLockInner {
+    /// 持有读锁的线程数
+    readers: usize,
+    writer: bool,
+    /// 等待的线程，以及它是不是写者
+    wait_queue: VecDeque<(Arc<TaskControlBlock>, bool)>,
+}
+
+impl RwLock {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinLock::new(RwLockInner {
+                readers: 0,
+                writer: false,
+                wait_queue: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn read(&self) {
+        self.acquire(false);
+    }
+
+    pub fn write(&self) {
+        self.acquire(true);
+    }
+
+    fn acquire(&self, write: bool) {
+        let mut inner = self.inner.exclusive_access();
+        if inner.wait_queue.is_empty() && !inner.writer && (!write || inner.readers == 0) {
+            if write {
+                inner.writer = true;
+            } else {
+                inner.readers += 1;
+            }
+            return;
+        }
+        // 释放锁的线程替我们拿到锁之后才唤醒
+        prepare_block();
+        inner.wait_queue.push_back((current_task().unwrap(), write));
+        drop(inner);
+        block_current_and_run_next();
+    }
+
+    /// 释放读锁或者写锁，没有被持有时返回 false
+    pub fn unlock(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.writer {
+            inner.writer = false;
+        } else if inner.readers > 0 {
+            inner.readers -= 1;
+        } else {
+            return false;
+        }
+        // 从队首开始唤醒：一个写者，或者连续的若干个读者
+        while let Some(&(_, write)) = inner.wait_queue.front() {
+            if inner.writer || (write && inner.readers > 0) {
+                break;
+            }
+            let (task, _) = inner.wait_queue.pop_front().unwrap();
+            if write {
+                inner.writer = true;
+            } else {
+                inner.readers += 1;
+            }
+            wakeup_task(task);
+        }
+        true
+    }
+
+    pub fn is_locked(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        inner.writer || inner.readers > 0
+    }
+}
@@ -1,12 +1,12 @@
 use alloc::{collections::VecDeque, sync::Arc};
 
-use crate::{
-    sync::UPSafeCell,
-    task::{add_task, block_current_and_run_next, current_task, TaskControlBlock},
+use super::{wait_queue::remove_waiter, SpinLock};
+use crate::task::{
+    block_current_until, current_task, prepare_block, wakeup_task, TaskControlBlock,
 };
 
 pub struct Semaphore {
-    pub inner: UPSafeCell<SemaphoreInner>,
+    pub inner: SpinLock<SemaphoreInner>,
 }
 
 pub struct SemaphoreInner {
@@ -17,12 +17,10 @@ pub struct SemaphoreInner {
 impl Semaphore {
     pub fn new(res_count: usize) -> Self {
         Self {
-            inner: unsafe {
-                UPSafeCell::new(SemaphoreInner {
-                    count: res_count as isize,
-                    wait_queue: VecDeque::new(),
-                })
-            },
+            inner: SpinLock::new(SemaphoreInner {
+                count: res_count as isize,
+                wait_queue: VecDeque::new(),
+            }),
         }
     }
     /// Release a resource V()
@@ -31,19 +29,48 @@ impl Semaphore {
         inner.count += 1;
         if inner.count <= 0 {
             if let Some(task) = inner.wait_queue.pop_front() {
-                add_task(task);
+                wakeup_task(task);
             }
         }
     }
 
     /// P()
     pub fn down(&self) {
+        self.down_timeout(None);
+    }
+
+    /// 没有资源时立即返回 false
+    pub fn try_down(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.count <= 0 {
+            return false;
+        }
+        inner.count -= 1;
+        true
+    }
+
+    /// 到 `expire_ns` 还没有得到资源时返回 false，为空时一直等待
+    pub fn down_timeout(&self, expire_ns: Option<usize>) -> bool {
         let mut inner = self.inner.exclusive_access();
         inner.count -= 1;
-        if inner.count < 0 {
-            inner.wait_queue.push_back(current_task().unwrap());
-            drop(inner);
-            block_current_and_run_next();
+        if inner.count >= 0 {
+            return true;
+        }
+        let task = current_task().unwrap();
+        prepare_block();
+        inner.wait_queue.push_back(Arc::clone(&task));
+        drop(inner);
+        block_current_until(expire_ns);
+        // up 取出队首的线程时资源已经交给了它，还在队列中说明超时了
+        let mut inner = self.inner.exclusive_access();
+        if remove_waiter(&mut inner.wait_queue, &task) {
+            inner.count += 1;
+            return false;
         }
+        true
+    }
+
+    pub fn has_waiters(&self) -> bool {
+        !self.inner.exclusive_access().wait_queue.is_empty()
     }
 }
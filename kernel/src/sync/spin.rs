@@ -0,0 +1,103 @@
+use core::{
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+};
+
+use spin::{Mutex, MutexGuard};
+
+use super::irq::{pop_off, preempt_disable, preempt_enable, push_off};
+
+/// 多个 CPU 共享的数据。
+///
+/// 接口和 [`UPSafeCell`](super::UPSafeCell) 相同，`exclusive_access` 在数据被
+/// 其他 CPU 占用时自旋等待。同一个 CPU 重复获取会死锁而不是 panic，
+/// 持有期间也不能切换线程。持有期间内核不会被抢占，但中断仍然打开，
+/// 中断处理程序不分情况都要访问的数据应该使用 [`IrqSafeSpinLock`]
+pub struct SpinLock<T> {
+    inner: Mutex<T>,
+}
+
+impl<T> SpinLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            inner: Mutex::new(value),
+        }
+    }
+    pub fn exclusive_access(&self) -> SpinLockGuard<'_, T> {
+        preempt_disable();
+        SpinLockGuard {
+            guard: ManuallyDrop::new(self.inner.lock()),
+        }
+    }
+}
+
+pub struct SpinLockGuard<'a, T> {
+    guard: ManuallyDrop<MutexGuard<'a, T>>,
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        // 先解锁再允许抢占
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        preempt_enable();
+    }
+}
+
+/// 持有期间关闭当前 CPU 的中断，中断处理程序也可以获取。
+/// 用于控制台、堆分配器和调度相关的数据
+pub struct IrqSafeSpinLock<T> {
+    inner: Mutex<T>,
+}
+
+impl<T> IrqSafeSpinLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            inner: Mutex::new(value),
+        }
+    }
+    pub fn exclusive_access(&self) -> IrqSafeSpinLockGuard<'_, T> {
+        push_off();
+        preempt_disable();
+        IrqSafeSpinLockGuard {
+            guard: ManuallyDrop::new(self.inner.lock()),
+        }
+    }
+}
+
+pub struct IrqSafeSpinLockGuard<'a, T> {
+    guard: ManuallyDrop<MutexGuard<'a, T>>,
+}
+
+impl<T> Deref for IrqSafeSpinLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for IrqSafeSpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for IrqSafeSpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        preempt_enable();
+        pop_off();
+    }
+}
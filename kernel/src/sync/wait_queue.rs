@@ -0,0 +1,88 @@
+use alloc::{collections::VecDeque, sync::Arc};
+
+use crate::{
+    error::{SysError, SysResult},
+    sync::SpinLock,
+    task::{
+        block_current_and_run_next, current_task, prepare_block, prepare_interruptible_block,
+        signal_interrupted, wakeup_task, TaskControlBlock,
+    },
+};
+
+/// Tasks blocked until some event happens, e.g. data arriving in a pipe.
+///
+/// A task may sit in several queues at once (see `sys_ppoll`), so waking is
+/// done through `wakeup_task`, which ignores tasks that are not blocked
+/// anymore. Such a task should `unregister` itself once it runs again.
+/// A task registering itself must call `prepare_block` first.
+pub struct WaitQueue {
+    inner: SpinLock<VecDeque<Arc<TaskControlBlock>>>,
+}
+
+/// 把 `task` 从等待队列中取出，它已经不在队列中（被唤醒了）时返回 false
+pub fn remove_waiter(
+    queue: &mut VecDeque<Arc<TaskControlBlock>>,
+    task: &Arc<TaskControlBlock>,
+) -> bool {
+    match queue.iter().position(|waiter| Arc::ptr_eq(waiter, task)) {
+        Some(pos) => {
+            queue.remove(pos);
+            true
+        }
+        None => false,
+    }
+}
+
+impl WaitQueue {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinLock::new(VecDeque::new()),
+        }
+    }
+    /// Block current task until the queue is woken
+    pub fn wait(&self) {
+        prepare_block();
+        self.register(current_task().unwrap());
+        block_current_and_run_next();
+    }
+    /// Block current task like `wait`. `guard` protects the condition that was
+    /// just checked and is only released once the task is in the queue, so a
+    /// wake-up coming in between is not lost.
+    pub fn wait_and_release<G>(&self, guard: G) {
+        prepare_block();
+        self.register(current_task().unwrap());
+        drop(guard);
+        block_current_and_run_next();
+    }
+    /// Like `wait_and_release`, but a signal to be handled by the task wakes it
+    /// up as well, in which case EINTR is returned
+    pub fn wait_interruptible_and_release<G>(&self, guard: G) -> SysResult<()> {
+        let task = current_task().unwrap();
+        prepare_interruptible_block()?;
+        self.register(Arc::clone(&task));
+        drop(guard);
+        block_current_and_run_next();
+        if signal_interrupted() {
+            self.unregister(&task);
+            return Err(SysError::EINTR);
+        }
+        Ok(())
+    }
+    /// Add a task to the queue without blocking it
+    pub fn register(&self, task: Arc<TaskControlBlock>) {
+        self.inner.exclusive_access().push_back(task);
+    }
+    /// Remove a task from the queue if it is still there
+    pub fn unregister(&self, task: &Arc<TaskControlBlock>) {
+        self.inner
+            .exclusive_access()
+            .retain(|waiter| !Arc::ptr_eq(waiter, task));
+    }
+    /// Wake every task in the queue
+    pub fn wake_all(&self) {
+        let waiters: VecDeque<_> = self.inner.exclusive_access().drain(..).collect();
+        for task in waiters {
+            wakeup_task(task);
+        }
+    }
+}
@@ -1,111 +1,563 @@
-use alloc::sync::Arc;
+use alloc::{string::String, sync::Arc, vec::Vec};
 
 use crate::{
-    fs::{make_pipe, open_file, OpenFlags},
+    config::PAGE_SIZE,
+    error::{SysError, SysResult},
+    fs::{
+        flock_lock, flock_unlock, make_fifo, make_pipe, open_fifo, open_file, record_conflict,
+        record_lock, record_unlock, release_record_locks, set_tty_foreground, statfs,
+        tty_foreground, File, OSInode, OpenFlags, PollEvents, PollFd, RecordLock, Stat, StatFs,
+        S_IFCHR, S_IFIFO, S_IFMT,
+    },
     list_apps,
-    mm::{translated_byte_buffer, translated_refmut, translated_str, UserBuffer},
-    task::{current_process, current_user_token},
+    mm::{UserPtr, UserSlice},
+    task::{
+        block_current_and_run_next, cancel_block, current_process, current_task,
+        prepare_interruptible_block, process_group, signal_interrupted,
+    },
+    timer::{add_timer, get_time_ns, remove_timer, TimeSpec},
 };
 
 const FD_STDOUT: usize = 1;
 const FD_STDIN: usize = 0;
 
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    let token = current_user_token();
+/// openat 的 dirfd：相对路径从当前目录开始
+const AT_FDCWD: isize = -100;
+
+/// readv 和 writev 最多接受的 iovec 个数
+const IOV_MAX: usize = 1024;
+
+const F_GETFL: usize = 3;
+const F_SETFL: usize = 4;
+const F_GETLK: usize = 5;
+const F_SETLK: usize = 6;
+const F_SETLKW: usize = 7;
+const F_SETPIPE_SZ: usize = 1031;
+const F_GETPIPE_SZ: usize = 1032;
+
+/// ioctl 的命令：读取和设置终端的前台进程组
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+
+/// flock 的操作
+const LOCK_SH: u32 = 1;
+const LOCK_EX: u32 = 2;
+const LOCK_NB: u32 = 4;
+const LOCK_UN: u32 = 8;
+
+/// struct flock 中的锁类型
+const F_RDLCK: i16 = 0;
+const F_WRLCK: i16 = 1;
+const F_UNLCK: i16 = 2;
+
+/// `struct flock` of `fcntl`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Flock {
+    l_type: i16,
+    l_whence: i16,
+    l_start: i64,
+    l_len: i64,
+    l_pid: i32,
+}
+
+/// `struct iovec` of `readv` and `writev`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct IoVec {
+    base: usize,
+    len: usize,
+}
+
+/// 取出当前进程的描述符 fd 对应的文件
+pub(super) fn get_file(fd: usize) -> SysResult<Arc<dyn File + Send + Sync>> {
     let process = current_process();
     let inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
+    match inner.fd_table.get(fd) {
+        Some(Some(file)) => Ok(file.clone()),
+        _ => Err(SysError::EBADF),
+    }
+}
+
+pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> SysResult {
+    let file = get_file(fd)?;
+    if !file.writable() {
+        return Err(SysError::EBADF);
+    }
+    let buffer = UserSlice::new(buf, len).buffer(false)?;
+    if file.nonblocking()
+        && !file
+            .poll()
+            .intersects(PollEvents::POLLOUT | PollEvents::POLLERR)
+    {
+        return Err(SysError::EAGAIN);
+    }
+    let write_size = file.write(buffer);
+    // no progress on a non-empty buffer: the disk is full or nobody reads the pipe
+    if write_size == 0 && len > 0 {
+        return Err(if file.as_inode().is_some() {
+            SysError::ENOSPC
+        } else {
+            SysError::EPIPE
+        });
+    }
+    Ok(write_size)
+}
+
+pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> SysResult {
+    let file = get_file(fd)?;
+    if !file.readable() {
+        return Err(SysError::EBADF);
     }
-    if let Some(file) = &inner.fd_table[fd] {
-        if !file.writable() {
-            return -1;
+    if file.as_inode().is_some_and(|inode| inode.is_dir()) {
+        return Err(SysError::EISDIR);
+    }
+    // the kernel writes into the buffer
+    let buffer = UserSlice::new(buf, len).buffer(true)?;
+    if file.nonblocking()
+        && !file
+            .poll()
+            .intersects(PollEvents::POLLIN | PollEvents::POLLHUP)
+    {
+        return Err(SysError::EAGAIN);
+    }
+    file.read(buffer)
+}
+/// 读取 iovec 数组，个数超过 IOV_MAX 时返回 EINVAL
+fn read_iovecs(iov: *const IoVec, iovcnt: usize) -> SysResult<Vec<IoVec>> {
+    if iovcnt > IOV_MAX {
+        return Err(SysError::EINVAL);
+    }
+    let iov = UserPtr::from(iov);
+    (0..iovcnt).map(|i| iov.add(i).read()).collect()
+}
+
+/// 依次写入每个缓冲区，写不完一个缓冲区就停下；已经写入了数据时不报告后面的错误
+pub fn sys_writev(fd: usize, iov: *const IoVec, iovcnt: usize) -> SysResult {
+    let mut total = 0;
+    for iovec in read_iovecs(iov, iovcnt)? {
+        match sys_write(fd, iovec.base as *const u8, iovec.len) {
+            Ok(written) => {
+                total += written;
+                if written < iovec.len {
+                    break;
+                }
+            }
+            Err(error) if total == 0 => return Err(error),
+            Err(_) => break,
         }
-        let file = file.clone();
-        // release current task TCB manually to avoid multi-borrow
-        drop(inner);
-        file.write(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
-    } else {
-        -1
     }
+    Ok(total)
 }
 
-pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
-    let token = current_user_token();
-    let process = current_process();
-    let inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
+/// 同 `sys_writev`，读满一个缓冲区才读下一个
+pub fn sys_readv(fd: usize, iov: *const IoVec, iovcnt: usize) -> SysResult {
+    let mut total = 0;
+    for iovec in read_iovecs(iov, iovcnt)? {
+        match sys_read(fd, iovec.base as *const u8, iovec.len) {
+            Ok(read) => {
+                total += read;
+                if read < iovec.len {
+                    break;
+                }
+            }
+            Err(error) if total == 0 => return Err(error),
+            Err(_) => break,
+        }
     }
-    if let Some(file) = &inner.fd_table[fd] {
-        let file = file.clone();
-        if !file.readable() {
-            return -1;
+    Ok(total)
+}
+
+/// 文件系统只有根目录一层：路径去掉开头的 `/` 和 `./` 后就是根目录中的名字，
+/// 空的名字表示根目录本身。相对路径的 dirfd 必须是 AT_FDCWD 或者打开的根目录
+fn resolve_path(dirfd: isize, path: &str) -> SysResult<String> {
+    if !path.starts_with('/') && dirfd != AT_FDCWD {
+        let dir = get_file(dirfd as usize)?;
+        if !dir.as_inode().is_some_and(|inode| inode.is_dir()) {
+            return Err(SysError::ENOTDIR);
         }
-        // release current task TCB manually to avoid multi-borrow
-        drop(inner);
-        file.read(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
-    } else {
-        -1
     }
+    let mut name = path.trim_start_matches('/');
+    while let Some(rest) = name.strip_prefix("./") {
+        name = rest.trim_start_matches('/');
+    }
+    if name == "." {
+        name = "";
+    }
+    if name.contains('/') {
+        return Err(SysError::ENOENT);
+    }
+    Ok(String::from(name))
 }
-pub fn sys_open(path: *const u8, flags: u32) -> isize {
+
+/// mode 被忽略，文件没有权限位
+pub fn sys_openat(dirfd: isize, path: *const u8, flags: u32, _mode: u32) -> SysResult {
     let process = current_process();
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    if let Some(inode) = open_file(path.as_str(), OpenFlags::from_bits(flags).unwrap()) {
-        let mut inner = process.inner_exclusive_access();
-        let fd = inner.alloc_fd();
-        inner.fd_table[fd] = Some(inode);
-        fd as isize
-    } else {
-        -1
+    let path = UserPtr::from(path).read_str()?;
+    let flags = OpenFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    let name = resolve_path(dirfd, &path)?;
+    let inode = open_file(name.as_str(), flags).ok_or(SysError::ENOENT)?;
+    if inode.is_dir() {
+        if inode.writable() {
+            return Err(SysError::EISDIR);
+        }
+    } else if flags.contains(OpenFlags::DIRECTORY) {
+        return Err(SysError::ENOTDIR);
     }
+    let nonblocking = flags.contains(OpenFlags::NONBLOCK);
+    let file: Arc<dyn File + Send + Sync> = if inode.is_fifo() {
+        let (readable, writable) = flags.read_write();
+        // may block until the other end shows up, a nonblocking writer
+        // without readers fails right away
+        open_fifo(name.as_str(), readable, writable, nonblocking).ok_or(SysError::ENXIO)?
+    } else {
+        inode
+    };
+    let mut inner = process.inner_exclusive_access();
+    let fd = inner.alloc_fd();
+    inner.fd_table[fd] = Some(file);
+    Ok(fd)
 }
 
-pub fn sys_close(fd: usize) -> isize {
+pub fn sys_fstat(fd: usize, stat: *mut Stat) -> SysResult {
+    let file = get_file(fd)?;
+    let stat_value = match file.as_inode() {
+        Some(inode) => inode.stat(),
+        None => Stat {
+            // the console, or a pipe
+            st_mode: if file.is_tty() {
+                S_IFCHR | 0o620
+            } else {
+                S_IFIFO | 0o600
+            },
+            st_nlink: 1,
+            st_blksize: PAGE_SIZE as i32,
+            ..Default::default()
+        },
+    };
+    UserPtr::from(stat).write(stat_value)?;
+    Ok(0)
+}
+
+/// 返回写入的字节数，读完时返回 0
+pub fn sys_getdents64(fd: usize, buf: *mut u8, len: usize) -> SysResult {
+    let file = get_file(fd)?;
+    let inode = file
+        .as_inode()
+        .filter(|inode| inode.is_dir())
+        .ok_or(SysError::ENOTDIR)?;
+    // check the buffer before the offset moves past the entries
+    UserSlice::new(buf, len).buffer(true)?;
+    let dirents = inode.read_dirents(len).ok_or(SysError::EINVAL)?;
+    UserSlice::new(buf, dirents.len()).write(&dirents)?;
+    Ok(dirents.len())
+}
+
+pub fn sys_close(fd: usize) -> SysResult {
     let process = current_process();
     let mut inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if inner.fd_table[fd].is_none() {
-        return -1;
+    let file = inner
+        .fd_table
+        .get_mut(fd)
+        .and_then(|file| file.take())
+        .ok_or(SysError::EBADF)?;
+    // closing any fd of a file drops the record locks of the process on it
+    if let Some(inode) = file.as_inode() {
+        release_record_locks(process.getpid(), Some(inode.ino()));
     }
-    inner.fd_table[fd].take();
-    0
+    Ok(0)
 }
 
-pub fn sys_pipe(pipe: *mut usize) -> isize {
+pub fn sys_pipe(pipe: *mut usize, flags: u32) -> SysResult {
     let process = current_process();
-    let token = current_user_token();
     let mut inner = process.inner_exclusive_access();
     let (pipe_read, pipe_write) = make_pipe();
+    if OpenFlags::from_bits_truncate(flags).contains(OpenFlags::NONBLOCK) {
+        pipe_read.set_nonblocking(true);
+        pipe_write.set_nonblocking(true);
+    }
     let read_fd = inner.alloc_fd();
     inner.fd_table[read_fd] = Some(pipe_read);
     let write_fd = inner.alloc_fd();
     inner.fd_table[write_fd] = Some(pipe_write);
-    *translated_refmut(token, pipe) = read_fd;
-    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd;
-    0
+    drop(inner);
+    let pipe = UserPtr::from(pipe);
+    if let Err(err) = pipe
+        .write(read_fd)
+        .and_then(|_| pipe.add(1).write(write_fd))
+    {
+        let mut inner = process.inner_exclusive_access();
+        inner.fd_table[read_fd] = None;
+        inner.fd_table[write_fd] = None;
+        return Err(err);
+    }
+    Ok(0)
 }
 
 /// 复制描述符，在重定向中使用
-pub fn sys_dup(fd: usize) -> isize {
+pub fn sys_dup(fd: usize) -> SysResult {
+    let file = get_file(fd)?;
     let process = current_process();
     let mut inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
+    let new_fd = inner.alloc_fd();
+    inner.fd_table[new_fd] = Some(file);
+    Ok(new_fd)
+}
+
+/// 在文件系统中创建节点，目前只支持命名管道。路径按 `resolve_path` 相对 dirfd 解析
+pub fn sys_mknodat(dirfd: isize, path: *const u8, mode: u32) -> SysResult {
+    let path = UserPtr::from(path).read_str()?;
+    if mode & S_IFMT != S_IFIFO {
+        return Err(SysError::EINVAL);
     }
-    if inner.fd_table[fd].is_none() {
-        return -1;
+    let name = resolve_path(dirfd, &path)?;
+    if open_file(name.as_str(), OpenFlags::RDONLY).is_some() {
+        return Err(SysError::EEXIST);
     }
-    let new_fd = inner.alloc_fd();
-    inner.fd_table[new_fd] = Some(Arc::clone(inner.fd_table[fd].as_ref().unwrap()));
-    new_fd as isize
+    if make_fifo(name.as_str()) {
+        Ok(0)
+    } else {
+        Err(SysError::ENOSPC)
+    }
+}
+
+/// 文件描述符控制，目前支持 O_NONBLOCK 和查询、调整管道缓冲区大小
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> SysResult {
+    let file = get_file(fd)?;
+    match cmd {
+        F_GETFL => {
+            let mut flags = match (file.readable(), file.writable()) {
+                (true, true) => OpenFlags::RDWR,
+                (false, true) => OpenFlags::WRONLY,
+                _ => OpenFlags::RDONLY,
+            };
+            flags.set(OpenFlags::NONBLOCK, file.nonblocking());
+            Ok(flags.bits() as usize)
+        }
+        F_SETFL => {
+            file.set_nonblocking(
+                OpenFlags::from_bits_truncate(arg as u32).contains(OpenFlags::NONBLOCK),
+            );
+            Ok(0)
+        }
+        F_GETLK | F_SETLK | F_SETLKW => {
+            let inode = file.as_inode().ok_or(SysError::EINVAL)?;
+            fcntl_lock(inode, cmd, arg as *mut Flock)
+        }
+        F_GETPIPE_SZ => file.pipe_size().ok_or(SysError::EBADF),
+        F_SETPIPE_SZ => {
+            file.pipe_size().ok_or(SysError::EBADF)?;
+            file.set_pipe_size(arg).ok_or(SysError::EINVAL)
+        }
+        _ => Err(SysError::EINVAL),
+    }
+}
+
+/// 处理 fcntl 中的记录锁命令
+fn fcntl_lock(inode: &OSInode, cmd: usize, flock_ptr: *mut Flock) -> SysResult {
+    let pid = current_process().getpid();
+    let flock_ptr = UserPtr::from(flock_ptr);
+    let mut flock = flock_ptr.read()?;
+    let base = match flock.l_whence {
+        0 => 0,
+        1 => inode.offset() as i64,
+        2 => inode.size() as i64,
+        _ => return Err(SysError::EINVAL),
+    };
+    // a negative length locks the bytes before l_start, 0 means up to the end
+    let (start, end) = match flock.l_len {
+        0 => (base + flock.l_start, u64::MAX),
+        len if len > 0 => (base + flock.l_start, (base + flock.l_start + len) as u64),
+        len => (base + flock.l_start + len, (base + flock.l_start) as u64),
+    };
+    if start < 0 {
+        return Err(SysError::EINVAL);
+    }
+    let start = start as u64;
+    let exclusive = match flock.l_type {
+        F_RDLCK if cmd == F_GETLK || inode.readable() => false,
+        F_WRLCK if cmd == F_GETLK || inode.writable() => true,
+        F_UNLCK if cmd != F_GETLK => {
+            record_unlock(inode.ino(), pid, start, end);
+            return Ok(0);
+        }
+        // the file is not open for the kind of lock asked for
+        F_RDLCK | F_WRLCK => return Err(SysError::EBADF),
+        _ => return Err(SysError::EINVAL),
+    };
+    let lock = RecordLock {
+        pid,
+        start,
+        end,
+        exclusive,
+    };
+    if cmd == F_GETLK {
+        match record_conflict(inode.ino(), &lock) {
+            Some(held) => {
+                flock.l_type = if held.exclusive { F_WRLCK } else { F_RDLCK };
+                flock.l_whence = 0;
+                flock.l_start = held.start as i64;
+                flock.l_len = if held.end == u64::MAX {
+                    0
+                } else {
+                    (held.end - held.start) as i64
+                };
+                flock.l_pid = held.pid as i32;
+            }
+            None => flock.l_type = F_UNLCK,
+        }
+        flock_ptr.write(flock)?;
+        return Ok(0);
+    }
+    // may block until the conflicting locks are released
+    if record_lock(inode.ino(), lock, cmd == F_SETLK) {
+        Ok(0)
+    } else {
+        Err(SysError::EAGAIN)
+    }
+}
+
+/// 对整个文件加建议锁或解锁，锁属于打开的文件，在 fork 后共享
+pub fn sys_flock(fd: usize, operation: u32) -> SysResult {
+    let file = get_file(fd)?;
+    let inode = file.as_inode().ok_or(SysError::EINVAL)?;
+    let nonblocking = operation & LOCK_NB != 0;
+    let locked = match operation & !LOCK_NB {
+        LOCK_SH => flock_lock(inode.ino(), inode.lock_owner(), false, nonblocking),
+        LOCK_EX => flock_lock(inode.ino(), inode.lock_owner(), true, nonblocking),
+        LOCK_UN => {
+            flock_unlock(inode.ino(), inode.lock_owner());
+            true
+        }
+        _ => return Err(SysError::EINVAL),
+    };
+    if locked {
+        Ok(0)
+    } else {
+        Err(SysError::EAGAIN)
+    }
+}
+
+/// 控制设备，目前只支持终端的前台进程组
+pub fn sys_ioctl(fd: usize, cmd: usize, arg: usize) -> SysResult {
+    let file = get_file(fd)?;
+    let sid = current_process().inner_exclusive_access().sid;
+    if !file.is_tty() {
+        return Err(SysError::ENOTTY);
+    }
+    match cmd {
+        TIOCGPGRP => {
+            let pgid = tty_foreground().ok_or(SysError::ENOTTY)?;
+            UserPtr::from(arg as *mut i32).write(pgid as i32)?;
+            Ok(0)
+        }
+        TIOCSPGRP => {
+            let pgid = UserPtr::from(arg as *const i32).read()? as usize;
+            // the group has to be in the session of the caller
+            let in_session = process_group(pgid)
+                .iter()
+                .any(|process| process.inner_exclusive_access().sid == sid);
+            if !in_session {
+                return Err(SysError::EPERM);
+            }
+            // the terminal belongs to another session
+            if !set_tty_foreground(sid, pgid) {
+                return Err(SysError::ENOTTY);
+            }
+            Ok(0)
+        }
+        _ => Err(SysError::EINVAL),
+    }
+}
+
+/// 等待一组文件描述符中的任意一个就绪，timeout 为空时一直等待，
+/// 返回就绪的描述符个数，超时返回 0
+pub fn sys_ppoll(fds: *mut PollFd, nfds: usize, timeout: *const TimeSpec) -> SysResult {
+    let deadline = if timeout.is_null() {
+        None
+    } else {
+        Some(get_time_ns().saturating_add(UserPtr::from(timeout).read()?.to_ns()))
+    };
+    let fds = UserPtr::from(fds);
+    let mut poll_fds = (0..nfds)
+        .map(|i| fds.add(i).read())
+        .collect::<SysResult<Vec<PollFd>>>()?;
+    let task = current_task().unwrap();
+    loop {
+        let process = current_process();
+        let inner = process.inner_exclusive_access();
+        let files: Vec<_> = poll_fds
+            .iter()
+            .map(|poll_fd| {
+                if poll_fd.fd < 0 {
+                    return None;
+                }
+                inner.fd_table.get(poll_fd.fd as usize).cloned().flatten()
+            })
+            .collect();
+        drop(inner);
+        // 先加入等待队列再检查文件，检查之后到来的事件也能唤醒我们
+        prepare_interruptible_block()?;
+        if let Some(deadline) = deadline {
+            add_timer(deadline, task.clone());
+        }
+        let mut ready = 0;
+        let mut wait_queues = Vec::new();
+        for (poll_fd, file) in poll_fds.iter_mut().zip(files.iter()) {
+            let revents = match file {
+                None if poll_fd.fd < 0 => PollEvents::empty(),
+                None => PollEvents::POLLNVAL,
+                Some(file) => {
+                    if let Some(wait_queue) = file.wait_queue() {
+                        wait_queue.register(task.clone());
+                        wait_queues.push(wait_queue);
+                    }
+                    // errors and hang-ups are always reported
+                    file.poll()
+                        & (PollEvents::from_bits_truncate(poll_fd.events)
+                            | PollEvents::POLLERR
+                            | PollEvents::POLLHUP)
+                }
+            };
+            poll_fd.revents = revents.bits();
+            if !revents.is_empty() {
+                ready += 1;
+            }
+        }
+        let done = ready > 0 || deadline.is_some_and(|deadline| get_time_ns() >= deadline);
+        if done {
+            cancel_block();
+        } else {
+            // sleep until any of the files or the timer wakes us up
+            block_current_and_run_next();
+        }
+        for wait_queue in wait_queues.iter() {
+            wait_queue.unregister(&task);
+        }
+        if deadline.is_some() {
+            remove_timer(task.clone());
+        }
+        if done {
+            for (i, poll_fd) in poll_fds.iter().enumerate() {
+                fds.add(i).write(*poll_fd)?;
+            }
+            return Ok(ready);
+        }
+        if signal_interrupted() {
+            return Err(SysError::EINTR);
+        }
+    }
+}
+
+/// 获取 path 所在文件系统的使用情况，写入 buf
+pub fn sys_statfs(path: *const u8, buf: *mut StatFs) -> SysResult {
+    let path = UserPtr::from(path).read_str()?;
+    let stat = statfs(path.as_str()).ok_or(SysError::ENOENT)?;
+    UserPtr::from(buf).write(stat)?;
+    Ok(0)
 }
 
-pub fn sys_ls() -> isize {
+pub fn sys_ls() -> SysResult {
     list_apps();
-    0
+    Ok(0)
 }
@@ -10,21 +10,15 @@ use crate::{
     fs::ROOT_INODE,
     loongarch::{VIRTGPU_XRES, VIRTGPU_YRES},
     rtc_time_read,
-    sync::UPSafeCell,
+    sync::SpinLock,
 };
 
 static DT: &[u8] = include_bytes!("../assert/desktop.bmp");
 
 lazy_static::lazy_static!(
-    pub static ref DESKTOP:UPSafeCell<Arc<dyn Component>> = unsafe {
-        UPSafeCell::new(Arc::new(Panel::new(Size::new(VIRTGPU_XRES, VIRTGPU_YRES), Point::new(0, 0))))
-    };
-    pub static ref PAD:UPSafeCell<Option<Arc<GodTerminal>>> = unsafe {
-        UPSafeCell::new(None)
-    };
-    pub static ref TIMER:UPSafeCell<Option<Arc<Button>>> = unsafe {
-        UPSafeCell::new(None)
-    };
+    pub static ref DESKTOP:SpinLock<Arc<dyn Component>> = SpinLock::new(Arc::new(Panel::new(Size::new(VIRTGPU_XRES, VIRTGPU_YRES), Point::new(0, 0))));
+    pub static ref PAD:SpinLock<Option<Arc<GodTerminal>>> = SpinLock::new(None);
+    pub static ref TIMER:SpinLock<Option<Arc<Button>>> = SpinLock::new(None);
 );
 
 pub fn create_desktop() -> isize {
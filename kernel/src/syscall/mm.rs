@@ -0,0 +1,179 @@
+use alloc::vec;
+
+use bitflags::bitflags;
+
+use crate::{
+    config::{PAGE_SIZE, USER_MMAP_BASE, USER_SPACE_END, USER_STACK_BASE},
+    error::{SysError, SysResult},
+    mm::{free_frames, MapPermission, VirtAddr, VirtPageNum},
+    task::current_process,
+};
+
+bitflags! {
+    /// mmap 的 prot
+    pub struct MmapProt: u32 {
+        const PROT_READ = 1;
+        const PROT_WRITE = 2;
+        const PROT_EXEC = 4;
+    }
+}
+
+bitflags! {
+    /// mmap 的 flags
+    pub struct MmapFlags: u32 {
+        const MAP_SHARED = 0x01;
+        const MAP_PRIVATE = 0x02;
+        const MAP_FIXED = 0x10;
+        const MAP_ANONYMOUS = 0x20;
+        // 以下几项没有作用
+        const MAP_NORESERVE = 0x4000;
+        const MAP_POPULATE = 0x8000;
+        const MAP_STACK = 0x20000;
+    }
+}
+
+impl From<MmapProt> for MapPermission {
+    fn from(prot: MmapProt) -> Self {
+        let mut permission = MapPermission::default();
+        if !prot.contains(MmapProt::PROT_READ) {
+            permission |= MapPermission::NR;
+        }
+        if prot.contains(MmapProt::PROT_WRITE) {
+            permission |= MapPermission::W;
+        }
+        if !prot.contains(MmapProt::PROT_EXEC) {
+            permission |= MapPermission::NX;
+        }
+        permission
+    }
+}
+
+/// 用户地址范围 `addr..addr + len` 覆盖的页，越界时返回 None
+fn user_pages(addr: usize, len: usize) -> Option<(VirtPageNum, VirtPageNum)> {
+    let end = addr.checked_add(len)?;
+    (end <= USER_SPACE_END).then(|| (VirtAddr::from(addr).floor(), VirtAddr::from(end).ceil()))
+}
+
+/// 只支持私有映射：匿名映射的页面全部为 0，文件映射在映射时复制文件的内容，
+/// 之后和文件没有联系。不是 MAP_FIXED 时 addr 只是提示，被占用时从
+/// USER_MMAP_BASE 开始找空闲的位置
+pub fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: u32,
+    flags: u32,
+    fd: usize,
+    offset: usize,
+) -> SysResult {
+    let prot = MmapProt::from_bits(prot).ok_or(SysError::EINVAL)?;
+    let flags = MmapFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    if len == 0 || addr % PAGE_SIZE != 0 || offset % PAGE_SIZE != 0 {
+        return Err(SysError::EINVAL);
+    }
+    if flags.contains(MmapFlags::MAP_SHARED) || !flags.contains(MmapFlags::MAP_PRIVATE) {
+        return Err(SysError::ENODEV);
+    }
+    // 用户地址空间放不下，或者没有足够的页帧
+    let pages = len.div_ceil(PAGE_SIZE);
+    if user_pages(0, len).is_none() || pages > free_frames() {
+        return Err(SysError::ENOMEM);
+    }
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let file = if flags.contains(MmapFlags::MAP_ANONYMOUS) {
+        None
+    } else {
+        let file = inner
+            .fd_table
+            .get(fd)
+            .and_then(|file| file.clone())
+            .ok_or(SysError::EBADF)?;
+        if file.as_inode().is_none() {
+            return Err(SysError::ENODEV);
+        }
+        if !file.readable() {
+            return Err(SysError::EACCES);
+        }
+        Some(file)
+    };
+    let hint = user_pages(addr, len).filter(|_| addr != 0);
+    let start = if flags.contains(MmapFlags::MAP_FIXED) {
+        let (start, end) = hint.ok_or(SysError::EINVAL)?;
+        inner.memory_set.remove_range(start, end);
+        start
+    } else {
+        match hint {
+            Some((start, end)) if inner.memory_set.is_free(start, end) => start,
+            _ => inner
+                .memory_set
+                .find_free(
+                    VirtAddr::from(USER_MMAP_BASE).floor(),
+                    VirtAddr::from(USER_STACK_BASE).floor(),
+                    pages,
+                )
+                .ok_or(SysError::ENOMEM)?,
+        }
+    };
+    let start_va = VirtAddr::from(start);
+    let end_va = VirtAddr::from(start + pages);
+    inner.memory_set.insert_area(start_va, end_va, prot.into());
+    drop(inner);
+    // 文件的内容一页一页地复制到刚映射的页中，读文件时不持有进程的锁。
+    // 文件结束之后的部分保持为 0
+    if let Some(inode) = file.as_ref().and_then(|file| file.as_inode()) {
+        let mut buf = vec![0u8; PAGE_SIZE];
+        for i in 0..pages {
+            let want = PAGE_SIZE.min(len - i * PAGE_SIZE);
+            let read = inode.read_at(offset.saturating_add(i * PAGE_SIZE), &mut buf[..want]);
+            let inner = process.inner_exclusive_access();
+            // 其他线程可能已经解除了映射
+            if !inner
+                .memory_set
+                .copy_to_page(VirtPageNum(start.0 + i), &buf[..read])
+                || read < want
+            {
+                break;
+            }
+        }
+    }
+    Ok(start_va.into())
+}
+
+pub fn sys_munmap(addr: usize, len: usize) -> SysResult {
+    if len == 0 || addr % PAGE_SIZE != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let (start, end) = user_pages(addr, len).ok_or(SysError::EINVAL)?;
+    let process = current_process();
+    process
+        .inner_exclusive_access()
+        .memory_set
+        .remove_range(start, end);
+    Ok(0)
+}
+
+/// 返回新的 program break；不能移动时和 Linux 一样返回原来的值，而不是错误
+pub fn sys_brk(addr: usize) -> SysResult {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let old_brk = inner.brk;
+    if addr < inner.heap_bottom || addr > USER_MMAP_BASE {
+        return Ok(old_brk);
+    }
+    let old_end = VirtAddr::from(old_brk).ceil();
+    let new_end = VirtAddr::from(addr).ceil();
+    if new_end > old_end {
+        if !inner.memory_set.is_free(old_end, new_end) {
+            return Ok(old_brk);
+        }
+        inner.memory_set.insert_area(
+            old_end.into(),
+            new_end.into(),
+            MapPermission::default() | MapPermission::W | MapPermission::NX,
+        );
+    } else if new_end < old_end {
+        inner.memory_set.remove_range(new_end, old_end);
+    }
+    inner.brk = addr;
+    Ok(addr)
+}
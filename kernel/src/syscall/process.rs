@@ -1,103 +1,418 @@
-use alloc::{string::String, sync::Arc, vec::Vec};
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
 
+use bitflags::bitflags;
+
+use super::{thread::clone_thread, time::RUsage};
 use crate::{
+    config::USER_STACK_SIZE,
+    error::{SysError, SysResult},
     fs::{open_file, OpenFlags},
-    get_time_ms,
-    mm::{translated_ref, translated_refmut, translated_str},
+    mm::{UserPtr, UserSlice},
+    sync::update_priority,
     task::{
-        current_process, current_task, current_user_token, exit_current_and_run_next,
-        suspend_current_and_run_next,
+        add_task, all_processes, current_process, current_task, exit_current_and_run_next,
+        exit_group_and_run_next, pid2process, process_group, suspend_current_and_run_next,
+        CpuTimes, ProcessControlBlock, WaitReport, NICE_MAX, NICE_MIN,
     },
 };
 
+/// clone 的 flags 中子进程退出信号所在的位
+const CSIGNAL: usize = 0xff;
+
+bitflags! {
+    /// clone 的 flags
+    pub struct CloneFlags: usize {
+        const CLONE_VM = 0x100;
+        const CLONE_FS = 0x200;
+        const CLONE_FILES = 0x400;
+        const CLONE_SIGHAND = 0x800;
+        const CLONE_THREAD = 0x10000;
+        const CLONE_SYSVSEM = 0x40000;
+        const CLONE_SETTLS = 0x80000;
+        const CLONE_PARENT_SETTID = 0x100000;
+        const CLONE_CHILD_CLEARTID = 0x200000;
+        const CLONE_DETACHED = 0x400000;
+        const CLONE_CHILD_SETTID = 0x1000000;
+    }
+}
+
+/// waitpid 的 options：没有子进程可以报告时不阻塞
+const WNOHANG: u32 = 1;
+/// waitpid 的 options：报告被暂停的子进程
+const WUNTRACED: u32 = 2;
+/// waitpid 的 options：报告被 SIGCONT 继续运行的子进程
+const WCONTINUED: u32 = 8;
+
 pub fn sys_exit(exit_code: i32) -> ! {
     exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
-pub fn sys_yield() -> isize {
+pub fn sys_exit_group(exit_code: i32) -> ! {
+    exit_group_and_run_next(exit_code);
+    panic!("Unreachable in sys_exit_group!");
+}
+
+pub fn sys_yield() -> SysResult {
     suspend_current_and_run_next();
-    0
+    Ok(0)
 }
 
-pub fn sys_get_time() -> isize {
-    get_time_ms() as isize
+/// `struct utsname`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct UtsName {
+    sysname: [u8; 65],
+    nodename: [u8; 65],
+    release: [u8; 65],
+    version: [u8; 65],
+    machine: [u8; 65],
+    domainname: [u8; 65],
 }
 
-pub fn sys_getpid() -> isize {
-    current_task().unwrap().process.upgrade().unwrap().getpid() as isize
+/// 以 `\0` 结尾的 utsname 字段
+fn uts_field(value: &str) -> [u8; 65] {
+    let mut field = [0u8; 65];
+    field[..value.len()].copy_from_slice(value.as_bytes());
+    field
 }
 
-pub fn sys_fork() -> isize {
-    let current_process = current_process();
-    let new_process = current_process.fork();
-    let new_pid = new_process.getpid();
+pub fn sys_uname(buf: *mut UtsName) -> SysResult {
+    let uts_name = UtsName {
+        sysname: uts_field("LoongRCore"),
+        nodename: uts_field("loongarch"),
+        release: uts_field(env!("CARGO_PKG_VERSION")),
+        version: uts_field("#1"),
+        machine: uts_field("loongarch64"),
+        domainname: uts_field("(none)"),
+    };
+    UserPtr::from(buf).write(uts_name)?;
+    Ok(0)
+}
+
+pub fn sys_getpid() -> SysResult {
+    Ok(current_task().unwrap().process.upgrade().unwrap().getpid())
+}
+
+/// 同一进程中的线程共享地址空间、文件描述符表和信号处理，所以 CLONE_THREAD 要求
+/// 同时给出这三项，而进程之间不能共享它们。CLONE_FS、CLONE_SYSVSEM 和 CLONE_DETACHED
+/// 没有作用。CLONE_THREAD 时返回进程内的 tid，没有给出栈则由内核分配；否则返回子进程的 pid
+pub fn sys_clone(
+    flags: usize,
+    stack: usize,
+    ptid: *mut u32,
+    ctid: *mut u32,
+    tls: usize,
+) -> SysResult {
+    // 低 8 位是子进程退出时发给父进程的信号，这里总是 SIGCHLD
+    let flags = CloneFlags::from_bits(flags & !CSIGNAL).ok_or(SysError::EINVAL)?;
+    let shared = CloneFlags::CLONE_VM | CloneFlags::CLONE_FILES | CloneFlags::CLONE_SIGHAND;
+    let thread = flags.contains(CloneFlags::CLONE_THREAD);
+    if (thread && !flags.contains(shared)) || (!thread && flags.intersects(shared)) {
+        return Err(SysError::EINVAL);
+    }
+    if flags.contains(CloneFlags::CLONE_PARENT_SETTID) {
+        let process = current_process();
+        let inner = process.inner_exclusive_access();
+        inner
+            .memory_set
+            .check_user_range(ptid as usize, core::mem::size_of::<u32>(), true)?;
+    }
+    let (new_task, new_id) = if thread {
+        let new_task = clone_thread(stack == 0)?;
+        let tid = new_task.inner_exclusive_access().res.as_ref().unwrap().tid;
+        if flags.contains(CloneFlags::CLONE_CHILD_SETTID) {
+            let _ = UserPtr::from(ctid).write(tid as u32);
+        }
+        (new_task, tid)
+    } else {
+        let child = current_process().fork();
+        let pid = child.getpid();
+        let child_inner = child.inner_exclusive_access();
+        if flags.contains(CloneFlags::CLONE_CHILD_SETTID) {
+            let _ = UserSlice::new(ctid as *const u8, core::mem::size_of::<u32>())
+                .write_in(&child_inner.memory_set, &(pid as u32).to_le_bytes());
+        }
+        (child_inner.get_task(0), pid)
+    };
+    let mut new_task_inner = new_task.inner_exclusive_access();
     // modify trap context of new_task, because it returns immediately after
     // switching
-    let new_process_inner = new_process.inner_exclusive_access();
-    let task = new_process_inner.tasks[0].as_ref().unwrap();
-    let trap_cx = task.inner_exclusive_access().get_trap_cx();
+    let trap_cx = new_task_inner.get_trap_cx();
     // we do not have to move to next instruction since we have done it before
-    // for child process, fork returns 0
+    // for the child, clone returns 0
     trap_cx.x[4] = 0;
-    new_pid as isize
+    if stack != 0 {
+        trap_cx.x[3] = stack;
+    }
+    if flags.contains(CloneFlags::CLONE_SETTLS) {
+        // tp
+        trap_cx.x[2] = tls;
+    }
+    if flags.contains(CloneFlags::CLONE_CHILD_CLEARTID) {
+        new_task_inner.clear_child_tid = ctid as usize;
+    }
+    drop(new_task_inner);
+    if flags.contains(CloneFlags::CLONE_PARENT_SETTID) {
+        UserPtr::from(ptid).write(new_id as u32)?;
+    }
+    if thread {
+        add_task(new_task);
+    }
+    Ok(new_id)
 }
 
-pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    let mut args_vec: Vec<String> = Vec::new();
+/// 读取以空指针结尾的字符串指针数组，`size` 累计它们在新用户栈上占用的空间
+fn read_str_array(array: *const usize, size: &mut usize) -> SysResult<Vec<String>> {
+    let mut strings = Vec::new();
+    if array.is_null() {
+        return Ok(strings);
+    }
+    let mut array = UserPtr::from(array);
     loop {
-        let arg_str_ptr = *translated_ref(token, args);
-        if arg_str_ptr == 0 {
+        let str_ptr = array.read()?;
+        if str_ptr == 0 {
             break;
         }
-        args_vec.push(translated_str(token, arg_str_ptr as *const u8));
-        unsafe {
-            args = args.add(1);
+        let string = UserPtr::from(str_ptr as *const u8).read_str()?;
+        *size += string.len() + 1 + core::mem::size_of::<usize>();
+        if *size > USER_STACK_SIZE / 2 {
+            return Err(SysError::E2BIG);
         }
+        strings.push(string);
+        array = array.add(1);
     }
-    if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
-        let all_data = app_inode.read_all();
-        let process = current_process();
-        let argc = args_vec.len();
-        process.exec(all_data.as_slice(), args_vec);
-        // return argc because cx.x[10] will be covered with it later
-        argc as isize
-    } else {
-        -1
-    }
+    Ok(strings)
+}
+
+/// 空的 envp 表示没有环境变量
+pub fn sys_exec(path: *const u8, args: *const usize, envs: *const usize) -> SysResult {
+    let path = UserPtr::from(path).read_str()?;
+    // argv、envp 和字符串都要放进新的用户栈，剩下的留给辅助向量和程序本身
+    let mut size = 2 * core::mem::size_of::<usize>();
+    let args_vec = read_str_array(args, &mut size)?;
+    let envs_vec = read_str_array(envs, &mut size)?;
+    let app_inode = open_file(path.as_str(), OpenFlags::RDONLY).ok_or(SysError::ENOENT)?;
+    let all_data = app_inode.read_all();
+    let process = current_process();
+    let argc = args_vec.len();
+    process.exec(all_data.as_slice(), args_vec, envs_vec)?;
+    // return argc because cx.x[10] will be covered with it later
+    Ok(argc)
 }
 
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+/// 没有符合条件的子进程时返回 ECHILD，子进程都还没有状态可以报告时阻塞，options 有 WNOHANG 时返回 0。
+/// rusage 不为空时写入子进程和它回收的子进程的运行时间，回收的子进程的时间计入当前进程
+pub fn sys_wait4(
+    pid: isize,
+    exit_code_ptr: *mut i32,
+    options: u32,
+    rusage: *mut RUsage,
+) -> SysResult {
     let process = current_process();
-    // find a child process
     let mut inner = process.inner_exclusive_access();
-    if !inner
-        .children
-        .iter()
-        .any(|p| pid == -1 || pid as usize == p.getpid())
+    // fail before a zombie is reaped into a bad pointer
+    if !exit_code_ptr.is_null() {
+        inner.memory_set.check_user_range(
+            exit_code_ptr as usize,
+            core::mem::size_of::<i32>(),
+            true,
+        )?;
+    }
+    if !rusage.is_null() {
+        inner
+            .memory_set
+            .check_user_range(rusage as usize, core::mem::size_of::<RUsage>(), true)?;
+    }
+    loop {
+        // find a child process
+        if !inner
+            .children
+            .iter()
+            .any(|p| pid == -1 || pid as usize == p.getpid())
+        {
+            return Err(SysError::ECHILD);
+            // ---- release current PCB
+        }
+        let pair = inner.children.iter().enumerate().find(|(_, p)| {
+            // ++++ temporarily access child PCB exclusively
+            // 退出的线程离开 CPU 之后才能回收
+            (pid == -1 || pid as usize == p.getpid())
+                && p.inner_exclusive_access().is_zombie
+                && p.running.is_zero()
+            // ++++ release child PCB
+        });
+        if let Some((idx, _)) = pair {
+            // 其他 CPU 可能还短暂持有子进程的引用（如发信号时），最后一个引用释放时才回收
+            let child = inner.children.remove(idx);
+            let found_pid = child.getpid();
+            // ++++ temporarily access child PCB exclusively
+            let child_inner = child.inner_exclusive_access();
+            let exit_code = child_inner.exit_code;
+            let mut times = child.cpu_times.load();
+            times += child_inner.children_times;
+            drop(child_inner);
+            // ++++ release child PCB
+            inner.children_times += times;
+            drop(inner);
+            write_status(exit_code_ptr, exit_code)?;
+            write_rusage(rusage, times)?;
+            return Ok(found_pid);
+        }
+        // 没有退出的子进程，再看是否有需要报告的暂停或继续
+        for child in inner.children.iter() {
+            if pid != -1 && pid as usize != child.getpid() {
+                continue;
+            }
+            let mut child_inner = child.inner_exclusive_access();
+            let status = match child_inner.wait_report {
+                Some(WaitReport::Stopped(signum)) if options & WUNTRACED != 0 => {
+                    ((signum as i32) << 8) | 0x7f
+                }
+                Some(WaitReport::Continued) if options & WCONTINUED != 0 => 0xffff,
+                _ => continue,
+            };
+            child_inner.wait_report = None;
+            let mut times = child.cpu_times.load();
+            times += child_inner.children_times;
+            drop(child_inner);
+            let child_pid = child.getpid();
+            drop(inner);
+            write_status(exit_code_ptr, status)?;
+            write_rusage(rusage, times)?;
+            return Ok(child_pid);
+        }
+        if options & WNOHANG != 0 {
+            return Ok(0);
+        }
+        // 子进程退出、暂停或继续时唤醒，持有自己的锁进入等待队列，唤醒不会丢失
+        process.wait_children.wait_and_release(inner);
+        inner = process.inner_exclusive_access();
+    }
+}
+
+/// 状态地址为空时不写入
+fn write_status(exit_code_ptr: *mut i32, status: i32) -> SysResult<()> {
+    if !exit_code_ptr.is_null() {
+        UserPtr::from(exit_code_ptr).write(status)?;
+    }
+    Ok(())
+}
+
+fn write_rusage(rusage: *mut RUsage, times: CpuTimes) -> SysResult<()> {
+    if !rusage.is_null() {
+        UserPtr::from(rusage).write(RUsage::from_times(times))?;
+    }
+    Ok(())
+}
+
+/// 把进程 pid 移到进程组 pgid 中，pid 为 0 表示当前进程，pgid 为 0 表示
+/// 以 pid 为组号新建进程组。只能移动当前进程或其子进程，且只能在同一会话内移动。
+pub fn sys_setpgid(pid: usize, pgid: usize) -> SysResult {
+    let current = current_process();
+    let target = if pid == 0 || pid == current.getpid() {
+        current.clone()
+    } else {
+        let inner = current.inner_exclusive_access();
+        match inner.children.iter().find(|child| child.getpid() == pid) {
+            Some(child) => child.clone(),
+            None => return Err(SysError::ESRCH),
+        }
+    };
+    let pgid = if pgid == 0 { target.getpid() } else { pgid };
+    let sid = current.inner_exclusive_access().sid;
+    let target_sid = target.inner_exclusive_access().sid;
+    // a session leader stays where it is
+    if target_sid != sid || target_sid == target.getpid() {
+        return Err(SysError::EPERM);
+    }
+    // joining a group needs the group to exist in the session
+    if pgid != target.getpid()
+        && !process_group(pgid)
+            .iter()
+            .any(|process| process.inner_exclusive_access().sid == sid)
     {
-        return -1;
-        // ---- release current PCB
-    }
-    let pair = inner.children.iter().enumerate().find(|(_, p)| {
-        // ++++ temporarily access child PCB exclusively
-        p.inner_exclusive_access().is_zombie && (pid == -1 || pid as usize == p.getpid())
-        // ++++ release child PCB
-    });
-    if let Some((idx, _)) = pair {
-        let child = inner.children.remove(idx);
-        // confirm that child will be deallocated after being removed from children list
-        assert_eq!(Arc::strong_count(&child), 1);
-        let found_pid = child.getpid();
-        // ++++ temporarily access child PCB exclusively
-        let exit_code = child.inner_exclusive_access().exit_code;
-        // ++++ release child PCB
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
-        found_pid as isize
+        return Err(SysError::EPERM);
+    }
+    target.inner_exclusive_access().pgid = pgid;
+    Ok(0)
+}
+
+pub fn sys_getpgid(pid: usize) -> SysResult {
+    let process = if pid == 0 {
+        current_process()
     } else {
-        -2
+        pid2process(pid).ok_or(SysError::ESRCH)?
+    };
+    let pgid = process.inner_exclusive_access().pgid;
+    Ok(pgid)
+}
+
+/// 新建一个会话和进程组，当前进程成为两者的首进程，返回新的会话号。
+/// 进程组的首进程不能这样做。
+pub fn sys_setsid() -> SysResult {
+    let process = current_process();
+    let pid = process.getpid();
+    if !process_group(pid).is_empty() {
+        return Err(SysError::EPERM);
     }
-    // ---- release current PCB automatically
+    let mut inner = process.inner_exclusive_access();
+    inner.pgid = pid;
+    inner.sid = pid;
+    Ok(pid)
+}
+
+/// setpriority 和 getpriority 的 which
+const PRIO_PROCESS: usize = 0;
+const PRIO_PGRP: usize = 1;
+const PRIO_USER: usize = 2;
+
+/// `which` 和 `who` 选中的进程，who 为 0 表示当前进程或者当前进程组；
+/// 只有一个用户，PRIO_USER 选中所有进程
+fn priority_targets(which: usize, who: usize) -> SysResult<Vec<Arc<ProcessControlBlock>>> {
+    let processes = match which {
+        PRIO_PROCESS if who == 0 => vec![current_process()],
+        PRIO_PROCESS => pid2process(who).into_iter().collect(),
+        PRIO_PGRP if who == 0 => process_group(current_process().inner_exclusive_access().pgid),
+        PRIO_PGRP => process_group(who),
+        PRIO_USER => all_processes(),
+        _ => return Err(SysError::EINVAL),
+    };
+    if processes.is_empty() {
+        return Err(SysError::ESRCH);
+    }
+    Ok(processes)
+}
+
+/// 设置选中进程所有线程的 nice，超出 -20 到 19 的值被截断
+pub fn sys_setpriority(which: usize, who: usize, nice: isize) -> SysResult {
+    let nice = nice.clamp(NICE_MIN as isize, NICE_MAX as isize) as i32;
+    for process in priority_targets(which, who)? {
+        let tasks: Vec<_> = process
+            .inner_exclusive_access()
+            .tasks
+            .iter()
+            .flatten()
+            .cloned()
+            .collect();
+        for task in tasks {
+            task.inner_exclusive_access().sched.nice = nice;
+            update_priority(task);
+        }
+    }
+    Ok(0)
+}
+
+/// 和 Linux 的系统调用一样返回 20 - nice，即 1 到 40，选中多个进程时取最高的优先级
+pub fn sys_getpriority(which: usize, who: usize) -> SysResult {
+    let nice = priority_targets(which, who)?
+        .iter()
+        .map(|process| {
+            let inner = process.inner_exclusive_access();
+            let main_task = inner.tasks.iter().flatten().next();
+            main_task.map_or(0, |task| task.inner_exclusive_access().sched.nice)
+        })
+        .min()
+        .unwrap();
+    Ok((20 - nice) as usize)
 }
@@ -1,27 +1,118 @@
-use crate::task::{pid2process, SignalFlags};
+use crate::{
+    error::{SysError, SysResult},
+    mm::UserPtr,
+    task::{
+        current_add_signal, current_process, current_task, current_trap_cx, pid2process,
+        process_group, send_signal, send_signal_to_group, SignalAction, SignalFlags, SignalFrame,
+    },
+};
 
-pub fn sys_kill(pid: usize, signal: u32) -> isize {
-    if let Some(process) = pid2process(pid) {
-        if let Some(flag) = SignalFlags::from_bits(signal) {
-            process.inner_exclusive_access().signals |= flag;
-            0
-        } else {
-            -1
-        }
+/// sigprocmask 的 how 参数
+const SIG_BLOCK: usize = 0;
+const SIG_UNBLOCK: usize = 1;
+const SIG_SETMASK: usize = 2;
+
+/// 向进程发送编号为 signum 的信号，signum 为 0 时只检查进程是否存在。
+/// pid 为 0 时发给当前进程组的所有进程，小于 -1 时发给进程组 -pid。
+pub fn sys_kill(pid: isize, signum: u32) -> SysResult {
+    let signal = match signum {
+        0 => None,
+        signum => Some(SignalFlags::from_signum(signum as usize).ok_or(SysError::EINVAL)?),
+    };
+    if pid <= 0 {
+        let pgid = match pid {
+            0 => current_process().inner_exclusive_access().pgid,
+            -1 => return Err(SysError::EINVAL),
+            pid => -pid as usize,
+        };
+        return match signal {
+            Some(signal) if send_signal_to_group(pgid, signal) => Ok(0),
+            None if !process_group(pgid).is_empty() => Ok(0),
+            _ => Err(SysError::ESRCH),
+        };
+    }
+    let process = pid2process(pid as usize).ok_or(SysError::ESRCH)?;
+    if let Some(signal) = signal {
+        send_signal(&process, signal);
+    }
+    Ok(0)
+}
+
+/// 设置进程对信号 signum 的处理方式，action 和 old_action 都可以为空
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> SysResult {
+    let signal = SignalFlags::from_signum(signum).ok_or(SysError::EINVAL)?;
+    if check_sigaction_error(signal, action as usize) {
+        return Err(SysError::EINVAL);
+    }
+    let process = current_task().unwrap().process.upgrade().unwrap();
+    // touch user memory first, so a bad pointer changes nothing
+    let action = UserPtr::from(action);
+    let new_action = if action.is_null() {
+        None
     } else {
-        -1
+        Some(action.read()?)
+    };
+    let old_action = UserPtr::from(old_action);
+    if !old_action.is_null() {
+        let current = process.inner_exclusive_access().signal_actions.table[signum];
+        old_action.write(current)?;
     }
+    if let Some(mut action) = new_action {
+        action.mask -= SignalFlags::UNBLOCKABLE;
+        process.inner_exclusive_access().signal_actions.table[signum] = action;
+    }
+    Ok(0)
+}
+
+/// SIGKILL and SIGSTOP can only be queried
+fn check_sigaction_error(signal: SignalFlags, action: usize) -> bool {
+    action != 0 && SignalFlags::UNBLOCKABLE.contains(signal)
 }
 
-#[allow(unused)]
-fn check_sigaction_error(signal: SignalFlags, action: usize, old_action: usize) -> bool {
-    if action == 0
-        || old_action == 0
-        || signal == SignalFlags::SIGKILL
-        || signal == SignalFlags::SIGSTOP
-    {
-        true
+/// 修改当前线程的信号掩码，set 为空时只取出原来的掩码
+pub fn sys_sigprocmask(how: usize, set: *const u32, old_set: *mut u32) -> SysResult {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let old_mask = inner.signal_mask;
+    let set = UserPtr::from(set);
+    let new_mask = if set.is_null() {
+        old_mask
     } else {
-        false
+        let set = SignalFlags::from_bits_truncate(set.read()?) - SignalFlags::UNBLOCKABLE;
+        match how {
+            SIG_BLOCK => old_mask | set,
+            SIG_UNBLOCK => old_mask - set,
+            SIG_SETMASK => set,
+            _ => return Err(SysError::EINVAL),
+        }
+    };
+    let old_set = UserPtr::from(old_set);
+    if !old_set.is_null() {
+        old_set.write(old_mask.bits())?;
     }
+    inner.signal_mask = new_mask;
+    Ok(0)
+}
+
+/// 从信号处理函数返回，恢复保存在用户栈上的上下文和信号掩码
+pub fn sys_sigreturn() -> SysResult {
+    let trap_cx = current_trap_cx();
+    // the handler has returned, so the frame is right at the stack pointer
+    let frame = match UserPtr::from(trap_cx.x[3] as *const SignalFrame).read() {
+        Ok(frame) => frame,
+        Err(err) => {
+            current_add_signal(SignalFlags::SIGSEGV);
+            return Err(err);
+        }
+    };
+    trap_cx.x = frame.x;
+    trap_cx.sepc = frame.sepc;
+    current_task().unwrap().inner_exclusive_access().signal_mask =
+        frame.mask - SignalFlags::UNBLOCKABLE;
+    // the syscall result goes to a0, which must keep its saved value
+    Ok(trap_cx.x[4])
 }
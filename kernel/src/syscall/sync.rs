@@ -1,137 +1,411 @@
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 
 use crate::{
-    sync::{Condvar, Mutex, MutexBlocking, MutexSpin, Semaphore},
-    task::{block_current_and_run_next, current_process, current_task},
-    timer::{add_timer, get_time_ms},
+    error::{SysError, SysResult},
+    mm::UserPtr,
+    sync::{
+        futex_requeue, futex_wait, futex_wake, Barrier, Condvar, Mutex, MutexBlocking, MutexSpin,
+        Resource, RwLock, Semaphore,
+    },
+    task::{block_current_and_run_next, current_process, current_task, prepare_block},
+    timer::{get_time_ns, TimeSpec},
 };
 
-pub fn sys_sleep(ms: usize) -> isize {
-    let expire_ms = get_time_ms() + ms;
-    let task = current_task().unwrap();
-    add_timer(expire_ms, task);
-    block_current_and_run_next();
-    0
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+const FUTEX_REQUEUE: usize = 3;
+/// 只在一个进程内使用，这里和共享的 futex 没有区别
+const FUTEX_PRIVATE_FLAG: usize = 128;
+const FUTEX_CLOCK_REALTIME: usize = 256;
+
+/// FUTEX_WAIT 的 timeout 是相对时间，FUTEX_REQUEUE 的第四个参数是移动的线程数。
+/// 不支持的操作返回 ENOSYS
+pub fn sys_futex(
+    uaddr: *mut u32,
+    op: usize,
+    val: usize,
+    timeout: usize,
+    uaddr2: *mut u32,
+    _val3: usize,
+) -> SysResult {
+    if uaddr as usize % 4 != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let uaddr = UserPtr::from(uaddr);
+    let key = uaddr.kernel_addr()?;
+    match op & !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME) {
+        FUTEX_WAIT => {
+            let expire_ns = deadline(timeout as *const TimeSpec)?;
+            futex_wait(key, uaddr, val as u32, expire_ns)
+        }
+        FUTEX_WAKE => Ok(futex_wake(key, val)),
+        FUTEX_REQUEUE => {
+            if uaddr2 as usize % 4 != 0 {
+                return Err(SysError::EINVAL);
+            }
+            let key2 = UserPtr::from(uaddr2).kernel_addr()?;
+            Ok(futex_requeue(key, val, key2, timeout))
+        }
+        _ => Err(SysError::ENOSYS),
+    }
+}
+
+fn current_tid() -> usize {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .res
+        .as_ref()
+        .unwrap()
+        .tid
+}
+
+/// 打开后申请互斥锁和信号量会导致死锁时返回 EDEADLK，见 [`DeadlockDetector`]
+///
+/// [`DeadlockDetector`]: crate::sync::DeadlockDetector
+pub fn sys_enable_deadlock_detect(enabled: usize) -> SysResult {
+    let enabled = match enabled {
+        0 => false,
+        1 => true,
+        _ => return Err(SysError::EINVAL),
+    };
+    current_process()
+        .inner_exclusive_access()
+        .deadlock_detector
+        .enabled = enabled;
+    Ok(0)
+}
+
+/// 按编号取出进程的同步原语，编号无效时返回 EINVAL
+fn get_sync<T: ?Sized>(list: &[Option<Arc<T>>], id: usize) -> SysResult<Arc<T>> {
+    list.get(id)
+        .and_then(|item| item.clone())
+        .ok_or(SysError::EINVAL)
 }
 
-pub fn sys_mutex_create(blocking: bool) -> isize {
+/// 把同步原语放到第一个空位上，返回它的编号
+fn insert_sync<T: ?Sized>(list: &mut Vec<Option<Arc<T>>>, item: Arc<T>) -> usize {
+    if let Some(id) = list.iter().position(|item| item.is_none()) {
+        list[id] = Some(item);
+        id
+    } else {
+        list.push(Some(item));
+        list.len() - 1
+    }
+}
+
+/// 销毁同步原语，之后它的编号可以重新分配。还有线程持有或者在等待时返回 EBUSY
+fn destroy_sync<T: ?Sized>(
+    list: &mut [Option<Arc<T>>],
+    id: usize,
+    busy: impl FnOnce(&T) -> bool,
+) -> SysResult {
+    let item = get_sync(list, id)?;
+    if busy(&item) {
+        return Err(SysError::EBUSY);
+    }
+    list[id] = None;
+    Ok(0)
+}
+
+/// 相对时间 `timeout` 对应的到期时间，空指针表示一直等待
+fn deadline(timeout: *const TimeSpec) -> SysResult<Option<usize>> {
+    if timeout.is_null() {
+        return Ok(None);
+    }
+    let timeout = UserPtr::from(timeout).read()?;
+    if !timeout.is_valid() {
+        return Err(SysError::EINVAL);
+    }
+    Ok(Some(get_time_ns().saturating_add(timeout.to_ns())))
+}
+
+pub fn sys_mutex_create(blocking: bool) -> SysResult {
     let process = current_process();
-    let mutex: Option<Arc<dyn Mutex>> = if !blocking {
-        Some(Arc::new(MutexSpin::new()))
+    let mutex: Arc<dyn Mutex> = if !blocking {
+        Arc::new(MutexSpin::new())
     } else {
-        Some(Arc::new(MutexBlocking::new()))
+        Arc::new(MutexBlocking::new())
     };
     let mut process_inner = process.inner_exclusive_access();
-    if let Some(id) = process_inner
-        .mutex_list
-        .iter()
-        .enumerate()
-        .find(|(_, item)| item.is_none())
-        .map(|(id, _)| id)
-    {
-        process_inner.mutex_list[id] = mutex;
-        id as isize
-    } else {
-        process_inner.mutex_list.push(mutex);
-        process_inner.mutex_list.len() as isize - 1
+    let id = insert_sync(&mut process_inner.mutex_list, mutex);
+    process_inner
+        .deadlock_detector
+        .add_resource(Resource::Mutex(id), 1);
+    Ok(id)
+}
+
+pub fn sys_mutex_lock(mutex_id: usize) -> SysResult {
+    sys_mutex_lock_timeout(mutex_id, core::ptr::null())
+}
+
+/// 锁被占用时返回 EBUSY
+pub fn sys_mutex_trylock(mutex_id: usize) -> SysResult {
+    let tid = current_tid();
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let mutex = get_sync(&process_inner.mutex_list, mutex_id)?;
+    if !mutex.try_lock() {
+        return Err(SysError::EBUSY);
     }
+    process_inner
+        .deadlock_detector
+        .acquired(tid, Resource::Mutex(mutex_id));
+    Ok(0)
 }
 
-pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+/// 超时返回 ETIMEDOUT，timeout 为空指针时一直等待
+pub fn sys_mutex_lock_timeout(mutex_id: usize, timeout: *const TimeSpec) -> SysResult {
+    let expire_ns = deadline(timeout)?;
+    let tid = current_tid();
     let process = current_process();
-    let process_inner = process.inner_exclusive_access();
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let mut process_inner = process.inner_exclusive_access();
+    let mutex = get_sync(&process_inner.mutex_list, mutex_id)?;
+    process_inner
+        .deadlock_detector
+        .request(tid, Resource::Mutex(mutex_id))?;
     drop(process_inner);
-    drop(process);
-    mutex.lock();
-    0
+    let locked = mutex.lock_timeout(expire_ns);
+    let mut process_inner = process.inner_exclusive_access();
+    if !locked {
+        process_inner
+            .deadlock_detector
+            .cancel_request(tid, Resource::Mutex(mutex_id));
+        return Err(SysError::ETIMEDOUT);
+    }
+    process_inner
+        .deadlock_detector
+        .acquired(tid, Resource::Mutex(mutex_id));
+    Ok(0)
 }
 
-pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+/// 解锁时持有进程的 inner，被唤醒的线程在这之后才能记录它得到了锁
+pub fn sys_mutex_unlock(mutex_id: usize) -> SysResult {
+    let tid = current_tid();
     let process = current_process();
-    let process_inner = process.inner_exclusive_access();
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
-    drop(process_inner);
-    drop(process);
-    mutex.unlock();
-    0
+    let mut process_inner = process.inner_exclusive_access();
+    let mutex = get_sync(&process_inner.mutex_list, mutex_id)?;
+    if !mutex.unlock() {
+        return Err(SysError::EPERM);
+    }
+    process_inner
+        .deadlock_detector
+        .release(tid, Resource::Mutex(mutex_id));
+    Ok(0)
 }
 
-pub fn sys_semaphore_create(res_count: usize) -> isize {
+pub fn sys_mutex_destroy(mutex_id: usize) -> SysResult {
     let process = current_process();
     let mut process_inner = process.inner_exclusive_access();
-    let id = if let Some(id) = process_inner
-        .semaphore_list
-        .iter()
-        .enumerate()
-        .find(|(_, item)| item.is_none())
-        .map(|(id, _)| id)
-    {
-        process_inner.semaphore_list[id] = Some(Arc::new(Semaphore::new(res_count)));
-        id
-    } else {
+    destroy_sync(&mut process_inner.mutex_list, mutex_id, |mutex| {
+        mutex.is_locked()
+    })?;
+    process_inner
+        .deadlock_detector
+        .remove_resource(Resource::Mutex(mutex_id));
+    Ok(0)
+}
+
+pub fn sys_semaphore_create(res_count: usize) -> SysResult {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let id = insert_sync(
+        &mut process_inner.semaphore_list,
+        Arc::new(Semaphore::new(res_count)),
+    );
+    process_inner
+        .deadlock_detector
+        .add_resource(Resource::Semaphore(id), res_count);
+    Ok(id)
+}
+
+pub fn sys_semaphore_up(sem_id: usize) -> SysResult {
+    let tid = current_tid();
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let sem = get_sync(&process_inner.semaphore_list, sem_id)?;
+    sem.up();
+    process_inner
+        .deadlock_detector
+        .release(tid, Resource::Semaphore(sem_id));
+    Ok(0)
+}
+
+pub fn sys_semaphore_down(sem_id: usize) -> SysResult {
+    sys_semaphore_down_timeout(sem_id, core::ptr::null())
+}
+
+/// 超时返回 ETIMEDOUT，timeout 为空指针时一直等待，为 0 时只尝试一次
+pub fn sys_semaphore_down_timeout(sem_id: usize, timeout: *const TimeSpec) -> SysResult {
+    let expire_ns = deadline(timeout)?;
+    let tid = current_tid();
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let sem = get_sync(&process_inner.semaphore_list, sem_id)?;
+    if expire_ns.is_some_and(|expire_ns| expire_ns <= get_time_ns()) {
+        if !sem.try_down() {
+            return Err(SysError::ETIMEDOUT);
+        }
         process_inner
-            .semaphore_list
-            .push(Some(Arc::new(Semaphore::new(res_count))));
-        process_inner.semaphore_list.len() - 1
-    };
-    id as isize
+            .deadlock_detector
+            .acquired(tid, Resource::Semaphore(sem_id));
+        return Ok(0);
+    }
+    process_inner
+        .deadlock_detector
+        .request(tid, Resource::Semaphore(sem_id))?;
+    drop(process_inner);
+    let acquired = sem.down_timeout(expire_ns);
+    let mut process_inner = process.inner_exclusive_access();
+    if !acquired {
+        process_inner
+            .deadlock_detector
+            .cancel_request(tid, Resource::Semaphore(sem_id));
+        return Err(SysError::ETIMEDOUT);
+    }
+    process_inner
+        .deadlock_detector
+        .acquired(tid, Resource::Semaphore(sem_id));
+    Ok(0)
 }
 
-pub fn sys_semaphore_up(sem_id: usize) -> isize {
+pub fn sys_semaphore_destroy(sem_id: usize) -> SysResult {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    destroy_sync(&mut process_inner.semaphore_list, sem_id, |sem| {
+        sem.has_waiters()
+    })?;
+    process_inner
+        .deadlock_detector
+        .remove_resource(Resource::Semaphore(sem_id));
+    Ok(0)
+}
+
+pub fn sys_condvar_create(_arg: usize) -> SysResult {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    Ok(insert_sync(
+        &mut process_inner.condvar_list,
+        Arc::new(Condvar::new()),
+    ))
+}
+
+pub fn sys_condvar_signal(condvar_id: usize) -> SysResult {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
+    let condvar = get_sync(&process_inner.condvar_list, condvar_id)?;
     drop(process_inner);
-    sem.up();
-    0
+    condvar.signal();
+    Ok(0)
 }
 
-pub fn sys_semaphore_down(sem_id: usize) -> isize {
+pub fn sys_condvar_broadcast(condvar_id: usize) -> SysResult {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
+    let condvar = get_sync(&process_inner.condvar_list, condvar_id)?;
     drop(process_inner);
-    sem.down();
-    0
+    condvar.broadcast();
+    Ok(0)
 }
 
-pub fn sys_condvar_create(_arg: usize) -> isize {
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> SysResult {
+    sys_condvar_wait_timeout(condvar_id, mutex_id, core::ptr::null())
+}
+
+/// 超时返回 ETIMEDOUT，返回时都重新持有了 mutex
+pub fn sys_condvar_wait_timeout(
+    condvar_id: usize,
+    mutex_id: usize,
+    timeout: *const TimeSpec,
+) -> SysResult {
+    let expire_ns = deadline(timeout)?;
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let condvar = get_sync(&process_inner.condvar_list, condvar_id)?;
+    let mutex = get_sync(&process_inner.mutex_list, mutex_id)?;
+    drop(process_inner);
+    // 死锁检测把等待期间的线程仍然看作锁的持有者
+    condvar.wait_timeout(mutex, expire_ns)?;
+    Ok(0)
+}
+
+pub fn sys_condvar_destroy(condvar_id: usize) -> SysResult {
     let process = current_process();
     let mut process_inner = process.inner_exclusive_access();
-    let id = if let Some(id) = process_inner
-        .condvar_list
-        .iter()
-        .enumerate()
-        .find(|(_, item)| item.is_none())
-        .map(|(id, _)| id)
-    {
-        process_inner.condvar_list[id] = Some(Arc::new(Condvar::new()));
-        id
+    destroy_sync(&mut process_inner.condvar_list, condvar_id, |condvar| {
+        condvar.has_waiters()
+    })
+}
+
+pub fn sys_rwlock_create() -> SysResult {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    Ok(insert_sync(
+        &mut process_inner.rwlock_list,
+        Arc::new(RwLock::new()),
+    ))
+}
+
+/// write 为真时申请写锁，否则申请读锁
+pub fn sys_rwlock_lock(rwlock_id: usize, write: bool) -> SysResult {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let rwlock = get_sync(&process_inner.rwlock_list, rwlock_id)?;
+    drop(process_inner);
+    if write {
+        rwlock.write();
     } else {
-        process_inner
-            .condvar_list
-            .push(Some(Arc::new(Condvar::new())));
-        process_inner.condvar_list.len() - 1
-    };
-    id as isize
+        rwlock.read();
+    }
+    Ok(0)
 }
 
-pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+pub fn sys_rwlock_unlock(rwlock_id: usize) -> SysResult {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let condvar = Arc::clone(process_inner.condvar_list[condvar_id].as_ref().unwrap());
+    let rwlock = get_sync(&process_inner.rwlock_list, rwlock_id)?;
     drop(process_inner);
-    condvar.signal();
-    0
+    if rwlock.unlock() {
+        Ok(0)
+    } else {
+        Err(SysError::EPERM)
+    }
 }
 
-pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+pub fn sys_rwlock_destroy(rwlock_id: usize) -> SysResult {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    destroy_sync(&mut process_inner.rwlock_list, rwlock_id, |rwlock| {
+        rwlock.is_locked()
+    })
+}
+
+/// count 个线程到达之后一起继续，count 为 0 时返回 EINVAL
+pub fn sys_barrier_create(count: usize) -> SysResult {
+    if count == 0 {
+        return Err(SysError::EINVAL);
+    }
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    Ok(insert_sync(
+        &mut process_inner.barrier_list,
+        Arc::new(Barrier::new(count)),
+    ))
+}
+
+/// 最后到达的线程返回 1，其他线程返回 0
+pub fn sys_barrier_wait(barrier_id: usize) -> SysResult {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let condvar = Arc::clone(process_inner.condvar_list[condvar_id].as_ref().unwrap());
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let barrier = get_sync(&process_inner.barrier_list, barrier_id)?;
     drop(process_inner);
-    condvar.wait(mutex);
-    0
+    Ok(barrier.wait() as usize)
+}
+
+pub fn sys_barrier_destroy(barrier_id: usize) -> SysResult {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    destroy_sync(&mut process_inner.barrier_list, barrier_id, |barrier| {
+        barrier.has_waiters()
+    })
 }
@@ -1,29 +1,43 @@
 use alloc::sync::Arc;
 
 use crate::{
-    task::{add_task, current_task, TaskControlBlock},
-    trap::TrapContext,
+    error::{SysError, SysResult},
+    sync::update_priority,
+    task::{current_task, SchedEntity, TaskControlBlock, NICE_MAX, NICE_MIN},
 };
 
-pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+/// 在当前进程中创建一个线程，它的 trap 上下文是调用者的副本。
+/// `alloc_ustack` 时由内核分配用户栈，新线程从栈顶开始运行。
+/// 新线程还没有加入调度队列。调用者已经被 exec 或者进程退出丢弃时返回 EAGAIN
+pub fn clone_thread(alloc_ustack: bool) -> SysResult<Arc<TaskControlBlock>> {
     let task = current_task().unwrap();
     let process = task.process.upgrade().unwrap();
+    let task_inner = task.inner_exclusive_access();
     // create a new thread
     let new_task = Arc::new(TaskControlBlock::new(
         Arc::clone(&process),
-        task.inner_exclusive_access()
-            .res
-            .as_ref()
-            .unwrap()
-            .ustack_base,
-        true,
+        task_inner.res.as_ref().unwrap().ustack_base,
+        alloc_ustack,
     ));
-    // add new task to scheduler
-    add_task(Arc::clone(&new_task));
-    let new_task_inner = new_task.inner_exclusive_access();
+    let mut new_task_inner = new_task.inner_exclusive_access();
+    // a new thread inherits the signal mask of its creator
+    new_task_inner.signal_mask = task_inner.signal_mask;
+    new_task_inner.sched = SchedEntity::inherit(&task_inner.sched);
+    new_task_inner.kstack.copy_from_other(&task_inner.kstack);
     let new_task_res = new_task_inner.res.as_ref().unwrap();
+    if alloc_ustack {
+        new_task_inner.get_trap_cx().x[3] = new_task_res.ustack_top();
+    }
     let new_task_tid = new_task_res.tid;
+    drop(new_task_inner);
     let mut process_inner = process.inner_exclusive_access();
+    if !process_inner.owns_task(&task) {
+        // 丢弃调用者的线程还在等它离开 CPU，新线程的资源可以在原来的地址空间中释放
+        drop(process_inner);
+        drop(task_inner);
+        drop(new_task);
+        return Err(SysError::EAGAIN);
+    }
     // add new thread to current process
     let tasks = &mut process_inner.tasks;
 
@@ -31,50 +45,77 @@ pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
         tasks.push(None);
     }
     tasks[new_task_tid] = Some(Arc::clone(&new_task));
-    let new_task_trap_cx = new_task_inner.get_trap_cx();
-    *new_task_trap_cx = TrapContext::app_init_context(entry, new_task_res.ustack_top());
-    new_task_trap_cx.x[4] = arg;
-    new_task_tid as isize
+    Ok(new_task)
+}
+
+/// 线程退出时在 tidptr 写 0，返回 tid
+pub fn sys_set_tid_address(tidptr: *mut u32) -> SysResult {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.clear_child_tid = tidptr as usize;
+    Ok(task_inner.res.as_ref().unwrap().tid)
 }
 
-pub fn sys_gettid() -> isize {
-    current_task()
+pub fn sys_gettid() -> SysResult {
+    Ok(current_task()
         .unwrap()
         .inner_exclusive_access()
         .res
         .as_ref()
         .unwrap()
-        .tid as isize
+        .tid)
 }
 
-/// thread does not exist, return -1
-/// thread has not exited yet, return -2
+/// thread does not exist, return ESRCH
+/// thread has not exited yet, return EAGAIN
 /// otherwise, return thread's exit code
-pub fn sys_waittid(tid: usize) -> i32 {
+pub fn sys_waittid(tid: usize) -> SysResult<i32> {
     let task = current_task().unwrap();
     let process = task.process.upgrade().unwrap();
     let task_inner = task.inner_exclusive_access();
     let mut process_inner = process.inner_exclusive_access();
     // a thread cannot wait for itself
     if task_inner.res.as_ref().unwrap().tid == tid {
-        return -1;
-    }
-    let mut exit_code: Option<i32> = None;
-    let waited_task = process_inner.tasks[tid].as_ref();
-    if let Some(waited_task) = waited_task {
-        if let Some(waited_exit_code) = waited_task.inner_exclusive_access().exit_code {
-            exit_code = Some(waited_exit_code);
-        }
-    } else {
-        // waited thread does not exist
-        return -1;
-    }
-    if let Some(exit_code) = exit_code {
-        // dealloc the exited thread
-        process_inner.tasks[tid] = None;
-        exit_code
-    } else {
-        // waited thread has not exited
-        -2
+        return Err(SysError::EDEADLK);
     }
+    let waited_task = process_inner
+        .tasks
+        .get(tid)
+        .and_then(|task| task.as_ref())
+        .ok_or(SysError::ESRCH)?;
+    let exit_code = waited_task
+        .inner_exclusive_access()
+        .exit_code
+        .ok_or(SysError::EAGAIN)?;
+    // dealloc the exited thread
+    process_inner.tasks[tid] = None;
+    Ok(exit_code)
+}
+
+/// 当前进程中编号为 `tid` 的线程
+fn thread_of_current_process(tid: usize) -> SysResult<Arc<TaskControlBlock>> {
+    let process = current_task().unwrap().process.upgrade().unwrap();
+    let process_inner = process.inner_exclusive_access();
+    let task = process_inner
+        .tasks
+        .get(tid)
+        .and_then(|task| task.as_ref())
+        .ok_or(SysError::ESRCH)?;
+    Ok(Arc::clone(task))
+}
+
+/// 只设置一个线程的 nice，超出 -20 到 19 的值被截断
+pub fn sys_thread_setpriority(tid: usize, nice: isize) -> SysResult {
+    let task = thread_of_current_process(tid)?;
+    let nice = nice.clamp(NICE_MIN as isize, NICE_MAX as isize) as i32;
+    task.inner_exclusive_access().sched.nice = nice;
+    update_priority(task);
+    Ok(0)
+}
+
+/// 返回 20 减去线程的有效优先级，其中包括从等待它持有的锁的线程继承来的优先级
+pub fn sys_thread_getpriority(tid: usize) -> SysResult {
+    let task = thread_of_current_process(tid)?;
+    let prio = task.inner_exclusive_access().sched.prio;
+    Ok((20 - prio) as usize)
 }
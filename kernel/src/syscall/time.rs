@@ -0,0 +1,323 @@
+use loongarch64::time::get_timer_freq;
+
+use super::fs::get_file;
+use crate::{
+    config::NSEC_PER_SEC,
+    error::{SysError, SysResult},
+    fs::{OpenFlags, TimerFd},
+    mm::UserPtr,
+    task::{
+        account_system_time, block_current_until, current_process, current_task, get_itimer,
+        prepare_interruptible_block, set_itimer, signal_interrupted, CpuTimes, ITIMER_COUNT,
+    },
+    timer::{get_realtime_ns, get_time_ms, get_time_ns, TimeSpec, TimeVal},
+};
+
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
+const CLOCK_MONOTONIC_RAW: usize = 4;
+const CLOCK_REALTIME_COARSE: usize = 5;
+const CLOCK_MONOTONIC_COARSE: usize = 6;
+const CLOCK_BOOTTIME: usize = 7;
+
+/// clock_nanosleep 的 flags，req 是绝对时间
+const TIMER_ABSTIME: usize = 1;
+/// timerfd_settime 的 flags，it_value 是绝对时间
+const TFD_TIMER_ABSTIME: usize = 1;
+
+/// times 返回的时间以 1/CLK_TCK 秒为单位，和 Linux 的 USER_HZ 相同
+const CLK_TCK: usize = 100;
+
+const RUSAGE_SELF: isize = 0;
+const RUSAGE_CHILDREN: isize = -1;
+const RUSAGE_THREAD: isize = 1;
+
+/// `struct tms`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Tms {
+    pub tms_utime: usize,
+    pub tms_stime: usize,
+    pub tms_cutime: usize,
+    pub tms_cstime: usize,
+}
+
+/// `struct rusage`，只统计了 CPU 时间
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RUsage {
+    pub ru_utime: TimeVal,
+    pub ru_stime: TimeVal,
+    /// ru_maxrss 到 ru_nivcsw，都为 0
+    pub ru_unused: [usize; 14],
+}
+
+impl RUsage {
+    pub fn from_times(times: CpuTimes) -> Self {
+        Self {
+            ru_utime: TimeVal::from_ns(times.user_ns),
+            ru_stime: TimeVal::from_ns(times.system_ns),
+            ..Self::default()
+        }
+    }
+}
+
+/// `struct itimerval`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ITimerVal {
+    pub it_interval: TimeVal,
+    pub it_value: TimeVal,
+}
+
+/// `struct itimerspec`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ITimerSpec {
+    pub it_interval: TimeSpec,
+    pub it_value: TimeSpec,
+}
+
+/// 时钟当前的纳秒数。CLOCK_REALTIME 是开机时从 RTC 读到的时间加上单调时钟，
+/// 其他时钟都从开机时算起，系统不会挂起，所以 CLOCK_BOOTTIME 和 CLOCK_MONOTONIC 相同
+fn clock_now(clock_id: usize) -> SysResult<usize> {
+    match clock_id {
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE => Ok(get_realtime_ns()),
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME => {
+            Ok(get_time_ns())
+        }
+        _ => Err(SysError::EINVAL),
+    }
+}
+
+/// 读取用户给出的时间，tv_nsec 超过一秒时返回 EINVAL
+fn read_timespec(ts: *const TimeSpec) -> SysResult<TimeSpec> {
+    let ts = UserPtr::from(ts).read()?;
+    if !ts.is_valid() {
+        return Err(SysError::EINVAL);
+    }
+    Ok(ts)
+}
+
+/// 睡眠到开机以来的 `expire_ns`，被信号打断时返回 EINTR
+fn sleep_until(expire_ns: usize) -> SysResult<()> {
+    if expire_ns <= get_time_ns() {
+        return Ok(());
+    }
+    prepare_interruptible_block()?;
+    block_current_until(Some(expire_ns));
+    if signal_interrupted() && get_time_ns() < expire_ns {
+        return Err(SysError::EINTR);
+    }
+    Ok(())
+}
+
+/// 睡眠被信号打断时，rem 不为空则写入剩余的时间
+fn write_remaining(rem: *mut TimeSpec, expire_ns: usize) -> SysResult<()> {
+    if !rem.is_null() {
+        let remaining = expire_ns.saturating_sub(get_time_ns());
+        UserPtr::from(rem).write(TimeSpec::from_ns(remaining))?;
+    }
+    Ok(())
+}
+
+/// 开机以来的毫秒数
+pub fn sys_get_time() -> SysResult {
+    Ok(get_time_ms())
+}
+
+pub fn sys_clock_gettime(clock_id: usize, tp: *mut TimeSpec) -> SysResult {
+    let now = clock_now(clock_id)?;
+    UserPtr::from(tp).write(TimeSpec::from_ns(now))?;
+    Ok(0)
+}
+
+/// 所有时钟都来自同一个计数器，精度是它的一个周期
+pub fn sys_clock_getres(clock_id: usize, res: *mut TimeSpec) -> SysResult {
+    clock_now(clock_id)?;
+    if !res.is_null() {
+        let period = NSEC_PER_SEC.div_ceil(get_timer_freq());
+        UserPtr::from(res).write(TimeSpec::from_ns(period))?;
+    }
+    Ok(0)
+}
+
+/// 被信号打断时返回 EINTR，rem 不为空则写入剩余的时间
+pub fn sys_nanosleep(req: *const TimeSpec, rem: *mut TimeSpec) -> SysResult {
+    let req = read_timespec(req)?;
+    let expire_ns = get_time_ns().saturating_add(req.to_ns());
+    if let Err(error) = sleep_until(expire_ns) {
+        write_remaining(rem, expire_ns)?;
+        return Err(error);
+    }
+    Ok(0)
+}
+
+/// 按照 `clock_id` 睡眠，flags 为 TIMER_ABSTIME 时 req 是这个时钟上的绝对时间。
+/// 被信号打断时返回 EINTR，相对时间的睡眠还会在 rem 不为空时写入剩余的时间
+pub fn sys_clock_nanosleep(
+    clock_id: usize,
+    flags: usize,
+    req: *const TimeSpec,
+    rem: *mut TimeSpec,
+) -> SysResult {
+    let now = clock_now(clock_id)?;
+    let req = read_timespec(req)?.to_ns();
+    let absolute = flags & TIMER_ABSTIME != 0;
+    let duration = if absolute {
+        req.saturating_sub(now)
+    } else {
+        req
+    };
+    let expire_ns = get_time_ns().saturating_add(duration);
+    if let Err(error) = sleep_until(expire_ns) {
+        if !absolute {
+            write_remaining(rem, expire_ns)?;
+        }
+        return Err(error);
+    }
+    Ok(0)
+}
+
+fn ns_to_clock_ticks(ns: usize) -> usize {
+    ns / (NSEC_PER_SEC / CLK_TCK)
+}
+
+/// 当前进程和已回收的子进程的运行时间，返回开机以来的时间，单位都是 1/CLK_TCK 秒
+pub fn sys_times(buf: *mut Tms) -> SysResult {
+    // 把这次系统调用到现在的时间也算上
+    account_system_time();
+    if !buf.is_null() {
+        let process = current_process();
+        let times = process.cpu_times.load();
+        let children = process.inner_exclusive_access().children_times;
+        UserPtr::from(buf).write(Tms {
+            tms_utime: ns_to_clock_ticks(times.user_ns),
+            tms_stime: ns_to_clock_ticks(times.system_ns),
+            tms_cutime: ns_to_clock_ticks(children.user_ns),
+            tms_cstime: ns_to_clock_ticks(children.system_ns),
+        })?;
+    }
+    Ok(ns_to_clock_ticks(get_time_ns()))
+}
+
+/// `who` 为 RUSAGE_SELF 时是当前进程所有线程的运行时间，RUSAGE_CHILDREN 是已回收的
+/// 子进程的，RUSAGE_THREAD 是当前线程的
+pub fn sys_getrusage(who: isize, usage: *mut RUsage) -> SysResult {
+    account_system_time();
+    let times = match who {
+        RUSAGE_SELF => current_process().cpu_times.load(),
+        RUSAGE_CHILDREN => current_process().inner_exclusive_access().children_times,
+        RUSAGE_THREAD => current_task().unwrap().inner_exclusive_access().cpu_times,
+        _ => return Err(SysError::EINVAL),
+    };
+    UserPtr::from(usage).write(RUsage::from_times(times))?;
+    Ok(0)
+}
+
+/// 返回间隔定时器 `which` 的剩余时间和周期
+pub fn sys_getitimer(which: usize, curr_value: *mut ITimerVal) -> SysResult {
+    if which >= ITIMER_COUNT {
+        return Err(SysError::EINVAL);
+    }
+    let (value, interval) = get_itimer(&current_process(), which);
+    UserPtr::from(curr_value).write(ITimerVal {
+        it_interval: TimeVal::from_ns(interval),
+        it_value: TimeVal::from_ns(value),
+    })?;
+    Ok(0)
+}
+
+/// 设置间隔定时器 `which`，it_value 为 0 时停止。old_value 不为空时写入原来的设置
+pub fn sys_setitimer(
+    which: usize,
+    new_value: *const ITimerVal,
+    old_value: *mut ITimerVal,
+) -> SysResult {
+    if which >= ITIMER_COUNT {
+        return Err(SysError::EINVAL);
+    }
+    let new_value = UserPtr::from(new_value).read()?;
+    if !new_value.it_value.is_valid() || !new_value.it_interval.is_valid() {
+        return Err(SysError::EINVAL);
+    }
+    let (value, interval) = set_itimer(
+        &current_process(),
+        which,
+        new_value.it_value.to_ns(),
+        new_value.it_interval.to_ns(),
+    );
+    if !old_value.is_null() {
+        UserPtr::from(old_value).write(ITimerVal {
+            it_interval: TimeVal::from_ns(interval),
+            it_value: TimeVal::from_ns(value),
+        })?;
+    }
+    Ok(0)
+}
+
+/// 创建按 `clock_id` 计时的 timerfd，flags 只支持 O_NONBLOCK 和 O_CLOEXEC
+pub fn sys_timerfd_create(clock_id: usize, flags: u32) -> SysResult {
+    clock_now(clock_id)?;
+    let flags = OpenFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    if !(OpenFlags::NONBLOCK | OpenFlags::CLOEXEC).contains(flags) {
+        return Err(SysError::EINVAL);
+    }
+    let timerfd = TimerFd::new(clock_id, flags.contains(OpenFlags::NONBLOCK));
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let fd = inner.alloc_fd();
+    inner.fd_table[fd] = Some(timerfd);
+    Ok(fd)
+}
+
+/// 取出描述符 fd 对应的 timerfd，不是 timerfd 时返回 EINVAL
+fn with_timerfd<T>(fd: usize, f: impl FnOnce(&TimerFd) -> SysResult<T>) -> SysResult<T> {
+    let file = get_file(fd)?;
+    let timerfd = file.as_timerfd().ok_or(SysError::EINVAL)?;
+    f(timerfd)
+}
+
+fn itimerspec(value: usize, interval: usize) -> ITimerSpec {
+    ITimerSpec {
+        it_interval: TimeSpec::from_ns(interval),
+        it_value: TimeSpec::from_ns(value),
+    }
+}
+
+/// 设置 timerfd，flags 为 TFD_TIMER_ABSTIME 时 it_value 是创建时选择的时钟上的绝对时间
+pub fn sys_timerfd_settime(
+    fd: usize,
+    flags: usize,
+    new_value: *const ITimerSpec,
+    old_value: *mut ITimerSpec,
+) -> SysResult {
+    let new_value = UserPtr::from(new_value).read()?;
+    if !new_value.it_value.is_valid() || !new_value.it_interval.is_valid() {
+        return Err(SysError::EINVAL);
+    }
+    let (value, interval) = with_timerfd(fd, |timerfd| {
+        let value = new_value.it_value.to_ns();
+        let expire_ns = if value == 0 {
+            0
+        } else if flags & TFD_TIMER_ABSTIME != 0 {
+            // 换算成单调时钟，已经过去的时间立即到期
+            let now = clock_now(timerfd.clock_id())?;
+            get_time_ns().saturating_add(value.saturating_sub(now))
+        } else {
+            get_time_ns().saturating_add(value)
+        };
+        Ok(timerfd.set(expire_ns, new_value.it_interval.to_ns()))
+    })?;
+    if !old_value.is_null() {
+        UserPtr::from(old_value).write(itimerspec(value, interval))?;
+    }
+    Ok(0)
+}
+
+/// 返回 timerfd 的剩余时间和周期
+pub fn sys_timerfd_gettime(fd: usize, curr_value: *mut ITimerSpec) -> SysResult {
+    let (value, interval) = with_timerfd(fd, |timerfd| Ok(timerfd.get()))?;
+    UserPtr::from(curr_value).write(itimerspec(value, interval))?;
+    Ok(0)
+}
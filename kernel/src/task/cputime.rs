@@ -0,0 +1,62 @@
+//! 线程和进程的 CPU 时间
+use core::{
+    ops::AddAssign,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::TaskControlBlock;
+
+/// 在用户态和内核态运行的纳秒数
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CpuTimes {
+    pub user_ns: usize,
+    pub system_ns: usize,
+}
+
+impl CpuTimes {
+    pub fn total_ns(&self) -> usize {
+        self.user_ns + self.system_ns
+    }
+    fn add(&mut self, user: bool, ns: usize) {
+        if user {
+            self.user_ns += ns;
+        } else {
+            self.system_ns += ns;
+        }
+    }
+}
+
+impl AddAssign for CpuTimes {
+    fn add_assign(&mut self, other: Self) {
+        self.user_ns += other.user_ns;
+        self.system_ns += other.system_ns;
+    }
+}
+
+/// 进程所有线程的 CPU 时间，线程退出后它的时间仍然计在这里
+#[derive(Default)]
+pub struct ProcessCpuTimes {
+    user_ns: AtomicUsize,
+    system_ns: AtomicUsize,
+}
+
+impl ProcessCpuTimes {
+    pub fn load(&self) -> CpuTimes {
+        CpuTimes {
+            user_ns: self.user_ns.load(Ordering::Relaxed),
+            system_ns: self.system_ns.load(Ordering::Relaxed),
+        }
+    }
+    fn add(&self, user: bool, ns: usize) {
+        let counter = if user { &self.user_ns } else { &self.system_ns };
+        counter.fetch_add(ns, Ordering::Relaxed);
+    }
+}
+
+/// `task` 在用户态（`user`）或内核态运行了 `ns` 纳秒，同时记到线程和进程上
+pub fn charge_cpu_time(task: &TaskControlBlock, user: bool, ns: usize) {
+    task.inner_exclusive_access().cpu_times.add(user, ns);
+    if let Some(process) = task.process.upgrade() {
+        process.cpu_times.add(user, ns);
+    }
+}
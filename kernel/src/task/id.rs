@@ -10,7 +10,7 @@ use crate::{
     config::{PAGE_SIZE, USER_STACK_SIZE},
     mm::{frame_alloc, FrameTracker, MapPermission, PhysAddr, VirtAddr},
     phys_to_virt,
-    sync::UPSafeCell,
+    sync::SpinLock,
     trap::TrapContext,
 };
 
@@ -46,8 +46,7 @@ impl RecycleAllocator {
 }
 
 lazy_static! {
-    static ref PID_ALLOCATOR: UPSafeCell<RecycleAllocator> =
-        unsafe { UPSafeCell::new(RecycleAllocator::new()) };
+    static ref PID_ALLOCATOR: SpinLock<RecycleAllocator> = SpinLock::new(RecycleAllocator::new());
 }
 
 pub const IDLE_PID: usize = 0;
@@ -118,11 +117,14 @@ impl KernelStack {
 pub struct TaskUserRes {
     pub tid: usize,
     pub ustack_base: usize,
+    /// 内核分配的用户栈的底部，线程使用自己提供的栈时为 None
+    pub ustack_bottom: Option<usize>,
     pub process: Weak<ProcessControlBlock>,
 }
 
-fn ustack_bottom_from_tid(ustack_base: usize, tid: usize) -> usize {
-    ustack_base + tid * (PAGE_SIZE + USER_STACK_SIZE)
+/// 第 slot 个内核分配的用户栈的底部，相邻的栈之间隔一个保护页
+fn ustack_bottom_from_slot(ustack_base: usize, slot: usize) -> usize {
+    ustack_base + slot * (PAGE_SIZE + USER_STACK_SIZE)
 }
 
 impl TaskUserRes {
@@ -132,9 +134,10 @@ impl TaskUserRes {
         alloc_user_res: bool,
     ) -> Self {
         let tid = process.inner_exclusive_access().alloc_tid();
-        let task_user_res = Self {
+        let mut task_user_res = Self {
             tid,
             ustack_base,
+            ustack_bottom: None,
             process: Arc::downgrade(&process),
         };
         if alloc_user_res {
@@ -143,18 +146,25 @@ impl TaskUserRes {
         task_user_res
     }
 
-    /// 申请线程资源
-    pub fn alloc_user_res(&self) {
+    /// 申请线程资源：在第一个空闲的位置分配用户栈
+    pub fn alloc_user_res(&mut self) {
         let process = self.process.upgrade().unwrap();
         let mut process_inner = process.inner_exclusive_access();
         // alloc user stack
-        let ustack_bottom = ustack_bottom_from_tid(self.ustack_base, self.tid);
+        let ustack_bottom = (0..)
+            .map(|slot| ustack_bottom_from_slot(self.ustack_base, slot))
+            .find(|&bottom| {
+                !process_inner
+                    .memory_set
+                    .is_mapped(VirtAddr::from(bottom).floor())
+            })
+            .unwrap();
         let ustack_top = ustack_bottom + USER_STACK_SIZE;
-        process_inner.memory_set.insert_area(
-            ustack_bottom.into(),
-            ustack_top.into(),
-            MapPermission::default() | MapPermission::W,
-        );
+        let permission = process_inner.stack_permission;
+        process_inner
+            .memory_set
+            .insert_area(ustack_bottom.into(), ustack_top.into(), permission);
+        self.ustack_bottom = Some(ustack_bottom);
     }
 
     fn dealloc_user_res(&self) {
@@ -162,10 +172,12 @@ impl TaskUserRes {
         let process = self.process.upgrade().unwrap();
         let mut process_inner = process.inner_exclusive_access();
         // dealloc ustack manually
-        let ustack_bottom_va: VirtAddr = ustack_bottom_from_tid(self.ustack_base, self.tid).into();
-        process_inner
-            .memory_set
-            .remove_area_with_start_vpn(ustack_bottom_va.into());
+        if let Some(ustack_bottom) = self.ustack_bottom {
+            let ustack_bottom_va: VirtAddr = ustack_bottom.into();
+            process_inner
+                .memory_set
+                .remove_area_with_start_vpn(ustack_bottom_va.into());
+        }
         // dealloc trap_cx manually
     }
     pub fn dealloc_tid(&self) {
@@ -177,8 +189,9 @@ impl TaskUserRes {
     pub fn ustack_base(&self) -> usize {
         self.ustack_base
     }
+    /// 内核分配的用户栈的栈顶
     pub fn ustack_top(&self) -> usize {
-        ustack_bottom_from_tid(self.ustack_base, self.tid) + USER_STACK_SIZE
+        self.ustack_bottom.unwrap() + USER_STACK_SIZE
     }
 }
 
@@ -0,0 +1,138 @@
+//! 进程的间隔定时器：ITIMER_REAL 挂在 [`crate::timer`] 的定时器堆上，
+//! ITIMER_VIRTUAL 和 ITIMER_PROF 按进程的 CPU 时间在时钟中断中检查
+use alloc::sync::Arc;
+
+use super::{current_task, send_signal, ProcessControlBlock, SignalFlags};
+use crate::timer::{add_timer_event, get_time_ns, remove_timer_events, TimerEvent};
+
+/// 按真实时间计时，到期发送 SIGALRM
+pub const ITIMER_REAL: usize = 0;
+/// 按进程在用户态运行的时间计时，到期发送 SIGVTALRM
+pub const ITIMER_VIRTUAL: usize = 1;
+/// 按进程在用户态和内核态运行的时间计时，到期发送 SIGPROF
+pub const ITIMER_PROF: usize = 2;
+pub const ITIMER_COUNT: usize = 3;
+
+/// `expire_ns` 为 0 表示定时器没有启动。ITIMER_REAL 的到期时间是开机以来的纳秒数，
+/// 另外两个是进程的 CPU 时间
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ITimer {
+    pub expire_ns: usize,
+    pub interval_ns: usize,
+}
+
+impl ITimer {
+    /// 在 `now` 到期之后的下一次到期时间，不是周期定时器时为 0。
+    /// 落后超过一个周期时从 `now` 重新开始，错过的到期不再补发
+    fn next_expire(&self, now: usize) -> usize {
+        if self.interval_ns == 0 {
+            return 0;
+        }
+        let next = self.expire_ns.saturating_add(self.interval_ns);
+        if next > now {
+            next
+        } else {
+            now.saturating_add(self.interval_ns)
+        }
+    }
+}
+
+/// 定时器 `which` 使用的时钟的当前值
+fn itimer_clock(process: &ProcessControlBlock, which: usize) -> usize {
+    match which {
+        ITIMER_REAL => get_time_ns(),
+        ITIMER_VIRTUAL => process.cpu_times.load().user_ns,
+        _ => process.cpu_times.load().total_ns(),
+    }
+}
+
+/// 剩余时间和周期，已经到期但还没有处理的定时器剩余 1 纳秒
+fn remaining(timer: &ITimer, now: usize) -> (usize, usize) {
+    if timer.expire_ns == 0 {
+        return (0, timer.interval_ns);
+    }
+    (
+        timer.expire_ns.saturating_sub(now).max(1),
+        timer.interval_ns,
+    )
+}
+
+/// 返回定时器 `which` 的剩余时间和周期
+pub fn get_itimer(process: &ProcessControlBlock, which: usize) -> (usize, usize) {
+    let now = itimer_clock(process, which);
+    remaining(&process.inner_exclusive_access().itimers[which], now)
+}
+
+/// `value_ns` 之后到期，之后每隔 `interval_ns` 到期一次，`value_ns` 为 0 时停止定时器。
+/// 返回原来的剩余时间和周期
+pub fn set_itimer(
+    process: &Arc<ProcessControlBlock>,
+    which: usize,
+    value_ns: usize,
+    interval_ns: usize,
+) -> (usize, usize) {
+    let now = itimer_clock(process, which);
+    let timer = ITimer {
+        expire_ns: if value_ns == 0 {
+            0
+        } else {
+            now.saturating_add(value_ns)
+        },
+        interval_ns,
+    };
+    let mut process_inner = process.inner_exclusive_access();
+    let old = core::mem::replace(&mut process_inner.itimers[which], timer);
+    drop(process_inner);
+    if which == ITIMER_REAL {
+        remove_timer_events(
+            |event| matches!(event, TimerEvent::ItimerReal(p) if p.as_ptr() == Arc::as_ptr(process)),
+        );
+        if timer.expire_ns != 0 {
+            add_timer_event(
+                timer.expire_ns,
+                TimerEvent::ItimerReal(Arc::downgrade(process)),
+            );
+        }
+    }
+    remaining(&old, now)
+}
+
+/// ITIMER_REAL 在 `expire_ns` 到期。定时器已经被重新设置过时忽略
+pub fn itimer_real_expired(process: &Arc<ProcessControlBlock>, expire_ns: usize) {
+    let mut process_inner = process.inner_exclusive_access();
+    if process_inner.is_zombie || process_inner.itimers[ITIMER_REAL].expire_ns != expire_ns {
+        return;
+    }
+    let timer = &mut process_inner.itimers[ITIMER_REAL];
+    let next = timer.next_expire(get_time_ns());
+    timer.expire_ns = next;
+    drop(process_inner);
+    if next != 0 {
+        add_timer_event(next, TimerEvent::ItimerReal(Arc::downgrade(process)));
+    }
+    send_signal(process, SignalFlags::SIGALRM);
+}
+
+/// 检查当前进程的 ITIMER_VIRTUAL 和 ITIMER_PROF。在时钟中断中调用，精度为一个时间片
+pub fn check_cpu_itimers() {
+    let Some(process) = current_task().and_then(|task| task.process.upgrade()) else {
+        return;
+    };
+    let times = process.cpu_times.load();
+    let mut signals = SignalFlags::empty();
+    let mut process_inner = process.inner_exclusive_access();
+    for (which, now, signal) in [
+        (ITIMER_VIRTUAL, times.user_ns, SignalFlags::SIGVTALRM),
+        (ITIMER_PROF, times.total_ns(), SignalFlags::SIGPROF),
+    ] {
+        let timer = &mut process_inner.itimers[which];
+        if timer.expire_ns != 0 && timer.expire_ns <= now {
+            timer.expire_ns = timer.next_expire(now);
+            signals |= signal;
+        }
+    }
+    drop(process_inner);
+    if !signals.is_empty() {
+        send_signal(&process, signals);
+    }
+}
@@ -1,71 +1,126 @@
 //!Implementation of [`TaskManager`]
-use alloc::{
-    collections::{BTreeMap, VecDeque},
-    sync::Arc,
-};
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
 
 use lazy_static::*;
 
-use super::TaskControlBlock;
-use crate::{sync::UPSafeCell, task::ProcessControlBlock};
+use super::{
+    sched::{default_scheduler, SchedEntity, Scheduler},
+    TaskControlBlock,
+};
+use crate::{
+    config::MAX_CPUS,
+    sync::{IrqSafeSpinLock, SpinLock},
+    task::{cpu_id, ProcessControlBlock},
+};
 
 ///A array of `TaskControlBlock` that is thread-safe
 pub struct TaskManager {
-    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    scheduler: Box<dyn Scheduler>,
 }
 
-/// A simple FIFO scheduler.
+/// 就绪线程的顺序由调度策略决定
 impl TaskManager {
     ///Creat an empty TaskManager
     pub fn new() -> Self {
         Self {
-            ready_queue: VecDeque::new(),
+            scheduler: default_scheduler(),
         }
     }
     ///Add a task to `TaskManager`
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
-        self.ready_queue.push_back(task);
+        self.scheduler.push(task);
     }
-    ///Remove the first task and return it,or `None` if `TaskManager` is empty
+    ///Remove the next task to run and return it,or `None` if `TaskManager` is empty
+    /// 还在其他 CPU 上保存上下文的线程留在队列中
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        self.ready_queue.pop_front()
+        self.scheduler.pop(&|task| !task.is_on_cpu())
+    }
+    pub fn len(&self) -> usize {
+        self.scheduler.len()
     }
     // 删除线程
     pub fn remove(&mut self, task: Arc<TaskControlBlock>) {
-        if let Some((id, _)) = self
-            .ready_queue
-            .iter()
-            .enumerate()
-            .find(|(_, t)| Arc::as_ptr(t) == Arc::as_ptr(&task))
-        {
-            self.ready_queue.remove(id); //通过检查两个线程是否是相同的
-        }
+        self.scheduler.remove(&task);
     }
 }
 
 lazy_static! {
-    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
-        unsafe { UPSafeCell::new(TaskManager::new()) };
-    pub static ref PID2PCB: UPSafeCell<BTreeMap<usize, Arc<ProcessControlBlock>>> =
-        unsafe { UPSafeCell::new(BTreeMap::new()) };
+    /// 每个 CPU 一个就绪队列，时钟中断中也会访问
+    pub static ref TASK_MANAGERS: Vec<IrqSafeSpinLock<TaskManager>> =
+        (0..MAX_CPUS).map(|_| IrqSafeSpinLock::new(TaskManager::new())).collect();
+    pub static ref PID2PCB: SpinLock<BTreeMap<usize, Arc<ProcessControlBlock>>> =
+        SpinLock::new(BTreeMap::new());
 }
+
+/// 读完 cpuid 之后可能被抢占到其他 CPU，这时用的是原来 CPU 的队列，不影响正确性
+fn local_manager() -> &'static IrqSafeSpinLock<TaskManager> {
+    &TASK_MANAGERS[cpu_id()]
+}
+
 ///Interface offered to add task
+/// 线程加入当前 CPU 的就绪队列
 pub fn add_task(task: Arc<TaskControlBlock>) {
-    TASK_MANAGER.exclusive_access().add(task);
+    local_manager().exclusive_access().add(task);
 }
 ///Interface offered to pop the first task
-pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
-    TASK_MANAGER.exclusive_access().fetch()
+/// 本地队列中没有可以运行的线程时，从就绪线程最多的 CPU 取一个过来
+pub fn fetch_task(cpu: usize) -> Option<Arc<TaskControlBlock>> {
+    if let Some(task) = TASK_MANAGERS[cpu].exclusive_access().fetch() {
+        return Some(task);
+    }
+    let busiest = (0..MAX_CPUS)
+        .filter(|&other| other != cpu)
+        .max_by_key(|&other| TASK_MANAGERS[other].exclusive_access().len())?;
+    TASK_MANAGERS[busiest].exclusive_access().fetch()
 }
 
+/// 线程可能在任何一个 CPU 的就绪队列中
 pub fn remove_task(task: Arc<TaskControlBlock>) {
-    TASK_MANAGER.exclusive_access().remove(task);
+    for manager in TASK_MANAGERS.iter() {
+        manager.exclusive_access().remove(Arc::clone(&task));
+    }
+}
+
+/// 线程离开 CPU 时记录它运行了多久
+pub fn charge_task(sched: &mut SchedEntity, ran: usize) {
+    local_manager()
+        .exclusive_access()
+        .scheduler
+        .charge(sched, ran);
+}
+
+/// 时钟中断时是否要切换到其他线程
+pub fn should_preempt(sched: &SchedEntity, ran: usize) -> bool {
+    local_manager()
+        .exclusive_access()
+        .scheduler
+        .preempt(sched, ran)
+}
+
+/// 线程的有效优先级改变
+pub fn set_task_prio(sched: &mut SchedEntity, prio: i32) {
+    local_manager()
+        .exclusive_access()
+        .scheduler
+        .set_prio(sched, prio);
 }
 
 pub fn pid2process(pid: usize) -> Option<Arc<ProcessControlBlock>> {
     let map = PID2PCB.exclusive_access();
     map.get(&pid).map(Arc::clone)
 }
+pub fn all_processes() -> Vec<Arc<ProcessControlBlock>> {
+    PID2PCB.exclusive_access().values().cloned().collect()
+}
+/// The live processes of process group `pgid`
+pub fn process_group(pgid: usize) -> Vec<Arc<ProcessControlBlock>> {
+    PID2PCB
+        .exclusive_access()
+        .values()
+        .filter(|process| process.inner_exclusive_access().pgid == pgid)
+        .cloned()
+        .collect()
+}
 pub fn remove_from_pid2process(pid: usize) {
     let mut map = PID2PCB.exclusive_access();
     if map.remove(&pid).is_none() {
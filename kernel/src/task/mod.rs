@@ -1,45 +1,130 @@
 mod context;
+mod cputime;
 mod id;
+mod itimer;
 mod manager;
 mod process;
 mod processor;
+mod sched;
 mod signal;
 mod switch;
 mod task;
+mod user_stack;
 
 use alloc::{sync::Arc, vec::Vec};
 use core::arch::asm;
 
 pub use context::TaskContext;
+pub use cputime::CpuTimes;
 pub use id::{pid_alloc, KernelStack, PidHandle, IDLE_PID};
+pub use itimer::{check_cpu_itimers, get_itimer, itimer_real_expired, set_itimer, ITIMER_COUNT};
 use lazy_static::*;
 use manager::fetch_task;
-pub use manager::{add_task, pid2process, remove_from_pid2process, remove_task};
-use process::ProcessControlBlock;
+pub use manager::{
+    add_task, all_processes, pid2process, process_group, remove_from_pid2process, remove_task,
+    set_task_prio,
+};
+pub use process::ProcessControlBlock;
+use process::ProcessControlBlockInner;
 pub use processor::{
-    current_process, current_task, current_trap_addr, current_trap_cx, current_user_token,
-    run_tasks, schedule, take_current_task,
+    account_system_time, account_user_time, cpu_id, current_process, current_task,
+    current_trap_addr, current_trap_cx, current_user_token, run_tasks, schedule,
+    should_preempt_current, take_current_task, RunningThreads,
+};
+pub use sched::{SchedEntity, NICE_MAX, NICE_MIN};
+pub use signal::{
+    DefaultAction, SignalAction, SignalActions, SignalFlags, SignalFrame, WaitReport, MAX_SIG,
+    SIGNAL_NAMES, SIG_DFL, SIG_IGN,
 };
-pub use signal::SignalFlags;
 use switch::__switch;
 pub use task::{TaskControlBlock, TaskStatus};
 
 use self::id::TaskUserRes;
 use crate::{
-    fs::{open_file, OpenFlags},
+    error::{SysError, SysResult},
+    fs::{open_file, release_record_locks, OpenFlags},
+    mm::UserPtr,
     println,
-    timer::remove_timer,
+    sync::futex_wake,
+    timer::{add_timer, remove_timer},
 };
 
+/// 当前线程准备阻塞：先标记为 Blocking，再加入等待队列或定时器，最后调用
+/// [`block_current_and_run_next`]。其他 CPU 或中断处理程序可能在这之间唤醒它，
+/// 这时线程已经回到了就绪队列，唤醒不会丢失
+pub fn prepare_block() {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.task_status = TaskStatus::Blocking;
+    task_inner.interruptible = false;
+}
+
+/// 和 [`prepare_block`] 相同，但要处理的信号到来时也会唤醒线程，醒来后用
+/// [`signal_interrupted`] 判断。已经有这样的信号时返回 EINTR，不阻塞
+pub fn prepare_interruptible_block() -> SysResult<()> {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    if task_inner.interrupted {
+        return Err(SysError::EINTR);
+    }
+    task_inner.task_status = TaskStatus::Blocking;
+    task_inner.interruptible = true;
+    Ok(())
+}
+
+/// 当前线程有信号要在返回用户态时处理，阻塞的系统调用应当返回 EINTR
+pub fn signal_interrupted() -> bool {
+    current_task().unwrap().inner_exclusive_access().interrupted
+}
+
+/// 等待的条件在阻塞之前已经满足。还没有被唤醒时直接继续运行，
+/// 已经被唤醒的线程在就绪队列中，让出一次 CPU
+pub fn cancel_block() {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    if task_inner.task_status == TaskStatus::Blocking {
+        task_inner.task_status = TaskStatus::Running;
+        return;
+    }
+    drop(task_inner);
+    drop(task);
+    block_current_and_run_next();
+}
+
+/// 必须先调用 [`prepare_block`]
 pub fn block_current_and_run_next() {
     let task = take_current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
     let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
-    task_inner.task_status = TaskStatus::Blocking;
     drop(task_inner);
     schedule(task_cx_ptr);
 }
 
+/// 和 [`block_current_and_run_next`] 相同，`expire_ns`（开机以来的纳秒数）不为空时到期也会被唤醒。
+/// 调用者根据自己是否还在等待队列中判断是不是超时了
+pub fn block_current_until(expire_ns: Option<usize>) {
+    let Some(expire_ns) = expire_ns else {
+        block_current_and_run_next();
+        return;
+    };
+    let task = current_task().unwrap();
+    add_timer(expire_ns, Arc::clone(&task));
+    block_current_and_run_next();
+    remove_timer(task);
+}
+
+/// Make a blocked task ready again. Tasks that have been woken already, are
+/// running or have exited are left alone.
+pub fn wakeup_task(task: Arc<TaskControlBlock>) {
+    let mut task_inner = task.inner_exclusive_access();
+    if task_inner.task_status != TaskStatus::Blocking || task_inner.res.is_none() {
+        return;
+    }
+    task_inner.task_status = TaskStatus::Ready;
+    drop(task_inner);
+    add_task(task);
+}
+
 pub fn suspend_current_and_run_next() {
     // There must be an application running.
     let task = take_current_task().unwrap();
@@ -58,7 +143,31 @@ pub fn suspend_current_and_run_next() {
     schedule(task_cx_ptr);
 }
 
+/// 结束当前线程，主线程退出时整个进程随之退出
 pub fn exit_current_and_run_next(exit_code: i32) {
+    exit_and_run_next(exit_code, false);
+}
+
+/// exit_group：不论哪个线程调用，整个进程都退出
+pub fn exit_group_and_run_next(exit_code: i32) {
+    exit_and_run_next(exit_code, true);
+}
+
+fn exit_and_run_next(exit_code: i32, group: bool) {
+    // CLONE_CHILD_CLEARTID：告诉等待者线程已经退出并唤醒在这个地址上的 futex，
+    // 地址无效时忽略
+    let clear_child_tid = current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .clear_child_tid;
+    if clear_child_tid != 0 {
+        let ctid = UserPtr::from(clear_child_tid as *mut u32);
+        if ctid.write(0).is_ok() {
+            if let Ok(key) = ctid.kernel_addr() {
+                futex_wake(key, 1);
+            }
+        }
+    }
     let task = take_current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
     let process = task.process.upgrade().unwrap();
@@ -69,10 +178,11 @@ pub fn exit_current_and_run_next(exit_code: i32) {
     // here we do not remove the thread since we are still using the kstack
     // it will be deallocated when sys_waittid is called
     drop(task_inner);
-    drop(task);
+    let mut process_inner = process.inner_exclusive_access();
+    process_inner.deadlock_detector.remove_thread(tid);
     // however, if this is the main thread of current process
-    // the process should terminate at once
-    if tid == 0 {
+    // the process should terminate at once. 另一个线程已经让进程退出时只结束当前线程
+    if (tid == 0 || group) && !process_inner.is_zombie {
         let pid = process.getpid();
         if pid == IDLE_PID {
             println!(
@@ -82,19 +192,38 @@ pub fn exit_current_and_run_next(exit_code: i32) {
             // 0号进程退出
             panic!("Idle process exit with exit_code {}", exit_code);
         }
-        remove_from_pid2process(pid); //移除对应关系，防止信号添加发生错误
-        let mut process_inner = process.inner_exclusive_access();
         // mark this process as a zombie process
         process_inner.is_zombie = true;
         // record exit code of main process
         process_inner.exit_code = exit_code;
+        // a zombie has nothing left to report but its exit
+        process_inner.wait_report = None;
+        process_inner.stopped_tasks.clear();
+        let parent = process_inner.parent.as_ref().and_then(|p| p.upgrade());
+        let children = core::mem::take(&mut process_inner.children);
+        // 其他线程可能正在别的 CPU 上运行，它们返回用户态之前发现进程已经退出，
+        // 离开 CPU 不再运行
+        let others: Vec<_> = process_inner
+            .tasks
+            .iter()
+            .flatten()
+            .filter(|t| !Arc::ptr_eq(t, &task))
+            .cloned()
+            .collect();
+        // 父进程在 waitpid 中先锁自己再锁子进程，这里不能反过来
+        drop(process_inner);
+        remove_from_pid2process(pid); //移除对应关系，防止信号添加发生错误
+                                      // tell the parent, after which it may reap us
+        if let Some(parent) = parent {
+            notify_parent(&parent);
+        }
 
         {
             // move all child processes under init process
             let mut initproc_inner = INITPROC.inner_exclusive_access();
-            for child in process_inner.children.iter() {
+            for child in children {
                 child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
-                initproc_inner.children.push(child.clone());
+                initproc_inner.children.push(child);
             }
         }
 
@@ -102,8 +231,7 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         // it has to be done before we dealloc the whole memory_set
         // otherwise they will be deallocated twice
         let mut recycle_res = Vec::<TaskUserRes>::new();
-        for task in process_inner.tasks.iter().filter(|t| t.is_some()) {
-            let task = task.as_ref().unwrap();
+        for task in others {
             // if other tasks are Ready in TaskManager or waiting for a timer to be
             // expired, we should remove them.
             //
@@ -111,22 +239,18 @@ pub fn exit_current_and_run_next(exit_code: i32) {
             // are limited in a single process. Therefore, the blocked tasks are
             // removed when the PCB is deallocated.
             remove_inactive_task(Arc::clone(&task));
-            let mut task_inner = task.inner_exclusive_access();
-            if let Some(res) = task_inner.res.take() {
-                recycle_res.push(res);
-            }
+            recycle_res.extend(take_res_off_cpu(&task));
         }
         // dealloc_tid and dealloc_user_res require access to PCB inner, so we
         // need to collect those user res first, then release process_inner
         // for now to avoid deadlock/double borrow problem.
-        drop(process_inner);
         recycle_res.clear();
 
         let mut process_inner = process.inner_exclusive_access();
-        process_inner.children.clear();
         // deallocate other data in user space i.e. program code/data section
         process_inner.memory_set.recycle_data_pages();
-        // drop file descriptors
+        // drop file descriptors, together with the locks held through them
+        release_record_locks(pid, None);
         process_inner.fd_table.clear();
         // remove all tasks
         // 这里会释放掉所有的线程
@@ -137,7 +261,10 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         unsafe {
             asm!("invtlb 0x4,{},$r0",in(reg) pid);
         }
+    } else {
+        drop(process_inner);
     }
+    drop(task);
     drop(process);
     // we do not have to save task context
     let mut _unused = TaskContext::zero_init();
@@ -156,10 +283,255 @@ pub fn add_initproc() {
     let _initproc = INITPROC.clone();
 }
 
-pub fn check_signals_of_current() -> Option<(i32, &'static str)> {
-    let process = current_process();
+/// How the current thread goes on after [`check_pending_signals`]
+enum SignalOutcome {
+    /// Return to user mode, maybe into a handler
+    Return,
+    /// The process exited or another thread replaced it with exec, leave the
+    /// CPU for good
+    Detach,
+    Stop(usize),
+    Terminate(usize),
+}
+
+/// Act on the pending signals of the current process that the current
+/// thread does not block, before it returns to user mode. Default actions
+/// may stop or end the process; for a caught signal, the user context is
+/// saved in a [`SignalFrame`] on the user stack and the thread is
+/// redirected to the handler, one signal at a time.
+pub fn handle_signals() {
+    loop {
+        match check_pending_signals() {
+            SignalOutcome::Return => return,
+            SignalOutcome::Detach => {
+                // 线程资源由让进程退出或者执行 exec 的线程回收
+                prepare_block();
+                block_current_and_run_next();
+            }
+            SignalOutcome::Stop(signum) => {
+                stop_current_process(signum);
+                // 被 SIGCONT 唤醒后再次检查信号
+            }
+            SignalOutcome::Terminate(signum) => {
+                let (name, description) = SIGNAL_NAMES[signum];
+                let core = SignalFlags::from_signum(signum).unwrap().default_action()
+                    == DefaultAction::Core;
+                println!(
+                    "[kernel] {}, {}={}{}",
+                    description,
+                    name,
+                    signum,
+                    if core { " (core dumped)" } else { "" }
+                );
+                exit_group_and_run_next(-(signum as i32));
+                return;
+            }
+        }
+    }
+}
+
+fn check_pending_signals() -> SignalOutcome {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let mut process_inner = process.inner_exclusive_access();
+    if !process_inner.owns_task(&task) {
+        return SignalOutcome::Detach;
+    }
+    let mut task_inner = task.inner_exclusive_access();
+    // 下面就处理这些信号
+    task_inner.interrupted = false;
+    if process_inner.stopped {
+        // another thread stopped the process while this one was in the kernel
+        return SignalOutcome::Stop(0);
+    }
+    loop {
+        let blocked = task_inner.signal_mask - SignalFlags::UNBLOCKABLE;
+        let Some(signum) = (process_inner.signals - blocked).first_signum() else {
+            return SignalOutcome::Return;
+        };
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        process_inner.signals.remove(signal);
+        let action = process_inner.signal_actions.table[signum];
+        match action.handler {
+            SIG_IGN => {}
+            // SIGCONT has done its job when it was sent
+            SIG_DFL => match signal.default_action() {
+                DefaultAction::Ignore | DefaultAction::Continue => {}
+                DefaultAction::Stop => return SignalOutcome::Stop(signum),
+                DefaultAction::Terminate | DefaultAction::Core => {
+                    return SignalOutcome::Terminate(signum)
+                }
+            },
+            handler => {
+                let trap_cx = task_inner.get_trap_cx();
+                let frame = SignalFrame {
+                    x: trap_cx.x,
+                    sepc: trap_cx.sepc,
+                    mask: task_inner.signal_mask,
+                };
+                let frame_size = core::mem::size_of::<SignalFrame>();
+                let frame_addr = trap_cx.x[3].wrapping_sub(frame_size) & !0xf;
+                drop(process_inner);
+                if UserPtr::from(frame_addr as *mut SignalFrame)
+                    .write(frame)
+                    .is_err()
+                {
+                    // no room for the frame
+                    return SignalOutcome::Terminate(11);
+                }
+                task_inner.signal_mask |= action.mask | signal;
+                trap_cx.sepc = handler;
+                trap_cx.x[1] = action.restorer;
+                trap_cx.x[3] = frame_addr;
+                trap_cx.x[4] = signum;
+                return SignalOutcome::Return;
+            }
+        }
+    }
+}
+
+/// Stop the current process because of signal `signum`, or just park the
+/// current thread if the process is already stopped (`signum` is 0 then).
+/// The other ready threads are taken off the ready queue at once, threads
+/// blocked in the kernel park themselves on their way back to user mode.
+fn stop_current_process(signum: usize) {
+    let task = take_current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let mut process_inner = process.inner_exclusive_access();
+    let mut parent = None;
+    if !process_inner.stopped {
+        process_inner.stopped = true;
+        process_inner.wait_report = Some(WaitReport::Stopped(signum));
+        let ready: Vec<_> = process_inner
+            .tasks
+            .iter()
+            .flatten()
+            .filter(|t| t.inner_exclusive_access().task_status == TaskStatus::Ready)
+            .cloned()
+            .collect();
+        for t in ready {
+            remove_task(Arc::clone(&t));
+            t.inner_exclusive_access().task_status = TaskStatus::Stopped;
+            process_inner.stopped_tasks.push(t);
+        }
+        parent = process_inner.parent.as_ref().and_then(|p| p.upgrade());
+    }
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.task_status = TaskStatus::Stopped;
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    drop(task_inner);
+    process_inner.stopped_tasks.push(task);
+    drop(process_inner);
+    drop(process);
+    if let Some(parent) = parent {
+        notify_parent(&parent);
+    }
+    schedule(task_cx_ptr);
+}
+
+/// Put the threads of a stopped process back on the ready queue
+fn continue_process(process_inner: &mut ProcessControlBlockInner) {
+    process_inner.stopped = false;
+    for task in process_inner.stopped_tasks.drain(..) {
+        task.inner_exclusive_access().task_status = TaskStatus::Ready;
+        add_task(task);
+    }
+}
+
+/// Send `signal` to `process`. SIGCONT resumes a stopped process right away
+/// and SIGKILL too so that it can die, since a stopped process never gets
+/// to look at its signals.
+pub fn send_signal(process: &Arc<ProcessControlBlock>, signal: SignalFlags) {
+    let stop_signals =
+        SignalFlags::SIGSTOP | SignalFlags::SIGTSTP | SignalFlags::SIGTTIN | SignalFlags::SIGTTOU;
+    let mut process_inner = process.inner_exclusive_access();
+    if process_inner.is_zombie {
+        return;
+    }
+    let mut parent = None;
+    if signal.intersects(SignalFlags::SIGCONT | SignalFlags::SIGKILL) {
+        // a pending stop is cancelled by SIGCONT
+        process_inner.signals -= stop_signals;
+        if process_inner.stopped {
+            continue_process(&mut process_inner);
+            if signal == SignalFlags::SIGCONT {
+                process_inner.wait_report = Some(WaitReport::Continued);
+                parent = process_inner.parent.as_ref().and_then(|p| p.upgrade());
+            }
+        }
+    } else if signal.intersects(stop_signals) {
+        process_inner.signals -= SignalFlags::SIGCONT;
+    }
+    process_inner.signals |= signal;
+    // 可中断地阻塞在内核中的线程先醒来，返回用户态处理信号
+    let mut interrupted = Vec::new();
+    for task in process_inner.tasks.iter().flatten() {
+        let mut task_inner = task.inner_exclusive_access();
+        let blocked = task_inner.signal_mask - SignalFlags::UNBLOCKABLE;
+        if !interrupts(&process_inner, signal - blocked) {
+            continue;
+        }
+        task_inner.interrupted = true;
+        if task_inner.task_status == TaskStatus::Blocking && task_inner.interruptible {
+            interrupted.push(Arc::clone(task));
+        }
+    }
+    drop(process_inner);
+    for task in interrupted {
+        wakeup_task(task);
+    }
+    if let Some(parent) = parent {
+        notify_parent(&parent);
+    }
+}
+
+/// Whether any of `signals` is caught by the process or kills it. Ignored
+/// signals and stops do not interrupt blocking system calls.
+fn interrupts(process_inner: &ProcessControlBlockInner, signals: SignalFlags) -> bool {
+    (1..=MAX_SIG).any(|signum| {
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        signals.contains(signal)
+            && match process_inner.signal_actions.table[signum].handler {
+                SIG_IGN => false,
+                SIG_DFL => matches!(
+                    signal.default_action(),
+                    DefaultAction::Terminate | DefaultAction::Core
+                ),
+                _ => true,
+            }
+    })
+}
+
+/// Tell `parent` that one of its children exited, stopped or continued
+fn notify_parent(parent: &Arc<ProcessControlBlock>) {
+    send_signal(parent, SignalFlags::SIGCHLD);
+    // send_signal 取过父进程的锁，等待者要么已经看到了变化，要么已经在队列中
+    parent.wait_children.wake_all();
+}
+
+/// An exited thread of `process` left the CPU. Wake the parent again if the
+/// process is a zombie, it can only be reaped from now on.
+fn exited_task_left_cpu(process: &ProcessControlBlock) {
     let process_inner = process.inner_exclusive_access();
-    process_inner.signals.check_error()
+    if !process_inner.is_zombie {
+        return;
+    }
+    let parent = process_inner.parent.as_ref().and_then(|p| p.upgrade());
+    drop(process_inner);
+    if let Some(parent) = parent {
+        // 等待者持有自己的锁检查子进程并进入队列，这里取一次锁再唤醒
+        drop(parent.inner_exclusive_access());
+        parent.wait_children.wake_all();
+    }
+}
+
+/// Send `signal` to every process of group `pgid`, false if there is none
+pub fn send_signal_to_group(pgid: usize, signal: SignalFlags) -> bool {
+    let group = process_group(pgid);
+    for process in group.iter() {
+        send_signal(process, signal);
+    }
+    !group.is_empty()
 }
 
 pub fn current_add_signal(signal: SignalFlags) {
@@ -168,6 +540,21 @@ pub fn current_add_signal(signal: SignalFlags) {
     process_inner.signals |= signal;
 }
 
+/// Take the user resources of another thread of the current process once it
+/// has left the CPU. It must have been dropped from the process already, so
+/// that it leaves at its next return to user mode at the latest.
+fn take_res_off_cpu(task: &TaskControlBlock) -> Option<TaskUserRes> {
+    loop {
+        // 先上 CPU 再检查线程资源的 run_tasks 要么被这里看到，要么看到资源已经被取走
+        let mut task_inner = task.inner_exclusive_access();
+        if !task.is_on_cpu() {
+            return task_inner.res.take();
+        }
+        drop(task_inner);
+        core::hint::spin_loop();
+    }
+}
+
 pub fn remove_inactive_task(task: Arc<TaskControlBlock>) {
     remove_task(Arc::clone(&task));
     remove_timer(Arc::clone(&task));
@@ -4,20 +4,33 @@ use alloc::{
     vec,
     vec::Vec,
 };
-use core::{arch::asm, cell::RefMut};
+use core::arch::asm;
 
 use log::info;
 use loongarch64::register::pgdl;
 
 use super::{
-    add_task, id::RecycleAllocator, manager::insert_into_pid2process, pid_alloc, PidHandle,
-    SignalFlags, TaskControlBlock,
+    add_task,
+    cputime::{CpuTimes, ProcessCpuTimes},
+    current_task,
+    id::{RecycleAllocator, TaskUserRes},
+    itimer::{ITimer, ITIMER_COUNT},
+    manager::insert_into_pid2process,
+    pid_alloc, remove_inactive_task,
+    sched::SchedEntity,
+    take_res_off_cpu,
+    user_stack::init_user_stack,
+    PidHandle, RunningThreads, SignalActions, SignalFlags, TaskControlBlock, WaitReport,
 };
 use crate::{
     config::PAGE_SIZE_BITS,
-    fs::{File, Stdin, Stdout},
-    mm::{translated_refmut, MemorySet},
-    sync::{Condvar, Mutex, Semaphore, UPSafeCell},
+    error::{SysError, SysResult},
+    fs::{open_file, File, OpenFlags, Stdin, Stdout},
+    mm::{ElfInfo, MapPermission, MemorySet, VirtAddr},
+    sync::{
+        update_priority, Barrier, Condvar, DeadlockDetector, Mutex, RwLock, Semaphore, SpinLock,
+        SpinLockGuard, WaitQueue,
+    },
     trap::TrapContext,
 };
 
@@ -25,23 +38,43 @@ use crate::{
 pub struct ProcessControlBlock {
     // immutable
     pub pid: PidHandle,
+    /// 正在 CPU 上运行的线程数
+    pub running: Arc<RunningThreads>,
+    /// 所有线程的运行时间
+    pub cpu_times: ProcessCpuTimes,
+    /// 在 wait4 中等待子进程状态变化的线程
+    pub wait_children: WaitQueue,
     // mutable
-    inner: UPSafeCell<ProcessControlBlockInner>,
+    inner: SpinLock<ProcessControlBlockInner>,
 }
 
 pub struct ProcessControlBlockInner {
     pub is_zombie: bool,
     pub memory_set: MemorySet,                     //地址空间
+    pub heap_bottom: usize,                        //堆的起点
+    pub brk: usize,                                //堆的当前终点
+    pub stack_permission: MapPermission,           //用户栈的权限
     pub parent: Option<Weak<ProcessControlBlock>>, // 父进程
     pub children: Vec<Arc<ProcessControlBlock>>,   //子进程
     pub exit_code: i32,
     pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>, //文件描述符表
-    pub signals: SignalFlags,                               //信号
+    pub signals: SignalFlags,                               //待处理的信号
+    pub signal_actions: SignalActions,                      //信号处理表
+    pub stopped: bool,                                      //是否被暂停
+    pub stopped_tasks: Vec<Arc<TaskControlBlock>>,          //暂停期间移出调度队列的线程
+    pub wait_report: Option<WaitReport>,                    //尚未被父进程取走的状态变化
+    pub pgid: usize,                                        //进程组
+    pub sid: usize,                                         //会话
     pub tasks: Vec<Option<Arc<TaskControlBlock>>>,          //线程控制块
     pub task_res_allocator: RecycleAllocator,               //资源分配器
     pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,            //互斥锁列表
     pub semaphore_list: Vec<Option<Arc<Semaphore>>>,        //信号量列表
     pub condvar_list: Vec<Option<Arc<Condvar>>>,            //条件变量列表
+    pub rwlock_list: Vec<Option<Arc<RwLock>>>,              //读写锁列表
+    pub barrier_list: Vec<Option<Arc<Barrier>>>,            //屏障列表
+    pub deadlock_detector: DeadlockDetector,                //死锁检测
+    pub itimers: [ITimer; ITIMER_COUNT],                    //间隔定时器
+    pub children_times: CpuTimes,                           //已回收的子进程的运行时间
 }
 
 impl ProcessControlBlockInner {
@@ -69,43 +102,75 @@ impl ProcessControlBlockInner {
     pub fn get_task(&self, tid: usize) -> Arc<TaskControlBlock> {
         self.tasks[tid].as_ref().unwrap().clone()
     }
+
+    /// `task` 仍然属于这个进程：进程没有退出，也没有其他线程执行 exec 把它丢弃
+    pub fn owns_task(&self, task: &Arc<TaskControlBlock>) -> bool {
+        !self.is_zombie && self.tasks.iter().flatten().any(|t| Arc::ptr_eq(t, task))
+    }
+}
+
+/// 加载程序，程序有 PT_INTERP 时把动态链接器也加载进来
+fn load_program(elf_data: &[u8]) -> SysResult<(MemorySet, usize, ElfInfo)> {
+    let (mut memory_set, ustack_base, mut elf_info) = MemorySet::from_elf(elf_data)?;
+    if let Some(interp) = elf_info.interp.as_deref() {
+        let interp = open_file(interp, OpenFlags::RDONLY).ok_or(SysError::ENOENT)?;
+        memory_set.load_interp(&interp.read_all(), &mut elf_info)?;
+    }
+    Ok((memory_set, ustack_base, elf_info))
 }
 
 impl ProcessControlBlock {
-    pub fn inner_exclusive_access(&self) -> RefMut<'_, ProcessControlBlockInner> {
+    pub fn inner_exclusive_access(&self) -> SpinLockGuard<'_, ProcessControlBlockInner> {
         self.inner.exclusive_access()
     }
 
     pub fn new(elf_data: &[u8]) -> Arc<Self> {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, ustack_base, elf_info) = load_program(elf_data).unwrap();
         // allocate a pid
         let pid_handle = pid_alloc();
+        // 第一个进程自成一个进程组和会话
+        let pid = pid_handle.0;
         let process = Arc::new(Self {
             pid: pid_handle,
-            inner: unsafe {
-                UPSafeCell::new(ProcessControlBlockInner {
-                    is_zombie: false,
-                    memory_set,
-                    parent: None,
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: vec![
-                        // 0 -> stdin
-                        Some(Arc::new(Stdin)),
-                        // 1 -> stdout
-                        Some(Arc::new(Stdout)),
-                        // 2 -> stderr
-                        Some(Arc::new(Stdout)),
-                    ],
-                    signals: SignalFlags::empty(),
-                    tasks: Vec::new(),
-                    task_res_allocator: RecycleAllocator::new(),
-                    mutex_list: Vec::new(),
-                    semaphore_list: Vec::new(),
-                    condvar_list: Vec::new(),
-                })
-            },
+            running: Arc::new(RunningThreads::new()),
+            cpu_times: ProcessCpuTimes::default(),
+            wait_children: WaitQueue::new(),
+            inner: SpinLock::new(ProcessControlBlockInner {
+                is_zombie: false,
+                memory_set,
+                heap_bottom: elf_info.brk,
+                brk: elf_info.brk,
+                stack_permission: elf_info.stack_permission,
+                parent: None,
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table: vec![
+                    // 0 -> stdin
+                    Some(Arc::new(Stdin::new())),
+                    // 1 -> stdout
+                    Some(Arc::new(Stdout)),
+                    // 2 -> stderr
+                    Some(Arc::new(Stdout)),
+                ],
+                signals: SignalFlags::empty(),
+                signal_actions: SignalActions::default(),
+                stopped: false,
+                stopped_tasks: Vec::new(),
+                wait_report: None,
+                pgid: pid,
+                sid: pid,
+                tasks: Vec::new(),
+                task_res_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                rwlock_list: Vec::new(),
+                barrier_list: Vec::new(),
+                deadlock_detector: DeadlockDetector::default(),
+                itimers: Default::default(),
+                children_times: CpuTimes::default(),
+            }),
         });
         // create a main thread, we should allocate ustack and trap_cx here
         let task = Arc::new(TaskControlBlock::new(
@@ -118,8 +183,20 @@ impl ProcessControlBlock {
         let trap_cx = task_inner.get_trap_cx();
         let ustack_top = task_inner.res.as_ref().unwrap().ustack_top();
         drop(task_inner);
+        // 地址空间还没有切换过去，直接写入新进程的用户栈
+        let stack = init_user_stack(
+            &process.inner_exclusive_access().memory_set,
+            ustack_top,
+            &[],
+            &[],
+            &elf_info,
+        );
         // waring:在内核栈上压入trap上下文，与rcore实现不同
-        *trap_cx = TrapContext::app_init_context(entry_point, ustack_top);
+        *trap_cx = TrapContext::app_init_context(elf_info.start, stack.sp);
+        trap_cx.x[2] = elf_info.tp;
+        trap_cx.x[4] = 0;
+        trap_cx.x[5] = stack.argv;
+        trap_cx.x[6] = stack.envp;
         // add main thread to the process
         let mut process_inner = process.inner_exclusive_access();
         process_inner.tasks.push(Some(Arc::clone(&task)));
@@ -130,51 +207,82 @@ impl ProcessControlBlock {
         process
     }
 
-    /// Only support processes with a single thread.
-    pub fn exec(self: &Arc<Self>, elf_data: &[u8], args: Vec<String>) {
-        assert_eq!(self.inner_exclusive_access().thread_count(), 1);
+    /// 可以由任何线程调用：其他线程被直接结束，调用者成为新程序的主线程。
+    /// 程序不能加载时返回错误，此时进程保持原样
+    pub fn exec(
+        self: &Arc<Self>,
+        elf_data: &[u8],
+        args: Vec<String>,
+        envs: Vec<String>,
+    ) -> SysResult<()> {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, ustack_base, elf_info) = load_program(elf_data)?;
+        let task = current_task().unwrap();
+        // 把同一进程的其他线程从就绪队列和定时器中摘下，线程资源连同调用者自己的
+        // 一起在旧的地址空间中回收。阻塞在同步原语上的线程随这些原语一起释放，
+        // 阻塞在其他等待队列中的线程没有了线程资源，不会再被唤醒
+        let mut inner = self.inner_exclusive_access();
+        if !inner.owns_task(&task) {
+            // 另一个线程先执行了 exec 或者让进程退出了，调用者返回用户态之前会离开 CPU
+            return Err(SysError::EAGAIN);
+        }
+        let others: Vec<_> = inner
+            .tasks
+            .drain(..)
+            .flatten()
+            .filter(|t| !Arc::ptr_eq(t, &task))
+            .collect();
+        inner.stopped_tasks.clear();
+        inner.mutex_list.clear();
+        inner.semaphore_list.clear();
+        inner.condvar_list.clear();
+        inner.rwlock_list.clear();
+        inner.barrier_list.clear();
+        inner.deadlock_detector = DeadlockDetector::default();
+        drop(inner);
+        let mut recycle_res = Vec::new();
+        for t in others {
+            remove_inactive_task(Arc::clone(&t));
+            recycle_res.extend(take_res_off_cpu(&t));
+        }
+        recycle_res.extend(task.inner_exclusive_access().res.take());
+        // TaskUserRes 释放时需要访问进程的 inner
+        recycle_res.clear();
+
         let new_token = memory_set.token();
         // substitute memory_set
-        self.inner_exclusive_access().memory_set = memory_set;
-        // then we alloc user resource for main thread again
-        // since memory_set has been changed
-        let task = self.inner_exclusive_access().get_task(0); //得到主进程的内容
+        let mut inner = self.inner_exclusive_access();
+        inner.memory_set = memory_set;
+        inner.heap_bottom = elf_info.brk;
+        inner.brk = elf_info.brk;
+        inner.stack_permission = elf_info.stack_permission;
+        inner.signal_actions.reset_handlers();
+        inner.task_res_allocator = RecycleAllocator::new();
+        inner.tasks.push(Some(Arc::clone(&task)));
+        drop(inner);
+        // the caller gets tid 0 and a user stack in the new memory_set
+        let res = TaskUserRes::new(Arc::clone(self), ustack_base, true);
         let mut task_inner = task.inner_exclusive_access();
-        task_inner.res.as_mut().unwrap().ustack_base = ustack_base;
-        task_inner.res.as_mut().unwrap().alloc_user_res(); //重新分配资源
+        task_inner.res = Some(res);
+        // 原来的锁和等待它们的线程都已经丢弃
+        task_inner.held_mutexes.clear();
 
-        // push arguments on user stack
-        let mut user_sp = task_inner.res.as_mut().unwrap().ustack_top();
-        user_sp -= (args.len() + 1) * core::mem::size_of::<usize>();
-        let argv_base = user_sp;
-        let mut argv: Vec<_> = (0..=args.len())
-            .map(|arg| {
-                translated_refmut(
-                    new_token,
-                    (argv_base + arg * core::mem::size_of::<usize>()) as *mut usize,
-                )
-            })
-            .collect();
-        *argv[args.len()] = 0;
-        for i in 0..args.len() {
-            user_sp -= args[i].len() + 1;
-            *argv[i] = user_sp;
-            let mut p = user_sp;
-            for c in args[i].as_bytes() {
-                *translated_refmut(new_token, p as *mut u8) = *c;
-                p += 1;
-            }
-            *translated_refmut(new_token, p as *mut u8) = 0;
-        }
-        // make the user_sp aligned to 8B for k210 platform
-        user_sp -= user_sp % core::mem::size_of::<usize>();
+        // push arguments, environment and auxiliary vector on user stack
+        let ustack_top = task_inner.res.as_ref().unwrap().ustack_top();
+        let stack = init_user_stack(
+            &self.inner_exclusive_access().memory_set,
+            ustack_top,
+            &args,
+            &envs,
+            &elf_info,
+        );
 
         // initialize trap_cx
-        let mut trap_cx = TrapContext::app_init_context(entry_point, user_sp);
+        let mut trap_cx = TrapContext::app_init_context(elf_info.start, stack.sp);
+        trap_cx.x[2] = elf_info.tp;
         trap_cx.x[4] = args.len();
-        trap_cx.x[5] = argv_base;
+        trap_cx.x[5] = stack.argv;
+        trap_cx.x[6] = stack.envp;
         *task_inner.get_trap_cx() = trap_cx;
 
         //由于切换了地址空间，因此之前的ASID对应的地址空间将不会再有用，
@@ -187,15 +295,29 @@ impl ProcessControlBlock {
         let pgd = new_token << PAGE_SIZE_BITS;
         // Pgdl::read().set_val(pgd).write(); //设置新的页基址
         pgdl::set_base(pgd); //设置新的页基址
+        drop(task_inner);
+        update_priority(task);
+        Ok(())
     }
 
-    /// Only support processes with a single thread.
+    /// 子进程只有一个线程，是调用者的副本，tid 为 0
     pub fn fork(self: &Arc<Self>) -> Arc<Self> {
+        let current = current_task().unwrap();
+        let current_inner = current.inner_exclusive_access();
         let mut parent = self.inner_exclusive_access();
-        assert_eq!(parent.thread_count(), 1);
         // clone parent's memory_set completely including trampoline/ustacks/trap_cxs
         // let memory_set = parent.memory_set.clone();
-        let memory_set = MemorySet::from_existed_user(&parent.memory_set);
+        let mut memory_set = MemorySet::from_existed_user(&parent.memory_set);
+        // 其他线程的用户栈不属于子进程
+        for task in parent.tasks.iter().flatten() {
+            if Arc::ptr_eq(task, &current) {
+                continue;
+            }
+            let task_inner = task.inner_exclusive_access();
+            if let Some(ustack_bottom) = task_inner.res.as_ref().and_then(|res| res.ustack_bottom) {
+                memory_set.remove_area_with_start_vpn(VirtAddr::from(ustack_bottom).into());
+            }
+        }
         // alloc a pid
         let pid = pid_alloc();
         // copy fd table
@@ -210,35 +332,46 @@ impl ProcessControlBlock {
         // create child process pcb
         let child = Arc::new(Self {
             pid,
-            inner: unsafe {
-                UPSafeCell::new(ProcessControlBlockInner {
-                    is_zombie: false,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: new_fd_table,
-                    signals: SignalFlags::empty(),
-                    tasks: Vec::new(),
-                    task_res_allocator: RecycleAllocator::new(),
-                    mutex_list: Vec::new(),
-                    semaphore_list: Vec::new(),
-                    condvar_list: Vec::new(),
-                })
-            },
+            running: Arc::new(RunningThreads::new()),
+            cpu_times: ProcessCpuTimes::default(),
+            wait_children: WaitQueue::new(),
+            inner: SpinLock::new(ProcessControlBlockInner {
+                is_zombie: false,
+                memory_set,
+                heap_bottom: parent.heap_bottom,
+                brk: parent.brk,
+                stack_permission: parent.stack_permission,
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table: new_fd_table,
+                signals: SignalFlags::empty(),
+                signal_actions: parent.signal_actions.clone(),
+                stopped: false,
+                stopped_tasks: Vec::new(),
+                wait_report: None,
+                pgid: parent.pgid,
+                sid: parent.sid,
+                tasks: Vec::new(),
+                task_res_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                rwlock_list: Vec::new(),
+                barrier_list: Vec::new(),
+                deadlock_detector: DeadlockDetector::default(),
+                itimers: Default::default(),
+                children_times: CpuTimes::default(),
+            }),
         });
         // add child
         parent.children.push(Arc::clone(&child));
+        drop(parent);
         // create main thread of child process
+        let current_res = current_inner.res.as_ref().unwrap();
         let task = Arc::new(TaskControlBlock::new(
             Arc::clone(&child),
-            parent
-                .get_task(0)
-                .inner_exclusive_access()
-                .res
-                .as_ref()
-                .unwrap()
-                .ustack_base(),
+            current_res.ustack_base(),
             // here we do not allocate trap_cx or ustack again
             // but mention that we allocate a new kstack here
             false,
@@ -247,14 +380,17 @@ impl ProcessControlBlock {
         let mut child_inner = child.inner_exclusive_access();
         child_inner.tasks.push(Some(Arc::clone(&task)));
         drop(child_inner);
-        // modify kstack_top in trap_cx of this thread
         let mut task_inner = task.inner_exclusive_access();
-        // 修改trap_cx的内容，使其保持与父进程相同
-        // 这需要拷贝父进程的主线程的内核栈到子进程的内核栈中
-        task_inner
-            .kstack
-            .copy_from_other(&parent.get_task(0).inner_exclusive_access().kstack);
+        // 调用者的用户栈已经复制到了子进程中
+        task_inner.res.as_mut().unwrap().ustack_bottom = current_res.ustack_bottom;
+        // the child starts with the signal mask of the parent
+        task_inner.signal_mask = current_inner.signal_mask;
+        task_inner.sched = SchedEntity::inherit(&current_inner.sched);
+        // 修改trap_cx的内容，使其保持与调用者相同
+        // 这需要拷贝调用者的内核栈到子进程的内核栈中
+        task_inner.kstack.copy_from_other(&current_inner.kstack);
         drop(task_inner);
+        drop(current_inner);
 
         insert_into_pid2process(child.getpid(), Arc::clone(&child));
         // add this thread to scheduler
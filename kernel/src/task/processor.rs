@@ -1,14 +1,58 @@
 //!Implementation of [`Processor`] and Intersection of control flow
-use alloc::sync::Arc;
-use core::arch::asm;
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use lazy_static::*;
-use loongarch64::register::{asid, pgdl};
+use loongarch64::{
+    register::{asid, cpuid, crmd, pgdl},
+    time::Time,
+};
 
-use super::{TaskContext, TaskControlBlock, TaskStatus, __switch, fetch_task};
+use super::{
+    add_task,
+    cputime::charge_cpu_time,
+    exited_task_left_cpu, fetch_task,
+    manager::{charge_task, should_preempt},
+    TaskContext, TaskControlBlock, TaskStatus, __switch,
+};
 use crate::{
-    config::PAGE_SIZE_BITS, sync::UPSafeCell, task::process::ProcessControlBlock, trap::TrapContext,
+    config::{IRQ_CPU, MAX_CPUS, PAGE_SIZE_BITS},
+    fs::poll_console,
+    sync::{pop_off, push_off, IrqSafeSpinLock, IrqSafeSpinLockGuard},
+    task::process::ProcessControlBlock,
+    timer::{get_time_ns, program_next_timer},
+    trap::{set_kernel_trap_entry, TrapContext},
 };
+
+/// 进程中正在 CPU 上运行的线程数，由进程和它的线程共享。
+///
+/// 线程切换走、上下文保存好之后才减少，计数为 0 的僵尸进程才能被回收
+pub struct RunningThreads(AtomicUsize);
+
+impl RunningThreads {
+    pub fn new() -> Self {
+        Self(AtomicUsize::new(0))
+    }
+    /// 没有线程在任何 CPU 上运行
+    pub fn is_zero(&self) -> bool {
+        self.0.load(Ordering::Acquire) == 0
+    }
+    pub fn enter(&self) {
+        self.0.fetch_add(1, Ordering::Acquire);
+    }
+    pub fn leave(&self) {
+        self.0.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// 当前 CPU 的编号
+pub fn cpu_id() -> usize {
+    cpuid::read().core_id()
+}
+
 /// Processor management structure
 pub struct Processor {
     /// The task currently executing on the current processor
@@ -16,6 +60,10 @@ pub struct Processor {
     /// The basic control flow of each core, helping to select and switch
     /// process
     idle_task_cx: TaskContext,
+    /// 当前线程这一次开始运行的时间
+    run_start: usize,
+    /// 当前线程的运行时间已经记到了这个时刻（开机以来的纳秒数）
+    accounted_ns: usize,
 }
 
 impl Processor {
@@ -24,6 +72,8 @@ impl Processor {
         Self {
             current: None,
             idle_task_cx: TaskContext::zero_init(),
+            run_start: 0,
+            accounted_ns: 0,
         }
     }
     ///Get mutable reference to `idle_task_cx`
@@ -41,50 +91,153 @@ impl Processor {
 }
 
 lazy_static! {
-    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+    /// 每个 CPU 一个，只由所属的 CPU 访问
+    static ref PROCESSORS: Vec<IrqSafeSpinLock<Processor>> = (0..MAX_CPUS)
+        .map(|_| IrqSafeSpinLock::new(Processor::new()))
+        .collect();
 }
+
+/// 当前 CPU 的 Processor。先关中断再读 cpuid，访问期间不会被抢占到其他 CPU 上
+fn processor() -> IrqSafeSpinLockGuard<'static, Processor> {
+    push_off();
+    let processor = PROCESSORS[cpu_id()].exclusive_access();
+    pop_off();
+    processor
+}
+
 /// The main part of process execution and scheduling
 /// Loop `fetch_task` to get the process that needs to run, and switch the
 /// process through `__switch`
 pub fn run_tasks() {
+    // idle 控制流关中断运行，只在没有线程可以运行时短暂地打开
+    crmd::set_ie(false);
+    let cpu = cpu_id();
+    let mut idle = false;
     loop {
-        let mut processor = PROCESSOR.exclusive_access();
-        if let Some(task) = fetch_task() {
-            let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
-            // access coming task TCB exclusively
-            let pid = task.process.upgrade().unwrap().getpid(); //应用进程号
-            let pgd = task.get_user_token() << PAGE_SIZE_BITS;
-            pgdl::set_base(pgd); //设置根页表基地址
-            asid::set_asid(pid); //设置ASID
-            let mut task_inner = task.inner_exclusive_access();
-            let next_task_cx_ptr = &task_inner.task_cx as *const TaskContext;
-            task_inner.task_status = TaskStatus::Running;
-            // 在进行线程切换的时候
-            // 地址空间是相同的，并且pgd也是相同的
-            // 每个线程都有自己的内核栈和用户栈，用户栈互相隔离
-            // 在进入用户态后应该每个线程的地址转换是相同的
-            unsafe {
-                asm!("invtlb 0x4,{},$r0",in(reg) pid);
+        let Some(task) = fetch_task(cpu) else {
+            if !idle {
+                // 没有线程运行时只在定时器到期时产生时钟中断
+                idle = true;
+                program_next_timer();
             }
-            drop(task_inner);
-            // release coming task TCB manually
-            processor.current = Some(task);
-            // release processor manually
-            drop(processor);
-            unsafe {
-                __switch(idle_task_cx_ptr, next_task_cx_ptr);
+            // 空闲时不再有时间片的时钟中断，由这里检查控制台输入
+            if cpu == IRQ_CPU {
+                poll_console();
             }
+            // 让时钟中断有机会唤醒睡眠的线程
+            crmd::set_ie(true);
+            crmd::set_ie(false);
+            continue;
+        };
+        idle = false;
+        if !task.claim_cpu() {
+            // 线程刚被唤醒，还在其他 CPU 上保存上下文
+            add_task(task);
+            continue;
+        }
+        if task.inner_exclusive_access().res.is_none() {
+            // 取出之后线程退出了，或者进程退出了、执行了 exec，这个线程不会再运行
+            leave_cpu(&task);
+            continue;
+        }
+        let mut processor = processor();
+        let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
+        // access coming task TCB exclusively
+        let pid = task.process.upgrade().unwrap().getpid(); //应用进程号
+        let pgd = task.get_user_token() << PAGE_SIZE_BITS;
+        pgdl::set_base(pgd); //设置根页表基地址
+        asid::set_asid(pid); //设置ASID
+        let mut task_inner = task.inner_exclusive_access();
+        let next_task_cx_ptr = &task_inner.task_cx as *const TaskContext;
+        task_inner.task_status = TaskStatus::Running;
+        // 在进行线程切换的时候
+        // 地址空间是相同的，并且pgd也是相同的
+        // 每个线程都有自己的内核栈和用户栈，用户栈互相隔离
+        // 在进入用户态后应该每个线程的地址转换是相同的
+        unsafe {
+            asm!("invtlb 0x4,{},$r0",in(reg) pid);
+        }
+        drop(task_inner);
+        // release coming task TCB manually
+        processor.current = Some(Arc::clone(&task));
+        processor.run_start = Time::read();
+        processor.accounted_ns = get_time_ns();
+        // release processor manually
+        drop(processor);
+        // 时间片从现在开始
+        program_next_timer();
+        unsafe {
+            __switch(idle_task_cx_ptr, next_task_cx_ptr);
+        }
+        // 退出的线程还在用自己的内核栈，到这里才能释放
+        leave_cpu(&task);
+        drop(task);
+    }
+}
+
+/// 线程的上下文已经保存好，其他 CPU 可以运行它了。退出的线程离开 CPU 后
+/// 它的进程才可能被回收
+fn leave_cpu(task: &TaskControlBlock) {
+    task.release_cpu();
+    if task.inner_exclusive_access().res.is_none() {
+        if let Some(process) = task.process.upgrade() {
+            exited_task_left_cpu(&process);
         }
     }
 }
 
 ///Take the current task,leaving a None in its place
+/// 线程离开 CPU 都要经过这里，它这次运行的时间记到调度状态中
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().take_current()
+    let mut processor = processor();
+    let task = processor.take_current()?;
+    let ran = Time::read() - processor.run_start;
+    // 线程总是在内核中切换走
+    let system_ns = get_time_ns() - processor.accounted_ns;
+    drop(processor);
+    charge_task(&mut task.inner_exclusive_access().sched, ran);
+    charge_cpu_time(&task, false, system_ns);
+    Some(task)
+}
+
+/// 当前线程从上次记账到现在的时间记为用户态（`user`）或内核态的运行时间
+fn account_current(user: bool) {
+    let mut processor = processor();
+    let Some(task) = processor.current() else {
+        return;
+    };
+    let now = get_time_ns();
+    let ns = now - processor.accounted_ns;
+    processor.accounted_ns = now;
+    drop(processor);
+    charge_cpu_time(&task, user, ns);
+}
+
+/// 从用户态进入内核时调用
+pub fn account_user_time() {
+    account_current(true);
+}
+
+/// 返回用户态之前调用
+pub fn account_system_time() {
+    account_current(false);
+}
+
+/// 时钟中断时由调度策略决定当前线程是否让出 CPU。
+/// 正在准备阻塞或者已经被唤醒的线程不能被抢占，否则会被重复加入就绪队列
+pub fn should_preempt_current() -> bool {
+    let processor = processor();
+    let Some(task) = processor.current() else {
+        return false;
+    };
+    let ran = Time::read() - processor.run_start;
+    drop(processor);
+    let task_inner = task.inner_exclusive_access();
+    task_inner.task_status == TaskStatus::Running && should_preempt(&task_inner.sched, ran)
 }
 ///Get running task
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    processor().current()
 }
 
 pub fn current_process() -> Arc<ProcessControlBlock> {
@@ -113,11 +266,17 @@ pub fn current_trap_addr() -> usize {
 }
 
 ///Return to idle control flow for new scheduling
+/// 切换期间关闭中断，切换回来之后恢复这个线程原来的中断状态
 pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
-    let mut processor = PROCESSOR.exclusive_access();
+    let ie = crmd::read().ie();
+    crmd::set_ie(false);
+    let mut processor = processor();
     let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
     drop(processor);
     unsafe {
         __switch(switched_task_cx_ptr, idle_task_cx_ptr);
     }
+    // 中间可能有其他线程返回过用户态，换成了用户态的异常入口
+    set_kernel_trap_entry();
+    crmd::set_ie(ie);
 }
@@ -0,0 +1,73 @@
+//! 类似 CFS 的调度：按权重折算线程的运行时间，总是运行虚拟运行时间最小的线程
+use alloc::{collections::VecDeque, sync::Arc};
+
+use super::{nice_to_weight, position, tick, SchedEntity, Scheduler, NICE_0_WEIGHT};
+use crate::task::TaskControlBlock;
+
+pub struct CfsScheduler {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    /// 单调不减，新就绪的线程从这里附近开始
+    min_vruntime: u64,
+}
+
+impl CfsScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+            min_vruntime: 0,
+        }
+    }
+}
+
+fn weighted(sched: &SchedEntity, ran: usize) -> u64 {
+    ran as u64 * NICE_0_WEIGHT / nice_to_weight(sched.prio)
+}
+
+impl Scheduler for CfsScheduler {
+    fn push(&mut self, task: Arc<TaskControlBlock>) {
+        // 醒来的线程最多领先一个时钟中断，不能因为睡眠而长期占用 CPU
+        let credit = tick() as u64;
+        let mut inner = task.inner_exclusive_access();
+        inner.sched.vruntime = inner
+            .sched
+            .vruntime
+            .max(self.min_vruntime.saturating_sub(credit));
+        drop(inner);
+        self.ready_queue.push_back(task);
+    }
+    fn pop(
+        &mut self,
+        runnable: &dyn Fn(&Arc<TaskControlBlock>) -> bool,
+    ) -> Option<Arc<TaskControlBlock>> {
+        let (id, _) = self
+            .ready_queue
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| runnable(t))
+            .min_by_key(|(_, t)| t.inner_exclusive_access().sched.vruntime)?;
+        let task = self.ready_queue.remove(id)?;
+        self.min_vruntime = self
+            .min_vruntime
+            .max(task.inner_exclusive_access().sched.vruntime);
+        Some(task)
+    }
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) {
+        if let Some(id) = position(&self.ready_queue, task) {
+            self.ready_queue.remove(id);
+        }
+    }
+    fn len(&self) -> usize {
+        self.ready_queue.len()
+    }
+    fn charge(&mut self, sched: &mut SchedEntity, ran: usize) {
+        sched.runtime += ran;
+        sched.vruntime += weighted(sched, ran);
+    }
+    fn preempt(&self, sched: &SchedEntity, ran: usize) -> bool {
+        // 当前线程超过了就绪线程中最小的虚拟运行时间
+        let current = sched.vruntime + weighted(sched, ran);
+        self.ready_queue
+            .iter()
+            .any(|t| t.inner_exclusive_access().sched.vruntime < current)
+    }
+}
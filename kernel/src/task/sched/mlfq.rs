@@ -0,0 +1,103 @@
+//! 多级反馈队列：线程在一级队列中用完时间配额后降到下一级，
+//! 较低的队列时间片更长；每隔一段时间所有线程回到最高一级，避免饥饿
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+
+use loongarch64::time::Time;
+
+use super::{position, tick, SchedEntity, Scheduler, NICE_MAX};
+use crate::task::TaskControlBlock;
+
+const LEVELS: usize = 3;
+/// 每隔这么多个时钟中断提升一次所有线程
+const BOOST_TICKS: usize = 100;
+
+pub struct MlfqScheduler {
+    queues: [VecDeque<Arc<TaskControlBlock>>; LEVELS],
+    last_boost: usize,
+}
+
+impl MlfqScheduler {
+    pub fn new() -> Self {
+        Self {
+            queues: Default::default(),
+            last_boost: Time::read(),
+        }
+    }
+
+    /// 所有就绪的线程回到它们的起始队列
+    fn boost(&mut self) {
+        let tasks: Vec<_> = self.queues.iter_mut().flat_map(|q| q.drain(..)).collect();
+        for task in tasks {
+            let mut inner = task.inner_exclusive_access();
+            inner.sched.level = base_level(inner.sched.prio);
+            inner.sched.slice_used = 0;
+            let level = inner.sched.level;
+            drop(inner);
+            self.queues[level].push_back(task);
+        }
+    }
+}
+
+/// prio 大于 0 的线程从较低的队列开始
+fn base_level(prio: i32) -> usize {
+    (prio.max(0) as usize * LEVELS / (NICE_MAX as usize + 1)).min(LEVELS - 1)
+}
+
+/// 第 `level` 级队列的时间配额
+fn quantum(level: usize) -> usize {
+    tick() << level
+}
+
+impl Scheduler for MlfqScheduler {
+    fn push(&mut self, task: Arc<TaskControlBlock>) {
+        let level = task.inner_exclusive_access().sched.level;
+        self.queues[level].push_back(task);
+    }
+    fn pop(
+        &mut self,
+        runnable: &dyn Fn(&Arc<TaskControlBlock>) -> bool,
+    ) -> Option<Arc<TaskControlBlock>> {
+        let now = Time::read();
+        if now - self.last_boost >= BOOST_TICKS * tick() {
+            self.last_boost = now;
+            self.boost();
+        }
+        self.queues.iter_mut().find_map(|q| {
+            let id = q.iter().position(|t| runnable(t))?;
+            q.remove(id)
+        })
+    }
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) {
+        for queue in self.queues.iter_mut() {
+            if let Some(id) = position(queue, task) {
+                queue.remove(id);
+                return;
+            }
+        }
+    }
+    fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+    fn charge(&mut self, sched: &mut SchedEntity, ran: usize) {
+        sched.runtime += ran;
+        sched.slice_used += ran;
+        if sched.slice_used >= quantum(sched.level) {
+            sched.level = (sched.level + 1).min(LEVELS - 1);
+            sched.slice_used = 0;
+        }
+    }
+    fn preempt(&self, sched: &SchedEntity, ran: usize) -> bool {
+        // 较高的队列中有线程时立即让出
+        let higher_ready = self.queues[..sched.level].iter().any(|q| !q.is_empty());
+        higher_ready || sched.slice_used + ran >= quantum(sched.level)
+    }
+    fn set_prio(&mut self, sched: &mut SchedEntity, prio: i32) {
+        // 提高优先级时至少回到对应的队列，降低时至少降到对应的队列
+        sched.level = if prio < sched.prio {
+            sched.level.min(base_level(prio))
+        } else {
+            sched.level.max(base_level(prio))
+        };
+        sched.prio = prio;
+    }
+}
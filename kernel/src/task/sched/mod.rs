@@ -0,0 +1,120 @@
+//! 可替换的调度策略
+//!
+//! 每个 CPU 的 `TaskManager` 把就绪线程交给一个 [`Scheduler`]，具体的策略由 cargo feature
+//! 选择：`sched_stride`、`sched_mlfq`、`sched_cfs`，都没有打开时使用轮转调度。
+//! 时间都以计时器的计数为单位。
+
+mod cfs;
+mod mlfq;
+mod rr;
+mod stride;
+
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc};
+
+pub use cfs::CfsScheduler;
+use loongarch64::time::get_timer_freq;
+pub use mlfq::MlfqScheduler;
+pub use rr::RoundRobinScheduler;
+pub use stride::StrideScheduler;
+
+use super::TaskControlBlock;
+use crate::config::TICKS_PER_SEC;
+
+pub const NICE_MIN: i32 = -20;
+pub const NICE_MAX: i32 = 19;
+
+/// nice 为 0 的线程的权重
+const NICE_0_WEIGHT: u64 = 1024;
+
+/// nice 从 -20 到 19 对应的权重，与 Linux 相同，相邻两级相差约 1.25 倍
+const NICE_TO_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291, 29154, 23254, 18705, 14949, 11916, 9548, 7620, 6100, 4904,
+    3906, 3121, 2501, 1991, 1586, 1277, 1024, 820, 655, 526, 423, 335, 272, 215, 172, 137, 110, 87,
+    70, 56, 45, 36, 29, 23, 18, 15,
+];
+
+pub fn nice_to_weight(nice: i32) -> u64 {
+    NICE_TO_WEIGHT[(nice.clamp(NICE_MIN, NICE_MAX) - NICE_MIN) as usize]
+}
+
+/// 一次时钟中断的间隔
+pub fn tick() -> usize {
+    get_timer_freq() / TICKS_PER_SEC
+}
+
+/// 每个线程的调度状态，各个策略只使用其中的一部分
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SchedEntity {
+    /// -20 到 19，越小优先级越高
+    pub nice: i32,
+    /// 有效优先级，取 nice 和持有的互斥锁的等待者中最高的优先级，调度时使用
+    pub prio: i32,
+    /// 累计运行的时间
+    pub runtime: usize,
+    /// stride 调度的行程值
+    pub pass: u64,
+    /// CFS 按权重折算的运行时间
+    pub vruntime: u64,
+    /// MLFQ 所在的队列
+    pub level: usize,
+    /// MLFQ 在当前队列已经用掉的时间
+    pub slice_used: usize,
+}
+
+impl SchedEntity {
+    /// 新线程继承创建者的 nice，其他状态从头开始
+    pub fn inherit(parent: &SchedEntity) -> Self {
+        Self {
+            nice: parent.nice,
+            prio: parent.nice,
+            ..Default::default()
+        }
+    }
+}
+
+pub trait Scheduler: Send {
+    /// 线程变为就绪
+    fn push(&mut self, task: Arc<TaskControlBlock>);
+    /// 在 `runnable` 的线程中取出下一个要运行的线程
+    fn pop(
+        &mut self,
+        runnable: &dyn Fn(&Arc<TaskControlBlock>) -> bool,
+    ) -> Option<Arc<TaskControlBlock>>;
+    /// 从就绪队列中删除线程
+    fn remove(&mut self, task: &Arc<TaskControlBlock>);
+    /// 就绪线程的数量
+    fn len(&self) -> usize;
+    /// 线程离开 CPU 时记录它这次运行的时间 `ran`
+    fn charge(&mut self, sched: &mut SchedEntity, ran: usize) {
+        sched.runtime += ran;
+    }
+    /// 时钟中断时当前线程已经连续运行了 `ran`，返回是否要切换到其他线程
+    fn preempt(&self, _sched: &SchedEntity, _ran: usize) -> bool {
+        true
+    }
+    /// 有效优先级改变之后调整线程的状态
+    fn set_prio(&mut self, sched: &mut SchedEntity, prio: i32) {
+        sched.prio = prio;
+    }
+}
+
+/// 按照打开的 feature 选择调度策略
+pub fn default_scheduler() -> Box<dyn Scheduler> {
+    if cfg!(feature = "sched_cfs") {
+        Box::new(CfsScheduler::new())
+    } else if cfg!(feature = "sched_mlfq") {
+        Box::new(MlfqScheduler::new())
+    } else if cfg!(feature = "sched_stride") {
+        Box::new(StrideScheduler::new())
+    } else {
+        Box::new(RoundRobinScheduler::new())
+    }
+}
+
+/// 在就绪队列中找到线程的位置
+fn position(
+    queue: &VecDeque<Arc<TaskControlBlock>>,
+    task: &Arc<TaskControlBlock>,
+) -> Option<usize> {
+    queue.iter().position(|t| Arc::ptr_eq(t, task))
+}
@@ -0,0 +1,44 @@
+//! 轮转调度：总是选有效优先级最高的线程，同一优先级的线程先进先出，每次时钟中断都切换
+use alloc::{collections::VecDeque, sync::Arc};
+
+use super::{position, Scheduler};
+use crate::task::TaskControlBlock;
+
+pub struct RoundRobinScheduler {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl RoundRobinScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for RoundRobinScheduler {
+    fn push(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    fn pop(
+        &mut self,
+        runnable: &dyn Fn(&Arc<TaskControlBlock>) -> bool,
+    ) -> Option<Arc<TaskControlBlock>> {
+        // 优先级相同时 min_by_key 返回最靠前的线程
+        let (id, _) = self
+            .ready_queue
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| runnable(t))
+            .min_by_key(|(_, t)| t.inner_exclusive_access().sched.prio)?;
+        self.ready_queue.remove(id)
+    }
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) {
+        if let Some(id) = position(&self.ready_queue, task) {
+            self.ready_queue.remove(id);
+        }
+    }
+    fn len(&self) -> usize {
+        self.ready_queue.len()
+    }
+}
@@ -0,0 +1,61 @@
+//! stride 调度：每次选行程值最小的线程，它运行后行程值增加与权重成反比的步长
+use alloc::{collections::VecDeque, sync::Arc};
+
+use super::{nice_to_weight, position, SchedEntity, Scheduler};
+use crate::task::TaskControlBlock;
+
+const BIG_STRIDE: u64 = 1 << 32;
+
+pub struct StrideScheduler {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    /// 最近一次选出的线程的行程值，新就绪的线程不能落后于它
+    min_pass: u64,
+}
+
+impl StrideScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+            min_pass: 0,
+        }
+    }
+}
+
+fn stride(sched: &SchedEntity) -> u64 {
+    BIG_STRIDE / nice_to_weight(sched.prio)
+}
+
+impl Scheduler for StrideScheduler {
+    fn push(&mut self, task: Arc<TaskControlBlock>) {
+        // 睡眠了很久的线程不能靠积攒的行程值长期占用 CPU
+        let mut inner = task.inner_exclusive_access();
+        inner.sched.pass = inner.sched.pass.max(self.min_pass);
+        drop(inner);
+        self.ready_queue.push_back(task);
+    }
+    fn pop(
+        &mut self,
+        runnable: &dyn Fn(&Arc<TaskControlBlock>) -> bool,
+    ) -> Option<Arc<TaskControlBlock>> {
+        let (id, _) = self
+            .ready_queue
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| runnable(t))
+            .min_by_key(|(_, t)| t.inner_exclusive_access().sched.pass)?;
+        let task = self.ready_queue.remove(id)?;
+        let mut inner = task.inner_exclusive_access();
+        self.min_pass = inner.sched.pass;
+        inner.sched.pass += stride(&inner.sched);
+        drop(inner);
+        Some(task)
+    }
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) {
+        if let Some(id) = position(&self.ready_queue, task) {
+            self.ready_queue.remove(id);
+        }
+    }
+    fn len(&self) -> usize {
+        self.ready_queue.len()
+    }
+}
@@ -2,7 +2,13 @@ use bitflags::*;
 
 pub const MAX_SIG: usize = 31;
 
+/// 使用信号的默认处理方式
+pub const SIG_DFL: usize = 0;
+/// 忽略信号
+pub const SIG_IGN: usize = 1;
+
 bitflags! {
+    #[repr(transparent)]
     pub struct SignalFlags: u32 {
         const SIGDEF = 1; // Default signal handling
         const SIGHUP = 1 << 1;
@@ -40,22 +46,167 @@ bitflags! {
 }
 
 impl SignalFlags {
-    pub fn check_error(&self) -> Option<(i32, &'static str)> {
-        if self.contains(Self::SIGINT) {
-            Some((-2, "Killed, SIGINT=2"))
-        } else if self.contains(Self::SIGILL) {
-            Some((-4, "Illegal Instruction, SIGILL=4"))
-        } else if self.contains(Self::SIGABRT) {
-            Some((-6, "Aborted, SIGABRT=6"))
-        } else if self.contains(Self::SIGFPE) {
-            Some((-8, "Erroneous Arithmetic Operation, SIGFPE=8"))
-        } else if self.contains(Self::SIGKILL) {
-            Some((-9, "Killed, SIGKILL=9"))
-        } else if self.contains(Self::SIGSEGV) {
-            Some((-11, "Segmentation Fault, SIGSEGV=11"))
+    /// Signals that can neither be caught nor blocked
+    pub const UNBLOCKABLE: Self =
+        Self::from_bits_truncate(Self::SIGKILL.bits() | Self::SIGSTOP.bits());
+
+    /// The flag of signal number `signum`, which goes from 1 to [`MAX_SIG`]
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if (1..=MAX_SIG).contains(&signum) {
+            Self::from_bits(1 << signum)
         } else {
-            //println!("[K] signalflags check_error  {:?}", self);
             None
         }
     }
+
+    /// The lowest signal number in the set
+    pub fn first_signum(&self) -> Option<usize> {
+        let bits = self.bits() & !Self::SIGDEF.bits();
+        if bits == 0 {
+            None
+        } else {
+            Some(bits.trailing_zeros() as usize)
+        }
+    }
+
+    /// What the signal does to a process that neither catches nor ignores it
+    pub fn default_action(&self) -> DefaultAction {
+        if self.intersects(Self::SIGCHLD | Self::SIGURG | Self::SIGWINCH) {
+            DefaultAction::Ignore
+        } else if self.intersects(Self::SIGSTOP | Self::SIGTSTP | Self::SIGTTIN | Self::SIGTTOU) {
+            DefaultAction::Stop
+        } else if self.contains(Self::SIGCONT) {
+            DefaultAction::Continue
+        } else if self.intersects(
+            Self::SIGQUIT
+                | Self::SIGILL
+                | Self::SIGTRAP
+                | Self::SIGABRT
+                | Self::SIGBUS
+                | Self::SIGFPE
+                | Self::SIGSEGV
+                | Self::SIGXCPU
+                | Self::SIGXFSZ
+                | Self::SIGSYS,
+        ) {
+            DefaultAction::Core
+        } else {
+            DefaultAction::Terminate
+        }
+    }
+}
+
+/// POSIX 规定的信号默认行为
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DefaultAction {
+    Terminate,
+    /// Terminate, a core dump would be written if we had one
+    Core,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+/// 信号的名字和说明，以信号编号为下标
+pub const SIGNAL_NAMES: [(&str, &str); MAX_SIG + 1] = [
+    ("SIGDEF", "Unknown signal"),
+    ("SIGHUP", "Hangup"),
+    ("SIGINT", "Interrupt"),
+    ("SIGQUIT", "Quit"),
+    ("SIGILL", "Illegal Instruction"),
+    ("SIGTRAP", "Trace/breakpoint trap"),
+    ("SIGABRT", "Aborted"),
+    ("SIGBUS", "Bus error"),
+    ("SIGFPE", "Erroneous Arithmetic Operation"),
+    ("SIGKILL", "Killed"),
+    ("SIGUSR1", "User defined signal 1"),
+    ("SIGSEGV", "Segmentation Fault"),
+    ("SIGUSR2", "User defined signal 2"),
+    ("SIGPIPE", "Broken pipe"),
+    ("SIGALRM", "Alarm clock"),
+    ("SIGTERM", "Terminated"),
+    ("SIGSTKFLT", "Stack fault"),
+    ("SIGCHLD", "Child exited"),
+    ("SIGCONT", "Continued"),
+    ("SIGSTOP", "Stopped (signal)"),
+    ("SIGTSTP", "Stopped"),
+    ("SIGTTIN", "Stopped (tty input)"),
+    ("SIGTTOU", "Stopped (tty output)"),
+    ("SIGURG", "Urgent I/O condition"),
+    ("SIGXCPU", "CPU time limit exceeded"),
+    ("SIGXFSZ", "File size limit exceeded"),
+    ("SIGVTALRM", "Virtual timer expired"),
+    ("SIGPROF", "Profiling timer expired"),
+    ("SIGWINCH", "Window changed"),
+    ("SIGIO", "I/O possible"),
+    ("SIGPWR", "Power failure"),
+    ("SIGSYS", "Bad system call"),
+];
+
+/// A state change of a child that its parent has not collected with
+/// `waitpid` yet
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WaitReport {
+    /// Stopped by the signal
+    Stopped(usize),
+    Continued,
+}
+
+/// What a process does on a signal, the layout is shared with user space
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalAction {
+    /// [`SIG_DFL`], [`SIG_IGN`] or the address of a handler
+    pub handler: usize,
+    /// Signals blocked while the handler runs, besides the signal itself
+    pub mask: SignalFlags,
+    /// Where the handler returns to, it has to call `sigreturn`
+    pub restorer: usize,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+            restorer: 0,
+        }
+    }
+}
+
+/// 进程的信号处理表，以信号编号为下标
+#[derive(Clone)]
+pub struct SignalActions {
+    pub table: [SignalAction; MAX_SIG + 1],
+}
+
+impl Default for SignalActions {
+    fn default() -> Self {
+        Self {
+            table: [SignalAction::default(); MAX_SIG + 1],
+        }
+    }
+}
+
+impl SignalActions {
+    /// exec 之后处理函数已经不存在了，恢复为默认处理方式，但保留被忽略的信号
+    pub fn reset_handlers(&mut self) {
+        for action in self.table.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+    }
+}
+
+/// Pushed on the user stack before a handler runs and popped by
+/// `sigreturn`. Only what user code may change is saved, so the handler
+/// cannot tamper with `prmd`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalFrame {
+    pub x: [usize; 32],
+    pub sepc: usize,
+    /// The signal mask of the thread before the handler
+    pub mask: SignalFlags,
 }
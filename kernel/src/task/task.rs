@@ -1,14 +1,26 @@
-use alloc::sync::{Arc, Weak};
-use core::cell::RefMut;
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
 
-use super::{KernelStack, ProcessControlBlock, TaskContext, TaskUserRes};
-use crate::{sync::UPSafeCell, trap::TrapContext};
+use super::{
+    cputime::CpuTimes, KernelStack, ProcessControlBlock, RunningThreads, SchedEntity, SignalFlags,
+    TaskContext, TaskUserRes,
+};
+use crate::{
+    sync::{MutexBlockingInner, SpinLock, SpinLockGuard},
+    trap::TrapContext,
+};
 
 pub struct TaskControlBlock {
     // immutable
     pub process: Weak<ProcessControlBlock>, //所属进程
+    pub running: Arc<RunningThreads>,       //所属进程正在运行的线程数
+    /// 正在某个 CPU 上运行，切换走、上下文保存好之后才清除
+    on_cpu: AtomicBool,
     // mutable
-    inner: UPSafeCell<TaskControlBlockInner>,
+    inner: SpinLock<TaskControlBlockInner>,
 }
 pub struct TaskControlBlockInner {
     pub kstack: KernelStack,      //每个线程都存在内核栈，其trap上下文位于内核栈上
@@ -16,13 +28,49 @@ pub struct TaskControlBlockInner {
     pub task_cx: TaskContext,     //线程上下文
     pub task_status: TaskStatus,  //线程状态
     pub exit_code: Option<i32>,   //线程退出码
+    pub signal_mask: SignalFlags, //被阻塞的信号
+    /// 阻塞时可以被信号唤醒，见 `prepare_interruptible_block`
+    pub interruptible: bool,
+    /// 有信号要在返回用户态时处理，可中断的阻塞直接返回 EINTR
+    pub interrupted: bool,
+    /// CLONE_CHILD_CLEARTID 给出的地址，线程退出时在这里写 0
+    pub clear_child_tid: usize,
+    pub sched: SchedEntity, //调度状态
+    /// 持有的阻塞互斥锁，它们的等待者的优先级会传给这个线程
+    pub held_mutexes: Vec<Arc<SpinLock<MutexBlockingInner>>>,
+    /// 正在等待的阻塞互斥锁
+    pub blocked_on: Option<Arc<SpinLock<MutexBlockingInner>>>,
+    pub cpu_times: CpuTimes, //运行时间
 }
 
 impl TaskControlBlock {
-    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
+    pub fn inner_exclusive_access(&self) -> SpinLockGuard<'_, TaskControlBlockInner> {
         self.inner.exclusive_access()
     }
 
+    /// 线程上 CPU。它还在其他 CPU 上（例如刚被唤醒，上下文还没有保存好）时返回 false
+    pub fn claim_cpu(&self) -> bool {
+        if self
+            .on_cpu
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        self.running.enter();
+        true
+    }
+
+    /// 线程切换走、上下文保存好之后调用
+    pub fn release_cpu(&self) {
+        self.on_cpu.store(false, Ordering::Release);
+        self.running.leave();
+    }
+
+    pub fn is_on_cpu(&self) -> bool {
+        self.on_cpu.load(Ordering::Acquire)
+    }
+
     pub fn get_user_token(&self) -> usize {
         let process = self.process.upgrade().unwrap();
         let inner = process.inner_exclusive_access();
@@ -50,15 +98,23 @@ impl TaskControlBlock {
         let kstack_top = kstack.get_trap_addr(); //存放了trap上下文后的地址
         Self {
             process: Arc::downgrade(&process),
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    kstack,
-                    res: Some(res),
-                    task_cx: TaskContext::goto_restore(kstack_top),
-                    task_status: TaskStatus::Ready,
-                    exit_code: None,
-                })
-            },
+            running: Arc::clone(&process.running),
+            on_cpu: AtomicBool::new(false),
+            inner: SpinLock::new(TaskControlBlockInner {
+                kstack,
+                res: Some(res),
+                task_cx: TaskContext::goto_restore(kstack_top),
+                task_status: TaskStatus::Ready,
+                exit_code: None,
+                signal_mask: SignalFlags::empty(),
+                interruptible: false,
+                interrupted: false,
+                clear_child_tid: 0,
+                sched: SchedEntity::default(),
+                held_mutexes: Vec::new(),
+                blocked_on: None,
+                cpu_times: CpuTimes::default(),
+            }),
         }
     }
 }
@@ -68,4 +124,6 @@ pub enum TaskStatus {
     Ready,
     Running,
     Blocking,
+    /// 所属进程被暂停，等待 SIGCONT
+    Stopped,
 }
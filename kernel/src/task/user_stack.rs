@@ -0,0 +1,103 @@
+//! 进程的初始用户栈
+//!
+//! 按 System V ABI 布置：栈顶是参数和环境变量字符串，以及 AT_RANDOM 指向的
+//! 16 个随机字节；往下依次是 argc、argv、envp 和辅助向量，sp 指向 argc，
+//! 并按 LoongArch ELF psABI 的要求 16 字节对齐。
+
+use alloc::{string::String, vec, vec::Vec};
+use core::mem::size_of;
+
+use loongarch64::time::Time;
+
+use crate::{
+    config::PAGE_SIZE,
+    mm::{ElfInfo, MemorySet, UserSlice},
+};
+
+/// 辅助向量的类型
+const AT_NULL: usize = 0;
+const AT_PHDR: usize = 3;
+const AT_PHENT: usize = 4;
+const AT_PHNUM: usize = 5;
+const AT_PAGESZ: usize = 6;
+const AT_BASE: usize = 7;
+const AT_ENTRY: usize = 9;
+const AT_RANDOM: usize = 25;
+
+/// 布置好的初始栈，程序从 sp 开始运行
+pub struct InitialStack {
+    pub sp: usize,
+    pub argv: usize,
+    pub envp: usize,
+}
+
+/// 用计数器的值做种子的 xorshift，只用来填 AT_RANDOM
+fn random_bytes() -> [u8; 16] {
+    let mut state = Time::read() as u64 | 1;
+    let mut bytes = [0u8; 16];
+    for chunk in bytes.chunks_mut(8) {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        chunk.copy_from_slice(&state.to_le_bytes());
+    }
+    bytes
+}
+
+/// 在 `memory_set` 中栈顶为 `ustack_top` 的栈上放好参数、环境变量和辅助向量。
+/// 栈必须已经映射并且放得下，`sys_exec` 限制了参数和环境变量的总大小。
+pub fn init_user_stack(
+    memory_set: &MemorySet,
+    ustack_top: usize,
+    args: &[String],
+    envs: &[String],
+    elf_info: &ElfInfo,
+) -> InitialStack {
+    let word = size_of::<usize>();
+    let random_addr = ustack_top - 16;
+    let strings_size: usize = args.iter().chain(envs).map(|s| s.len() + 1).sum();
+    let strings_base = random_addr - strings_size;
+    let auxv = [
+        (AT_PHDR, elf_info.phdr),
+        (AT_PHENT, elf_info.phent),
+        (AT_PHNUM, elf_info.phnum),
+        (AT_PAGESZ, PAGE_SIZE),
+        (AT_BASE, elf_info.base),
+        (AT_ENTRY, elf_info.entry),
+        (AT_RANDOM, random_addr),
+        (AT_NULL, 0),
+    ];
+    let table_words = 1 + (args.len() + 1) + (envs.len() + 1) + 2 * auxv.len();
+    let sp = (strings_base - table_words * word) & !0xf;
+
+    let mut table = Vec::with_capacity(table_words);
+    let mut strings = Vec::with_capacity(strings_size);
+    table.push(args.len());
+    for list in [args, envs] {
+        for s in list {
+            table.push(strings_base + strings.len());
+            strings.extend_from_slice(s.as_bytes());
+            strings.push(0);
+        }
+        table.push(0);
+    }
+    for (key, value) in auxv {
+        table.push(key);
+        table.push(value);
+    }
+
+    let mut image = vec![0u8; ustack_top - sp];
+    for (i, value) in table.iter().enumerate() {
+        image[i * word..(i + 1) * word].copy_from_slice(&value.to_le_bytes());
+    }
+    image[strings_base - sp..random_addr - sp].copy_from_slice(&strings);
+    image[random_addr - sp..].copy_from_slice(&random_bytes());
+    UserSlice::new(sp as *const u8, image.len())
+        .write_in(memory_set, &image)
+        .unwrap();
+    InitialStack {
+        sp,
+        argv: sp + word,
+        envp: sp + (args.len() + 2) * word,
+    }
+}
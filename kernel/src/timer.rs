@@ -1,34 +1,167 @@
-use alloc::{collections::BinaryHeap, sync::Arc};
-use core::cmp::Ordering;
+use alloc::{
+    collections::BinaryHeap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
+    cmp::Ordering,
+    sync::atomic::{self, AtomicUsize},
+};
 
-use loongarch64::time::{get_timer_freq, Time};
-use spin::{Lazy, Mutex};
+use loongarch64::{
+    register::tcfg,
+    time::{get_timer_freq, Time},
+};
 
 use crate::{
-    config::MSEC_PER_SEC,
-    task::{add_task, TaskControlBlock},
+    config::{MSEC_PER_SEC, NSEC_PER_MSEC, NSEC_PER_SEC, NSEC_PER_USEC, TICKS_PER_SEC},
+    fs::TimerFd,
+    loongarch::rtc_unix_time,
+    sync::IrqSafeSpinLock,
+    task::{current_task, itimer_real_expired, wakeup_task, ProcessControlBlock, TaskControlBlock},
 };
 
+/// 计数器的计数换算成纳秒，中间结果用 128 位避免溢出
+fn ticks_to_ns(ticks: usize) -> usize {
+    (ticks as u128 * NSEC_PER_SEC as u128 / get_timer_freq() as u128) as usize
+}
+
+fn ns_to_ticks(ns: usize) -> usize {
+    (ns as u128 * get_timer_freq() as u128 / NSEC_PER_SEC as u128) as usize
+}
+
+/// 开机以来的纳秒数，来自恒定频率的计数器
+pub fn get_time_ns() -> usize {
+    ticks_to_ns(Time::read())
+}
+
 pub fn get_time_ms() -> usize {
-    Time::read() / (get_timer_freq() / MSEC_PER_SEC)
+    get_time_ns() / NSEC_PER_MSEC
+}
+
+/// 开机时的 UNIX 时间，单位为纳秒，加上单调时钟就是 CLOCK_REALTIME
+static BOOT_REALTIME_NS: AtomicUsize = AtomicUsize::new(0);
+
+/// 用 RTC 的时间设置实时时钟，RTC 只精确到秒
+pub fn init_realtime() {
+    let now = rtc_unix_time() * NSEC_PER_SEC;
+    BOOT_REALTIME_NS.store(now.saturating_sub(get_time_ns()), atomic::Ordering::Relaxed);
+}
+
+/// 当前的 UNIX 时间，单位为纳秒
+pub fn get_realtime_ns() -> usize {
+    BOOT_REALTIME_NS.load(atomic::Ordering::Relaxed) + get_time_ns()
+}
+
+/// `struct timespec`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TimeSpec {
+    pub tv_sec: usize,
+    pub tv_nsec: usize,
+}
+
+impl TimeSpec {
+    /// 开机以来的时间
+    pub fn now() -> Self {
+        Self::from_ns(get_time_ns())
+    }
+    pub fn from_ns(ns: usize) -> Self {
+        Self {
+            tv_sec: ns / NSEC_PER_SEC,
+            tv_nsec: ns % NSEC_PER_SEC,
+        }
+    }
+    pub fn to_ms(&self) -> usize {
+        self.tv_sec * MSEC_PER_SEC + self.tv_nsec / NSEC_PER_MSEC
+    }
+    /// 太大的时间截断为 usize::MAX
+    pub fn to_ns(&self) -> usize {
+        self.tv_sec
+            .saturating_mul(NSEC_PER_SEC)
+            .saturating_add(self.tv_nsec)
+    }
+    /// tv_nsec 必须小于一秒
+    pub fn is_valid(&self) -> bool {
+        self.tv_nsec < NSEC_PER_SEC
+    }
+}
+
+const USEC_PER_SEC: usize = NSEC_PER_SEC / NSEC_PER_USEC;
+
+/// `struct timeval`，用于 setitimer
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TimeVal {
+    pub tv_sec: usize,
+    pub tv_usec: usize,
+}
+
+impl TimeVal {
+    /// 不足一微秒的部分向上取整，非零的时间不会变成 0
+    pub fn from_ns(ns: usize) -> Self {
+        let us = ns.div_ceil(NSEC_PER_USEC);
+        Self {
+            tv_sec: us / USEC_PER_SEC,
+            tv_usec: us % USEC_PER_SEC,
+        }
+    }
+    pub fn to_ns(&self) -> usize {
+        self.tv_sec
+            .saturating_mul(NSEC_PER_SEC)
+            .saturating_add(self.tv_usec * NSEC_PER_USEC)
+    }
+    /// tv_usec 必须小于一秒
+    pub fn is_valid(&self) -> bool {
+        self.tv_usec < USEC_PER_SEC
+    }
+}
+
+/// 定时器到期时要做的事
+pub enum TimerEvent {
+    /// 唤醒阻塞的线程
+    Wakeup(Arc<TaskControlBlock>),
+    /// 进程的 ITIMER_REAL 到期，发送 SIGALRM
+    ItimerReal(Weak<ProcessControlBlock>),
+    /// timerfd 到期，计数加一
+    TimerFd(Weak<TimerFd>),
+}
+
+impl TimerEvent {
+    /// 到期时执行，不持有 TIMERS
+    fn fire(self, expire_ns: usize) {
+        match self {
+            Self::Wakeup(task) => wakeup_task(task),
+            Self::ItimerReal(process) => {
+                if let Some(process) = process.upgrade() {
+                    itimer_real_expired(&process, expire_ns);
+                }
+            }
+            Self::TimerFd(timerfd) => {
+                if let Some(timerfd) = timerfd.upgrade() {
+                    timerfd.expired(expire_ns);
+                }
+            }
+        }
+    }
 }
 
 pub struct TimerCondVar {
-    pub expire_ms: usize,
-    pub task: Arc<TaskControlBlock>,
+    /// 开机以来的纳秒数
+    pub expire_ns: usize,
+    pub event: TimerEvent,
 }
 
 impl PartialEq for TimerCondVar {
     fn eq(&self, other: &Self) -> bool {
-        self.expire_ms == other.expire_ms
+        self.expire_ns == other.expire_ns
     }
 }
 impl Eq for TimerCondVar {}
 impl PartialOrd for TimerCondVar {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let a = -(self.expire_ms as isize);
-        let b = -(other.expire_ms as isize);
-        Some(a.cmp(&b))
+        // BinaryHeap 是最大堆，反过来比较让最早到期的在堆顶
+        Some(other.expire_ns.cmp(&self.expire_ns))
     }
 }
 
@@ -38,36 +171,77 @@ impl Ord for TimerCondVar {
     }
 }
 
-static TIMERS: Lazy<Mutex<BinaryHeap<TimerCondVar>>> =
-    Lazy::new(|| Mutex::new(BinaryHeap::<TimerCondVar>::new()));
+static TIMERS: IrqSafeSpinLock<BinaryHeap<TimerCondVar>> = IrqSafeSpinLock::new(BinaryHeap::new());
 
-pub fn add_timer(expire_ms: usize, task: Arc<TaskControlBlock>) {
-    let mut timers = TIMERS.lock();
-    timers.push(TimerCondVar { expire_ms, task });
+/// 到 `expire_ns` 时唤醒 `task`
+pub fn add_timer(expire_ns: usize, task: Arc<TaskControlBlock>) {
+    add_timer_event(expire_ns, TimerEvent::Wakeup(task));
 }
 
-/// 将对应与线程的时钟删除,其它的仍然存在最小堆中
+pub fn add_timer_event(expire_ns: usize, event: TimerEvent) {
+    let mut timers = TIMERS.exclusive_access();
+    timers.push(TimerCondVar { expire_ns, event });
+    drop(timers);
+    // 新的定时器可能比已经设置的时钟中断更早到期
+    program_next_timer();
+}
+
+/// 删除所有满足 `pred` 的定时器，其它的仍然存在最小堆中
+pub fn remove_timer_events(pred: impl Fn(&TimerEvent) -> bool) {
+    TIMERS
+        .exclusive_access()
+        .retain(|condvar| !pred(&condvar.event));
+}
+
+/// 将对应与线程的时钟删除
 pub fn remove_timer(task: Arc<TaskControlBlock>) {
-    let mut timers = TIMERS.lock();
-    let mut temp = BinaryHeap::<TimerCondVar>::new();
-    for condvar in timers.drain() {
-        if Arc::as_ptr(&task) != Arc::as_ptr(&condvar.task) {
-            temp.push(condvar);
-        }
-    }
-    timers.clear();
-    timers.append(&mut temp);
+    remove_timer_events(
+        |event| matches!(event, TimerEvent::Wakeup(waiter) if Arc::ptr_eq(waiter, &task)),
+    );
 }
 
+/// 取出所有到期的定时器，释放 TIMERS 之后再处理，处理时可能会加入新的定时器
 pub fn check_timer() {
-    let current_ms = get_time_ms();
-    let mut timers = TIMERS.lock();
-    while let Some(timer) = timers.peek() {
-        if timer.expire_ms <= current_ms {
-            add_task(Arc::clone(&timer.task));
-            timers.pop();
-        } else {
-            break;
-        }
+    let current_ns = get_time_ns();
+    let mut timers = TIMERS.exclusive_access();
+    let mut expired = Vec::new();
+    while timers
+        .peek()
+        .is_some_and(|timer| timer.expire_ns <= current_ns)
+    {
+        expired.push(timers.pop().unwrap());
+    }
+    drop(timers);
+    for timer in expired {
+        timer.event.fire(timer.expire_ns);
+    }
+}
+
+/// 一个时间片的长度
+pub const TIME_SLICE_NS: usize = NSEC_PER_SEC / TICKS_PER_SEC;
+
+/// 没有定时器也没有线程运行时，最多隔这么久产生一次时钟中断，不超出计时器的位数
+const MAX_TIMER_INTERVAL_NS: usize = NSEC_PER_SEC;
+
+/// 计时器以单次触发的方式在 `ns` 纳秒之后产生时钟中断
+pub fn set_timer_after(ns: usize) {
+    // 初值必须是 4 的倍数，而且不能为 0
+    let ticks = (ns_to_ticks(ns).max(4) + 3) & !3;
+    tcfg::set_periodic(false);
+    tcfg::set_init_val(ticks);
+    tcfg::set_en(true);
+}
+
+/// 在最早的定时器到期时产生下一次时钟中断；当前 CPU 上有线程运行时最晚到时间片用完，
+/// 空闲的 CPU 不再每个时间片都被打断
+pub fn program_next_timer() {
+    let now = get_time_ns();
+    let mut next = now + MAX_TIMER_INTERVAL_NS;
+    if let Some(timer) = TIMERS.exclusive_access().peek() {
+        next = next.min(timer.expire_ns);
+    }
+    if current_task().is_some() {
+        next = next.min(now + TIME_SLICE_NS);
     }
+    set_timer_after(next.saturating_sub(now));
 }
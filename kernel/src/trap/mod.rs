@@ -15,16 +15,17 @@ use loongarch64::{
 };
 
 use crate::{
-    config::TICKS_PER_SEC,
+    fs::poll_console,
     loongarch::{
         extioi_claim, extioi_complete, kbd_has_data, kbd_read_scancode, ls7a_intc_complete,
         KEYBOARD_IRQ, MOUSE_IRQ, UART0_IRQ,
     },
     mm::{PageTable, VirtAddr, VirtPageNum},
     println,
+    sync::preemptible,
     syscall::syscall,
     task::*,
-    timer::check_timer,
+    timer::{check_timer, program_next_timer, set_timer_after, TIME_SLICE_NS},
 };
 
 global_asm!(include_str!("trap.s"));
@@ -68,13 +69,9 @@ pub fn init() {
 }
 
 pub fn enable_timer_interrupt() {
-    let timer_freq = get_timer_freq();
     ticlr::clear_timer_interrupt();
-    // 设置计时器的配置
-    // println!("timer freq: {}", timer_freq);
-    tcfg::set_init_val(timer_freq / TICKS_PER_SEC);
-    tcfg::set_en(true);
-    tcfg::set_periodic(true);
+    // 计时器以单次触发的方式工作，每次时钟中断之后设置下一次
+    program_next_timer();
 
     // 开启全局中断
     ecfg::set_lie(LineBasedInterrupt::TIMER | LineBasedInterrupt::HWI0);
@@ -94,6 +91,7 @@ pub fn set_kernel_trap_entry() {
 }
 #[no_mangle]
 pub fn trap_return() {
+    account_system_time();
     set_user_trap_entry();
     let trap_addr = current_trap_addr();
     unsafe {
@@ -105,6 +103,8 @@ pub fn trap_return() {
 #[no_mangle]
 pub fn trap_handler(mut cx: &mut TrapContext) -> &mut TrapContext {
     set_kernel_trap_entry();
+    // 到这里为止的时间是在用户态运行的
+    account_user_time();
     let estat = estat::read();
     let crmd = crmd::read();
     if crmd.ie() {
@@ -116,7 +116,12 @@ pub fn trap_handler(mut cx: &mut TrapContext) -> &mut TrapContext {
             //系统调用
             cx.sepc += 4;
             // INFO!("call id:{}, {} {} {}",cx.x[11], cx.x[4], cx.x[5], cx.x[6]);
-            let result = syscall(cx.x[11], [cx.x[4], cx.x[5], cx.x[6]]) as usize;
+            // a0-a5 是参数，a7 是系统调用号
+            let args = [cx.x[4], cx.x[5], cx.x[6], cx.x[7], cx.x[8], cx.x[9]];
+            // 系统调用期间打开中断，内核可以被时钟中断抢占
+            crmd::set_ie(true);
+            let result = syscall(cx.x[11], args) as usize;
+            crmd::set_ie(false);
             cx = current_trap_cx();
             cx.x[4] = result;
         }
@@ -164,29 +169,33 @@ pub fn trap_handler(mut cx: &mut TrapContext) -> &mut TrapContext {
             panic!("{:?}", estat.cause());
         }
     }
-    // check error signals (if error then exit)
-    if let Some((errno, msg)) = check_signals_of_current() {
-        println!("[kernel] {}", msg);
-        exit_current_and_run_next(errno);
-    }
+    // 返回用户态之前处理信号，可能会结束当前进程或转到信号处理函数
+    handle_signals();
+    account_system_time();
     set_user_trap_entry();
     cx
 }
 
 fn timer_handler() {
     // println!("timer interrupt from user");
-    // 释放那些处于等待的任务
-    check_timer();
     // 清除时钟中断
     ticlr::clear_timer_interrupt();
-    suspend_current_and_run_next();
+    // 释放那些处于等待的任务，处理到期的定时器
+    check_timer();
+    check_cpu_itimers();
+    // 唤醒等待控制台输入的任务
+    poll_console();
+    program_next_timer();
+    // 时间片是否用完由调度策略决定
+    if should_preempt_current() {
+        suspend_current_and_run_next();
+    }
 }
 
 /// 当在内核态发生异常或中断时处理
 /// 这里主要时处理时钟中断
-/// 由于主函数开启时钟中断后会进行加载任务操作
-/// 而加载任务的时间可能会触发时钟中断
-/// 在正常运行后系统在从用户态trap进入内核态后是不会触发中断的
+/// 系统调用期间中断是打开的，当前 CPU 没有持有锁时时钟中断可以抢占内核，
+/// 否则只清除中断，一个时间片之后再检查
 #[no_mangle]
 pub fn trap_handler_kernel() {
     // println!("kernel trap");
@@ -199,9 +208,13 @@ pub fn trap_handler_kernel() {
     }
     match estat.cause() {
         Trap::Interrupt(Interrupt::Timer) => {
-            // 清除时钟专断
-            info!("timer interrupt from kernel");
-            ticlr::clear_timer_interrupt();
+            if preemptible() {
+                timer_handler();
+            } else {
+                // 清除时钟专断
+                ticlr::clear_timer_interrupt();
+                set_timer_after(TIME_SLICE_NS);
+            }
         }
         Trap::Interrupt(Interrupt::HWI0) => {
             // 中断0 --- 外部中断处理
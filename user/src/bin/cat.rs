@@ -5,16 +5,15 @@
 extern crate user_lib;
 extern crate alloc;
 
-use user_lib::{close, open, read, OpenFlags};
+use user_lib::{close, open, read, OpenFlags, SysError};
 
 #[no_mangle]
 pub fn main(argc: usize, argv: &[&str]) -> i32 {
     assert!(argc == 2);
-    let fd = open(argv[1], OpenFlags::RDONLY);
-    if fd == -1 {
-        panic!("Error occured when opening fs");
-    }
-    let fd = fd as usize;
+    let fd = match SysError::check(open(argv[1], OpenFlags::RDONLY)) {
+        Ok(fd) => fd,
+        Err(error) => panic!("Error occured when opening fs: {}", error),
+    };
     let mut buf = [0u8; 256];
     loop {
         let size = read(fd, &mut buf) as usize;
@@ -0,0 +1,94 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    clock_getres, clock_nanosleep, clock_now, nanosleep, SysError, TimeSpec, CLOCK_BOOTTIME,
+    CLOCK_MONOTONIC, CLOCK_REALTIME, TIMER_ABSTIME,
+};
+
+const MSEC: usize = 1_000_000;
+/// 2000-01-01 00:00:00 UTC
+const Y2K: usize = 946_684_800;
+
+fn monotonic() -> usize {
+    clock_now(CLOCK_MONOTONIC).unwrap()
+}
+
+fn test_clocks() {
+    assert!(clock_now(CLOCK_REALTIME).unwrap() / 1_000_000_000 > Y2K);
+    assert!(clock_now(CLOCK_BOOTTIME).is_ok());
+    assert_eq!(clock_now(42), Err(SysError::EINVAL));
+    let mut res = TimeSpec::default();
+    assert_eq!(clock_getres(CLOCK_MONOTONIC, &mut res), 0);
+    assert!(res.tv_sec == 0 && res.tv_nsec > 0 && res.tv_nsec < 1000);
+    // 单调时钟不会倒退，而且比毫秒更精细
+    let mut last = monotonic();
+    let mut sub_ms = false;
+    for _ in 0..100 {
+        let now = monotonic();
+        assert!(now >= last);
+        sub_ms |= now % MSEC != 0;
+        last = now;
+    }
+    assert!(sub_ms);
+}
+
+fn test_nanosleep() {
+    let start = monotonic();
+    assert_eq!(nanosleep(&TimeSpec::from_ms(20)), 0);
+    assert!(monotonic() - start >= 20 * MSEC);
+    // 不需要等到下一个时间片
+    let start = monotonic();
+    assert_eq!(nanosleep(&TimeSpec::from_ns(100_000)), 0);
+    let elapsed = monotonic() - start;
+    assert!(elapsed >= 100_000 && elapsed < 10 * MSEC);
+    let invalid = TimeSpec {
+        tv_sec: 0,
+        tv_nsec: 1_000_000_000,
+    };
+    assert_eq!(SysError::check(nanosleep(&invalid)), Err(SysError::EINVAL));
+}
+
+fn test_clock_nanosleep() {
+    let start = monotonic();
+    assert_eq!(
+        clock_nanosleep(CLOCK_MONOTONIC, 0, &TimeSpec::from_ms(10)),
+        0
+    );
+    assert!(monotonic() - start >= 10 * MSEC);
+    for clock in [CLOCK_MONOTONIC, CLOCK_REALTIME] {
+        let target = clock_now(clock).unwrap() + 20 * MSEC;
+        assert_eq!(
+            clock_nanosleep(clock, TIMER_ABSTIME, &TimeSpec::from_ns(target)),
+            0
+        );
+        assert!(clock_now(clock).unwrap() >= target);
+    }
+    // 已经过去的时刻立即返回
+    let start = monotonic();
+    assert_eq!(
+        clock_nanosleep(
+            CLOCK_MONOTONIC,
+            TIMER_ABSTIME,
+            &TimeSpec::from_ns(start / 2)
+        ),
+        0
+    );
+    assert!(monotonic() - start < 10 * MSEC);
+    assert_eq!(
+        SysError::check(clock_nanosleep(42, 0, &TimeSpec::from_ms(1))),
+        Err(SysError::EINVAL)
+    );
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    test_clocks();
+    test_nanosleep();
+    test_clock_nanosleep();
+    println!("clock_test passed!");
+    0
+}
@@ -0,0 +1,111 @@
+#![no_std]
+#![no_main]
+#![allow(static_mut_refs)]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use user_lib::{
+    clone, exit, fork, gettid, thread_create, waitpid, waittid, CloneFlags, SysError, THREAD_FLAGS,
+};
+
+/// 新线程自己的栈，由调用者提供
+#[repr(align(16))]
+struct Stack([u8; 0x2000]);
+static mut STACK: Stack = Stack([0; 0x2000]);
+
+const TLS: usize = 0x1234_5670;
+
+static PARENT_TID: AtomicU32 = AtomicU32::new(0);
+static CHILD_TID: AtomicU32 = AtomicU32::new(u32::MAX);
+static SHARED: AtomicU32 = AtomicU32::new(0);
+
+fn thread_pointer() -> usize {
+    let tp: usize;
+    unsafe { asm!("move {}, $tp", out(reg) tp) };
+    tp
+}
+
+fn child_thread(arg: usize) -> ! {
+    assert_eq!(arg, 42);
+    assert_eq!(thread_pointer(), TLS);
+    // CLONE_CHILD_SETTID wrote the tid before the thread started
+    assert_eq!(CHILD_TID.load(Ordering::SeqCst), gettid() as u32);
+    let sp: usize;
+    unsafe { asm!("move {}, $sp", out(reg) sp) };
+    let stack = unsafe { STACK.0.as_ptr_range() };
+    assert!((stack.start as usize..stack.end as usize).contains(&sp));
+    // memory is shared with the parent
+    SHARED.store(1, Ordering::SeqCst);
+    exit(5)
+}
+
+fn short_thread() -> ! {
+    exit(0)
+}
+
+/// 从线程中 fork：子进程只有这一个线程
+fn forking_thread() -> ! {
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(gettid(), 0);
+        // the other user stacks were not copied, so new threads find room
+        let tid = thread_create(short_thread as usize, 0);
+        assert_eq!(waittid(tid as usize), 0);
+        exit(9);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 9);
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let null = core::ptr::null_mut();
+    // threads share everything or nothing
+    let thread_only = CloneFlags::CLONE_THREAD;
+    assert_eq!(
+        clone(short_thread as usize, 0, thread_only, 0, null, null, 0),
+        SysError::EINVAL.ret()
+    );
+    let vm_only = CloneFlags::CLONE_VM;
+    assert_eq!(
+        clone(short_thread as usize, 0, vm_only, 0, null, null, 0),
+        SysError::EINVAL.ret()
+    );
+
+    let flags = THREAD_FLAGS
+        | CloneFlags::CLONE_SETTLS
+        | CloneFlags::CLONE_PARENT_SETTID
+        | CloneFlags::CLONE_CHILD_SETTID
+        | CloneFlags::CLONE_CHILD_CLEARTID;
+    let stack_top = unsafe { STACK.0.as_ptr_range().end } as usize;
+    let tid = clone(
+        child_thread as usize,
+        42,
+        flags,
+        stack_top,
+        PARENT_TID.as_ptr(),
+        CHILD_TID.as_ptr(),
+        TLS,
+    );
+    assert!(tid > 0);
+    assert_eq!(PARENT_TID.load(Ordering::SeqCst), tid as u32);
+    assert_eq!(waittid(tid as usize), 5);
+    assert_eq!(SHARED.load(Ordering::SeqCst), 1);
+    // CLONE_CHILD_CLEARTID cleared the tid when the thread exited
+    assert_eq!(CHILD_TID.load(Ordering::SeqCst), 0);
+    // the parent's thread pointer is untouched
+    assert_ne!(thread_pointer(), TLS);
+
+    let tid = thread_create(forking_thread as usize, 0);
+    assert_eq!(waittid(tid as usize), 0);
+    println!("clone_test passed!");
+    0
+}
@@ -0,0 +1,117 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use user_lib::{
+    enable_deadlock_detect, exit, mutex_blocking_create, mutex_lock, mutex_unlock,
+    semaphore_create, semaphore_down, semaphore_up, sleep, thread_create, waittid, yield_,
+    SysError,
+};
+
+const MUTEX: usize = 0;
+const SEMAPHORE: usize = 1;
+
+static THREAD_HOLDS: AtomicBool = AtomicBool::new(false);
+static MAIN_HOLDS: AtomicBool = AtomicBool::new(false);
+static DEADLOCKS: AtomicUsize = AtomicUsize::new(0);
+
+fn acquire(kind: usize, id: usize) -> bool {
+    let ret = if kind == MUTEX {
+        mutex_lock(id)
+    } else {
+        semaphore_down(id)
+    };
+    if SysError::check(ret) == Err(SysError::EDEADLK) {
+        DEADLOCKS.fetch_add(1, Ordering::Relaxed);
+        return false;
+    }
+    assert_eq!(ret, 0);
+    true
+}
+
+fn release(kind: usize, id: usize) {
+    let ret = if kind == MUTEX {
+        mutex_unlock(id)
+    } else {
+        semaphore_up(id)
+    };
+    assert_eq!(ret, 0);
+}
+
+/// 先拿 0 号再拿 1 号，和主线程的顺序相反
+fn crossed(kind: usize) -> ! {
+    acquire(kind, 0);
+    THREAD_HOLDS.store(true, Ordering::Release);
+    while !MAIN_HOLDS.load(Ordering::Acquire) {
+        yield_();
+    }
+    if acquire(kind, 1) {
+        release(kind, 1);
+    }
+    release(kind, 0);
+    exit(0)
+}
+
+/// 两个线程以相反的顺序申请两个资源，先让等待成环的一方失败
+fn test_crossed(kind: usize) {
+    THREAD_HOLDS.store(false, Ordering::Relaxed);
+    MAIN_HOLDS.store(false, Ordering::Relaxed);
+    DEADLOCKS.store(0, Ordering::Relaxed);
+    for expected in 0..2 {
+        let id = if kind == MUTEX {
+            mutex_blocking_create()
+        } else {
+            semaphore_create(1)
+        };
+        assert_eq!(id, expected);
+    }
+    let tid = thread_create(crossed as usize, kind) as usize;
+    while !THREAD_HOLDS.load(Ordering::Acquire) {
+        yield_();
+    }
+    acquire(kind, 1);
+    MAIN_HOLDS.store(true, Ordering::Release);
+    sleep(20);
+    if acquire(kind, 0) {
+        release(kind, 0);
+    }
+    release(kind, 1);
+    assert_eq!(waittid(tid), 0);
+    assert_eq!(DEADLOCKS.load(Ordering::Relaxed), 1);
+}
+
+fn contender() -> ! {
+    for _ in 0..100 {
+        assert!(acquire(MUTEX, 0));
+        release(MUTEX, 0);
+    }
+    exit(0)
+}
+
+/// 只有一把锁时怎么竞争都不会死锁
+fn test_no_false_positive() {
+    DEADLOCKS.store(0, Ordering::Relaxed);
+    let threads: Vec<_> = (0..4)
+        .map(|_| thread_create(contender as usize, 0) as usize)
+        .collect();
+    for tid in threads {
+        assert_eq!(waittid(tid), 0);
+    }
+    assert_eq!(DEADLOCKS.load(Ordering::Relaxed), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(enable_deadlock_detect(true), 0);
+    test_crossed(MUTEX);
+    test_crossed(SEMAPHORE);
+    test_no_false_positive();
+    println!("deadlock_test passed!");
+    0
+}
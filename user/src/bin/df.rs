@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{statfs, StatFs};
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    // argv 中的字符串在内存中以 \0 结尾，可以直接传给内核
+    let path = if argc > 1 { argv[1] } else { "/\0" };
+    let mut stat = StatFs::default();
+    if statfs(path, &mut stat) != 0 {
+        println!("df: cannot stat {}", path.trim_end_matches('\0'));
+        return -1;
+    }
+    let kib = |blocks: u64| blocks * stat.f_bsize / 1024;
+    let used = stat.f_blocks - stat.f_bfree;
+    let percent = if stat.f_blocks == 0 {
+        0
+    } else {
+        (used * 100 + stat.f_blocks - 1) / stat.f_blocks
+    };
+    println!(
+        "{:>10} {:>10} {:>10} {:>5}",
+        "1K-blocks", "Used", "Available", "Use%"
+    );
+    println!(
+        "{:>10} {:>10} {:>10} {:>4}%",
+        kib(stat.f_blocks),
+        kib(used),
+        kib(stat.f_bavail),
+        percent
+    );
+    println!(
+        "inodes: {} total, {} used, {} free; block size {}, max name length {}",
+        stat.f_files,
+        stat.f_files - stat.f_ffree,
+        stat.f_ffree,
+        stat.f_bsize,
+        stat.f_namelen
+    );
+    0
+}
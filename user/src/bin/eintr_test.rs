@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use user_lib::{
+    close, exit, fork, get_time, getpid, kill, nanosleep, pipe, read, sigaction, sleep, waitpid,
+    SignalAction, SignalFlags, SysError, TimeSpec, SIGKILL, SIGUSR1,
+};
+
+static HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn on_usr1(_signum: i32) {
+    HANDLED.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Send SIGUSR1 to the current process from a child after `delay_ms`
+fn signal_self_later(delay_ms: usize) -> usize {
+    let parent = getpid() as usize;
+    let child = fork();
+    if child == 0 {
+        sleep(delay_ms);
+        kill(parent, SIGUSR1);
+        exit(0);
+    }
+    child as usize
+}
+
+fn reap(pid: usize) -> i32 {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+    exit_code
+}
+
+/// A caught signal ends a read on an empty pipe with EINTR
+fn test_pipe_read() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let handled = HANDLED.load(Ordering::SeqCst);
+    let child = signal_self_later(20);
+    let mut buf = [0u8; 8];
+    assert_eq!(
+        SysError::check(read(fds[0], &mut buf)),
+        Err(SysError::EINTR)
+    );
+    assert_eq!(HANDLED.load(Ordering::SeqCst), handled + 1);
+    assert_eq!(reap(child), 0);
+    close(fds[0]);
+    close(fds[1]);
+}
+
+/// SIGKILL gets rid of a process blocked on an empty pipe
+fn test_kill_blocked() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let child = fork();
+    if child == 0 {
+        let mut buf = [0u8; 8];
+        read(fds[0], &mut buf);
+        exit(1);
+    }
+    sleep(20);
+    assert_eq!(kill(child as usize, SIGKILL), 0);
+    assert_eq!(reap(child as usize), -SIGKILL);
+    close(fds[0]);
+    close(fds[1]);
+}
+
+/// A caught signal cuts a sleep short
+fn test_nanosleep() {
+    let start = get_time();
+    let child = signal_self_later(20);
+    assert_eq!(
+        SysError::check(nanosleep(&TimeSpec::from_ms(1000))),
+        Err(SysError::EINTR)
+    );
+    assert!(get_time() - start < 1000);
+    assert_eq!(reap(child), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let action = SignalAction::new(on_usr1, SignalFlags::empty());
+    assert_eq!(sigaction(SIGUSR1, Some(&action), None), 0);
+    test_pipe_read();
+    test_kill_blocked();
+    test_nanosleep();
+    println!("eintr_test passed!");
+    0
+}
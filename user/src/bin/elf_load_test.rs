@@ -0,0 +1,114 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exec, fork, getauxval, open, waitpid, write, OpenFlags, SysError, AT_BASE};
+
+const IMAGE: &str = "elf_load_image\0";
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+const PT_LOAD: u32 = 1;
+const PT_INTERP: u32 = 3;
+
+/// pcaddu12i a0, 0; srli.d a0, a0, 40; li.w a7, 93; syscall 0
+/// 以 pc 的高位作为退出码，位置无关
+const CODE: [u32; 4] = [0x1c00_0004, 0x0045_a084, 0x0381_740b, 0x002b_0000];
+const INTERP: &[u8] = b"/no_such_interp\0";
+
+fn put(image: &mut [u8], offset: usize, bytes: &[u8]) {
+    image[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+/// 一个只有代码段的 ET_DYN 程序，`with_interp` 时再加上一个 PT_INTERP
+fn build_image(with_interp: bool) -> ([u8; 512], usize) {
+    let mut image = [0u8; 512];
+    let phnum = if with_interp { 2 } else { 1 };
+    let code_offset = EHDR_SIZE + phnum * PHDR_SIZE;
+    let interp_offset = code_offset + CODE.len() * 4;
+    let size = interp_offset + if with_interp { INTERP.len() } else { 0 };
+    put(&mut image, 0, &[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+    put(&mut image, 16, &3u16.to_le_bytes()); // ET_DYN
+    put(&mut image, 18, &258u16.to_le_bytes()); // EM_LOONGARCH
+    put(&mut image, 20, &1u32.to_le_bytes());
+    put(&mut image, 24, &(code_offset as u64).to_le_bytes());
+    put(&mut image, 32, &(EHDR_SIZE as u64).to_le_bytes());
+    put(&mut image, 52, &(EHDR_SIZE as u16).to_le_bytes());
+    put(&mut image, 54, &(PHDR_SIZE as u16).to_le_bytes());
+    put(&mut image, 56, &(phnum as u16).to_le_bytes());
+    // PT_LOAD covering the whole file, R|X
+    let ph = EHDR_SIZE;
+    put(&mut image, ph, &PT_LOAD.to_le_bytes());
+    put(&mut image, ph + 4, &5u32.to_le_bytes());
+    put(&mut image, ph + 32, &(size as u64).to_le_bytes());
+    put(&mut image, ph + 40, &(size as u64).to_le_bytes());
+    put(&mut image, ph + 48, &0x4000u64.to_le_bytes());
+    if with_interp {
+        let ph = EHDR_SIZE + PHDR_SIZE;
+        put(&mut image, ph, &PT_INTERP.to_le_bytes());
+        put(&mut image, ph + 4, &4u32.to_le_bytes());
+        put(&mut image, ph + 8, &(interp_offset as u64).to_le_bytes());
+        put(&mut image, ph + 16, &(interp_offset as u64).to_le_bytes());
+        put(&mut image, ph + 32, &(INTERP.len() as u64).to_le_bytes());
+        put(&mut image, ph + 40, &(INTERP.len() as u64).to_le_bytes());
+        put(&mut image, interp_offset, INTERP);
+    }
+    for (i, inst) in CODE.iter().enumerate() {
+        put(&mut image, code_offset + i * 4, &inst.to_le_bytes());
+    }
+    (image, size)
+}
+
+fn write_image(image: &[u8]) {
+    let fd = open(
+        IMAGE,
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    );
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, image), image.len() as isize);
+    close(fd as usize);
+}
+
+/// 在子进程中 exec 这个文件，返回 exec 的错误或者子进程的退出码
+fn run_image(image: &[u8]) -> Result<i32, SysError> {
+    write_image(image);
+    let pid = fork();
+    if pid == 0 {
+        let ret = exec(IMAGE, &[IMAGE.as_ptr(), core::ptr::null()]);
+        // exec failed, report the errno as the exit code
+        user_lib::exit(1000 + SysError::check(ret).unwrap_err() as i32);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    if exit_code >= 1000 {
+        Err(SysError::from_errno((exit_code - 1000) as isize).unwrap())
+    } else {
+        Ok(exit_code)
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // a static program runs without an interpreter
+    assert_eq!(getauxval(AT_BASE), Some(0));
+
+    // loaded somewhere above ELF_DYN_BASE (0x0400_0000_0000)
+    let (image, size) = build_image(false);
+    assert_eq!(run_image(&image[..size]), Ok(4));
+
+    assert_eq!(run_image(b"#!not an elf\n"), Err(SysError::ENOEXEC));
+    // truncated: the segment is not in the file
+    assert_eq!(run_image(&image[..size - 4]), Err(SysError::ENOEXEC));
+    let mut wrong_machine = image;
+    put(&mut wrong_machine, 18, &62u16.to_le_bytes());
+    assert_eq!(run_image(&wrong_machine[..size]), Err(SysError::ENOEXEC));
+    let mut bad_align = image;
+    put(&mut bad_align, EHDR_SIZE + 16, &0x100u64.to_le_bytes());
+    assert_eq!(run_image(&bad_align[..size]), Err(SysError::ENOEXEC));
+
+    let (image, size) = build_image(true);
+    assert_eq!(run_image(&image[..size]), Err(SysError::ENOENT));
+    println!("elf_load_test passed!");
+    0
+}
@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exec, execve, exit, fork, getauxval, getenv, waitpid, AT_ENTRY, AT_PAGESZ, AT_PHENT, AT_PHNUM,
+    AT_RANDOM,
+};
+
+/// 子进程先用给定的环境变量运行自己，再用 exec 把它们传下去
+fn run_explicit() -> i32 {
+    assert_eq!(getenv("FOO"), Some("bar"));
+    assert_eq!(getenv("EMPTY"), Some(""));
+    assert_eq!(getenv("FO"), None);
+    // nothing else leaks in from the parent
+    assert_eq!(getenv("HOME"), None);
+    let args = [
+        "env_test\0".as_ptr(),
+        "inherited\0".as_ptr(),
+        core::ptr::null(),
+    ];
+    exec("env_test\0", &args);
+    -1
+}
+
+fn run_inherited() -> i32 {
+    assert_eq!(getenv("FOO"), Some("bar"));
+    assert_eq!(getauxval(AT_PAGESZ), Some(0x4000));
+    assert_eq!(getauxval(AT_ENTRY), Some(user_lib::_start as usize));
+    assert_eq!(getauxval(AT_PHENT), Some(56));
+    assert!(getauxval(AT_PHNUM).unwrap() > 0);
+    let random = getauxval(AT_RANDOM).unwrap() as *const [u8; 16];
+    assert_ne!(unsafe { random.read() }, [0; 16]);
+    0
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    match argc {
+        2 if argv[1] == "explicit" => return run_explicit(),
+        2 if argv[1] == "inherited" => return run_inherited(),
+        _ => {}
+    }
+    let pid = fork();
+    if pid == 0 {
+        let args = [
+            "env_test\0".as_ptr(),
+            "explicit\0".as_ptr(),
+            core::ptr::null(),
+        ];
+        let envs = ["FOO=bar\0".as_ptr(), "EMPTY=\0".as_ptr(), core::ptr::null()];
+        execve("env_test\0", &args, &envs);
+        exit(-1);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("env_test passed!");
+    0
+}
@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exec, gettid, kill, mutex_blocking_create, mutex_lock, mutex_unlock, open, pipe, read,
+    wait, waittid, write, OpenFlags, SysError, SIGTERM,
+};
+
+/// 内核没有映射的地址
+const BAD_ADDRESS: usize = 0x10;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // file descriptors
+    assert_eq!(close(1000), SysError::EBADF.ret());
+    assert_eq!(read(1000, &mut [0u8; 4]), SysError::EBADF.ret());
+    assert_eq!(
+        open("errno_test_missing\0", OpenFlags::RDONLY),
+        SysError::ENOENT.ret()
+    );
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(write(pipe_fd[0], b"x"), SysError::EBADF.ret());
+
+    // user pointers are checked instead of trusted
+    assert_eq!(write(pipe_fd[1], b"x"), 1);
+    let bad_buffer = unsafe { core::slice::from_raw_parts_mut(BAD_ADDRESS as *mut u8, 4) };
+    assert_eq!(read(pipe_fd[0], bad_buffer), SysError::EFAULT.ret());
+    // nothing was consumed by the failed read
+    let mut buffer = [0u8; 4];
+    assert_eq!(read(pipe_fd[0], &mut buffer), 1);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    let args = [BAD_ADDRESS as *const u8, core::ptr::null()];
+    assert_eq!(exec("hello_world\0", &args), SysError::EFAULT.ret());
+
+    // processes and threads
+    assert_eq!(wait(&mut 0), SysError::ECHILD.ret());
+    assert_eq!(kill(0x7fff_ffff, SIGTERM), SysError::ESRCH.ret());
+    assert_eq!(waittid(gettid() as usize), SysError::EDEADLK.ret());
+    assert_eq!(waittid(1000), SysError::ESRCH.ret());
+
+    // synchronization primitives
+    assert_eq!(mutex_lock(1000), SysError::EINVAL.ret());
+    let mutex = mutex_blocking_create() as usize;
+    assert_eq!(mutex_unlock(mutex), SysError::EPERM.ret());
+    assert_eq!(mutex_lock(mutex), 0);
+    assert_eq!(mutex_unlock(mutex), 0);
+    println!("errno_test passed!");
+    0
+}
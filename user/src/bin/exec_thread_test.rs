@@ -0,0 +1,88 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exec, exit, exit_group, fork, gettid, mutex_blocking_create, mutex_lock, sleep, thread_create,
+    waitpid, waittid, yield_,
+};
+
+static mut MUTEX: usize = 0;
+
+fn spinner() -> ! {
+    loop {
+        yield_();
+    }
+}
+
+fn sleeper() -> ! {
+    loop {
+        sleep(1000);
+    }
+}
+
+fn mutex_waiter() -> ! {
+    // main thread holds the mutex forever
+    mutex_lock(unsafe { MUTEX });
+    panic!("the mutex was never unlocked");
+}
+
+fn exec_from_thread() -> ! {
+    let args = [
+        "exec_thread_test\0".as_ptr(),
+        "after\0".as_ptr(),
+        core::ptr::null(),
+    ];
+    exec("exec_thread_test\0", &args);
+    panic!("exec failed");
+}
+
+fn exit_whole_process() -> ! {
+    exit_group(7)
+}
+
+fn short_thread() -> ! {
+    exit(3)
+}
+
+/// 在 exec 之后运行：只剩调用 exec 的线程，它成了主线程
+fn after_exec() -> i32 {
+    assert_eq!(gettid(), 0);
+    // exit_group from another thread ends the process with its exit code
+    let pid = fork();
+    if pid == 0 {
+        thread_create(sleeper as usize, 0);
+        thread_create(exit_whole_process as usize, 0);
+        sleeper();
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 7);
+
+    // tids start over in the new program
+    let tid = thread_create(short_thread as usize, 0);
+    assert_eq!(tid, 1);
+    assert_eq!(waittid(tid as usize), 3);
+    println!("exec_thread_test passed!");
+    0
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc == 2 && argv[1] == "after" {
+        return after_exec();
+    }
+    unsafe {
+        MUTEX = mutex_blocking_create() as usize;
+        mutex_lock(MUTEX);
+    }
+    thread_create(spinner as usize, 0);
+    thread_create(sleeper as usize, 0);
+    thread_create(mutex_waiter as usize, 0);
+    let tid = thread_create(exec_from_thread as usize, 0);
+    // the exec kills this thread while it waits
+    waittid(tid as usize);
+    panic!("the main thread survived exec");
+}
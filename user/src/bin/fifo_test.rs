@@ -0,0 +1,66 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fcntl, fork, mkfifo, open, read, wait, write, OpenFlags, SysError, F_GETPIPE_SZ,
+    F_SETPIPE_SZ,
+};
+
+static FIFO: &str = "fifo_test_pipe\0";
+const PAGE_SIZE: usize = 0x4000;
+const LENGTH: usize = 3 * PAGE_SIZE;
+const CHUNK: usize = 1024;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the fifo stays in the filesystem, it may be left by an earlier run
+    mkfifo(FIFO);
+    if fork() == 0 {
+        // blocks until the parent opens the read end
+        let fd = open(FIFO, OpenFlags::WRONLY);
+        assert!(fd >= 0);
+        let fd = fd as usize;
+        assert_eq!(fcntl(fd, F_GETPIPE_SZ, 0), PAGE_SIZE as isize);
+        // round up to whole pages
+        assert_eq!(
+            fcntl(fd, F_SETPIPE_SZ, LENGTH + 1),
+            (LENGTH + PAGE_SIZE) as isize
+        );
+        assert_eq!(
+            fcntl(fd, F_SETPIPE_SZ, usize::MAX / 2),
+            SysError::EINVAL.ret()
+        );
+        let data = [b'x'; CHUNK];
+        for _ in 0..LENGTH / CHUNK {
+            assert_eq!(write(fd, &data), CHUNK as isize);
+        }
+        close(fd);
+        0
+    } else {
+        let fd = open(FIFO, OpenFlags::RDONLY);
+        assert!(fd >= 0);
+        let fd = fd as usize;
+        let mut buffer = [0u8; CHUNK];
+        let mut total = 0usize;
+        // read until the writer closes its end
+        loop {
+            let len = read(fd, &mut buffer);
+            assert!(len >= 0);
+            if len == 0 {
+                break;
+            }
+            assert!(buffer[..len as usize].iter().all(|b| *b == b'x'));
+            total += len as usize;
+        }
+        assert_eq!(total, LENGTH);
+        close(fd);
+        let mut exit_code: i32 = 0;
+        wait(&mut exit_code);
+        assert_eq!(exit_code, 0);
+        println!("fifo_test passed!");
+        0
+    }
+}
@@ -0,0 +1,81 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fcntl_lock, flock, fork, get_time, getpid, open, sleep, wait, Flock, OpenFlags,
+    EAGAIN, F_GETLK, F_RDLCK, F_SETLK, F_SETLKW, F_UNLCK, F_WRLCK, LOCK_EX, LOCK_NB, LOCK_SH,
+    LOCK_UN,
+};
+
+static FILE: &str = "flock_test_file\0";
+
+fn open_file() -> usize {
+    let fd = open(FILE, OpenFlags::RDWR | OpenFlags::CREATE);
+    assert!(fd >= 0);
+    fd as usize
+}
+
+fn wait_child() {
+    let mut exit_code: i32 = 0;
+    wait(&mut exit_code);
+    assert_eq!(exit_code, 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open_file();
+    // flock: the lock belongs to the open file, the child opens its own
+    assert_eq!(flock(fd, LOCK_EX), 0);
+    if fork() == 0 {
+        let fd = open_file();
+        assert_eq!(flock(fd, LOCK_SH | LOCK_NB), EAGAIN);
+        assert_eq!(flock(fd, LOCK_EX | LOCK_NB), EAGAIN);
+        let start = get_time();
+        assert_eq!(flock(fd, LOCK_EX), 0);
+        assert!(get_time() - start >= 100);
+        // released when the child exits
+        exit(0);
+    }
+    sleep(100);
+    assert_eq!(flock(fd, LOCK_UN), 0);
+    wait_child();
+    assert_eq!(flock(fd, LOCK_SH | LOCK_NB), 0);
+    assert_eq!(flock(fd, LOCK_UN), 0);
+
+    // fcntl record locks belong to the process
+    let parent = getpid() as i32;
+    let mut lock = Flock::new(F_WRLCK, 0, 10);
+    assert_eq!(fcntl_lock(fd, F_SETLK, &mut lock), 0);
+    if fork() == 0 {
+        let fd = open_file();
+        let mut query = Flock::new(F_RDLCK, 5, 1);
+        assert_eq!(fcntl_lock(fd, F_GETLK, &mut query), 0);
+        assert_eq!(query.l_type, F_WRLCK);
+        assert_eq!((query.l_start, query.l_len, query.l_pid), (0, 10, parent));
+        // disjoint ranges do not conflict
+        let mut lock = Flock::new(F_WRLCK, 10, 10);
+        assert_eq!(fcntl_lock(fd, F_SETLK, &mut lock), 0);
+        let mut lock = Flock::new(F_RDLCK, 0, 5);
+        assert_eq!(fcntl_lock(fd, F_SETLK, &mut lock), EAGAIN);
+        let start = get_time();
+        assert_eq!(fcntl_lock(fd, F_SETLKW, &mut lock), 0);
+        assert!(get_time() - start >= 100);
+        close(fd);
+        exit(0);
+    }
+    sleep(100);
+    // unlocking a part of the lock keeps the rest, [5, 10) is still held
+    let mut unlock = Flock::new(F_UNLCK, 0, 5);
+    assert_eq!(fcntl_lock(fd, F_SETLK, &mut unlock), 0);
+    wait_child();
+    // closing the fd in the child dropped all its locks
+    let mut query = Flock::new(F_WRLCK, 0, 0);
+    assert_eq!(fcntl_lock(fd, F_GETLK, &mut query), 0);
+    assert_eq!(query.l_type, F_UNLCK);
+    close(fd);
+    println!("flock_test passed!");
+    0
+}
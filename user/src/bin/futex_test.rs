@@ -0,0 +1,159 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use user_lib::{
+    clone, exit, futex_requeue, futex_wait, futex_wake, get_time, sleep, thread_create, waittid,
+    CloneFlags, Condvar, Mutex, SysError, TimeSpec, THREAD_FLAGS,
+};
+
+const THREAD_COUNT: usize = 8;
+const PER_THREAD: usize = 1000;
+
+fn errno(ret: isize) -> SysError {
+    SysError::check(ret).unwrap_err()
+}
+
+fn test_wait_wake() {
+    let futex = AtomicU32::new(1);
+    // 值不同时立即返回
+    assert_eq!(errno(futex_wait(&futex, 0, None)), SysError::EAGAIN);
+    let start = get_time();
+    let timeout = TimeSpec::from_ms(50);
+    assert_eq!(
+        errno(futex_wait(&futex, 1, Some(&timeout))),
+        SysError::ETIMEDOUT
+    );
+    assert!(get_time() - start >= 50);
+    assert_eq!(futex_wake(&futex, 1), 0);
+}
+
+static LOCK: Mutex = Mutex::new();
+static mut COUNTER: usize = 0;
+
+fn adder() -> ! {
+    for _ in 0..PER_THREAD {
+        LOCK.lock();
+        unsafe {
+            let counter = core::ptr::addr_of_mut!(COUNTER);
+            counter.write_volatile(counter.read_volatile() + 1);
+        }
+        LOCK.unlock();
+    }
+    exit(0)
+}
+
+fn test_mutex() {
+    assert!(LOCK.try_lock());
+    assert!(!LOCK.try_lock());
+    LOCK.unlock();
+    let threads: Vec<_> = (0..THREAD_COUNT)
+        .map(|_| thread_create(adder as usize, 0) as usize)
+        .collect();
+    for tid in threads {
+        assert_eq!(waittid(tid), 0);
+    }
+    assert_eq!(unsafe { COUNTER }, THREAD_COUNT * PER_THREAD);
+}
+
+static QUEUE_LOCK: Mutex = Mutex::new();
+static NOT_EMPTY: Condvar = Condvar::new();
+static ITEMS: AtomicUsize = AtomicUsize::new(0);
+
+fn consumer() -> ! {
+    for _ in 0..PER_THREAD {
+        QUEUE_LOCK.lock();
+        while ITEMS.load(Ordering::Relaxed) == 0 {
+            NOT_EMPTY.wait(&QUEUE_LOCK);
+        }
+        ITEMS.fetch_sub(1, Ordering::Relaxed);
+        QUEUE_LOCK.unlock();
+    }
+    exit(0)
+}
+
+fn test_condvar() {
+    let threads: Vec<_> = (0..2)
+        .map(|_| thread_create(consumer as usize, 0) as usize)
+        .collect();
+    for _ in 0..2 * PER_THREAD {
+        QUEUE_LOCK.lock();
+        ITEMS.fetch_add(1, Ordering::Relaxed);
+        NOT_EMPTY.notify_one();
+        QUEUE_LOCK.unlock();
+    }
+    for tid in threads {
+        assert_eq!(waittid(tid), 0);
+    }
+    assert_eq!(ITEMS.load(Ordering::Relaxed), 0);
+    // 没有人通知时超时返回
+    QUEUE_LOCK.lock();
+    assert!(!NOT_EMPTY.wait_timeout(&QUEUE_LOCK, Some(&TimeSpec::from_ms(20))));
+    QUEUE_LOCK.unlock();
+}
+
+static GATE: AtomicU32 = AtomicU32::new(0);
+static TARGET: AtomicU32 = AtomicU32::new(0);
+
+fn gate_waiter() -> ! {
+    while GATE.load(Ordering::Acquire) == 0 {
+        futex_wait(&GATE, 0, None);
+    }
+    exit(0)
+}
+
+fn test_requeue() {
+    let threads: Vec<_> = (0..THREAD_COUNT)
+        .map(|_| thread_create(gate_waiter as usize, 0) as usize)
+        .collect();
+    sleep(20);
+    GATE.store(1, Ordering::Release);
+    // 值已经改变，之后不会再有线程在 GATE 上睡眠
+    let woken = futex_requeue(&GATE, 1, &TARGET, usize::MAX);
+    assert!(woken <= 1);
+    assert_eq!(futex_wake(&GATE, usize::MAX), 0);
+    futex_wake(&TARGET, usize::MAX);
+    for tid in threads {
+        assert_eq!(waittid(tid), 0);
+    }
+}
+
+static CHILD_TID: AtomicU32 = AtomicU32::new(0);
+
+fn short_lived() -> ! {
+    sleep(10);
+    exit(0)
+}
+
+fn test_clear_child_tid() {
+    let flags = THREAD_FLAGS | CloneFlags::CLONE_PARENT_SETTID | CloneFlags::CLONE_CHILD_CLEARTID;
+    let tid_ptr = CHILD_TID.as_ptr();
+    let tid = clone(short_lived as usize, 0, flags, 0, tid_ptr, tid_ptr, 0);
+    assert!(tid > 0);
+    // 线程退出时内核清零并唤醒
+    loop {
+        let current = CHILD_TID.load(Ordering::Acquire);
+        if current == 0 {
+            break;
+        }
+        futex_wait(&CHILD_TID, current, None);
+    }
+    waittid(tid as usize);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    test_wait_wake();
+    test_mutex();
+    test_condvar();
+    test_requeue();
+    test_clear_child_tid();
+    println!("futex_test passed!");
+    0
+}
@@ -3,17 +3,22 @@
 
 extern crate user_lib;
 
-use user_lib::{exec, fork, wait, yield_};
+use user_lib::{execve, fork, wait, yield_};
 
 #[no_mangle]
 fn main() -> i32 {
     if fork() == 0 {
-        exec("user_shell\0", &[core::ptr::null::<u8>()]);
+        let envs = [
+            "HOME=/\0".as_ptr(),
+            "PATH=/\0".as_ptr(),
+            core::ptr::null::<u8>(),
+        ];
+        execve("user_shell\0", &[core::ptr::null::<u8>()], &envs);
     } else {
         loop {
             let mut exit_code: i32 = 0;
             let pid = wait(&mut exit_code);
-            if pid == -1 {
+            if pid < 0 {
                 yield_();
                 continue;
             }
@@ -0,0 +1,196 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use user_lib::{
+    alarm, clock_now, close, get_time, getitimer, read, setitimer, sigaction, sleep,
+    timerfd_create, timerfd_gettime, timerfd_settime, ITimerSpec, ITimerVal, OpenFlags,
+    SignalAction, SignalFlags, SysError, SysResult, TimeSpec, TimeVal, CLOCK_MONOTONIC,
+    CLOCK_REALTIME, ITIMER_PROF, ITIMER_REAL, ITIMER_VIRTUAL, SIGALRM, SIGPROF, SIGVTALRM,
+    TFD_TIMER_ABSTIME,
+};
+
+static ALARMS: AtomicUsize = AtomicUsize::new(0);
+static VTALARMS: AtomicUsize = AtomicUsize::new(0);
+static PROFS: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn on_alarm(signum: i32) {
+    let counter = match signum {
+        SIGALRM => &ALARMS,
+        SIGVTALRM => &VTALARMS,
+        _ => &PROFS,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+fn itimerval(value_ms: usize, interval_ms: usize) -> ITimerVal {
+    ITimerVal {
+        it_interval: TimeVal::from_ms(interval_ms),
+        it_value: TimeVal::from_ms(value_ms),
+    }
+}
+
+fn itimerspec(value_ms: usize, interval_ms: usize) -> ITimerSpec {
+    ITimerSpec {
+        it_interval: TimeSpec::from_ms(interval_ms),
+        it_value: TimeSpec::from_ms(value_ms),
+    }
+}
+
+/// 睡眠等到 `counter` 达到 `count`，最多等 1 秒
+fn wait_count(counter: &AtomicUsize, count: usize) -> bool {
+    let start = get_time();
+    while counter.load(Ordering::Relaxed) < count {
+        if get_time() - start > 1000 {
+            return false;
+        }
+        sleep(1);
+    }
+    true
+}
+
+fn test_alarm() {
+    assert_eq!(alarm(5), 0);
+    assert_eq!(alarm(0), 5);
+    let mut curr = ITimerVal::default();
+    assert_eq!(getitimer(ITIMER_REAL, &mut curr), 0);
+    assert_eq!(curr.it_value, TimeVal::default());
+    assert_eq!(
+        SysError::check(setitimer(3, &itimerval(10, 0), None)),
+        Err(SysError::EINVAL)
+    );
+}
+
+fn test_real() {
+    let start = get_time();
+    assert_eq!(setitimer(ITIMER_REAL, &itimerval(30, 0), None), 0);
+    let mut curr = ITimerVal::default();
+    assert_eq!(getitimer(ITIMER_REAL, &mut curr), 0);
+    assert!(curr.it_value.to_us() > 0 && curr.it_value.to_us() <= 30_000);
+    assert!(wait_count(&ALARMS, 1));
+    assert!(get_time() - start >= 30);
+    // 单次的定时器到期后停止
+    assert_eq!(getitimer(ITIMER_REAL, &mut curr), 0);
+    assert_eq!(curr.it_value, TimeVal::default());
+
+    // 周期定时器一直到期，直到被停止
+    assert_eq!(setitimer(ITIMER_REAL, &itimerval(10, 10), None), 0);
+    assert!(wait_count(&ALARMS, 4));
+    let mut old = ITimerVal::default();
+    assert_eq!(
+        setitimer(ITIMER_REAL, &ITimerVal::default(), Some(&mut old)),
+        0
+    );
+    assert_eq!(old.it_interval, TimeVal::from_ms(10));
+    let alarms = ALARMS.load(Ordering::Relaxed);
+    sleep(30);
+    assert_eq!(ALARMS.load(Ordering::Relaxed), alarms);
+}
+
+/// 占用 CPU 直到 `counter` 增加，`syscalls` 为真时一直进入内核，最多等 2 秒
+fn spin_until(counter: &AtomicUsize, syscalls: bool) -> bool {
+    let start = get_time();
+    let mut spins = 0usize;
+    while counter.load(Ordering::Relaxed) == 0 {
+        if (syscalls || spins % 4096 == 0) && get_time() - start > 2000 {
+            return false;
+        }
+        spins = spins.wrapping_add(1);
+    }
+    true
+}
+
+fn test_cpu_timers() {
+    let start = get_time();
+    assert_eq!(setitimer(ITIMER_VIRTUAL, &itimerval(20, 0), None), 0);
+    assert!(spin_until(&VTALARMS, false));
+    assert!(get_time() - start >= 20);
+
+    // 睡眠的时间不计入 ITIMER_PROF
+    assert_eq!(setitimer(ITIMER_PROF, &itimerval(20, 0), None), 0);
+    sleep(50);
+    assert_eq!(PROFS.load(Ordering::Relaxed), 0);
+    let mut curr = ITimerVal::default();
+    assert_eq!(getitimer(ITIMER_PROF, &mut curr), 0);
+    assert!(curr.it_value.to_us() > 0);
+    assert!(spin_until(&PROFS, true));
+}
+
+fn read_expirations(fd: usize) -> SysResult<u64> {
+    let mut buf = [0u8; 8];
+    SysError::check(read(fd, &mut buf))?;
+    Ok(u64::from_ne_bytes(buf))
+}
+
+fn test_timerfd() {
+    assert_eq!(
+        SysError::check(timerfd_create(42, OpenFlags::empty())),
+        Err(SysError::EINVAL)
+    );
+    let mut curr = ITimerSpec::default();
+    assert_eq!(
+        SysError::check(timerfd_gettime(0, &mut curr)),
+        Err(SysError::EINVAL)
+    );
+
+    // 还没有到期时非阻塞地读返回 EAGAIN
+    let fd = timerfd_create(CLOCK_MONOTONIC, OpenFlags::NONBLOCK) as usize;
+    assert_eq!(read_expirations(fd), Err(SysError::EAGAIN));
+    assert_eq!(timerfd_settime(fd, 0, &itimerspec(10, 0), None), 0);
+    sleep(30);
+    assert_eq!(read_expirations(fd), Ok(1));
+    assert_eq!(read_expirations(fd), Err(SysError::EAGAIN));
+    assert_eq!(close(fd), 0);
+
+    // 阻塞地读等到定时器到期，错过的到期也被计入
+    let fd = timerfd_create(CLOCK_MONOTONIC, OpenFlags::empty()) as usize;
+    let start = get_time();
+    assert_eq!(timerfd_settime(fd, 0, &itimerspec(20, 10), None), 0);
+    assert!(read_expirations(fd).unwrap() >= 1);
+    assert!(get_time() - start >= 20);
+    assert_eq!(timerfd_gettime(fd, &mut curr), 0);
+    assert_eq!(curr.it_interval.to_ns(), 10_000_000);
+    assert!(curr.it_value.to_ns() > 0 && curr.it_value.to_ns() <= 10_000_000);
+    sleep(35);
+    assert!(read_expirations(fd).unwrap() >= 3);
+    let mut old = ITimerSpec::default();
+    assert_eq!(
+        timerfd_settime(fd, 0, &ITimerSpec::default(), Some(&mut old)),
+        0
+    );
+    assert_eq!(old.it_interval.to_ns(), 10_000_000);
+    assert_eq!(timerfd_gettime(fd, &mut curr), 0);
+    assert_eq!(curr.it_value.to_ns(), 0);
+    assert_eq!(close(fd), 0);
+
+    // CLOCK_REALTIME 上的绝对时间
+    let fd = timerfd_create(CLOCK_REALTIME, OpenFlags::empty()) as usize;
+    let start = get_time();
+    let deadline = clock_now(CLOCK_REALTIME).unwrap() + 15_000_000;
+    let abs = ITimerSpec {
+        it_interval: TimeSpec::default(),
+        it_value: TimeSpec::from_ns(deadline),
+    };
+    assert_eq!(timerfd_settime(fd, TFD_TIMER_ABSTIME, &abs, None), 0);
+    assert_eq!(read_expirations(fd), Ok(1));
+    assert!(get_time() - start >= 15);
+    assert_eq!(close(fd), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let action = SignalAction::new(on_alarm, SignalFlags::empty());
+    for signum in [SIGALRM, SIGVTALRM, SIGPROF] {
+        assert_eq!(sigaction(signum, Some(&action), None), 0);
+    }
+    test_alarm();
+    test_real();
+    test_cpu_timers();
+    test_timerfd();
+    println!("itimer_test passed!");
+    0
+}
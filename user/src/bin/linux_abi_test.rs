@@ -0,0 +1,163 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    brk, clock_gettime, close, dirents, fstat, getdents64, gettid, mmap, munmap, open, openat,
+    read, readv, sbrk, set_tid_address, uname, uts_str, writev, IoVec, MmapFlags, MmapProt,
+    OpenFlags, Stat, SysError, TimeSpec, UtsName, AT_FDCWD, CLOCK_MONOTONIC, DT_DIR, DT_REG,
+    S_IFDIR, S_IFMT, S_IFREG,
+};
+
+const FILE: &str = "linux_abi_file\0";
+const PAGE_SIZE: usize = 0x4000;
+
+fn errno(ret: isize) -> SysError {
+    SysError::check(ret).unwrap_err()
+}
+
+fn test_files() {
+    // no O_CREAT, no file
+    assert_eq!(errno(open(FILE, OpenFlags::RDONLY)), SysError::ENOENT);
+    let fd = open(
+        FILE,
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    ) as usize;
+    let (head, tail) = (b"hello, ", b"writev");
+    let iov = [IoVec::from(&head[..]), IoVec::from(&tail[..])];
+    assert_eq!(writev(fd, &iov), 13);
+    let mut stat = Stat::default();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.st_mode & S_IFMT, S_IFREG);
+    assert_eq!(stat.st_size, 13);
+    close(fd);
+
+    // opening without O_TRUNC keeps the contents
+    let fd = open(FILE, OpenFlags::RDONLY) as usize;
+    let mut head = [0u8; 7];
+    let mut tail = [0u8; 16];
+    let iov = [IoVec::from(&mut head[..]), IoVec::from(&mut tail[..])];
+    assert_eq!(readv(fd, &iov), 13);
+    assert_eq!(&head, b"hello, ");
+    assert_eq!(&tail[..6], b"writev");
+    assert_eq!(
+        errno(openat(AT_FDCWD, FILE, OpenFlags::DIRECTORY)),
+        SysError::ENOTDIR
+    );
+    close(fd);
+}
+
+fn test_getdents() {
+    let fd = open("/\0", OpenFlags::RDONLY | OpenFlags::DIRECTORY) as usize;
+    let mut stat = Stat::default();
+    fstat(fd, &mut stat);
+    assert_eq!(stat.st_mode & S_IFMT, S_IFDIR);
+    assert_eq!(errno(read(fd, &mut [0u8; 8])), SysError::EISDIR);
+    // a small buffer forces several calls
+    let mut buf = [0u8; 64];
+    let (mut dots, mut found) = (0, false);
+    loop {
+        let len = getdents64(fd, &mut buf);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        for dirent in dirents(&buf[..len as usize]) {
+            match dirent.name {
+                "." | ".." => {
+                    assert_eq!(dirent.d_type, DT_DIR);
+                    dots += 1;
+                }
+                name if name == &FILE[..FILE.len() - 1] => {
+                    assert_eq!(dirent.d_type, DT_REG);
+                    found = true;
+                }
+                _ => {}
+            }
+        }
+    }
+    assert_eq!(dots, 2);
+    assert!(found);
+    close(fd);
+}
+
+fn test_memory() {
+    let prot = MmapProt::PROT_READ | MmapProt::PROT_WRITE;
+    let flags = MmapFlags::MAP_PRIVATE | MmapFlags::MAP_ANONYMOUS;
+    let addr = mmap(0, 2 * PAGE_SIZE, prot, flags, 0, 0);
+    assert!(addr > 0 && addr as usize % PAGE_SIZE == 0);
+    let area = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, 2 * PAGE_SIZE) };
+    assert!(area.iter().all(|&b| b == 0));
+    area.fill(0x5a);
+    // unmap the first page only, the second one stays
+    assert_eq!(munmap(addr as usize, PAGE_SIZE), 0);
+    assert_eq!(area[PAGE_SIZE], 0x5a);
+    assert_eq!(munmap(addr as usize + PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(
+        errno(mmap(0, PAGE_SIZE, prot, MmapFlags::MAP_SHARED, 0, 0)),
+        SysError::ENODEV
+    );
+
+    // a private file mapping sees the file contents
+    let fd = open(FILE, OpenFlags::RDONLY) as usize;
+    let addr = mmap(
+        0,
+        PAGE_SIZE,
+        MmapProt::PROT_READ,
+        MmapFlags::MAP_PRIVATE,
+        fd,
+        0,
+    );
+    assert!(addr > 0);
+    let data = unsafe { core::slice::from_raw_parts(addr as *const u8, 13) };
+    assert_eq!(data, b"hello, writev");
+    munmap(addr as usize, PAGE_SIZE);
+    // far more than the address space or memory can hold
+    assert_eq!(
+        errno(mmap(
+            0,
+            1 << 40,
+            MmapProt::PROT_READ,
+            MmapFlags::MAP_PRIVATE,
+            fd,
+            0
+        )),
+        SysError::ENOMEM
+    );
+    close(fd);
+
+    let start = brk(0);
+    assert!(start > 0);
+    assert_eq!(sbrk(3 * PAGE_SIZE as isize), start);
+    let heap = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, 3 * PAGE_SIZE) };
+    heap.fill(1);
+    assert_eq!(brk(start as usize), start);
+    // below the heap bottom the break does not move
+    assert_eq!(brk(PAGE_SIZE), start);
+}
+
+fn test_misc() {
+    let mut uts = UtsName::default();
+    assert_eq!(uname(&mut uts), 0);
+    assert_eq!(uts_str(&uts.machine), "loongarch64");
+    let (mut t0, mut t1) = (TimeSpec::default(), TimeSpec::default());
+    assert_eq!(clock_gettime(CLOCK_MONOTONIC, &mut t0), 0);
+    assert_eq!(clock_gettime(CLOCK_MONOTONIC, &mut t1), 0);
+    assert!((t1.tv_sec, t1.tv_nsec) >= (t0.tv_sec, t0.tv_nsec));
+    assert_eq!(errno(clock_gettime(100, &mut t0)), SysError::EINVAL);
+    let mut tid = 1u32;
+    assert_eq!(set_tid_address(&mut tid), gettid());
+    set_tid_address(core::ptr::null_mut());
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    test_files();
+    test_getdents();
+    test_memory();
+    test_misc();
+    println!("linux_abi_test passed!");
+    0
+}
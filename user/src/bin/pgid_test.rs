@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, getpgid, getpid, killpg, setpgid, setsid, waitpid, yield_, SysError, SIGTERM,
+};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pgid = getpgid(0);
+    assert!(pgid >= 0);
+    assert_eq!(getpgid(getpid() as usize), pgid);
+    // a child starts in our group and moves to a group of its own
+    let child = fork();
+    if child == 0 {
+        assert_eq!(getpgid(0), pgid);
+        assert_eq!(setpgid(0, 0), 0);
+        assert_eq!(getpgid(0), getpid());
+        // a group leader cannot start a session
+        assert_eq!(setsid(), SysError::EPERM.ret());
+        loop {
+            yield_();
+        }
+    }
+    // the parent may do it as well, whoever comes first
+    assert_eq!(setpgid(child as usize, child as usize), 0);
+    assert_eq!(getpgid(child as usize), child);
+    // groups that do not exist cannot be joined
+    assert_eq!(setpgid(0, 0x7fff_ffff), SysError::EPERM.ret());
+    assert_eq!(killpg(child as usize, SIGTERM), 0);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, -SIGTERM);
+    assert_eq!(killpg(child as usize, SIGTERM), SysError::ESRCH.ret());
+
+    // a new session starts a new group too
+    let child = fork();
+    if child == 0 {
+        let pid = getpid();
+        assert_eq!(setsid(), pid);
+        assert_eq!(getpgid(0), pid);
+        // a session leader stays in its own group
+        assert_eq!(setpgid(0, pgid as usize), SysError::EPERM.ret());
+        exit(0);
+    }
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, 0);
+    println!("pgid_test passed!");
+    0
+}
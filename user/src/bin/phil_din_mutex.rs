@@ -10,6 +10,7 @@ use alloc::vec::Vec;
 
 use user_lib::{
     exit, get_time, mutex_blocking_create, mutex_lock, mutex_unlock, sleep, thread_create, waittid,
+    Mutex,
 };
 
 const N: usize = 5;
@@ -31,6 +32,25 @@ const ARR: [[usize; ROUND * 2]; N] = [
 ];
 static mut THINK: [[usize; ROUND * 2]; N] = [[0; ROUND * 2]; N];
 static mut EAT: [[usize; ROUND * 2]; N] = [[0; ROUND * 2]; N];
+/// 参数为 futex 时叉子用用户态的锁，和内核的锁比较耗时
+static mut USE_FUTEX: bool = false;
+static FORKS: [Mutex; N] = [const { Mutex::new() }; N];
+
+fn take_fork(id: usize) {
+    if unsafe { USE_FUTEX } {
+        FORKS[id].lock();
+    } else {
+        mutex_lock(id);
+    }
+}
+
+fn put_fork(id: usize) {
+    if unsafe { USE_FUTEX } {
+        FORKS[id].unlock();
+    } else {
+        mutex_unlock(id);
+    }
+}
 
 fn philosopher_dining_problem(id: *const usize) {
     let id = unsafe { *id };
@@ -48,8 +68,8 @@ fn philosopher_dining_problem(id: *const usize) {
             THINK[id][2 * round + 1] = get_time_u();
         }
         // wait for forks
-        mutex_lock(min);
-        mutex_lock(max);
+        take_fork(min);
+        take_fork(max);
         // eating
         unsafe {
             EAT[id][2 * round] = get_time_u();
@@ -58,14 +78,17 @@ fn philosopher_dining_problem(id: *const usize) {
         unsafe {
             EAT[id][2 * round + 1] = get_time_u();
         }
-        mutex_unlock(max);
-        mutex_unlock(min);
+        put_fork(max);
+        put_fork(min);
     }
     exit(0)
 }
 
 #[no_mangle]
-pub fn main() -> i32 {
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    unsafe {
+        USE_FUTEX = argc > 1 && argv[1] == "futex";
+    }
     let mut v = Vec::new();
     let ids: Vec<_> = (0..N).collect();
     let start = get_time_u();
@@ -0,0 +1,140 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use user_lib::{
+    exit, get_time, gettid, mutex_blocking_create, mutex_lock, mutex_unlock, sleep, thread_create,
+    thread_getpriority, thread_setpriority, waittid, SysError,
+};
+
+const HIGH: i32 = -10;
+const MEDIUM: i32 = 0;
+const MIDDLE: i32 = 5;
+const LOW: i32 = 10;
+
+/// 第一个场景用 0 号锁，第二个场景的低优先级线程持有 1 号锁，中间的线程持有 2 号锁
+const M0: usize = 0;
+const M1: usize = 1;
+const M2: usize = 2;
+
+static LOCKED: AtomicBool = AtomicBool::new(false);
+static MIDDLE_LOCKED: AtomicBool = AtomicBool::new(false);
+static MEDIUM_DONE: AtomicBool = AtomicBool::new(false);
+static BOOSTED: AtomicBool = AtomicBool::new(false);
+static RESTORED: AtomicBool = AtomicBool::new(false);
+static MIDDLE_KEPT: AtomicBool = AtomicBool::new(false);
+static MIDDLE_RESTORED: AtomicBool = AtomicBool::new(false);
+
+fn set_my_priority(nice: i32) {
+    assert_eq!(thread_setpriority(gettid() as usize, nice), 0);
+}
+
+fn my_priority() -> i32 {
+    thread_getpriority(gettid() as usize).unwrap()
+}
+
+/// 占用 CPU 等到当前线程的有效优先级变为 `prio`，最多等 1 秒
+fn wait_priority(prio: i32) -> bool {
+    let start = get_time();
+    while get_time() - start < 1000 {
+        if my_priority() == prio {
+            return true;
+        }
+    }
+    false
+}
+
+/// 持有 `mutex` 直到被提升到最高的优先级
+fn low(mutex: usize) -> ! {
+    set_my_priority(LOW);
+    assert_eq!(mutex_lock(mutex), 0);
+    LOCKED.store(true, Ordering::Relaxed);
+    BOOSTED.store(wait_priority(HIGH), Ordering::Relaxed);
+    assert_eq!(mutex_unlock(mutex), 0);
+    RESTORED.store(my_priority() == LOW, Ordering::Relaxed);
+    exit(0)
+}
+
+/// 和持有锁的低优先级线程抢 CPU
+fn medium() -> ! {
+    set_my_priority(MEDIUM);
+    let start = get_time();
+    while get_time() - start < 200 {}
+    MEDIUM_DONE.store(true, Ordering::Relaxed);
+    exit(0)
+}
+
+/// 持有 2 号锁的同时等待 1 号锁
+fn middle() -> ! {
+    set_my_priority(MIDDLE);
+    assert_eq!(mutex_lock(M2), 0);
+    MIDDLE_LOCKED.store(true, Ordering::Relaxed);
+    assert_eq!(mutex_lock(M1), 0);
+    // 主线程还在等 2 号锁
+    MIDDLE_KEPT.store(my_priority() == HIGH, Ordering::Relaxed);
+    assert_eq!(mutex_unlock(M1), 0);
+    assert_eq!(mutex_unlock(M2), 0);
+    MIDDLE_RESTORED.store(my_priority() == MIDDLE, Ordering::Relaxed);
+    exit(0)
+}
+
+fn wait_for(flag: &AtomicBool) {
+    while !flag.load(Ordering::Relaxed) {
+        sleep(1);
+    }
+}
+
+/// 低优先级线程持有高优先级线程需要的锁，中优先级线程一直占用 CPU
+fn test_inversion() {
+    LOCKED.store(false, Ordering::Relaxed);
+    let low_tid = thread_create(low as usize, M0) as usize;
+    wait_for(&LOCKED);
+    let medium_tid = thread_create(medium as usize, 0) as usize;
+    assert_eq!(mutex_lock(M0), 0);
+    // 低优先级线程借用了主线程的优先级，在中优先级线程之前释放了锁
+    assert!(!MEDIUM_DONE.load(Ordering::Relaxed));
+    assert_eq!(mutex_unlock(M0), 0);
+    assert_eq!(waittid(low_tid), 0);
+    assert_eq!(waittid(medium_tid), 0);
+    assert!(BOOSTED.load(Ordering::Relaxed));
+    assert!(RESTORED.load(Ordering::Relaxed));
+}
+
+/// 主线程等待中间的线程，中间的线程等待低优先级线程，优先级沿着等待链传递
+fn test_nested() {
+    LOCKED.store(false, Ordering::Relaxed);
+    BOOSTED.store(false, Ordering::Relaxed);
+    RESTORED.store(false, Ordering::Relaxed);
+    let low_tid = thread_create(low as usize, M1) as usize;
+    wait_for(&LOCKED);
+    let middle_tid = thread_create(middle as usize, 0) as usize;
+    wait_for(&MIDDLE_LOCKED);
+    assert_eq!(mutex_lock(M2), 0);
+    assert_eq!(mutex_unlock(M2), 0);
+    assert_eq!(waittid(middle_tid), 0);
+    assert_eq!(waittid(low_tid), 0);
+    assert!(BOOSTED.load(Ordering::Relaxed));
+    assert!(RESTORED.load(Ordering::Relaxed));
+    assert!(MIDDLE_KEPT.load(Ordering::Relaxed));
+    assert!(MIDDLE_RESTORED.load(Ordering::Relaxed));
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(thread_getpriority(100), Err(SysError::ESRCH));
+    for expected in [M0, M1, M2] {
+        assert_eq!(mutex_blocking_create(), expected as isize);
+    }
+    // 新线程继承主线程的优先级，开始运行后再降低自己的优先级
+    assert_eq!(thread_setpriority(0, HIGH), 0);
+    assert_eq!(thread_getpriority(0), Ok(HIGH));
+    test_inversion();
+    test_nested();
+    thread_setpriority(0, 0);
+    println!("pi_test passed!");
+    0
+}
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fcntl, fork, get_time, pipe2, poll, read, sleep, wait, write, OpenFlags,
+    PollEvents, PollFd, EAGAIN, F_GETFL, F_SETFL,
+};
+
+static STR: &str = "Hello, poll!";
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe2(&mut pipe_fd, OpenFlags::NONBLOCK), 0);
+    let (read_end, write_end) = (pipe_fd[0], pipe_fd[1]);
+    assert!(fcntl(read_end, F_GETFL, 0) as u32 & OpenFlags::NONBLOCK.bits() != 0);
+    // nothing to read yet
+    let mut buffer = [0u8; 32];
+    assert_eq!(read(read_end, &mut buffer), EAGAIN);
+    let mut fds = [PollFd::new(read_end, PollEvents::POLLIN)];
+    assert_eq!(poll(&mut fds, 0), 0);
+    // a timeout expires
+    let start = get_time();
+    assert_eq!(poll(&mut fds, 50), 0);
+    assert!(get_time() - start >= 50);
+    if fork() == 0 {
+        close(read_end);
+        sleep(100);
+        assert_eq!(write(write_end, STR.as_bytes()), STR.len() as isize);
+        sleep(100);
+        close(write_end);
+        exit(0);
+    }
+    close(write_end);
+    // blocks until the child writes
+    assert_eq!(poll(&mut fds, -1), 1);
+    assert!(fds[0].revents().contains(PollEvents::POLLIN));
+    // a non-blocking read returns what is there
+    let len = read(read_end, &mut buffer);
+    assert_eq!(&buffer[..len as usize], STR.as_bytes());
+    // blocks until the child closes its end
+    assert_eq!(fcntl(read_end, F_SETFL, 0), 0);
+    assert_eq!(poll(&mut fds, -1), 1);
+    assert!(fds[0].revents().contains(PollEvents::POLLHUP));
+    assert_eq!(read(read_end, &mut buffer), 0);
+    close(read_end);
+    let mut exit_code: i32 = 0;
+    wait(&mut exit_code);
+    assert_eq!(exit_code, 0);
+    println!("poll_test passed!");
+    0
+}
@@ -9,30 +9,52 @@ use alloc::vec::Vec;
 use core::ptr::addr_of_mut;
 
 use user_lib::{
-    exit, get_time, mutex_blocking_create, mutex_lock, mutex_unlock, thread_create, waittid,
+    exit, get_time, mutex_blocking_create, mutex_lock, mutex_unlock, thread_create, waittid, Mutex,
 };
 
 static mut A: usize = 0;
 const PER_THREAD: usize = 1000;
 const THREAD_COUNT: usize = 16;
+/// 参数为 futex 时使用用户态的锁，和内核的锁比较耗时
+static mut USE_FUTEX: bool = false;
+static FUTEX_MUTEX: Mutex = Mutex::new();
+
+fn lock() {
+    if unsafe { USE_FUTEX } {
+        FUTEX_MUTEX.lock();
+    } else {
+        mutex_lock(0);
+    }
+}
+
+fn unlock() {
+    if unsafe { USE_FUTEX } {
+        FUTEX_MUTEX.unlock();
+    } else {
+        mutex_unlock(0);
+    }
+}
 
 unsafe fn f() -> ! {
     let mut t = 2usize;
     for _ in 0..PER_THREAD {
-        mutex_lock(0);
+        lock();
         let a = addr_of_mut!(A);
         let cur = a.read_volatile();
         for _ in 0..500 {
             t = t * t % 10007;
         }
         a.write_volatile(cur + 1);
-        mutex_unlock(0);
+        unlock();
     }
     exit(t as i32)
 }
 
 #[no_mangle]
-pub fn main() -> i32 {
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    unsafe {
+        USE_FUTEX = argc > 1 && argv[1] == "futex";
+    }
     let start = get_time();
     assert_eq!(mutex_blocking_create(), 0);
     let mut v = Vec::new();
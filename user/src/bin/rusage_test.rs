@@ -0,0 +1,101 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, get_time, getrusage, sleep, thread_create, times, wait4, waittid, RUsage, SysError,
+    Tms, CLK_TCK, RUSAGE_CHILDREN, RUSAGE_SELF, RUSAGE_THREAD,
+};
+
+/// 微秒
+fn usage(who: isize) -> (usize, usize) {
+    let mut usage = RUsage::default();
+    assert_eq!(getrusage(who, &mut usage), 0);
+    (usage.ru_utime.to_us(), usage.ru_stime.to_us())
+}
+
+/// 在用户态占用 CPU，直到 `who` 的用户态时间增加了 `us` 微秒，最多等 2 秒
+fn spin_user(who: isize, us: usize) -> bool {
+    let (start_user, _) = usage(who);
+    let start = get_time();
+    loop {
+        for _ in 0..10000 {
+            core::hint::spin_loop();
+        }
+        if usage(who).0 - start_user >= us {
+            return true;
+        }
+        if get_time() - start > 2000 {
+            return false;
+        }
+    }
+}
+
+fn test_self() {
+    assert_eq!(
+        SysError::check(getrusage(5, &mut RUsage::default())),
+        Err(SysError::EINVAL)
+    );
+    assert!(spin_user(RUSAGE_SELF, 20_000));
+    // 睡眠的时间不算在运行时间中
+    let (user, system) = usage(RUSAGE_SELF);
+    sleep(50);
+    let (user_after, system_after) = usage(RUSAGE_SELF);
+    assert!(user_after + system_after - user - system < 20_000);
+}
+
+fn spinner() -> ! {
+    assert!(spin_user(RUSAGE_THREAD, 20_000));
+    exit(0)
+}
+
+fn test_thread() {
+    let (process_user, _) = usage(RUSAGE_SELF);
+    let tid = thread_create(spinner as usize, 0) as usize;
+    assert_eq!(waittid(tid), 0);
+    // 退出的线程的时间仍然计在进程上
+    let (user, _) = usage(RUSAGE_SELF);
+    assert!(user - process_user >= 20_000);
+    let (thread_user, _) = usage(RUSAGE_THREAD);
+    assert!(thread_user < user);
+}
+
+fn test_children() {
+    let mut tms = Tms::default();
+    let start = times(&mut tms);
+    assert!(start > 0);
+    assert_eq!((tms.tms_cutime, tms.tms_cstime), (0, 0));
+    assert_eq!(usage(RUSAGE_CHILDREN), (0, 0));
+    let pid = fork();
+    if pid == 0 {
+        assert!(spin_user(RUSAGE_SELF, 30_000));
+        exit(7);
+    }
+    let mut exit_code = 0;
+    let mut rusage = RUsage::default();
+    assert_eq!(wait4(pid, &mut exit_code, 0, &mut rusage), pid);
+    assert_eq!(exit_code, 7);
+    assert!(rusage.ru_utime.to_us() >= 30_000);
+    // 回收之后子进程的时间计入 RUSAGE_CHILDREN
+    let (children_user, _) = usage(RUSAGE_CHILDREN);
+    assert!(children_user >= rusage.ru_utime.to_us());
+    let now = times(&mut tms);
+    assert!(now >= start);
+    assert!(tms.tms_cutime >= 30_000 * CLK_TCK / 1_000_000);
+    assert!(tms.tms_utime > 0);
+    assert_eq!(
+        SysError::check(wait4(-1, &mut exit_code, 0, &mut rusage)),
+        Err(SysError::ECHILD)
+    );
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    test_self();
+    test_thread();
+    test_children();
+    println!("rusage_test passed!");
+    0
+}
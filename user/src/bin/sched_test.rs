@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, get_time, getpid, getpriority, nice, setpriority, waitpid, SysError, PRIO_PGRP,
+    PRIO_PROCESS,
+};
+
+/// 占用 CPU 一段时间
+fn spin(ms: isize) {
+    let start = get_time();
+    while get_time() - start < ms {}
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(getpriority(PRIO_PROCESS, 0), Ok(0));
+    assert_eq!(setpriority(PRIO_PROCESS, 0, 5), 0);
+    assert_eq!(getpriority(PRIO_PROCESS, getpid() as usize), Ok(5));
+    assert_eq!(nice(3), Ok(8));
+    assert_eq!(nice(-10), Ok(-2));
+    // out of range values are clamped
+    assert_eq!(nice(100), Ok(19));
+    assert_eq!(setpriority(PRIO_PROCESS, 0, -100), 0);
+    assert_eq!(getpriority(PRIO_PROCESS, 0), Ok(-20));
+    assert_eq!(SysError::check(setpriority(7, 0, 0)), Err(SysError::EINVAL));
+    assert_eq!(getpriority(PRIO_PROCESS, 100000), Err(SysError::ESRCH));
+    assert_eq!(getpriority(PRIO_PGRP, 0), Ok(-20));
+    setpriority(PRIO_PROCESS, 0, 0);
+
+    // children inherit the nice value of the parent, which can renice them
+    // afterwards; even the lowest priority gets to run
+    let mut pids = [0isize; 2];
+    for (i, child_nice) in [0, 19].into_iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            spin(100);
+            exit(getpriority(PRIO_PROCESS, 0).unwrap());
+        }
+        assert_eq!(getpriority(PRIO_PROCESS, pid as usize), Ok(0));
+        assert_eq!(setpriority(PRIO_PROCESS, pid as usize, child_nice), 0);
+        pids[i] = pid;
+    }
+    for (pid, expected) in pids.into_iter().zip([0, 19]) {
+        let mut exit_code = 0;
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        assert_eq!(exit_code, expected);
+    }
+    println!("sched_test passed!");
+    0
+}
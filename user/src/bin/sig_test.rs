@@ -0,0 +1,89 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use user_lib::{
+    exit, fork, getpid, kill, sigaction, sigprocmask, waitpid, yield_, SignalAction, SignalFlags,
+    SysError, SIGINT, SIGKILL, SIGUSR1, SIGUSR2, SIG_BLOCK, SIG_IGN, SIG_UNBLOCK,
+};
+
+static HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn on_usr1(signum: i32) {
+    assert_eq!(signum, SIGUSR1);
+    // the signal itself and the mask of the action are blocked in the handler
+    let mut mask = SignalFlags::empty();
+    sigprocmask(SIG_BLOCK, None, Some(&mut mask));
+    assert!(mask.contains(SignalFlags::SIGUSR1 | SignalFlags::SIGUSR2));
+    HANDLED.fetch_add(1, Ordering::SeqCst);
+}
+
+extern "C" fn on_usr2(_signum: i32) {
+    exit(7);
+}
+
+fn current_mask() -> SignalFlags {
+    let mut mask = SignalFlags::empty();
+    sigprocmask(SIG_BLOCK, None, Some(&mut mask));
+    mask
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid() as usize;
+    let action = SignalAction::new(on_usr1, SignalFlags::SIGUSR2);
+    assert_eq!(sigaction(SIGUSR1, Some(&action), None), 0);
+    // the handler runs before kill returns, and kill still returns 0
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+    assert!(current_mask().is_empty());
+
+    // a blocked signal stays pending until it is unblocked
+    assert_eq!(sigprocmask(SIG_BLOCK, Some(SignalFlags::SIGUSR1), None), 0);
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        sigprocmask(SIG_UNBLOCK, Some(SignalFlags::SIGUSR1), None),
+        0
+    );
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 2);
+
+    // SIGKILL can be neither caught nor blocked
+    assert_eq!(
+        sigaction(SIGKILL, Some(&action), None),
+        SysError::EINVAL.ret()
+    );
+    assert_eq!(sigprocmask(SIG_BLOCK, Some(SignalFlags::SIGKILL), None), 0);
+    assert!(!current_mask().contains(SignalFlags::SIGKILL));
+
+    // an ignored signal does nothing
+    let ignore = SignalAction {
+        handler: SIG_IGN,
+        ..Default::default()
+    };
+    assert_eq!(sigaction(SIGINT, Some(&ignore), None), 0);
+    assert_eq!(kill(pid, SIGINT), 0);
+    let mut old = SignalAction::default();
+    assert_eq!(sigaction(SIGINT, None, Some(&mut old)), 0);
+    assert_eq!(old.handler, SIG_IGN);
+
+    // handlers are inherited by children
+    let action = SignalAction::new(on_usr2, SignalFlags::empty());
+    assert_eq!(sigaction(SIGUSR2, Some(&action), None), 0);
+    let child = fork();
+    if child == 0 {
+        loop {
+            yield_();
+        }
+    }
+    assert_eq!(kill(child as usize, SIGUSR2), 0);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, 7);
+    println!("sig_test passed!");
+    0
+}
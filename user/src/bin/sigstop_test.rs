@@ -0,0 +1,92 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use user_lib::{
+    fork, kill, sigaction, waitpid_nb, waitpid_options, yield_, SignalAction, SignalFlags,
+    WaitStatus, SIGCHLD, SIGCONT, SIGKILL, SIGSTOP, SIGTERM, WCONTINUED, WUNTRACED,
+};
+
+static CHLD: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn on_chld(_signum: i32) {
+    CHLD.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Give the child the chance to run and the signal handler to fire
+fn wait_for_chld(count: usize) {
+    while CHLD.load(Ordering::SeqCst) < count {
+        yield_();
+    }
+}
+
+fn spin_child() -> isize {
+    let child = fork();
+    if child == 0 {
+        loop {
+            yield_();
+        }
+    }
+    child
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let action = SignalAction::new(on_chld, SignalFlags::empty());
+    assert_eq!(sigaction(SIGCHLD, Some(&action), None), 0);
+
+    let child = spin_child();
+    // the stop is reported with WUNTRACED
+    assert_eq!(kill(child as usize, SIGSTOP), 0);
+    assert_eq!(
+        waitpid_options(child, WUNTRACED),
+        Ok((child as usize, WaitStatus::Stopped(SIGSTOP)))
+    );
+    wait_for_chld(1);
+    // a stopped child does not act on SIGTERM until it is continued
+    assert_eq!(kill(child as usize, SIGTERM), 0);
+    for _ in 0..10 {
+        yield_();
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid_nb(child as usize, &mut exit_code), 0);
+    assert_eq!(kill(child as usize, SIGCONT), 0);
+    wait_for_chld(2);
+    assert_eq!(
+        waitpid_options(child, 0),
+        Ok((child as usize, WaitStatus::Exited(-SIGTERM)))
+    );
+    wait_for_chld(3);
+
+    let child = spin_child();
+    assert_eq!(kill(child as usize, SIGSTOP), 0);
+    assert_eq!(
+        waitpid_options(child, WUNTRACED),
+        Ok((child as usize, WaitStatus::Stopped(SIGSTOP)))
+    );
+    wait_for_chld(4);
+    // the continue is reported with WCONTINUED
+    assert_eq!(kill(child as usize, SIGCONT), 0);
+    wait_for_chld(5);
+    assert_eq!(
+        waitpid_options(child, WUNTRACED | WCONTINUED),
+        Ok((child as usize, WaitStatus::Continued))
+    );
+    // SIGKILL gets through to a stopped child as well
+    assert_eq!(kill(child as usize, SIGSTOP), 0);
+    assert_eq!(
+        waitpid_options(child, WUNTRACED),
+        Ok((child as usize, WaitStatus::Stopped(SIGSTOP)))
+    );
+    assert_eq!(kill(child as usize, SIGKILL), 0);
+    assert_eq!(
+        waitpid_options(child, 0),
+        Ok((child as usize, WaitStatus::Exited(-SIGKILL)))
+    );
+    println!("sigstop_test passed!");
+    0
+}
@@ -0,0 +1,198 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use user_lib::{
+    barrier_create, barrier_destroy, barrier_wait, condvar_broadcast, condvar_create,
+    condvar_destroy, condvar_wait, condvar_wait_timeout, exit, get_time, mutex_blocking_create,
+    mutex_create, mutex_destroy, mutex_lock, mutex_lock_timeout, mutex_try_lock, mutex_unlock,
+    rwlock_create, rwlock_destroy, rwlock_read, rwlock_unlock, rwlock_write, semaphore_create,
+    semaphore_destroy, semaphore_down_timeout, semaphore_up, sleep, thread_create, waittid, yield_,
+    SysError, TimeSpec,
+};
+
+fn errno(ret: isize) -> SysError {
+    SysError::check(ret).unwrap_err()
+}
+
+fn test_mutex(id: usize) {
+    assert_eq!(mutex_try_lock(id), 0);
+    assert_eq!(errno(mutex_try_lock(id)), SysError::EBUSY);
+    let start = get_time();
+    assert_eq!(
+        errno(mutex_lock_timeout(id, &TimeSpec::from_ms(30))),
+        SysError::ETIMEDOUT
+    );
+    assert!(get_time() - start >= 30);
+    assert_eq!(errno(mutex_destroy(id)), SysError::EBUSY);
+    assert_eq!(mutex_unlock(id), 0);
+    assert_eq!(mutex_lock_timeout(id, &TimeSpec::from_ms(30)), 0);
+    assert_eq!(mutex_unlock(id), 0);
+    assert_eq!(mutex_destroy(id), 0);
+    assert_eq!(errno(mutex_lock(id)), SysError::EINVAL);
+}
+
+static SEM: AtomicUsize = AtomicUsize::new(0);
+
+fn late_up() -> ! {
+    sleep(10);
+    semaphore_up(SEM.load(Ordering::Relaxed));
+    exit(0)
+}
+
+fn test_semaphore() {
+    let sem = semaphore_create(1) as usize;
+    SEM.store(sem, Ordering::Relaxed);
+    let zero = TimeSpec::default();
+    assert_eq!(semaphore_down_timeout(sem, &zero), 0);
+    assert_eq!(
+        errno(semaphore_down_timeout(sem, &zero)),
+        SysError::ETIMEDOUT
+    );
+    assert_eq!(
+        errno(semaphore_down_timeout(sem, &TimeSpec::from_ms(20))),
+        SysError::ETIMEDOUT
+    );
+    let tid = thread_create(late_up as usize, 0) as usize;
+    assert_eq!(semaphore_down_timeout(sem, &TimeSpec::from_ms(1000)), 0);
+    waittid(tid);
+    assert_eq!(semaphore_destroy(sem), 0);
+}
+
+const WAITERS: usize = 4;
+static MUTEX: AtomicUsize = AtomicUsize::new(0);
+static CONDVAR: AtomicUsize = AtomicUsize::new(0);
+static WAITING: AtomicUsize = AtomicUsize::new(0);
+static GO: AtomicBool = AtomicBool::new(false);
+
+fn broadcast_waiter() -> ! {
+    let (mutex, condvar) = (
+        MUTEX.load(Ordering::Relaxed),
+        CONDVAR.load(Ordering::Relaxed),
+    );
+    mutex_lock(mutex);
+    WAITING.fetch_add(1, Ordering::Relaxed);
+    while !GO.load(Ordering::Relaxed) {
+        condvar_wait(condvar, mutex);
+    }
+    mutex_unlock(mutex);
+    exit(0)
+}
+
+fn test_condvar() {
+    let mutex = mutex_blocking_create() as usize;
+    let condvar = condvar_create() as usize;
+    MUTEX.store(mutex, Ordering::Relaxed);
+    CONDVAR.store(condvar, Ordering::Relaxed);
+    // 超时返回时重新持有了锁
+    mutex_lock(mutex);
+    assert_eq!(
+        errno(condvar_wait_timeout(condvar, mutex, &TimeSpec::from_ms(20))),
+        SysError::ETIMEDOUT
+    );
+    assert_eq!(mutex_unlock(mutex), 0);
+
+    let threads: Vec<_> = (0..WAITERS)
+        .map(|_| thread_create(broadcast_waiter as usize, 0) as usize)
+        .collect();
+    while WAITING.load(Ordering::Relaxed) < WAITERS {
+        yield_();
+    }
+    mutex_lock(mutex);
+    GO.store(true, Ordering::Relaxed);
+    condvar_broadcast(condvar);
+    mutex_unlock(mutex);
+    for tid in threads {
+        assert_eq!(waittid(tid), 0);
+    }
+    assert_eq!(condvar_destroy(condvar), 0);
+    assert_eq!(mutex_destroy(mutex), 0);
+}
+
+static RWLOCK: AtomicUsize = AtomicUsize::new(0);
+static WRITTEN: AtomicBool = AtomicBool::new(false);
+
+fn reader() -> ! {
+    let rwlock = RWLOCK.load(Ordering::Relaxed);
+    assert_eq!(rwlock_read(rwlock), 0);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+    exit(0)
+}
+
+fn writer() -> ! {
+    let rwlock = RWLOCK.load(Ordering::Relaxed);
+    assert_eq!(rwlock_write(rwlock), 0);
+    WRITTEN.store(true, Ordering::Relaxed);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+    exit(0)
+}
+
+fn test_rwlock() {
+    let rwlock = rwlock_create() as usize;
+    RWLOCK.store(rwlock, Ordering::Relaxed);
+    assert_eq!(rwlock_read(rwlock), 0);
+    // 读者之间不互斥
+    let tid = thread_create(reader as usize, 0) as usize;
+    assert_eq!(waittid(tid), 0);
+    // 写者要等到读锁释放
+    let tid = thread_create(writer as usize, 0) as usize;
+    sleep(20);
+    assert!(!WRITTEN.load(Ordering::Relaxed));
+    assert_eq!(errno(rwlock_destroy(rwlock)), SysError::EBUSY);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+    assert_eq!(waittid(tid), 0);
+    assert!(WRITTEN.load(Ordering::Relaxed));
+    assert_eq!(errno(rwlock_unlock(rwlock)), SysError::EPERM);
+    assert_eq!(rwlock_destroy(rwlock), 0);
+}
+
+const PARTIES: usize = 4;
+const ROUNDS: usize = 3;
+static BARRIER: AtomicUsize = AtomicUsize::new(0);
+static ARRIVED: AtomicUsize = AtomicUsize::new(0);
+static SERIAL: AtomicUsize = AtomicUsize::new(0);
+
+fn party() -> ! {
+    let barrier = BARRIER.load(Ordering::Relaxed);
+    for round in 1..=ROUNDS {
+        ARRIVED.fetch_add(1, Ordering::Relaxed);
+        let ret = barrier_wait(barrier);
+        assert!(ret == 0 || ret == 1);
+        SERIAL.fetch_add(ret as usize, Ordering::Relaxed);
+        // 所有线程都到达了这一轮
+        assert!(ARRIVED.load(Ordering::Relaxed) >= round * PARTIES);
+    }
+    exit(0)
+}
+
+fn test_barrier() {
+    assert_eq!(errno(barrier_create(0)), SysError::EINVAL);
+    let barrier = barrier_create(PARTIES) as usize;
+    BARRIER.store(barrier, Ordering::Relaxed);
+    let threads: Vec<_> = (0..PARTIES)
+        .map(|_| thread_create(party as usize, 0) as usize)
+        .collect();
+    for tid in threads {
+        assert_eq!(waittid(tid), 0);
+    }
+    assert_eq!(SERIAL.load(Ordering::Relaxed), ROUNDS);
+    assert_eq!(barrier_destroy(barrier), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    test_mutex(mutex_create() as usize);
+    test_mutex(mutex_blocking_create() as usize);
+    test_semaphore();
+    test_condvar();
+    test_rwlock();
+    test_barrier();
+    println!("sync_ext_test passed!");
+    0
+}
@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use user_lib::{exec, exit, fork, get_time, wait4, RUsage, SysError, TimeVal};
+
+fn print_time(name: &str, ms: usize) {
+    println!("{}\t{}.{:03}s", name, ms / 1000, ms % 1000);
+}
+
+fn ms(time: &TimeVal) -> usize {
+    time.to_us() / 1000
+}
+
+/// 运行命令，结束后打印经过的时间和它在用户态、内核态运行的时间
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc < 2 {
+        println!("usage: time COMMAND [ARG]...");
+        return -1;
+    }
+    // _start 给出的参数后面紧跟着 \0
+    let mut args: Vec<*const u8> = argv[1..].iter().map(|arg| arg.as_ptr()).collect();
+    args.push(core::ptr::null());
+    let start = get_time();
+    let pid = fork();
+    if pid == 0 {
+        let error = SysError::check(exec(argv[1], &args));
+        println!("time: cannot run {}: {}", argv[1], error.unwrap_err());
+        exit(-4);
+    }
+    let mut exit_code = 0;
+    let mut rusage = RUsage::default();
+    assert_eq!(wait4(pid, &mut exit_code, 0, &mut rusage), pid);
+    let real = (get_time() - start) as usize;
+    print_time("real", real);
+    print_time("user", ms(&rusage.ru_utime));
+    print_time("sys", ms(&rusage.ru_stime));
+    exit_code
+}
@@ -0,0 +1,66 @@
+#![no_std]
+#![no_main]
+#![allow(static_mut_refs)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, pipe, read, statfs, write, StatFs, SysError};
+
+const PAGE_SIZE: usize = 0x4000;
+
+/// 一段跨越页边界的可写内存
+static mut BUFFER: [u8; PAGE_SIZE + 64] = [0; PAGE_SIZE + 64];
+/// 只读数据段
+static RODATA: [u8; 16] = [1; 16];
+/// 用户态不能访问的内核地址
+const KERNEL_ADDRESS: usize = 0x9000_0000_0000_0000;
+
+/// 缓冲区中紧挨着页边界前 `before` 个字节的位置
+fn page_boundary(before: usize) -> *mut u8 {
+    let start = unsafe { BUFFER.as_mut_ptr() } as usize;
+    ((start + PAGE_SIZE) & !(PAGE_SIZE - 1)).wrapping_sub(before) as *mut u8
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let (read_end, write_end) = (pipe_fd[0], pipe_fd[1]);
+
+    // data crossing a page boundary is copied piece by piece
+    let crossing = unsafe { core::slice::from_raw_parts_mut(page_boundary(16), 32) };
+    for (i, byte) in crossing.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    assert_eq!(write(write_end, crossing), 32);
+    let mut data = [0u8; 32];
+    assert_eq!(read(read_end, &mut data), 32);
+    assert!(data.iter().enumerate().all(|(i, &byte)| byte == i as u8));
+
+    // so is a struct crossing a page boundary
+    let mut expected = StatFs::default();
+    assert_eq!(statfs("/\0", &mut expected), 0);
+    let crossing = unsafe { &mut *(page_boundary(8) as *mut StatFs) };
+    assert_eq!(statfs("/\0", crossing), 0);
+    assert_eq!(crossing.f_blocks, expected.f_blocks);
+    assert_eq!(crossing.f_namelen, expected.f_namelen);
+
+    // the kernel does not write into read-only pages for us
+    assert_eq!(write(write_end, b"0123456789abcdef"), 16);
+    let rodata = unsafe { core::slice::from_raw_parts_mut(RODATA.as_ptr() as *mut u8, 16) };
+    assert_eq!(read(read_end, rodata), SysError::EFAULT.ret());
+    assert_eq!(RODATA, [1; 16]);
+    // nor reads or writes kernel memory
+    let kernel = unsafe { core::slice::from_raw_parts_mut(KERNEL_ADDRESS as *mut u8, 16) };
+    assert_eq!(write(write_end, kernel), SysError::EFAULT.ret());
+    assert_eq!(read(read_end, kernel), SysError::EFAULT.ret());
+    // and fails on memory that is not mapped at all
+    let unmapped = unsafe { &mut *((usize::MAX & !(PAGE_SIZE - 1)) as *mut StatFs) };
+    assert_eq!(statfs("/\0", unmapped), SysError::EFAULT.ret());
+    assert_eq!(read(read_end, &mut data[..16]), 16);
+    close(read_end);
+    close(write_end);
+    println!("user_mem_test passed!");
+    0
+}
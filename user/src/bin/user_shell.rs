@@ -15,7 +15,21 @@ const LINE_START: &str = ">> ";
 
 use alloc::{string::String, vec::Vec};
 
-use user_lib::{close, console::getchar, dup, exec, fork, open, pipe, waitpid, OpenFlags};
+use user_lib::{
+    close, console::getchar, dup, exec, exit, fork, getpgid, killpg, open, pipe, setpgid, setsid,
+    sigaction, tcsetpgrp, waitpid_nb, waitpid_options, OpenFlags, SignalAction, SysError,
+    WaitStatus, SIGCONT, SIGINT, SIGQUIT, SIGTSTP, SIG_DFL, SIG_IGN, WUNTRACED,
+};
+
+/// A command line started by the shell, whose processes form a process group
+struct Job {
+    id: usize,
+    pgid: usize,
+    /// Processes that have not exited yet
+    pids: Vec<isize>,
+    command: String,
+    stopped: bool,
+}
 
 #[derive(Debug)]
 struct ProcessArguments {
@@ -73,9 +87,248 @@ impl ProcessArguments {
     }
 }
 
+/// The shell ignores the signals typed on the console, its children get the
+/// default actions back before exec
+fn set_job_signals(handler: usize) {
+    let action = SignalAction {
+        handler,
+        ..Default::default()
+    };
+    for signum in [SIGINT, SIGQUIT, SIGTSTP] {
+        sigaction(signum, Some(&action), None);
+    }
+}
+
+/// Give the console to `job` and wait until all its processes exit or it is
+/// stopped, in which case it goes back to `jobs`
+fn wait_foreground(mut job: Job, jobs: &mut Vec<Job>, shell_pgid: usize) {
+    tcsetpgrp(0, job.pgid);
+    job.pids
+        .retain(|&pid| match waitpid_options(pid, WUNTRACED) {
+            Ok((_, WaitStatus::Stopped(_))) => true,
+            Ok((_, WaitStatus::Exited(exit_code))) => {
+                if exit_code == -SIGINT {
+                    println!("");
+                }
+                false
+            }
+            _ => false,
+        });
+    tcsetpgrp(0, shell_pgid);
+    if !job.pids.is_empty() {
+        job.stopped = true;
+        println!("");
+        println!("[{}]+  Stopped    {}", job.id, job.command);
+        jobs.push(job);
+    }
+}
+
+/// Reap the background jobs that have finished
+fn report_done_jobs(jobs: &mut Vec<Job>) {
+    let mut exit_code: i32 = 0;
+    for job in jobs.iter_mut() {
+        job.pids
+            .retain(|&pid| waitpid_nb(pid as usize, &mut exit_code) == 0);
+        if job.pids.is_empty() {
+            println!("[{}]+  Done       {}", job.id, job.command);
+        }
+    }
+    jobs.retain(|job| !job.pids.is_empty());
+}
+
+/// The job numbered by the argument of `fg` or `bg`, the latest one by default
+fn take_job(jobs: &mut Vec<Job>, arg: Option<&str>) -> Option<Job> {
+    let idx = match arg {
+        Some(arg) => {
+            let id: usize = arg.trim_start_matches('%').parse().ok()?;
+            jobs.iter().position(|job| job.id == id)?
+        }
+        None => jobs.len().checked_sub(1)?,
+    };
+    Some(jobs.remove(idx))
+}
+
+/// Run the job control commands, false if `line` is not one of them
+fn run_builtin(line: &str, jobs: &mut Vec<Job>, shell_pgid: usize) -> bool {
+    let mut words = line.split(' ').filter(|word| !word.is_empty());
+    let command = words.next().unwrap_or("");
+    let arg = words.next();
+    match command {
+        "jobs" => {
+            for job in jobs.iter() {
+                let state = if job.stopped { "Stopped" } else { "Running" };
+                println!("[{}]  {:<10} {}", job.id, state, job.command);
+            }
+        }
+        "fg" => match take_job(jobs, arg) {
+            Some(mut job) => {
+                println!("{}", job.command);
+                if job.stopped {
+                    job.stopped = false;
+                    killpg(job.pgid, SIGCONT);
+                }
+                wait_foreground(job, jobs, shell_pgid);
+            }
+            None => println!("fg: no such job"),
+        },
+        "bg" => match take_job(jobs, arg) {
+            Some(mut job) => {
+                if job.stopped {
+                    job.stopped = false;
+                    killpg(job.pgid, SIGCONT);
+                }
+                println!("[{}]+ {} &", job.id, job.command);
+                jobs.push(job);
+            }
+            None => println!("bg: no such job"),
+        },
+        _ => return false,
+    }
+    true
+}
+
+/// Start the pipeline in `line` as a new job, in the background if it ends
+/// with `&`
+fn run_command(line: &str, jobs: &mut Vec<Job>, shell_pgid: usize) {
+    let (line, background) = match line.strip_suffix('&') {
+        Some(line) => (line.trim_end(), true),
+        None => (line, false),
+    };
+    let splited: Vec<_> = line.split('|').collect();
+    let process_arguments_list: Vec<_> = splited
+        .iter()
+        .map(|&cmd| ProcessArguments::new(cmd))
+        .collect();
+    let mut valid = true;
+    for (i, process_args) in process_arguments_list.iter().enumerate() {
+        if process_args.args_copy.is_empty() {
+            valid = false;
+        } else if i == 0 {
+            if !process_args.output.is_empty() {
+                valid = false;
+            }
+        } else if i == process_arguments_list.len() - 1 {
+            if !process_args.input.is_empty() {
+                valid = false;
+            }
+        } else if !process_args.output.is_empty() || !process_args.input.is_empty() {
+            valid = false;
+        }
+    }
+    if process_arguments_list.len() == 1 && !process_arguments_list[0].args_copy.is_empty() {
+        valid = true;
+    }
+    if !valid {
+        println!("Invalid command: Inputs/Outputs cannot be correctly binded!");
+        return;
+    }
+    // create pipes
+    let mut pipes_fd: Vec<[usize; 2]> = Vec::new();
+    for _ in 0..process_arguments_list.len() - 1 {
+        let mut pipe_fd = [0usize; 2];
+        pipe(&mut pipe_fd);
+        pipes_fd.push(pipe_fd);
+    }
+    let mut children: Vec<_> = Vec::new();
+    // the first process leads the process group of the job
+    let mut pgid = 0;
+    for (i, process_argument) in process_arguments_list.iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            setpgid(0, pgid);
+            set_job_signals(SIG_DFL);
+            let input = &process_argument.input;
+            let output = &process_argument.output;
+            let args_copy = &process_argument.args_copy;
+            let args_addr = &process_argument.args_addr;
+            // redirect input
+            if !input.is_empty() {
+                let input_fd = match SysError::check(open(input.as_str(), OpenFlags::RDONLY)) {
+                    Ok(fd) => fd,
+                    Err(error) => {
+                        println!("Error when opening fs {}: {}", input, error);
+                        exit(-4);
+                    }
+                };
+                close(0);
+                assert_eq!(dup(input_fd), 0);
+                close(input_fd);
+            }
+            // redirect output
+            if !output.is_empty() {
+                let output_fd = match SysError::check(open(
+                    output.as_str(),
+                    OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+                )) {
+                    Ok(fd) => fd,
+                    Err(error) => {
+                        println!("Error when opening fs {}: {}", output, error);
+                        exit(-4);
+                    }
+                };
+                close(1);
+                assert_eq!(dup(output_fd), 1);
+                close(output_fd);
+            }
+            // receive input from the previous process
+            if i > 0 {
+                close(0);
+                let read_end = pipes_fd.get(i - 1).unwrap()[0];
+                assert_eq!(dup(read_end), 0);
+            }
+            // send output to the next process
+            if i < process_arguments_list.len() - 1 {
+                close(1);
+                let write_end = pipes_fd.get(i).unwrap()[1];
+                assert_eq!(dup(write_end), 1);
+            }
+            // close all pipe ends inherited from the parent process
+            for pipe_fd in pipes_fd.iter() {
+                close(pipe_fd[0]);
+                close(pipe_fd[1]);
+            }
+            // execute new application
+            // exec only returns on failure
+            let error = SysError::check(exec(args_copy[0].as_str(), args_addr.as_slice()));
+            println!("Error when executing: {}", error.unwrap_err());
+            exit(-4);
+        } else {
+            if pgid == 0 {
+                pgid = pid as usize;
+            }
+            // also done here, so that the group exists whichever runs first
+            setpgid(pid as usize, pgid);
+            children.push(pid);
+        }
+    }
+    for pipe_fd in pipes_fd.iter() {
+        close(pipe_fd[0]);
+        close(pipe_fd[1]);
+    }
+    let job = Job {
+        id: jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1,
+        pgid,
+        pids: children,
+        command: String::from(line),
+        stopped: false,
+    };
+    if background {
+        println!("[{}] {}", job.id, job.pgid);
+        jobs.push(job);
+    } else {
+        wait_foreground(job, jobs, shell_pgid);
+    }
+}
+
 #[no_mangle]
 pub fn main() -> i32 {
     println!("Rust user shell");
+    // take the console in a session of our own, for job control
+    setsid();
+    let shell_pgid = getpgid(0) as usize;
+    tcsetpgrp(0, shell_pgid);
+    set_job_signals(SIG_IGN);
+    let mut jobs: Vec<Job> = Vec::new();
     let mut line: String = String::new();
     print!("{}", LINE_START);
     loop {
@@ -83,118 +336,12 @@ pub fn main() -> i32 {
         match c {
             LF | CR => {
                 println!("");
-                if !line.is_empty() {
-                    let splited: Vec<_> = line.as_str().split('|').collect();
-                    let process_arguments_list: Vec<_> = splited
-                        .iter()
-                        .map(|&cmd| ProcessArguments::new(cmd))
-                        .collect();
-                    let mut valid = true;
-                    for (i, process_args) in process_arguments_list.iter().enumerate() {
-                        if i == 0 {
-                            if !process_args.output.is_empty() {
-                                valid = false;
-                            }
-                        } else if i == process_arguments_list.len() - 1 {
-                            if !process_args.input.is_empty() {
-                                valid = false;
-                            }
-                        } else if !process_args.output.is_empty() || !process_args.input.is_empty()
-                        {
-                            valid = false;
-                        }
-                    }
-                    if process_arguments_list.len() == 1 {
-                        valid = true;
-                    }
-                    if !valid {
-                        println!("Invalid command: Inputs/Outputs cannot be correctly binded!");
-                    } else {
-                        // create pipes
-                        let mut pipes_fd: Vec<[usize; 2]> = Vec::new();
-                        if !process_arguments_list.is_empty() {
-                            for _ in 0..process_arguments_list.len() - 1 {
-                                let mut pipe_fd = [0usize; 2];
-                                pipe(&mut pipe_fd);
-                                pipes_fd.push(pipe_fd);
-                            }
-                        }
-                        let mut children: Vec<_> = Vec::new();
-                        for (i, process_argument) in process_arguments_list.iter().enumerate() {
-                            let pid = fork();
-                            if pid == 0 {
-                                let input = &process_argument.input;
-                                let output = &process_argument.output;
-                                let args_copy = &process_argument.args_copy;
-                                let args_addr = &process_argument.args_addr;
-                                // redirect input
-                                if !input.is_empty() {
-                                    let input_fd = open(input.as_str(), OpenFlags::RDONLY);
-                                    if input_fd == -1 {
-                                        println!("Error when opening fs {}", input);
-                                        return -4;
-                                    }
-                                    let input_fd = input_fd as usize;
-                                    close(0);
-                                    assert_eq!(dup(input_fd), 0);
-                                    close(input_fd);
-                                }
-                                // redirect output
-                                if !output.is_empty() {
-                                    let output_fd = open(
-                                        output.as_str(),
-                                        OpenFlags::CREATE | OpenFlags::WRONLY,
-                                    );
-                                    if output_fd == -1 {
-                                        println!("Error when opening fs {}", output);
-                                        return -4;
-                                    }
-                                    let output_fd = output_fd as usize;
-                                    close(1);
-                                    assert_eq!(dup(output_fd), 1);
-                                    close(output_fd);
-                                }
-                                // receive input from the previous process
-                                if i > 0 {
-                                    close(0);
-                                    let read_end = pipes_fd.get(i - 1).unwrap()[0];
-                                    assert_eq!(dup(read_end), 0);
-                                }
-                                // send output to the next process
-                                if i < process_arguments_list.len() - 1 {
-                                    close(1);
-                                    let write_end = pipes_fd.get(i).unwrap()[1];
-                                    assert_eq!(dup(write_end), 1);
-                                }
-                                // close all pipe ends inherited from the parent process
-                                for pipe_fd in pipes_fd.iter() {
-                                    close(pipe_fd[0]);
-                                    close(pipe_fd[1]);
-                                }
-                                // execute new application
-                                if exec(args_copy[0].as_str(), args_addr.as_slice()) == -1 {
-                                    println!("Error when executing!");
-                                    return -4;
-                                }
-                                unreachable!();
-                            } else {
-                                children.push(pid);
-                            }
-                        }
-                        for pipe_fd in pipes_fd.iter() {
-                            close(pipe_fd[0]);
-                            close(pipe_fd[1]);
-                        }
-                        let mut exit_code: i32 = 0;
-                        for pid in children.into_iter() {
-                            let exit_pid = waitpid(pid as usize, &mut exit_code);
-                            assert_eq!(pid, exit_pid);
-                            //println!("Shell: Process {} exited with code {}",
-                            // pid, exit_code);
-                        }
-                    }
-                    line.clear();
+                let command = line.trim();
+                if !command.is_empty() && !run_builtin(command, &mut jobs, shell_pgid) {
+                    run_command(command, &mut jobs, shell_pgid);
                 }
+                line.clear();
+                report_done_jobs(&mut jobs);
                 print!("{}", LINE_START);
             }
             BS | DL => {
@@ -11,32 +11,57 @@ extern crate user_lib;
 static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("filetest_simple\0", "\0", "\0", "\0", 0),
     ("cat\0", "filea\0", "\0", "\0", 0),
+    ("clock_test\0", "\0", "\0", "\0", 0),
+    ("clone_test\0", "\0", "\0", "\0", 0),
     ("cmdline_args\0", "1\0", "2\0", "3\0", 0),
+    ("deadlock_test\0", "\0", "\0", "\0", 0),
+    ("eintr_test\0", "\0", "\0", "\0", 0),
+    ("elf_load_test\0", "\0", "\0", "\0", 0),
+    ("env_test\0", "\0", "\0", "\0", 0),
+    ("errno_test\0", "\0", "\0", "\0", 0),
+    ("exec_thread_test\0", "\0", "\0", "\0", 0),
     ("exit\0", "\0", "\0", "\0", 0),
     ("fantastic_text\0", "\0", "\0", "\0", 0),
+    ("fifo_test\0", "\0", "\0", "\0", 0),
+    ("flock_test\0", "\0", "\0", "\0", 0),
     ("forktest_simple\0", "\0", "\0", "\0", 0),
     ("forktest\0", "\0", "\0", "\0", 0),
     ("forktest2\0", "\0", "\0", "\0", 0),
     ("forktree\0", "\0", "\0", "\0", 0),
+    ("futex_test\0", "\0", "\0", "\0", 0),
     ("hello_world\0", "\0", "\0", "\0", 0),
     ("huge_write\0", "\0", "\0", "\0", 0),
+    ("itimer_test\0", "\0", "\0", "\0", 0),
+    ("linux_abi_test\0", "\0", "\0", "\0", 0),
     ("matrix\0", "\0", "\0", "\0", 0),
     ("mpsc_sem\0", "\0", "\0", "\0", 0),
+    ("pgid_test\0", "\0", "\0", "\0", 0),
     ("phil_din_mutex\0", "\0", "\0", "\0", 0),
+    ("phil_din_mutex\0", "futex\0", "\0", "\0", 0),
+    ("pi_test\0", "\0", "\0", "\0", 0),
     ("pipe_large_test\0", "\0", "\0", "\0", 0),
     ("pipetest\0", "\0", "\0", "\0", 0),
+    ("poll_test\0", "\0", "\0", "\0", 0),
     ("race_adder_arg\0", "3\0", "\0", "\0", 0),
     ("race_adder_atomic\0", "\0", "\0", "\0", 0),
     ("race_adder_mutex_blocking\0", "\0", "\0", "\0", 0),
+    ("race_adder_mutex_blocking\0", "futex\0", "\0", "\0", 0),
     ("race_adder_mutex_spin\0", "\0", "\0", "\0", 0),
     ("run_pipe_test\0", "\0", "\0", "\0", 0),
+    ("rusage_test\0", "\0", "\0", "\0", 0),
+    ("sched_test\0", "\0", "\0", "\0", 0),
+    ("sig_test\0", "\0", "\0", "\0", 0),
+    ("sigstop_test\0", "\0", "\0", "\0", 0),
     ("sleep_simple\0", "\0", "\0", "\0", 0),
     ("sleep\0", "\0", "\0", "\0", 0),
     ("sleep_simple\0", "\0", "\0", "\0", 0),
+    ("sync_ext_test\0", "\0", "\0", "\0", 0),
     ("sync_sem\0", "\0", "\0", "\0", 0),
     ("test_condvar\0", "\0", "\0", "\0", 0),
     ("threads_arg\0", "\0", "\0", "\0", 0),
     ("threads\0", "\0", "\0", "\0", 0),
+    ("time\0", "hello_world\0", "\0", "\0", 0),
+    ("user_mem_test\0", "\0", "\0", "\0", 0),
     ("yield\0", "\0", "\0", "\0", 0),
 ];
 
@@ -1,7 +1,7 @@
 use core::fmt::{self, Arguments, Write};
 
 use super::write;
-use crate::read;
+use crate::{read, SysError};
 
 struct Stdout;
 
@@ -38,6 +38,7 @@ macro_rules! println {
 
 pub fn getchar() -> u8 {
     let mut c = [0u8; 1];
-    read(STDIN, &mut c);
+    // 处理完信号后继续读
+    while SysError::check(read(STDIN, &mut c)) == Err(SysError::EINTR) {}
     c[0]
 }
@@ -0,0 +1,93 @@
+//! 系统调用失败时返回的 `-errno`
+
+use core::fmt::{self, Display, Formatter};
+
+macro_rules! sys_errors {
+    ($($name:ident = $errno:literal, $desc:literal;)*) => {
+        /// A Linux errno returned by the kernel
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(isize)]
+        pub enum SysError {
+            $(
+                #[doc = $desc]
+                $name = $errno,
+            )*
+        }
+
+        impl SysError {
+            /// The error of a positive errno
+            pub fn from_errno(errno: isize) -> Option<Self> {
+                match errno {
+                    $($errno => Some(Self::$name),)*
+                    _ => None,
+                }
+            }
+
+            /// What the error means, as `strerror` puts it
+            pub fn description(self) -> &'static str {
+                match self {
+                    $(Self::$name => $desc,)*
+                }
+            }
+        }
+    };
+}
+
+sys_errors! {
+    EPERM = 1, "Operation not permitted";
+    ENOENT = 2, "No such file or directory";
+    ESRCH = 3, "No such process";
+    EINTR = 4, "Interrupted system call";
+    EIO = 5, "I/O error";
+    ENXIO = 6, "No such device or address";
+    E2BIG = 7, "Argument list too long";
+    ENOEXEC = 8, "Exec format error";
+    EBADF = 9, "Bad file number";
+    ECHILD = 10, "No child processes";
+    EAGAIN = 11, "Try again";
+    ENOMEM = 12, "Out of memory";
+    EACCES = 13, "Permission denied";
+    EFAULT = 14, "Bad address";
+    EBUSY = 16, "Device or resource busy";
+    EEXIST = 17, "File exists";
+    ENODEV = 19, "No such device";
+    ENOTDIR = 20, "Not a directory";
+    EISDIR = 21, "Is a directory";
+    EINVAL = 22, "Invalid argument";
+    EMFILE = 24, "Too many open files";
+    ENOTTY = 25, "Not a typewriter";
+    ENOSPC = 28, "No space left on device";
+    ESPIPE = 29, "Illegal seek";
+    EPIPE = 32, "Broken pipe";
+    ERANGE = 34, "Math result not representable";
+    EDEADLK = 35, "Resource deadlock would occur";
+    ENAMETOOLONG = 36, "File name too long";
+    ENOSYS = 38, "Function not implemented";
+    ETIMEDOUT = 110, "Connection timed out";
+}
+
+/// The value of a successful syscall, or why it failed
+pub type SysResult<T = usize> = Result<T, SysError>;
+
+impl SysError {
+    /// Split the return value of a syscall into its result and error,
+    /// an errno this library does not know becomes `EIO`
+    pub fn check(ret: isize) -> SysResult {
+        if ret >= 0 {
+            Ok(ret as usize)
+        } else {
+            Err(Self::from_errno(-ret).unwrap_or(Self::EIO))
+        }
+    }
+
+    /// The value a syscall returns for this error
+    pub fn ret(self) -> isize {
+        -(self as isize)
+    }
+}
+
+impl Display for SysError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
@@ -5,11 +5,211 @@ bitflags! {
         const RDONLY = 0;
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
-        const CREATE = 1 << 9;
-        const TRUNC = 1 << 10;
+        const CREATE = 0o100;
+        const TRUNC = 0o1000;
+        const NONBLOCK = 0o4000;
+        const DIRECTORY = 0o200000;
+        const CLOEXEC = 0o2000000;
     }
 }
 
+bitflags! {
+    pub struct PollEvents: i16 {
+        const POLLIN = 0x001;
+        const POLLPRI = 0x002;
+        const POLLOUT = 0x004;
+        const POLLERR = 0x008;
+        const POLLHUP = 0x010;
+        const POLLNVAL = 0x020;
+    }
+}
+
+/// An entry of the `fds` array of `poll`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+impl PollFd {
+    pub fn new(fd: usize, events: PollEvents) -> Self {
+        Self {
+            fd: fd as i32,
+            events: events.bits(),
+            revents: 0,
+        }
+    }
+    pub fn revents(&self) -> PollEvents {
+        PollEvents::from_bits_truncate(self.revents)
+    }
+}
+
+/// Returned by `read` and `write` on a non-blocking fd that is not ready
+pub const EAGAIN: isize = -(SysError::EAGAIN as isize);
+
+/// Resolve relative paths of `*at` calls from the root directory
+pub const AT_FDCWD: isize = -100;
+
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFREG: u32 = 0o100000;
+
+/// `d_type` of a `Dirent`
+pub const DT_FIFO: u8 = 1;
+pub const DT_CHR: u8 = 2;
+pub const DT_DIR: u8 = 4;
+pub const DT_REG: u8 = 8;
+
+/// Get the access mode and `O_NONBLOCK` of a fd
+pub const F_GETFL: usize = 3;
+/// Set `O_NONBLOCK` of a fd
+pub const F_SETFL: usize = 4;
+/// Query the capacity of a pipe
+pub const F_GETPIPE_SZ: usize = 1032;
+/// Resize a pipe, the new capacity is returned
+pub const F_SETPIPE_SZ: usize = 1031;
+/// Find a record lock that would conflict with the given one
+pub const F_GETLK: usize = 5;
+/// Acquire or release a record lock, `EAGAIN` on conflict
+pub const F_SETLK: usize = 6;
+/// Acquire a record lock, waiting for conflicting locks to go away
+pub const F_SETLKW: usize = 7;
+
+pub const F_RDLCK: i16 = 0;
+pub const F_WRLCK: i16 = 1;
+pub const F_UNLCK: i16 = 2;
+
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+
+pub const LOCK_SH: u32 = 1;
+pub const LOCK_EX: u32 = 2;
+pub const LOCK_NB: u32 = 4;
+pub const LOCK_UN: u32 = 8;
+
+/// A byte range lock, see `fcntl_lock`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Flock {
+    pub l_type: i16,
+    /// 0: from the beginning, 1: from the current offset, 2: from the end
+    pub l_whence: i16,
+    pub l_start: i64,
+    /// 0 means up to the end of the file, however long it grows
+    pub l_len: i64,
+    pub l_pid: i32,
+}
+
+impl Flock {
+    pub fn new(l_type: i16, l_start: i64, l_len: i64) -> Self {
+        Self {
+            l_type,
+            l_start,
+            l_len,
+            ..Default::default()
+        }
+    }
+}
+
+/// Filesystem statistics, see `statfs`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StatFs {
+    pub f_type: u64,
+    pub f_bsize: u64,
+    pub f_blocks: u64,
+    pub f_bfree: u64,
+    pub f_bavail: u64,
+    pub f_files: u64,
+    pub f_ffree: u64,
+    pub f_fsid: [i32; 2],
+    pub f_namelen: u64,
+    pub f_frsize: u64,
+    pub f_flags: u64,
+    pub f_spare: [u64; 4],
+}
+
+/// File status, see `fstat`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stat {
+    pub st_dev: u64,
+    pub st_ino: u64,
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: u64,
+    pub __pad1: u64,
+    pub st_size: i64,
+    pub st_blksize: i32,
+    pub __pad2: i32,
+    pub st_blocks: i64,
+    pub st_atime_sec: i64,
+    pub st_atime_nsec: i64,
+    pub st_mtime_sec: i64,
+    pub st_mtime_nsec: i64,
+    pub st_ctime_sec: i64,
+    pub st_ctime_nsec: i64,
+    pub __unused: [u32; 2],
+}
+
+/// A buffer of `readv` and `writev`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct IoVec {
+    pub base: *const u8,
+    pub len: usize,
+}
+
+impl From<&[u8]> for IoVec {
+    fn from(buf: &[u8]) -> Self {
+        Self {
+            base: buf.as_ptr(),
+            len: buf.len(),
+        }
+    }
+}
+
+impl From<&mut [u8]> for IoVec {
+    fn from(buf: &mut [u8]) -> Self {
+        Self {
+            base: buf.as_mut_ptr(),
+            len: buf.len(),
+        }
+    }
+}
+
+/// An entry parsed from the `linux_dirent64` records of `getdents64`
+#[derive(Debug, Clone, Copy)]
+pub struct Dirent<'a> {
+    pub ino: u64,
+    pub d_type: u8,
+    pub name: &'a str,
+}
+
+/// Iterate over the records `getdents64` wrote into `buf`
+pub fn dirents(buf: &[u8]) -> impl Iterator<Item = Dirent<'_>> {
+    let mut rest = buf;
+    core::iter::from_fn(move || {
+        if rest.len() < 19 {
+            return None;
+        }
+        let ino = u64::from_le_bytes(rest[0..8].try_into().unwrap());
+        let reclen = u16::from_le_bytes(rest[16..18].try_into().unwrap()) as usize;
+        let d_type = rest[18];
+        let name = &rest[19..reclen];
+        let name_len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        let name = core::str::from_utf8(&name[..name_len]).unwrap_or("");
+        rest = &rest[reclen..];
+        Some(Dirent { ino, d_type, name })
+    })
+}
+
 pub fn read(fd: usize, buf: &mut [u8]) -> isize {
     sys_read(fd, buf)
 }
@@ -18,7 +218,29 @@ pub fn write(fd: usize, buf: &[u8]) -> isize {
 }
 
 pub fn open(path: &str, flags: OpenFlags) -> isize {
-    sys_open(path, flags.bits)
+    sys_openat(AT_FDCWD, path, flags.bits, 0o666)
+}
+
+pub fn openat(dirfd: isize, path: &str, flags: OpenFlags) -> isize {
+    sys_openat(dirfd, path, flags.bits, 0o666)
+}
+
+pub fn readv(fd: usize, iov: &[IoVec]) -> isize {
+    sys_readv(fd, iov)
+}
+
+pub fn writev(fd: usize, iov: &[IoVec]) -> isize {
+    sys_writev(fd, iov)
+}
+
+pub fn fstat(fd: usize, stat: &mut Stat) -> isize {
+    sys_fstat(fd, stat)
+}
+
+/// Read the next entries of the directory `fd` into `buf`, parse them with
+/// `dirents`
+pub fn getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    sys_getdents64(fd, buf)
 }
 pub fn close(fd: usize) -> isize {
     sys_close(fd)
@@ -27,10 +249,62 @@ pub fn pipe(pipe_fd: &mut [usize]) -> isize {
     sys_pipe(pipe_fd)
 }
 
+pub fn pipe2(pipe_fd: &mut [usize], flags: OpenFlags) -> isize {
+    sys_pipe2(pipe_fd, flags.bits)
+}
+
+/// Wait until any of `fds` is ready, for at most `timeout_ms` milliseconds
+/// unless it is negative
+pub fn poll(fds: &mut [PollFd], timeout_ms: isize) -> isize {
+    if timeout_ms < 0 {
+        sys_ppoll(fds, None)
+    } else {
+        let timeout = TimeSpec::from_ms(timeout_ms as usize);
+        sys_ppoll(fds, Some(&timeout))
+    }
+}
+
 pub fn dup(fd: usize) -> isize {
     sys_dup(fd)
 }
 
+pub fn mkfifo(path: &str) -> isize {
+    sys_mknodat(AT_FDCWD, path, S_IFIFO | 0o666)
+}
+
+pub fn fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    sys_fcntl(fd, cmd, arg)
+}
+
+/// `F_GETLK`, `F_SETLK` or `F_SETLKW` with a `Flock`
+pub fn fcntl_lock(fd: usize, cmd: usize, lock: &mut Flock) -> isize {
+    sys_fcntl(fd, cmd, lock as *mut Flock as usize)
+}
+
+pub fn flock(fd: usize, operation: u32) -> isize {
+    sys_flock(fd, operation)
+}
+
+/// The foreground process group of the terminal `fd`
+pub fn tcgetpgrp(fd: usize) -> isize {
+    let mut pgid: i32 = 0;
+    match sys_ioctl(fd, TIOCGPGRP, &mut pgid as *mut i32 as usize) {
+        0 => pgid as isize,
+        error => error,
+    }
+}
+
+/// Make `pgid` the foreground process group of the terminal `fd`, which
+/// gets the signals of Ctrl-C, Ctrl-\ and Ctrl-Z
+pub fn tcsetpgrp(fd: usize, pgid: usize) -> isize {
+    let pgid = pgid as i32;
+    sys_ioctl(fd, TIOCSPGRP, &pgid as *const i32 as usize)
+}
+
+pub fn statfs(path: &str, buf: &mut StatFs) -> isize {
+    sys_statfs(path, buf)
+}
+
 pub fn ls() -> isize {
     sys_ls()
 }
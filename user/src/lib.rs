@@ -3,8 +3,10 @@
 #![allow(static_mut_refs)]
 #[macro_use]
 pub mod console;
+mod error;
 mod fs;
 mod lang_items;
+mod mm;
 mod process;
 mod sign;
 mod sync;
@@ -21,7 +23,9 @@ extern crate alloc;
 extern crate bitflags;
 extern crate buddy_system_allocator;
 
+pub use error::*;
 pub use fs::*;
+pub use mm::*;
 pub use process::*;
 pub use sign::*;
 pub use sync::*;
@@ -35,29 +39,30 @@ static HEAP: LockedHeap<32> = LockedHeap::empty();
 
 #[no_mangle]
 #[link_section = ".text.entry"]
-pub extern "C" fn _start(argc: usize, argv: usize) -> ! {
+pub extern "C" fn _start(argc: usize, argv: usize, envp: usize) -> ! {
     clear_bss();
     unsafe {
         HEAP.lock()
             .init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
+        ENVIRON = envp as *const *const u8;
     }
     let mut v: Vec<&'static str> = Vec::new();
     for i in 0..argc {
         let str_start =
             unsafe { ((argv + i * core::mem::size_of::<usize>()) as *const usize).read_volatile() };
-        let len = (0usize..)
-            .find(|i| unsafe { ((str_start + *i) as *const u8).read_volatile() == 0 })
-            .unwrap();
-        v.push(
-            core::str::from_utf8(unsafe {
-                core::slice::from_raw_parts(str_start as *const u8, len)
-            })
-            .unwrap(),
-        );
+        v.push(c_str(str_start));
     }
     exit(main(argc, v.as_slice()))
 }
 
+/// 内核放在用户栈上的以 `\0` 结尾的字符串
+fn c_str(start: usize) -> &'static str {
+    let len = (0usize..)
+        .find(|i| unsafe { ((start + *i) as *const u8).read_volatile() == 0 })
+        .unwrap();
+    core::str::from_utf8(unsafe { core::slice::from_raw_parts(start as *const u8, len) }).unwrap()
+}
+
 #[linkage = "weak"]
 #[no_mangle]
 fn main(_argc: usize, _argv: &[&str]) -> i32 {
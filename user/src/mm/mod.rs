@@ -0,0 +1,60 @@
+use bitflags::bitflags;
+
+use crate::syscall::*;
+
+bitflags! {
+    pub struct MmapProt: u32 {
+        const PROT_NONE = 0;
+        const PROT_READ = 1;
+        const PROT_WRITE = 2;
+        const PROT_EXEC = 4;
+    }
+}
+
+bitflags! {
+    pub struct MmapFlags: u32 {
+        const MAP_SHARED = 0x01;
+        const MAP_PRIVATE = 0x02;
+        const MAP_FIXED = 0x10;
+        const MAP_ANONYMOUS = 0x20;
+    }
+}
+
+/// Returned by `mmap` on failure, like `(void *)-1` in C
+pub const MAP_FAILED: isize = -1;
+
+/// 映射成功时返回映射的起始地址，否则返回 -errno
+pub fn mmap(
+    addr: usize,
+    len: usize,
+    prot: MmapProt,
+    flags: MmapFlags,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    sys_mmap(addr, len, prot.bits(), flags.bits(), fd, offset)
+}
+
+pub fn munmap(addr: usize, len: usize) -> isize {
+    sys_munmap(addr, len)
+}
+
+/// 返回新的 program break，不能移动时返回原来的值
+pub fn brk(addr: usize) -> isize {
+    sys_brk(addr)
+}
+
+/// 把 program break 移动 `increment` 字节，返回原来的 program break，
+/// 失败时返回 -1
+pub fn sbrk(increment: isize) -> isize {
+    let old = sys_brk(0);
+    if increment == 0 {
+        return old;
+    }
+    let new = old + increment;
+    if sys_brk(new as usize) == new {
+        old
+    } else {
+        -1
+    }
+}
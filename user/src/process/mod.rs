@@ -2,38 +2,175 @@ use crate::*;
 pub fn getpid() -> isize {
     sys_getpid()
 }
+pub fn getpgid(pid: usize) -> isize {
+    sys_getpgid(pid)
+}
+pub fn setpgid(pid: usize, pgid: usize) -> isize {
+    sys_setpgid(pid, pgid)
+}
+pub fn setsid() -> isize {
+    sys_setsid()
+}
+/// `struct utsname`, each field ends with `\0`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct UtsName {
+    pub sysname: [u8; 65],
+    pub nodename: [u8; 65],
+    pub release: [u8; 65],
+    pub version: [u8; 65],
+    pub machine: [u8; 65],
+    pub domainname: [u8; 65],
+}
+
+impl Default for UtsName {
+    fn default() -> Self {
+        Self {
+            sysname: [0; 65],
+            nodename: [0; 65],
+            release: [0; 65],
+            version: [0; 65],
+            machine: [0; 65],
+            domainname: [0; 65],
+        }
+    }
+}
+
+/// A field of `UtsName` without the trailing `\0`
+pub fn uts_str(field: &[u8; 65]) -> &str {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    core::str::from_utf8(&field[..len]).unwrap_or("")
+}
+
+pub fn uname(buf: &mut UtsName) -> isize {
+    sys_uname(buf)
+}
+pub const PRIO_PROCESS: usize = 0;
+pub const PRIO_PGRP: usize = 1;
+pub const PRIO_USER: usize = 2;
+
+pub fn setpriority(which: usize, who: usize, nice: i32) -> isize {
+    sys_setpriority(which, who, nice)
+}
+/// 选中的进程中最小的 nice 值
+pub fn getpriority(which: usize, who: usize) -> SysResult<i32> {
+    SysError::check(sys_getpriority(which, who)).map(|prio| 20 - prio as i32)
+}
+/// 把当前进程的 nice 增加 `inc`，返回新的 nice 值
+pub fn nice(inc: i32) -> SysResult<i32> {
+    let nice = getpriority(PRIO_PROCESS, 0)? + inc;
+    SysError::check(setpriority(PRIO_PROCESS, 0, nice))?;
+    getpriority(PRIO_PROCESS, 0)
+}
 pub fn fork() -> isize {
     sys_fork()
 }
+/// 新程序继承当前的环境变量
 pub fn exec(path: &str, args: &[*const u8]) -> isize {
-    sys_exec(path, args)
+    sys_exec(path, args, unsafe { ENVIRON })
+}
+/// 用 `envs` 作为新程序的环境变量，它和 `args` 一样以空指针结尾
+pub fn execve(path: &str, args: &[*const u8], envs: &[*const u8]) -> isize {
+    sys_exec(path, args, envs.as_ptr())
 }
 
-/// 等待任意一个子进程结束
-pub fn wait(exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(-1, exit_code as *mut _) {
-            -2 => {
-                yield_();
+/// 内核传给 `_start` 的 envp
+pub(crate) static mut ENVIRON: *const *const u8 = core::ptr::null();
+
+/// The environment, each entry as `NAME=value`
+pub fn environ() -> impl Iterator<Item = &'static str> {
+    let envp = unsafe { ENVIRON };
+    (0..)
+        .map(move |i| unsafe { envp.add(i).read() } as usize)
+        .take_while(|&env| env != 0)
+        .map(c_str)
+}
+
+/// The value of the environment variable `name`
+pub fn getenv(name: &str) -> Option<&'static str> {
+    environ().find_map(|env| {
+        env.strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('='))
+    })
+}
+
+/// Types of the auxiliary vector entries
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_BASE: usize = 7;
+pub const AT_ENTRY: usize = 9;
+pub const AT_RANDOM: usize = 25;
+
+/// The value of an auxiliary vector entry, which the kernel puts right after
+/// the NULL that ends envp
+pub fn getauxval(type_: usize) -> Option<usize> {
+    let mut entry = unsafe { ENVIRON };
+    unsafe {
+        while !entry.read().is_null() {
+            entry = entry.add(1);
+        }
+        let mut auxv = entry.add(1) as *const usize;
+        loop {
+            match auxv.read() {
+                0 => return None,
+                t if t == type_ => return Some(auxv.add(1).read()),
+                _ => auxv = auxv.add(2),
             }
-            // -1 or a real pid
-            exit_pid => return exit_pid,
         }
     }
 }
 
+/// 等待任意一个子进程结束
+pub fn wait(exit_code: &mut i32) -> isize {
+    // -ECHILD or a real pid
+    sys_waitpid(-1, exit_code as *mut _, 0)
+}
+
 pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(pid as isize, exit_code as *mut _) {
-            -2 => {
-                yield_();
-            }
-            // -1 or a real pid
-            exit_pid => return exit_pid,
-        }
-    }
+    sys_waitpid(pid as isize, exit_code as *mut _, 0)
+}
+
+/// 和 `waitpid` 相同，同时取得子进程的运行时间
+pub fn wait4(pid: isize, exit_code: &mut i32, options: u32, rusage: &mut RUsage) -> isize {
+    sys_wait4(pid, exit_code as *mut _, options, rusage as *mut _)
 }
 
+/// 不阻塞地回收子进程，子进程还没有结束时返回 0
 pub fn waitpid_nb(pid: usize, exit_code: &mut i32) -> isize {
-    sys_waitpid(pid as isize, exit_code as *mut _)
+    sys_waitpid(pid as isize, exit_code as *mut _, WNOHANG)
+}
+
+/// Return at once if no child has changed state
+pub const WNOHANG: u32 = 1;
+/// Also report children stopped by a signal
+pub const WUNTRACED: u32 = 2;
+/// Also report stopped children continued by SIGCONT
+pub const WCONTINUED: u32 = 8;
+
+/// A state change of a child, see `waitpid_options`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitStatus {
+    /// The exit code, `-signum` if it was killed by a signal
+    Exited(i32),
+    /// Stopped by the signal
+    Stopped(i32),
+    Continued,
+}
+
+/// Wait for `pid` (any child if -1) to exit, or to stop or continue if
+/// `options` asks for it. An exit code that looks like the stop or continue
+/// encoding of `sys_waitpid` cannot be told apart from them.
+pub fn waitpid_options(pid: isize, options: u32) -> SysResult<(usize, WaitStatus)> {
+    let mut status = 0;
+    let found_pid = SysError::check(sys_waitpid(pid, &mut status as *mut _, options))?;
+    let status = if options & WUNTRACED != 0 && status & 0xff == 0x7f {
+        WaitStatus::Stopped(status >> 8)
+    } else if options & WCONTINUED != 0 && status == 0xffff {
+        WaitStatus::Continued
+    } else {
+        WaitStatus::Exited(status)
+    };
+    Ok((found_pid, status))
 }
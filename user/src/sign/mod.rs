@@ -1,6 +1,6 @@
 use bitflags::bitflags;
 
-use crate::sys_kill;
+use crate::{sys_kill, sys_sigaction, sys_sigprocmask};
 
 pub const SIGDEF: i32 = 0; // Default signal handling
 pub const SIGHUP: i32 = 1;
@@ -36,6 +36,7 @@ pub const SIGPWR: i32 = 30;
 pub const SIGSYS: i32 = 31;
 
 bitflags! {
+    #[repr(transparent)]
     pub struct SignalFlags: i32 {
         const SIGDEF = 1; // Default signal handling
         const SIGHUP = 1 << 1;
@@ -72,6 +73,93 @@ bitflags! {
     }
 }
 
+/// Use the default action of a signal
+pub const SIG_DFL: usize = 0;
+/// Ignore a signal
+pub const SIG_IGN: usize = 1;
+
+/// `how` of `sigprocmask`
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+impl SignalFlags {
+    /// The flag of signal number `signum`
+    pub fn from_signum(signum: i32) -> Self {
+        Self::from_bits_truncate(1 << signum)
+    }
+}
+
+/// What the process does on a signal, see `sigaction`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalAction {
+    /// `SIG_DFL`, `SIG_IGN` or the address of an `extern "C" fn(i32)`
+    pub handler: usize,
+    /// Signals blocked while the handler runs, besides the signal itself
+    pub mask: SignalFlags,
+    /// Filled in by `sigaction`
+    pub restorer: usize,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+            restorer: 0,
+        }
+    }
+}
+
+impl SignalAction {
+    pub fn new(handler: extern "C" fn(i32), mask: SignalFlags) -> Self {
+        Self {
+            handler: handler as usize,
+            mask,
+            restorer: 0,
+        }
+    }
+}
+
 pub fn kill(pid: usize, signal: i32) -> isize {
     sys_kill(pid, signal)
 }
+
+/// Send `signal` to every process of group `pgid`
+pub fn killpg(pgid: usize, signal: i32) -> isize {
+    sys_kill((pgid as isize).wrapping_neg() as usize, signal)
+}
+
+/// Set the action for `signum` to `action` if given, the previous one is
+/// stored in `old_action` if given
+pub fn sigaction(
+    signum: i32,
+    action: Option<&SignalAction>,
+    old_action: Option<&mut SignalAction>,
+) -> isize {
+    extern "C" {
+        fn __sigreturn_trampoline();
+    }
+    let action = action.map(|action| SignalAction {
+        restorer: __sigreturn_trampoline as usize,
+        ..*action
+    });
+    sys_sigaction(signum, action.as_ref(), old_action)
+}
+
+/// Change the signal mask of the current thread as `how` says, the previous
+/// mask is stored in `old_set` if given
+pub fn sigprocmask(
+    how: usize,
+    set: Option<SignalFlags>,
+    old_set: Option<&mut SignalFlags>,
+) -> isize {
+    let set = set.map(|set| set.bits());
+    let mut old = 0;
+    let ret = sys_sigprocmask(how, set.as_ref(), Some(&mut old));
+    if let Some(old_set) = old_set {
+        *old_set = SignalFlags::from_bits_truncate(old);
+    }
+    ret
+}
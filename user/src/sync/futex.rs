@@ -0,0 +1,110 @@
+//! 基于 futex 的互斥锁和条件变量，没有竞争时不进入内核
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{syscall::*, SysError, TimeSpec};
+
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+pub const FUTEX_REQUEUE: usize = 3;
+pub const FUTEX_PRIVATE_FLAG: usize = 128;
+
+/// `futex` 的值仍然是 `expected` 时阻塞，直到被唤醒或者超时
+pub fn futex_wait(futex: &AtomicU32, expected: u32, timeout: Option<&TimeSpec>) -> isize {
+    sys_futex(
+        futex.as_ptr(),
+        FUTEX_WAIT,
+        expected as usize,
+        timeout.map_or(0, |timeout| timeout as *const TimeSpec as usize),
+        core::ptr::null(),
+        0,
+    )
+}
+
+/// 唤醒最多 `count` 个线程，返回唤醒的数量
+pub fn futex_wake(futex: &AtomicU32, count: usize) -> isize {
+    sys_futex(futex.as_ptr(), FUTEX_WAKE, count, 0, core::ptr::null(), 0)
+}
+
+/// 唤醒最多 `wake` 个线程，剩下的最多 `requeue` 个改为在 `target` 上等待
+pub fn futex_requeue(futex: &AtomicU32, wake: usize, target: &AtomicU32, requeue: usize) -> isize {
+    sys_futex(
+        futex.as_ptr(),
+        FUTEX_REQUEUE,
+        wake,
+        requeue,
+        target.as_ptr(),
+        0,
+    )
+}
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+/// 有线程在等待，解锁时需要唤醒
+const CONTENDED: u32 = 2;
+
+/// 互斥锁，可以放在 static 中在线程之间共享
+pub struct Mutex {
+    state: AtomicU32,
+}
+
+impl Mutex {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+        }
+    }
+    pub fn try_lock(&self) -> bool {
+        self.state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+    pub fn lock(&self) {
+        if self.try_lock() {
+            return;
+        }
+        // 拿不到锁时标记为有竞争，被唤醒后同样以有竞争的状态拿锁，
+        // 因为可能还有其他线程在等待
+        while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            futex_wait(&self.state, CONTENDED, None);
+        }
+    }
+    pub fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            futex_wake(&self.state, 1);
+        }
+    }
+}
+
+/// 条件变量。每次通知都改变序号，等待者在解锁前读到的序号不同时不会睡眠，
+/// 所以解锁和睡眠之间的通知不会丢失
+pub struct Condvar {
+    seq: AtomicU32,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+        }
+    }
+    pub fn wait(&self, mutex: &Mutex) {
+        self.wait_timeout(mutex, None);
+    }
+    /// 超时返回 false。和 pthread 一样可能被虚假唤醒，调用者要重新检查条件
+    pub fn wait_timeout(&self, mutex: &Mutex, timeout: Option<&TimeSpec>) -> bool {
+        let seq = self.seq.load(Ordering::Relaxed);
+        mutex.unlock();
+        let ret = futex_wait(&self.seq, seq, timeout);
+        mutex.lock();
+        SysError::check(ret) != Err(SysError::ETIMEDOUT)
+    }
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        futex_wake(&self.seq, 1);
+    }
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        futex_wake(&self.seq, i32::MAX as usize);
+    }
+}
@@ -1,32 +1,87 @@
+mod futex;
+
+pub use futex::*;
+
 use crate::*;
 
+pub fn enable_deadlock_detect(enabled: bool) -> isize {
+    sys_enable_deadlock_detect(enabled as usize)
+}
 pub fn mutex_create() -> isize {
     sys_mutex_create(false)
 }
 pub fn mutex_blocking_create() -> isize {
     sys_mutex_create(true)
 }
-pub fn mutex_lock(mutex_id: usize) {
-    sys_mutex_lock(mutex_id);
+pub fn mutex_lock(mutex_id: usize) -> isize {
+    sys_mutex_lock(mutex_id)
 }
-pub fn mutex_unlock(mutex_id: usize) {
-    sys_mutex_unlock(mutex_id);
+pub fn mutex_unlock(mutex_id: usize) -> isize {
+    sys_mutex_unlock(mutex_id)
 }
 pub fn semaphore_create(res_count: usize) -> isize {
     sys_semaphore_create(res_count)
 }
-pub fn semaphore_up(sem_id: usize) {
-    sys_semaphore_up(sem_id);
+pub fn semaphore_up(sem_id: usize) -> isize {
+    sys_semaphore_up(sem_id)
 }
-pub fn semaphore_down(sem_id: usize) {
-    sys_semaphore_down(sem_id);
+pub fn semaphore_down(sem_id: usize) -> isize {
+    sys_semaphore_down(sem_id)
 }
 pub fn condvar_create() -> isize {
     sys_condvar_create(0)
 }
-pub fn condvar_signal(condvar_id: usize) {
-    sys_condvar_signal(condvar_id);
+pub fn condvar_signal(condvar_id: usize) -> isize {
+    sys_condvar_signal(condvar_id)
+}
+pub fn condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    sys_condvar_wait(condvar_id, mutex_id)
+}
+pub fn mutex_try_lock(mutex_id: usize) -> isize {
+    sys_mutex_trylock(mutex_id)
+}
+pub fn mutex_lock_timeout(mutex_id: usize, timeout: &TimeSpec) -> isize {
+    sys_mutex_lock_timeout(mutex_id, timeout)
+}
+pub fn mutex_destroy(mutex_id: usize) -> isize {
+    sys_mutex_destroy(mutex_id)
+}
+pub fn semaphore_down_timeout(sem_id: usize, timeout: &TimeSpec) -> isize {
+    sys_semaphore_down_timeout(sem_id, timeout)
+}
+pub fn semaphore_destroy(sem_id: usize) -> isize {
+    sys_semaphore_destroy(sem_id)
+}
+pub fn condvar_broadcast(condvar_id: usize) -> isize {
+    sys_condvar_broadcast(condvar_id)
+}
+pub fn condvar_wait_timeout(condvar_id: usize, mutex_id: usize, timeout: &TimeSpec) -> isize {
+    sys_condvar_wait_timeout(condvar_id, mutex_id, timeout)
+}
+pub fn condvar_destroy(condvar_id: usize) -> isize {
+    sys_condvar_destroy(condvar_id)
+}
+pub fn rwlock_create() -> isize {
+    sys_rwlock_create()
+}
+pub fn rwlock_read(rwlock_id: usize) -> isize {
+    sys_rwlock_read(rwlock_id)
+}
+pub fn rwlock_write(rwlock_id: usize) -> isize {
+    sys_rwlock_write(rwlock_id)
+}
+pub fn rwlock_unlock(rwlock_id: usize) -> isize {
+    sys_rwlock_unlock(rwlock_id)
+}
+pub fn rwlock_destroy(rwlock_id: usize) -> isize {
+    sys_rwlock_destroy(rwlock_id)
+}
+pub fn barrier_create(count: usize) -> isize {
+    sys_barrier_create(count)
+}
+pub fn barrier_wait(barrier_id: usize) -> isize {
+    sys_barrier_wait(barrier_id)
 }
-pub fn condvar_wait(condvar_id: usize, mutex_id: usize) {
-    sys_condvar_wait(condvar_id, mutex_id);
+pub fn barrier_destroy(barrier_id: usize) -> isize {
+    sys_barrier_destroy(barrier_id)
 }
@@ -1,15 +1,44 @@
-    .section .text
-    .globl do_syscall
-    .align 4
-do_syscall:
-    #syscall(id: usize, args0: usize,args1:usize,args2:usize,)
-    move $t0,$a0
-    move $t1,$a1
-    move $t2,$a2
-    move $t3,$a3
-    move $a7, $t0
-    move $a0, $t1
-    move $a1, $t2
-    move $a2, $t3
-    syscall 0
-    jr $ra
\ No newline at end of file
+    .section .text
+    .globl do_syscall
+    .align 4
+do_syscall:
+    # do_syscall(id, args0, args1, args2, args3, args4, args5)
+    move $a7, $a0
+    move $a0, $a1
+    move $a1, $a2
+    move $a2, $a3
+    move $a3, $a4
+    move $a4, $a5
+    move $a5, $a6
+    syscall 0
+    jr $ra
+
+    .globl __clone
+    .align 4
+__clone:
+    # __clone(entry, arg, flags, stack, ptid, ctid, tls)
+    # the child gets a copy of all registers, so entry and arg survive in t0/t1
+    move $t0, $a0
+    move $t1, $a1
+    move $a0, $a2
+    move $a1, $a3
+    move $a2, $a4
+    move $a3, $a5
+    move $a4, $a6
+    li.w $a7, 220
+    syscall 0
+    bnez $a0, 1f
+    # child: entry(arg), exit with what it returns
+    move $a0, $t1
+    jirl $ra, $t0, 0
+    li.w $a7, 93
+    syscall 0
+1:
+    jr $ra
+
+    # signal handlers return here, sigreturn (139) never comes back
+    .globl __sigreturn_trampoline
+    .align 4
+__sigreturn_trampoline:
+    li.w $a7, 139
+    syscall 0
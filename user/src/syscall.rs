@@ -1,42 +1,111 @@
 use core::arch::global_asm;
 
+use crate::{
+    ITimerSpec, ITimerVal, IoVec, PollFd, RUsage, SignalAction, Stat, StatFs, TimeSpec, Tms,
+    UtsName,
+};
+
 const SYSCALL_DUP: usize = 24;
-const SYSCALL_OPEN: usize = 56;
+const SYSCALL_FCNTL: usize = 25;
+const SYSCALL_IOCTL: usize = 29;
+const SYSCALL_FLOCK: usize = 32;
+const SYSCALL_MKNODAT: usize = 33;
+const SYSCALL_STATFS: usize = 43;
+const SYSCALL_OPENAT: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_PIPE: usize = 59;
+const SYSCALL_GETDENTS64: usize = 61;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_READV: usize = 65;
+const SYSCALL_WRITEV: usize = 66;
+const SYSCALL_PPOLL: usize = 73;
+const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_TIMERFD_CREATE: usize = 85;
+const SYSCALL_TIMERFD_SETTIME: usize = 86;
+const SYSCALL_TIMERFD_GETTIME: usize = 87;
 const SYSCALL_EXIT: usize = 93;
-const SYSCALL_SLEEP: usize = 101;
+const SYSCALL_EXIT_GROUP: usize = 94;
+const SYSCALL_SET_TID_ADDRESS: usize = 96;
+const SYSCALL_FUTEX: usize = 98;
+const SYSCALL_NANOSLEEP: usize = 101;
+const SYSCALL_GETITIMER: usize = 102;
+const SYSCALL_SETITIMER: usize = 103;
+const SYSCALL_CLOCK_GETTIME: usize = 113;
+const SYSCALL_CLOCK_GETRES: usize = 114;
+const SYSCALL_CLOCK_NANOSLEEP: usize = 115;
 const SYSCALL_YIELD: usize = 124;
 const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SETPRIORITY: usize = 140;
+const SYSCALL_GETPRIORITY: usize = 141;
+const SYSCALL_TIMES: usize = 153;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_SETSID: usize = 157;
+const SYSCALL_UNAME: usize = 160;
+const SYSCALL_GETRUSAGE: usize = 165;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GETPID: usize = 172;
-const SYSCALL_FORK: usize = 220;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
+const SYSCALL_CLONE: usize = 220;
 const SYSCALL_EXEC: usize = 221;
-const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_WAIT4: usize = 260;
+const SYSCALL_ENABLE_DEADLOCK_DETECT: usize = 469;
 
-const SYSCALL_THREAD_CREATE: usize = 1000;
 const SYSCALL_GETTID: usize = 1001;
 const SYSCALL_WAITTID: usize = 1002;
+const SYSCALL_THREAD_SETPRIORITY: usize = 1003;
+const SYSCALL_THREAD_GETPRIORITY: usize = 1004;
 const SYSCALL_MUTEX_CREATE: usize = 1010;
 const SYSCALL_MUTEX_LOCK: usize = 1011;
 const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+const SYSCALL_MUTEX_TRYLOCK: usize = 1013;
+const SYSCALL_MUTEX_LOCK_TIMEOUT: usize = 1014;
+const SYSCALL_MUTEX_DESTROY: usize = 1015;
 const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
 const SYSCALL_SEMAPHORE_UP: usize = 1021;
 const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
+const SYSCALL_SEMAPHORE_DOWN_TIMEOUT: usize = 1023;
+const SYSCALL_SEMAPHORE_DESTROY: usize = 1024;
 const SYSCALL_CONDVAR_CREATE: usize = 1030;
 const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
 const SYSCALL_CONDVAR_WAIT: usize = 1032;
+const SYSCALL_CONDVAR_BROADCAST: usize = 1033;
+const SYSCALL_CONDVAR_WAIT_TIMEOUT: usize = 1034;
+const SYSCALL_CONDVAR_DESTROY: usize = 1035;
 const SYSCALL_LS: usize = 1040; //list files in a directory
+const SYSCALL_RWLOCK_CREATE: usize = 1050;
+const SYSCALL_RWLOCK_READ: usize = 1051;
+const SYSCALL_RWLOCK_WRITE: usize = 1052;
+const SYSCALL_RWLOCK_UNLOCK: usize = 1053;
+const SYSCALL_RWLOCK_DESTROY: usize = 1054;
+const SYSCALL_BARRIER_CREATE: usize = 1060;
+const SYSCALL_BARRIER_WAIT: usize = 1061;
+const SYSCALL_BARRIER_DESTROY: usize = 1062;
 
 global_asm!(include_str!("syscall.asm"));
 
 pub fn syscall(id: usize, args0: usize, args1: usize, args2: usize) -> isize {
+    syscall6(id, [args0, args1, args2, 0, 0, 0])
+}
+
+pub fn syscall6(id: usize, args: [usize; 6]) -> isize {
     extern "C" {
-        fn do_syscall(id: usize, args0: usize, args1: usize, args2: usize) -> isize;
+        fn do_syscall(
+            id: usize,
+            args0: usize,
+            args1: usize,
+            args2: usize,
+            args3: usize,
+            args4: usize,
+            args5: usize,
+        ) -> isize;
     }
-    unsafe { do_syscall(id, args0, args1, args2) }
+    unsafe { do_syscall(id, args[0], args[1], args[2], args[3], args[4], args[5]) }
 }
 pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
     syscall(SYSCALL_READ, fd, buffer.as_mut_ptr() as usize, buffer.len())
@@ -51,6 +120,15 @@ pub fn sys_exit(exit_code: i32) -> ! {
     panic!("sys_exit called");
 }
 
+/// 功能：结束当前进程的所有线程。
+/// 参数：`exit_code` 表示进程的返回值。
+/// 返回值：该系统调用不应该返回。
+/// syscall ID：94
+pub fn sys_exit_group(exit_code: i32) -> ! {
+    syscall(SYSCALL_EXIT_GROUP, exit_code as usize, 0, 0);
+    panic!("sys_exit_group called");
+}
+
 pub fn sys_yield() -> isize {
     syscall(SYSCALL_YIELD, 0, 0, 0)
 }
@@ -67,19 +145,46 @@ pub fn sys_getpid() -> isize {
 /// 返回值：对于子进程返回 0，对于当前进程则返回子进程的 PID 。
 /// syscall ID：220
 pub fn sys_fork() -> isize {
-    syscall(SYSCALL_FORK, 0, 0, 0)
+    syscall(SYSCALL_CLONE, crate::SIGCHLD as usize, 0, 0)
+}
+/// 功能：创建子进程或者线程，它从 `entry(arg)` 开始运行，`entry` 不能返回。
+/// 参数：flags、stack、ptid、ctid 和 tls 与 Linux 的 clone 相同，stack 为 0
+/// 时子进程沿用调用者的栈，线程则使用内核分配的栈。
+/// 返回值：对于调用者返回子进程的 PID 或者线程的 TID。
+/// syscall ID：220
+pub fn sys_clone(
+    entry: usize,
+    arg: usize,
+    flags: usize,
+    stack: usize,
+    ptid: *mut u32,
+    ctid: *mut u32,
+    tls: usize,
+) -> isize {
+    extern "C" {
+        fn __clone(
+            entry: usize,
+            arg: usize,
+            flags: usize,
+            stack: usize,
+            ptid: *mut u32,
+            ctid: *mut u32,
+            tls: usize,
+        ) -> isize;
+    }
+    unsafe { __clone(entry, arg, flags, stack, ptid, ctid, tls) }
 }
 /// 功能：将当前进程的地址空间清空并加载一个特定的可执行文件，
 /// 返回用户态后开始它的执行。 参数：path 给出了要加载的可执行文件的名字；
 /// 返回值：如果出错的话（如找不到名字相符的可执行文件）则返回
-/// -1，否则不应该返回。 syscall ID：221
-
-pub fn sys_exec(path: &str, args: &[*const u8]) -> isize {
+/// -1，否则不应该返回。args 和 envs 都以空指针结尾，envs 为空指针表示没有环境变量。
+/// syscall ID：221
+pub fn sys_exec(path: &str, args: &[*const u8], envs: *const *const u8) -> isize {
     syscall(
         SYSCALL_EXEC,
         path.as_ptr() as usize,
         args.as_ptr() as usize,
-        0,
+        envs as usize,
     )
 }
 
@@ -87,20 +192,73 @@ pub fn sys_exec(path: &str, args: &[*const u8]) -> isize {
 /// 参数：pid 表示要等待的子进程的进程 ID，如果为 -1
 /// 的话表示等待任意一个子进程； exit_code 表示保存子进程返回值的地址，
 /// 如果这个地址为 0 的话表示不必保存。 返回值：如果要等待的子进程不存在则返回
-/// -1；否则如果要等待的子进程均未结束则返回 -2； 否则返回结束的子进程的进程
-/// ID。 syscall ID：260
-pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
-    syscall(SYSCALL_WAITPID, pid as usize, exit_code as usize, 0)
+/// -ECHILD；否则阻塞到有子进程结束，返回结束的子进程的进程 ID，options 中有 WNOHANG
+/// 时不阻塞，子进程均未结束则返回 0。 options 中的 WUNTRACED 和 WCONTINUED 表示子进程被暂停或继续运行时也返回，
+/// 此时 exit_code 中保存的分别是 (信号 << 8) | 0x7f 和 0xffff。
+/// syscall ID：260
+pub fn sys_waitpid(pid: isize, exit_code: *mut i32, options: u32) -> isize {
+    syscall(
+        SYSCALL_WAIT4,
+        pid as usize,
+        exit_code as usize,
+        options as usize,
+    )
+}
+
+/// 功能：与 sys_waitpid 相同，rusage 不为空时还写入子进程（包括它回收的子进程）的运行时间。
+/// syscall ID：260
+pub fn sys_wait4(pid: isize, exit_code: *mut i32, options: u32, rusage: *mut RUsage) -> isize {
+    syscall6(
+        SYSCALL_WAIT4,
+        [
+            pid as usize,
+            exit_code as usize,
+            options as usize,
+            rusage as usize,
+            0,
+            0,
+        ],
+    )
 }
 
-/// 功能：打开一个常规文件，并返回可以访问它的文件描述符。
-/// 参数：path 描述要打开的文件的文件名（简单起见，文件系统不需要支持目录，
-/// 所有的文件都放在根目录 / 下）， flags 描述打开文件的标志，具体含义下面给出。
-/// 返回值：如果出现了错误则返回
-/// -1，否则返回打开常规文件的文件描述符。可能的错误原因是：文件不存在。 syscall
-/// ID：56
-pub fn sys_open(path: &str, flags: u32) -> isize {
-    syscall(SYSCALL_OPEN, path.as_ptr() as usize, flags as usize, 0)
+/// 功能：获取当前进程和已回收的子进程在用户态和内核态运行的时间，单位为 1/CLK_TCK 秒。
+/// 返回值：开机以来经过的时间，单位同样为 1/CLK_TCK 秒。
+/// syscall ID：153
+pub fn sys_times(buf: &mut Tms) -> isize {
+    syscall(SYSCALL_TIMES, buf as *mut Tms as usize, 0, 0)
+}
+
+/// 功能：获取当前进程（RUSAGE_SELF）、已回收的子进程（RUSAGE_CHILDREN）或当前线程
+/// （RUSAGE_THREAD）的运行时间。
+/// 返回值：成功返回 0，who 不是这三者之一时返回 -EINVAL。
+/// syscall ID：165
+pub fn sys_getrusage(who: isize, usage: &mut RUsage) -> isize {
+    syscall(
+        SYSCALL_GETRUSAGE,
+        who as usize,
+        usage as *mut RUsage as usize,
+        0,
+    )
+}
+
+/// 功能：打开一个文件或目录，并返回可以访问它的文件描述符。
+/// 参数：dirfd 为 AT_FDCWD 或目录的文件描述符，相对路径从它开始查找；
+/// path 描述要打开的文件的文件名，flags 描述打开文件的标志，mode 被忽略。
+/// 返回值：如果出现了错误则返回 -errno，否则返回打开文件的文件描述符。
+/// 可能的错误原因是：文件不存在且没有 O_CREAT、带 O_DIRECTORY 打开普通文件。
+/// syscall ID：56
+pub fn sys_openat(dirfd: isize, path: &str, flags: u32, mode: u32) -> isize {
+    syscall6(
+        SYSCALL_OPENAT,
+        [
+            dirfd as usize,
+            path.as_ptr() as usize,
+            flags as usize,
+            mode as usize,
+            0,
+            0,
+        ],
+    )
 }
 
 /// 功能：当前进程关闭一个文件。
@@ -117,7 +275,54 @@ pub fn sys_close(fd: usize) -> isize {
 /// 返回值：如果出现了错误则返回 -1，否则返回 0
 /// 。可能的错误原因是：传入的地址不合法。 syscall ID：59
 pub fn sys_pipe(pipe: &mut [usize]) -> isize {
-    syscall(SYSCALL_PIPE, pipe.as_mut_ptr() as usize, 0, 0)
+    sys_pipe2(pipe, 0)
+}
+
+/// 功能：与 sys_pipe 相同，flags 可以包含 O_NONBLOCK，作用于管道的两端。
+pub fn sys_pipe2(pipe: &mut [usize], flags: u32) -> isize {
+    syscall(SYSCALL_PIPE, pipe.as_mut_ptr() as usize, flags as usize, 0)
+}
+
+/// 功能：读取目录中的若干项，每一项是一个 `linux_dirent64`。
+/// 参数：fd 为打开的目录，buf 用于保存目录项。
+/// 返回值：写入 buf 的字节数，目录读完时返回 0，buf 放不下一项时返回 -EINVAL。
+/// syscall ID：61
+pub fn sys_getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    syscall(SYSCALL_GETDENTS64, fd, buf.as_mut_ptr() as usize, buf.len())
+}
+
+/// 功能：依次读入到一组缓冲区中。
+/// 返回值：读到的总字节数。
+/// syscall ID：65
+pub fn sys_readv(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_READV, fd, iov.as_ptr() as usize, iov.len())
+}
+
+/// 功能：依次写出一组缓冲区。
+/// 返回值：写出的总字节数。
+/// syscall ID：66
+pub fn sys_writev(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_WRITEV, fd, iov.as_ptr() as usize, iov.len())
+}
+
+/// 功能：获取打开的文件的信息。
+/// 返回值：成功返回 0，fd 不合法时返回 -EBADF。
+/// syscall ID：80
+pub fn sys_fstat(fd: usize, stat: &mut Stat) -> isize {
+    syscall(SYSCALL_FSTAT, fd, stat as *mut Stat as usize, 0)
+}
+
+/// 功能：等待一组文件描述符中的任意一个就绪。
+/// 参数：fds 为要等待的描述符及事件，timeout 为空时一直等待。
+/// 返回值：就绪的描述符个数，超时返回 0。
+/// syscall ID：73
+pub fn sys_ppoll(fds: &mut [PollFd], timeout: Option<&TimeSpec>) -> isize {
+    syscall(
+        SYSCALL_PPOLL,
+        fds.as_mut_ptr() as usize,
+        fds.len(),
+        timeout.map_or(0, |timeout| timeout as *const TimeSpec as usize),
+    )
 }
 
 /// 功能：将进程中一个已经打开的文件复制一份并分配到一个新的文件描述符中。
@@ -129,19 +334,295 @@ pub fn sys_dup(fd: usize) -> isize {
     syscall(SYSCALL_DUP, fd, 0, 0)
 }
 
+/// 功能：控制一个已经打开的文件。
+/// 参数：fd 为文件描述符，cmd 为命令，arg 为命令的参数。
+/// 返回值：出错返回 -1，否则返回值取决于 cmd。
+/// syscall ID：25
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    syscall(SYSCALL_FCNTL, fd, cmd, arg)
+}
+
+/// 功能：控制设备，目前只支持读取和设置终端的前台进程组。
+/// 参数：fd 为终端的文件描述符，cmd 为 TIOCGPGRP 或 TIOCSPGRP，arg 指向进程组号。
+/// 返回值：成功返回 0，fd 不是终端、进程组不在当前会话中或终端属于其他会话时返回 -1。
+/// syscall ID：29
+pub fn sys_ioctl(fd: usize, cmd: usize, arg: usize) -> isize {
+    syscall(SYSCALL_IOCTL, fd, cmd, arg)
+}
+
+/// 功能：对一个打开的文件加建议锁或解锁。
+/// 参数：fd 为文件描述符，operation 为 LOCK_SH、LOCK_EX 或 LOCK_UN，可以或上 LOCK_NB。
+/// 返回值：成功返回 0，带 LOCK_NB 且锁被占用时返回 -11，其他错误返回 -1。
+/// syscall ID：32
+pub fn sys_flock(fd: usize, operation: u32) -> isize {
+    syscall(SYSCALL_FLOCK, fd, operation as usize, 0)
+}
+
+/// 功能：在文件系统中创建一个节点，目前只支持命名管道。
+/// 参数：dirfd 被忽略，path 为节点的名字，mode 为节点的类型和权限。
+/// 返回值：成功返回 0，名字已经存在或类型不支持则返回 -1。
+/// syscall ID：33
+pub fn sys_mknodat(dirfd: isize, path: &str, mode: u32) -> isize {
+    syscall(
+        SYSCALL_MKNODAT,
+        dirfd as usize,
+        path.as_ptr() as usize,
+        mode as usize,
+    )
+}
+
+/// 功能：获取 path 所在文件系统的使用情况。
+/// 参数：path 为文件系统中任意一个文件的路径，buf 用于保存统计信息。
+/// 返回值：成功返回 0，如果 path 不存在则返回 -1。
+/// syscall ID：43
+pub fn sys_statfs(path: &str, buf: &mut StatFs) -> isize {
+    syscall(
+        SYSCALL_STATFS,
+        path.as_ptr() as usize,
+        buf as *mut StatFs as usize,
+        0,
+    )
+}
+
 // 将某信号发送给某进程
-// pid：进程pid
+// pid：进程pid，为 0 时发给当前进程组，小于 -1 时发给进程组 -pid
 // signal：信号的整数码
 pub fn sys_kill(pid: usize, signal: i32) -> isize {
     syscall(SYSCALL_KILL, pid, signal as usize, 0)
 }
 
-/// 功能：当前进程创建一个新的线程
-/// 参数：entry 表示线程的入口函数地址
-/// 参数：arg：表示线程的一个参数
-pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
-    syscall(SYSCALL_THREAD_CREATE, entry, arg, 0)
+/// 功能：设置当前进程对某个信号的处理方式。
+/// 参数：signum 为信号的整数码，action 为新的处理方式，old_action 用于保存原来的处理方式，
+/// 两者都可以为空。
+/// 返回值：成功返回 0，信号不存在或试图改变 SIGKILL、SIGSTOP 的处理方式时返回 -1。
+/// syscall ID：134
+pub fn sys_sigaction(
+    signum: i32,
+    action: Option<&SignalAction>,
+    old_action: Option<&mut SignalAction>,
+) -> isize {
+    syscall(
+        SYSCALL_SIGACTION,
+        signum as usize,
+        action.map_or(0, |action| action as *const _ as usize),
+        old_action.map_or(0, |old_action| old_action as *mut _ as usize),
+    )
+}
+
+/// 功能：修改当前线程的信号掩码。
+/// 参数：how 为 SIG_BLOCK、SIG_UNBLOCK 或 SIG_SETMASK，set 为新的掩码，
+/// old_set 用于保存原来的掩码，两者都可以为空。
+/// 返回值：成功返回 0，how 不合法时返回 -1。
+/// syscall ID：135
+pub fn sys_sigprocmask(how: usize, set: Option<&i32>, old_set: Option<&mut i32>) -> isize {
+    syscall(
+        SYSCALL_SIGPROCMASK,
+        how,
+        set.map_or(0, |set| set as *const _ as usize),
+        old_set.map_or(0, |old_set| old_set as *mut _ as usize),
+    )
+}
+
+/// 功能：设置进程的 nice 值，作用于进程的所有线程。
+/// 参数：which 为 PRIO_PROCESS、PRIO_PGRP 或 PRIO_USER，who 为对应的进程号、
+/// 进程组号，0 表示当前进程或当前进程组；nice 超出 -20 到 19 时被截断。
+/// 返回值：成功返回 0，which 不合法时返回 -EINVAL，没有选中任何进程时返回 -ESRCH。
+/// syscall ID：140
+pub fn sys_setpriority(which: usize, who: usize, nice: i32) -> isize {
+    syscall(SYSCALL_SETPRIORITY, which, who, nice as isize as usize)
+}
+
+/// 功能：获取进程的 nice 值。
+/// 返回值：20 - nice，选中多个进程时取其中最小的 nice；出错时返回 -errno。
+/// syscall ID：141
+pub fn sys_getpriority(which: usize, who: usize) -> isize {
+    syscall(SYSCALL_GETPRIORITY, which, who, 0)
+}
+
+/// 功能：把进程移到另一个进程组中。
+/// 参数：pid 为当前进程或其子进程，0 表示当前进程；pgid 为同一会话中的进程组，
+/// 0 表示以 pid 为组号新建进程组。
+/// 返回值：成功返回 0，否则返回 -1。
+/// syscall ID：154
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    syscall(SYSCALL_SETPGID, pid, pgid, 0)
+}
+
+/// 功能：获取进程所在的进程组。
+/// 参数：pid 为进程号，0 表示当前进程。
+/// 返回值：进程组号，进程不存在时返回 -1。
+/// syscall ID：155
+pub fn sys_getpgid(pid: usize) -> isize {
+    syscall(SYSCALL_GETPGID, pid, 0, 0)
+}
+
+/// 功能：新建一个会话，当前进程成为新会话和新进程组的首进程。
+/// 返回值：新的会话号，当前进程是进程组的首进程时返回 -1。
+/// syscall ID：157
+pub fn sys_setsid() -> isize {
+    syscall(SYSCALL_SETSID, 0, 0, 0)
+}
+
+/// 功能：设置当前线程退出时清零并唤醒的地址，与 CLONE_CHILD_CLEARTID 相同。
+/// 返回值：当前线程的 TID。
+/// syscall ID：96
+pub fn sys_set_tid_address(tidptr: *mut u32) -> isize {
+    syscall(SYSCALL_SET_TID_ADDRESS, tidptr as usize, 0, 0)
+}
+
+/// 功能：在 uaddr 上等待或者唤醒等待的线程，op 为 FUTEX_WAIT、FUTEX_WAKE 等。
+/// 参数：FUTEX_WAIT 时 timeout 为相对时间的 TimeSpec 指针，FUTEX_REQUEUE 时为移动的线程数。
+/// 返回值：FUTEX_WAIT 成功返回 0，值不等于 val 时返回 -EAGAIN，超时返回 -ETIMEDOUT；
+/// 其他操作返回唤醒的线程数。
+/// syscall ID：98
+pub fn sys_futex(
+    uaddr: *const u32,
+    op: usize,
+    val: usize,
+    timeout: usize,
+    uaddr2: *const u32,
+    val3: usize,
+) -> isize {
+    syscall6(
+        SYSCALL_FUTEX,
+        [uaddr as usize, op, val, timeout, uaddr2 as usize, val3],
+    )
+}
+
+/// 功能：读取时钟，clock_id 为 CLOCK_REALTIME、CLOCK_MONOTONIC 等。
+/// 返回值：成功返回 0，时钟不存在时返回 -EINVAL。
+/// syscall ID：113
+pub fn sys_clock_gettime(clock_id: usize, tp: &mut TimeSpec) -> isize {
+    syscall(
+        SYSCALL_CLOCK_GETTIME,
+        clock_id,
+        tp as *mut TimeSpec as usize,
+        0,
+    )
+}
+
+/// 功能：读取时钟的精度。
+/// 返回值：成功返回 0，时钟不存在时返回 -EINVAL。
+/// syscall ID：114
+pub fn sys_clock_getres(clock_id: usize, res: &mut TimeSpec) -> isize {
+    syscall(
+        SYSCALL_CLOCK_GETRES,
+        clock_id,
+        res as *mut TimeSpec as usize,
+        0,
+    )
+}
+
+/// 功能：按照 clock_id 给出的时钟睡眠，flags 为 TIMER_ABSTIME 时 req 是绝对时间。
+/// 返回值：成功返回 0，时钟不存在或 tv_nsec 不小于 10^9 时返回 -EINVAL，被信号打断时返回 -EINTR。
+/// syscall ID：115
+pub fn sys_clock_nanosleep(clock_id: usize, flags: usize, req: &TimeSpec) -> isize {
+    syscall6(
+        SYSCALL_CLOCK_NANOSLEEP,
+        [clock_id, flags, req as *const TimeSpec as usize, 0, 0, 0],
+    )
+}
+
+/// 功能：读取间隔定时器 which 的剩余时间和周期。
+/// 返回值：成功返回 0，which 不是 ITIMER_REAL、ITIMER_VIRTUAL 或 ITIMER_PROF 时返回 -EINVAL。
+/// syscall ID：102
+pub fn sys_getitimer(which: usize, curr_value: &mut ITimerVal) -> isize {
+    syscall(
+        SYSCALL_GETITIMER,
+        which,
+        curr_value as *mut ITimerVal as usize,
+        0,
+    )
+}
+
+/// 功能：设置间隔定时器 which，it_value 为 0 时停止定时器，old_value 不为空时写入原来的设置。
+/// 返回值：成功返回 0，which 不存在或 tv_usec 不小于 10^6 时返回 -EINVAL。
+/// syscall ID：103
+pub fn sys_setitimer(
+    which: usize,
+    new_value: &ITimerVal,
+    old_value: Option<&mut ITimerVal>,
+) -> isize {
+    syscall(
+        SYSCALL_SETITIMER,
+        which,
+        new_value as *const ITimerVal as usize,
+        old_value.map_or(0, |old| old as *mut ITimerVal as usize),
+    )
+}
+
+/// 功能：创建一个到期后可读的定时器文件描述符，读出 8 字节的到期次数。
+/// 参数：clock_id 为 CLOCK_REALTIME 或 CLOCK_MONOTONIC 等时钟，flags 可以包含 O_NONBLOCK。
+/// 返回值：成功返回文件描述符，时钟不存在或 flags 不支持时返回 -EINVAL。
+/// syscall ID：85
+pub fn sys_timerfd_create(clock_id: usize, flags: u32) -> isize {
+    syscall(SYSCALL_TIMERFD_CREATE, clock_id, flags as usize, 0)
+}
+
+/// 功能：设置 timerfd，flags 为 TFD_TIMER_ABSTIME 时 it_value 是绝对时间，it_value 为 0 时停止。
+/// 返回值：成功返回 0，fd 不是 timerfd 时返回 -EINVAL。
+/// syscall ID：86
+pub fn sys_timerfd_settime(
+    fd: usize,
+    flags: usize,
+    new_value: &ITimerSpec,
+    old_value: Option<&mut ITimerSpec>,
+) -> isize {
+    syscall6(
+        SYSCALL_TIMERFD_SETTIME,
+        [
+            fd,
+            flags,
+            new_value as *const ITimerSpec as usize,
+            old_value.map_or(0, |old| old as *mut ITimerSpec as usize),
+            0,
+            0,
+        ],
+    )
 }
+
+/// 功能：读取 timerfd 的剩余时间和周期。
+/// 返回值：成功返回 0，fd 不是 timerfd 时返回 -EINVAL。
+/// syscall ID：87
+pub fn sys_timerfd_gettime(fd: usize, curr_value: &mut ITimerSpec) -> isize {
+    syscall(
+        SYSCALL_TIMERFD_GETTIME,
+        fd,
+        curr_value as *mut ITimerSpec as usize,
+        0,
+    )
+}
+
+/// 功能：获取系统的名称和版本。
+/// syscall ID：160
+pub fn sys_uname(buf: &mut UtsName) -> isize {
+    syscall(SYSCALL_UNAME, buf as *mut UtsName as usize, 0, 0)
+}
+
+/// 功能：把 program break 移到 addr，addr 为 0 时只查询。
+/// 返回值：新的 program break，不能移动时返回原来的值。
+/// syscall ID：214
+pub fn sys_brk(addr: usize) -> isize {
+    syscall(SYSCALL_BRK, addr, 0, 0)
+}
+
+/// 功能：解除 `addr..addr + len` 中所有页的映射。
+/// 返回值：成功返回 0，addr 没有按页对齐时返回 -EINVAL。
+/// syscall ID：215
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNMAP, addr, len, 0)
+}
+
+/// 功能：映射一段匿名内存或文件的内容，只支持 MAP_PRIVATE。
+/// 返回值：映射的起始地址，出错时返回 -errno。
+/// syscall ID：222
+pub fn sys_mmap(addr: usize, len: usize, prot: u32, flags: u32, fd: usize, offset: usize) -> isize {
+    syscall6(
+        SYSCALL_MMAP,
+        [addr, len, prot as usize, flags as usize, fd, offset],
+    )
+}
+
 /// 参数：tid表示线程id
 /// 返回值：如果线程不存在，返回-1；如果线程还没退出，返回-2；其他情况下，
 /// 返回结束线程的退出码
@@ -153,8 +634,40 @@ pub fn sys_waittid(tid: usize) -> isize {
     syscall(SYSCALL_WAITTID, tid, 0, 0)
 }
 
-pub fn sys_sleep(ms: usize) -> isize {
-    syscall(SYSCALL_SLEEP, ms, 0, 0)
+/// 功能：设置当前进程中一个线程的 nice 值。
+/// 参数：tid 为线程号；nice 超出 -20 到 19 时被截断。
+/// 返回值：成功返回 0，线程不存在时返回 -ESRCH。
+/// syscall ID：1003
+pub fn sys_thread_setpriority(tid: usize, nice: i32) -> isize {
+    syscall(SYSCALL_THREAD_SETPRIORITY, tid, nice as isize as usize, 0)
+}
+
+/// 功能：获取当前进程中一个线程的有效优先级，包括从等待它持有的互斥锁的线程继承来的优先级。
+/// 返回值：20 - 有效的 nice 值，线程不存在时返回 -ESRCH。
+/// syscall ID：1004
+pub fn sys_thread_getpriority(tid: usize) -> isize {
+    syscall(SYSCALL_THREAD_GETPRIORITY, tid, 0, 0)
+}
+
+/// 功能：当前线程睡眠 req 给出的时间。
+/// 参数：rem 不为空时，被信号打断后在这里写入剩余的时间。
+/// 返回值：成功返回 0，tv_nsec 不小于 10^9 时返回 -EINVAL，被信号打断时返回 -EINTR。
+/// syscall ID：101
+pub fn sys_nanosleep(req: &TimeSpec, rem: *mut TimeSpec) -> isize {
+    syscall(
+        SYSCALL_NANOSLEEP,
+        req as *const TimeSpec as usize,
+        rem as usize,
+        0,
+    )
+}
+
+/// 功能：打开或关闭当前进程的死锁检测，打开后申请互斥锁或信号量会导致死锁时返回 -EDEADLK。
+/// 参数：enabled 为 1 打开，为 0 关闭。
+/// 返回值：成功返回 0，参数不是 0 或 1 时返回 -EINVAL。
+/// syscall ID：469
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    syscall(SYSCALL_ENABLE_DEADLOCK_DETECT, enabled, 0, 0)
 }
 
 pub fn sys_mutex_create(blocking: bool) -> isize {
@@ -166,6 +679,23 @@ pub fn sys_mutex_lock(id: usize) -> isize {
 pub fn sys_mutex_unlock(id: usize) -> isize {
     syscall(SYSCALL_MUTEX_UNLOCK, id, 0, 0)
 }
+/// 锁被占用时返回 -EBUSY
+pub fn sys_mutex_trylock(id: usize) -> isize {
+    syscall(SYSCALL_MUTEX_TRYLOCK, id, 0, 0)
+}
+/// timeout 为相对时间，超时返回 -ETIMEDOUT
+pub fn sys_mutex_lock_timeout(id: usize, timeout: &TimeSpec) -> isize {
+    syscall(
+        SYSCALL_MUTEX_LOCK_TIMEOUT,
+        id,
+        timeout as *const TimeSpec as usize,
+        0,
+    )
+}
+/// 锁被持有时返回 -EBUSY，之后编号可以被重新分配
+pub fn sys_mutex_destroy(id: usize) -> isize {
+    syscall(SYSCALL_MUTEX_DESTROY, id, 0, 0)
+}
 
 pub fn sys_semaphore_create(res_count: usize) -> isize {
     syscall(SYSCALL_SEMAPHORE_CREATE, res_count, 0, 0)
@@ -179,6 +709,21 @@ pub fn sys_semaphore_down(sem_id: usize) -> isize {
     syscall(SYSCALL_SEMAPHORE_DOWN, sem_id, 0, 0)
 }
 
+/// timeout 为相对时间，超时返回 -ETIMEDOUT，为 0 时只尝试一次
+pub fn sys_semaphore_down_timeout(sem_id: usize, timeout: &TimeSpec) -> isize {
+    syscall(
+        SYSCALL_SEMAPHORE_DOWN_TIMEOUT,
+        sem_id,
+        timeout as *const TimeSpec as usize,
+        0,
+    )
+}
+
+/// 有线程在等待时返回 -EBUSY
+pub fn sys_semaphore_destroy(sem_id: usize) -> isize {
+    syscall(SYSCALL_SEMAPHORE_DESTROY, sem_id, 0, 0)
+}
+
 pub fn sys_condvar_create(_arg: usize) -> isize {
     syscall(SYSCALL_CONDVAR_CREATE, _arg, 0, 0)
 }
@@ -191,6 +736,62 @@ pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
     syscall(SYSCALL_CONDVAR_WAIT, condvar_id, mutex_id, 0)
 }
 
+pub fn sys_condvar_broadcast(condvar_id: usize) -> isize {
+    syscall(SYSCALL_CONDVAR_BROADCAST, condvar_id, 0, 0)
+}
+
+/// timeout 为相对时间，超时返回 -ETIMEDOUT，返回时都重新持有了 mutex
+pub fn sys_condvar_wait_timeout(condvar_id: usize, mutex_id: usize, timeout: &TimeSpec) -> isize {
+    syscall(
+        SYSCALL_CONDVAR_WAIT_TIMEOUT,
+        condvar_id,
+        mutex_id,
+        timeout as *const TimeSpec as usize,
+    )
+}
+
+/// 有线程在等待时返回 -EBUSY
+pub fn sys_condvar_destroy(condvar_id: usize) -> isize {
+    syscall(SYSCALL_CONDVAR_DESTROY, condvar_id, 0, 0)
+}
+
+pub fn sys_rwlock_create() -> isize {
+    syscall(SYSCALL_RWLOCK_CREATE, 0, 0, 0)
+}
+
+pub fn sys_rwlock_read(rwlock_id: usize) -> isize {
+    syscall(SYSCALL_RWLOCK_READ, rwlock_id, 0, 0)
+}
+
+pub fn sys_rwlock_write(rwlock_id: usize) -> isize {
+    syscall(SYSCALL_RWLOCK_WRITE, rwlock_id, 0, 0)
+}
+
+/// 释放读锁或者写锁
+pub fn sys_rwlock_unlock(rwlock_id: usize) -> isize {
+    syscall(SYSCALL_RWLOCK_UNLOCK, rwlock_id, 0, 0)
+}
+
+/// 锁被持有时返回 -EBUSY
+pub fn sys_rwlock_destroy(rwlock_id: usize) -> isize {
+    syscall(SYSCALL_RWLOCK_DESTROY, rwlock_id, 0, 0)
+}
+
+/// count 个线程到达之后一起继续
+pub fn sys_barrier_create(count: usize) -> isize {
+    syscall(SYSCALL_BARRIER_CREATE, count, 0, 0)
+}
+
+/// 最后到达的线程返回 1，其他线程返回 0
+pub fn sys_barrier_wait(barrier_id: usize) -> isize {
+    syscall(SYSCALL_BARRIER_WAIT, barrier_id, 0, 0)
+}
+
+/// 有线程在等待时返回 -EBUSY
+pub fn sys_barrier_destroy(barrier_id: usize) -> isize {
+    syscall(SYSCALL_BARRIER_DESTROY, barrier_id, 0, 0)
+}
+
 pub fn sys_ls() -> isize {
     syscall(SYSCALL_LS, 0, 0, 0)
 }
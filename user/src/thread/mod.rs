@@ -1,7 +1,56 @@
-use crate::syscall::*;
+use bitflags::bitflags;
 
+use crate::{syscall::*, SysError, SysResult, EAGAIN};
+
+bitflags! {
+    /// The flags of `clone`
+    pub struct CloneFlags: usize {
+        const CLONE_VM = 0x100;
+        const CLONE_FS = 0x200;
+        const CLONE_FILES = 0x400;
+        const CLONE_SIGHAND = 0x800;
+        const CLONE_THREAD = 0x10000;
+        const CLONE_SYSVSEM = 0x40000;
+        const CLONE_SETTLS = 0x80000;
+        const CLONE_PARENT_SETTID = 0x100000;
+        const CLONE_CHILD_CLEARTID = 0x200000;
+        const CLONE_DETACHED = 0x400000;
+        const CLONE_CHILD_SETTID = 0x1000000;
+    }
+}
+
+/// What a new thread shares with the rest of its process
+pub const THREAD_FLAGS: CloneFlags = CloneFlags::from_bits_truncate(
+    CloneFlags::CLONE_VM.bits()
+        | CloneFlags::CLONE_FILES.bits()
+        | CloneFlags::CLONE_SIGHAND.bits()
+        | CloneFlags::CLONE_THREAD.bits(),
+);
+
+/// 创建子进程或者线程并在其中运行 `entry(arg)`，其余参数同 Linux 的 clone
+pub fn clone(
+    entry: usize,
+    arg: usize,
+    flags: CloneFlags,
+    stack: usize,
+    ptid: *mut u32,
+    ctid: *mut u32,
+    tls: usize,
+) -> isize {
+    sys_clone(entry, arg, flags.bits(), stack, ptid, ctid, tls)
+}
+
+/// 创建使用内核分配的栈的线程
 pub fn thread_create(entry: usize, arg: usize) -> isize {
-    sys_thread_create(entry, arg)
+    clone(
+        entry,
+        arg,
+        THREAD_FLAGS,
+        0,
+        core::ptr::null_mut(),
+        core::ptr::null_mut(),
+        0,
+    )
 }
 pub fn gettid() -> isize {
     sys_gettid()
@@ -10,7 +59,7 @@ pub fn gettid() -> isize {
 pub fn waittid(tid: usize) -> isize {
     loop {
         match sys_waittid(tid) {
-            -2 => {
+            EAGAIN => {
                 yield_();
             }
             exit_code => return exit_code,
@@ -18,10 +67,31 @@ pub fn waittid(tid: usize) -> isize {
     }
 }
 
+/// 只设置一个线程的 nice，`setpriority` 作用于进程的所有线程
+pub fn thread_setpriority(tid: usize, nice: i32) -> isize {
+    sys_thread_setpriority(tid, nice)
+}
+
+/// 线程的有效 nice 值，持有的阻塞互斥锁有优先级更高的等待者时比设置的值小
+pub fn thread_getpriority(tid: usize) -> SysResult<i32> {
+    SysError::check(sys_thread_getpriority(tid)).map(|prio| 20 - prio as i32)
+}
+
+/// 当前线程退出时把 `*tidptr` 清零，返回当前线程的 TID
+pub fn set_tid_address(tidptr: *mut u32) -> isize {
+    sys_set_tid_address(tidptr)
+}
+
+/// 结束当前线程，在主线程中调用时结束整个进程
 pub fn exit(exit_code: i32) -> ! {
     sys_exit(exit_code);
 }
 
+/// 从任何线程结束整个进程
+pub fn exit_group(exit_code: i32) -> ! {
+    sys_exit_group(exit_code);
+}
+
 pub fn yield_() -> isize {
     sys_yield()
 }
@@ -1,8 +1,199 @@
 use crate::*;
+
+/// `struct timespec`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TimeSpec {
+    pub tv_sec: usize,
+    pub tv_nsec: usize,
+}
+
+impl TimeSpec {
+    pub fn from_ms(ms: usize) -> Self {
+        Self {
+            tv_sec: ms / 1000,
+            tv_nsec: ms % 1000 * 1_000_000,
+        }
+    }
+    pub fn from_ns(ns: usize) -> Self {
+        Self {
+            tv_sec: ns / 1_000_000_000,
+            tv_nsec: ns % 1_000_000_000,
+        }
+    }
+    pub fn to_ns(&self) -> usize {
+        self.tv_sec * 1_000_000_000 + self.tv_nsec
+    }
+}
+
+/// `struct timeval`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TimeVal {
+    pub tv_sec: usize,
+    pub tv_usec: usize,
+}
+
+impl TimeVal {
+    pub fn from_ms(ms: usize) -> Self {
+        Self {
+            tv_sec: ms / 1000,
+            tv_usec: ms % 1000 * 1000,
+        }
+    }
+    pub fn to_us(&self) -> usize {
+        self.tv_sec * 1_000_000 + self.tv_usec
+    }
+}
+
+/// `struct tms`，单位为 1/CLK_TCK 秒
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Tms {
+    pub tms_utime: usize,
+    pub tms_stime: usize,
+    pub tms_cutime: usize,
+    pub tms_cstime: usize,
+}
+
+/// `struct rusage`，内核只统计 CPU 时间
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RUsage {
+    pub ru_utime: TimeVal,
+    pub ru_stime: TimeVal,
+    pub ru_unused: [usize; 14],
+}
+
+/// `struct itimerval`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ITimerVal {
+    pub it_interval: TimeVal,
+    pub it_value: TimeVal,
+}
+
+/// `struct itimerspec`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ITimerSpec {
+    pub it_interval: TimeSpec,
+    pub it_value: TimeSpec,
+}
+
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+pub const CLOCK_MONOTONIC_RAW: usize = 4;
+pub const CLOCK_REALTIME_COARSE: usize = 5;
+pub const CLOCK_MONOTONIC_COARSE: usize = 6;
+pub const CLOCK_BOOTTIME: usize = 7;
+
+/// clock_nanosleep 的 flags，req 是绝对时间
+pub const TIMER_ABSTIME: usize = 1;
+
+/// times 的时间单位是 1/CLK_TCK 秒
+pub const CLK_TCK: usize = 100;
+
+pub const RUSAGE_SELF: isize = 0;
+pub const RUSAGE_CHILDREN: isize = -1;
+pub const RUSAGE_THREAD: isize = 1;
+
+/// 按真实时间计时，到期发送 SIGALRM
+pub const ITIMER_REAL: usize = 0;
+/// 按用户态运行时间计时，到期发送 SIGVTALRM
+pub const ITIMER_VIRTUAL: usize = 1;
+/// 按用户态和内核态运行时间计时，到期发送 SIGPROF
+pub const ITIMER_PROF: usize = 2;
+
+/// timerfd_settime 的 flags，it_value 是绝对时间
+pub const TFD_TIMER_ABSTIME: usize = 1;
+
+pub fn clock_gettime(clock_id: usize, tp: &mut TimeSpec) -> isize {
+    sys_clock_gettime(clock_id, tp)
+}
+
+pub fn clock_getres(clock_id: usize, res: &mut TimeSpec) -> isize {
+    sys_clock_getres(clock_id, res)
+}
+
+/// 读取时钟，返回纳秒数
+pub fn clock_now(clock_id: usize) -> SysResult<usize> {
+    let mut tp = TimeSpec::default();
+    SysError::check(clock_gettime(clock_id, &mut tp))?;
+    Ok(tp.to_ns())
+}
+
+pub fn nanosleep(req: &TimeSpec) -> isize {
+    sys_nanosleep(req, core::ptr::null_mut())
+}
+
+pub fn clock_nanosleep(clock_id: usize, flags: usize, req: &TimeSpec) -> isize {
+    sys_clock_nanosleep(clock_id, flags, req)
+}
+
 pub fn get_time() -> isize {
     sys_get_time()
 }
 
+/// 被信号打断后继续睡眠剩下的时间
 pub fn sleep(period_ms: usize) {
-    sys_sleep(period_ms);
+    let mut req = TimeSpec::from_ms(period_ms);
+    let mut rem = TimeSpec::default();
+    while SysError::check(sys_nanosleep(&req, &mut rem)) == Err(SysError::EINTR) {
+        req = rem;
+    }
+}
+
+/// 返回开机以来经过的时间
+pub fn times(buf: &mut Tms) -> isize {
+    sys_times(buf)
+}
+
+pub fn getrusage(who: isize, usage: &mut RUsage) -> isize {
+    sys_getrusage(who, usage)
+}
+
+pub fn getitimer(which: usize, curr_value: &mut ITimerVal) -> isize {
+    sys_getitimer(which, curr_value)
+}
+
+pub fn setitimer(which: usize, new_value: &ITimerVal, old_value: Option<&mut ITimerVal>) -> isize {
+    sys_setitimer(which, new_value, old_value)
+}
+
+/// `seconds` 秒之后收到 SIGALRM，为 0 时取消。返回上一个闹钟剩余的秒数，四舍五入，
+/// 不足半秒但还没有到期时为 1
+pub fn alarm(seconds: usize) -> usize {
+    let new_value = ITimerVal {
+        it_interval: TimeVal::default(),
+        it_value: TimeVal {
+            tv_sec: seconds,
+            tv_usec: 0,
+        },
+    };
+    let mut old_value = ITimerVal::default();
+    sys_setitimer(ITIMER_REAL, &new_value, Some(&mut old_value));
+    let old = old_value.it_value;
+    if old.tv_usec >= 500_000 || (old.tv_sec == 0 && old.tv_usec > 0) {
+        old.tv_sec + 1
+    } else {
+        old.tv_sec
+    }
+}
+
+pub fn timerfd_create(clock_id: usize, flags: OpenFlags) -> isize {
+    sys_timerfd_create(clock_id, flags.bits())
+}
+
+pub fn timerfd_settime(
+    fd: usize,
+    flags: usize,
+    new_value: &ITimerSpec,
+    old_value: Option<&mut ITimerSpec>,
+) -> isize {
+    sys_timerfd_settime(fd, flags, new_value, old_value)
+}
+
+pub fn timerfd_gettime(fd: usize, curr_value: &mut ITimerSpec) -> isize {
+    sys_timerfd_gettime(fd, curr_value)
 }